@@ -3,22 +3,32 @@ package main
 import "C"
 import (
 	"context"
+	"errors"
 	"fmt"
 	"io"
 	"net"
 	"net/http"
+	"net/netip"
+	"path/filepath"
+	"sort"
 	"strings"
 	"sync"
+	"sync/atomic"
 	"time"
 
 	"os"
 
 	box "github.com/sagernet/sing-box"
+	"github.com/sagernet/sing-box/adapter"
+	"github.com/sagernet/sing-box/common/srs"
+	sbconstant "github.com/sagernet/sing-box/constant"
+	"github.com/sagernet/sing-box/experimental/clashapi"
 	"github.com/sagernet/sing-box/include"
 	"github.com/sagernet/sing-box/option"
 	"github.com/sagernet/sing-box/protocol/group"
 	sjson "github.com/sagernet/sing/common/json"
 	"github.com/sagernet/sing/common/metadata"
+	"github.com/sagernet/sing/service"
 
 	_ "github.com/anytls/sing-anytls"
 )
@@ -28,14 +38,80 @@ var (
 	mu       sync.Mutex
 	cancel   context.CancelFunc
 
+	// Service registry of the running instance, used to look up the clash server for stats
+	boxCtx context.Context
+
 	currentLogLevel string = "info"
 )
 
+// logOptions maps the app log level, including "disabled", onto sing-box log options.
+func logOptions(level string) *option.LogOptions {
+	if level == "disabled" {
+		return &option.LogOptions{Disabled: true}
+	}
+	return &option.LogOptions{Level: level}
+}
+
+// syncLogLevel remembers the level of the running config for later test instances.
+func syncLogLevel(opts *option.LogOptions) {
+	if opts == nil {
+		return
+	}
+	if opts.Disabled {
+		currentLogLevel = "disabled"
+	} else {
+		currentLogLevel = opts.Level
+	}
+}
+
+// callResult is the structured reply of calls whose failures the Rust side tells apart:
+// Ok with an optional Body, or a Kind naming the failed step ("decode", "create",
+// "start", ...) and a Message. Matching on Kind keeps callers independent of sing-box's wording.
+type callResult struct {
+	Ok      bool   `json:"ok"`
+	Kind    string `json:"kind,omitempty"`
+	Message string `json:"message,omitempty"`
+	Body    string `json:"body,omitempty"`
+}
+
+func (r callResult) cString() *C.char {
+	data, err := sjson.Marshal(r)
+	if err != nil {
+		return C.CString(fmt.Sprintf(`{"ok": false, "kind": "encode", "message": %q}`, err.Error()))
+	}
+	return C.CString(string(data))
+}
+
+func okResult(body string) *C.char {
+	return callResult{Ok: true, Body: body}.cString()
+}
+
+func errorResult(kind string, err error) *C.char {
+	return callResult{Kind: kind, Message: err.Error()}.cString()
+}
+
+// errorJSON is the {"error": ...} reply of calls returning plain JSON, escaped
+// whatever quotes or backslashes the message carries.
+func errorJSON(format string, args ...any) *C.char {
+	data, err := sjson.Marshal(map[string]string{"error": fmt.Sprintf(format, args...)})
+	if err != nil {
+		return C.CString(`{"error": "encode error"}`)
+	}
+	return C.CString(string(data))
+}
+
 //export LibboxHello
 func LibboxHello() *C.char {
 	return C.CString("Hello from Go Libbox!")
 }
 
+// LibboxVersion reports the sing-box release this library was built from.
+//
+//export LibboxVersion
+func LibboxVersion() *C.char {
+	return C.CString(sbconstant.Version)
+}
+
 //export LibboxStart
 func LibboxStart(configJSON *C.char, logFD C.longlong) *C.char {
 	mu.Lock()
@@ -55,7 +131,8 @@ func LibboxStart(configJSON *C.char, logFD C.longlong) *C.char {
 
 	ctx, cancelFunc := context.WithCancel(context.Background())
 	cancel = cancelFunc
-	ctx = include.Context(ctx)
+	ctx = service.ContextWithDefaultRegistry(include.Context(ctx))
+	boxCtx = ctx
 
 	var options option.Options
 	if err := sjson.UnmarshalContext(ctx, []byte(configStr), &options); err != nil {
@@ -65,9 +142,7 @@ func LibboxStart(configJSON *C.char, logFD C.longlong) *C.char {
 	}
 
 	// Sync current log level
-	if options.Log != nil {
-		currentLogLevel = options.Log.Level
-	}
+	syncLogLevel(options.Log)
 
 	var err error
 	// v1.12+ box.New might fail if registries are not in context?
@@ -120,9 +195,197 @@ func LibboxStop() *C.char {
 	}
 
 	instance = nil
+	boxCtx = nil
+	return nil
+}
+
+//export LibboxStats
+func LibboxStats() *C.char {
+	mu.Lock()
+	defer mu.Unlock()
+
+	if instance == nil || boxCtx == nil {
+		return C.CString("{\"error\": \"service not running\"}")
+	}
+
+	server, ok := service.FromContext[adapter.ClashServer](boxCtx).(*clashapi.Server)
+	if !ok || server == nil {
+		return C.CString("{\"error\": \"clash api not enabled\"}")
+	}
+
+	up, down := server.TrafficManager().Total()
+	return C.CString(fmt.Sprintf("{\"up\": %d, \"down\": %d}", up, down))
+}
+
+// LibboxHealth reports why the running instance is not serving, or nil when it is.
+// A failed reload closes the old instance, so callers cannot rely on their own flags.
+//
+//export LibboxHealth
+func LibboxHealth() *C.char {
+	mu.Lock()
+	defer mu.Unlock()
+
+	if instance == nil || boxCtx == nil {
+		return C.CString("service not running")
+	}
+	if err := boxCtx.Err(); err != nil {
+		return C.CString(fmt.Sprintf("service context closed: %s", err))
+	}
 	return nil
 }
 
+//export LibboxGeoIPLookup
+func LibboxGeoIPLookup(ipStr *C.char, ruleSetDir *C.char) *C.char {
+	addr, err := netip.ParseAddr(C.GoString(ipStr))
+	if err != nil {
+		return errorJSON("invalid ip: %v", err)
+	}
+	addr = addr.Unmap()
+
+	// Every downloaded geoip-<code>.srs rule set doubles as a country table
+	paths, _ := filepath.Glob(filepath.Join(C.GoString(ruleSetDir), "geoip-*.srs"))
+	for _, path := range paths {
+		code := strings.TrimSuffix(strings.TrimPrefix(filepath.Base(path), "geoip-"), ".srs")
+		if strings.ContainsAny(code, "!@") {
+			continue
+		}
+		if ruleSetContains(path, addr) {
+			return C.CString(fmt.Sprintf("{\"country\": %q}", strings.ToUpper(code)))
+		}
+	}
+	return C.CString("{\"country\": \"\"}")
+}
+
+// ruleSetContains reports whether any ip_cidr of a binary rule set covers addr.
+func ruleSetContains(path string, addr netip.Addr) bool {
+	f, err := os.Open(path)
+	if err != nil {
+		return false
+	}
+	defer f.Close()
+
+	ruleSet, err := srs.Read(f, true)
+	if err != nil {
+		return false
+	}
+	for _, rule := range ruleSet.Rules {
+		if rule.Type != "" && rule.Type != "default" {
+			continue
+		}
+		for _, cidr := range rule.DefaultOptions.IPCIDR {
+			prefix, err := netip.ParsePrefix(cidr)
+			if err != nil {
+				continue
+			}
+			if prefix.Contains(addr) {
+				return true
+			}
+		}
+	}
+	return false
+}
+
+// LibboxReload swaps the running instance for one built from configJSON and returns a
+// callResult. Only a "decode" failure leaves the old instance running.
+//
+//export LibboxReload
+func LibboxReload(configJSON *C.char) *C.char {
+	mu.Lock()
+	defer mu.Unlock()
+
+	if instance == nil {
+		return errorResult("state", errors.New("service not running"))
+	}
+
+	configStr := C.GoString(configJSON)
+
+	ctx, cancelFunc := context.WithCancel(context.Background())
+	ctx = service.ContextWithDefaultRegistry(include.Context(ctx))
+
+	// Decode before touching the running instance so a bad config keeps it alive
+	var options option.Options
+	if err := sjson.UnmarshalContext(ctx, []byte(configStr), &options); err != nil {
+		cancelFunc()
+		return errorResult("decode", err)
+	}
+
+	// The old instance must release its listeners and TUN device first
+	if cancel != nil {
+		cancel()
+		cancel = nil
+	}
+	if err := instance.Close(); err != nil && !strings.Contains(err.Error(), "service not running") {
+		instance = nil
+		cancelFunc()
+		return errorResult("close", err)
+	}
+	instance = nil
+
+	syncLogLevel(options.Log)
+
+	newInstance, err := box.New(box.Options{
+		Context: ctx,
+		Options: options,
+	})
+	if err != nil {
+		cancelFunc()
+		return errorResult("create", err)
+	}
+
+	if err := newInstance.Start(); err != nil {
+		newInstance.Close()
+		cancelFunc()
+		return errorResult("start", err)
+	}
+
+	instance = newInstance
+	cancel = cancelFunc
+	boxCtx = ctx
+	return okResult("")
+}
+
+// privilegedInbounds need root or CAP_NET_ADMIN just to be created, so an unprivileged
+// check leaves them out.
+var privilegedInbounds = map[string]bool{
+	sbconstant.TypeTun:      true,
+	sbconstant.TypeTProxy:   true,
+	sbconstant.TypeRedirect: true,
+}
+
+// LibboxCheck validates a config like `sing-box check`, building the instance without
+// starting it, and returns a callResult. It runs in the unprivileged app, so the TUN and
+// transparent proxy inbounds of a helper config are decoded but not instantiated.
+//
+//export LibboxCheck
+func LibboxCheck(configJSON *C.char) *C.char {
+	ctx, cancelFunc := context.WithCancel(context.Background())
+	defer cancelFunc()
+	ctx = service.ContextWithDefaultRegistry(include.Context(ctx))
+
+	var options option.Options
+	if err := sjson.UnmarshalContext(ctx, []byte(C.GoString(configJSON)), &options); err != nil {
+		return errorResult("decode", err)
+	}
+
+	inbounds := options.Inbounds[:0]
+	for _, inbound := range options.Inbounds {
+		if !privilegedInbounds[inbound.Type] {
+			inbounds = append(inbounds, inbound)
+		}
+	}
+	options.Inbounds = inbounds
+
+	checkInstance, err := box.New(box.Options{
+		Context: ctx,
+		Options: options,
+	})
+	if err != nil {
+		return errorResult("create", err)
+	}
+	checkInstance.Close()
+	return okResult("")
+}
+
 //export LibboxStartMobile
 func LibboxStartMobile(fd C.int, configJSON *C.char, logFD C.longlong) *C.char {
 	mu.Lock()
@@ -142,7 +405,8 @@ func LibboxStartMobile(fd C.int, configJSON *C.char, logFD C.longlong) *C.char {
 
 	ctx, cancelFunc := context.WithCancel(context.Background())
 	cancel = cancelFunc
-	ctx = include.Context(ctx)
+	ctx = service.ContextWithDefaultRegistry(include.Context(ctx))
+	boxCtx = ctx
 
 	// Inject FD into TUN inbounds if they don't have one
 	var rawConfig map[string]any
@@ -181,9 +445,7 @@ func LibboxStartMobile(fd C.int, configJSON *C.char, logFD C.longlong) *C.char {
 	}
 
 	// Sync current log level
-	if options.Log != nil {
-		currentLogLevel = options.Log.Level
-	}
+	syncLogLevel(options.Log)
 
 	instance, err = box.New(box.Options{
 		Context: ctx,
@@ -216,6 +478,7 @@ func LibboxTestOutbound(outboundJSON *C.char, targetURL *C.char, timeoutMS C.lon
 
 	ctx, cancel := context.WithTimeout(context.Background(), timeout)
 	defer cancel()
+	defer trackTest(cancel)()
 
 	// Ensure registries are initialized
 	ctx = include.Context(ctx)
@@ -228,14 +491,13 @@ func LibboxTestOutbound(outboundJSON *C.char, targetURL *C.char, timeoutMS C.lon
 		options.Tag = "test-outbound"
 	}
 
-	// Prepare minimal box options
+	// Prepare minimal box options; dial the server outside a running TUN like the batch test
 	boxOptions := box.Options{
 		Context: ctx,
 		Options: option.Options{
-			Log: &option.LogOptions{
-				Level: currentLogLevel,
-			},
+			Log:       logOptions(currentLogLevel),
 			Outbounds: []option.Outbound{options},
+			Route:     &option.RouteOptions{AutoDetectInterface: true},
 		},
 	}
 
@@ -257,10 +519,17 @@ func LibboxTestOutbound(outboundJSON *C.char, targetURL *C.char, timeoutMS C.lon
 
 	start := time.Now()
 
+	// Time until the outbound has a connection through the proxy, before the request
+	// (and any TLS with the target) goes over it. Set from the transport's dial goroutine.
+	var connectMS atomic.Int64
 	transport := &http.Transport{
 		DialContext: func(ctx context.Context, network, addr string) (net.Conn, error) {
 			mAddr := metadata.ParseSocksaddr(addr)
-			return out.DialContext(ctx, "tcp", mAddr)
+			conn, err := out.DialContext(ctx, "tcp", mAddr)
+			if err == nil {
+				connectMS.CompareAndSwap(0, time.Since(start).Milliseconds())
+			}
+			return conn, err
 		},
 		DisableKeepAlives: true,
 	}
@@ -268,6 +537,10 @@ func LibboxTestOutbound(outboundJSON *C.char, targetURL *C.char, timeoutMS C.lon
 	client := &http.Client{
 		Transport: transport,
 		Timeout:   timeout,
+		// A redirect (e.g. a captive portal or HTTP->HTTPS) is not a successful test
+		CheckRedirect: func(*http.Request, []*http.Request) error {
+			return http.ErrUseLastResponse
+		},
 	}
 
 	req, err := http.NewRequestWithContext(ctx, "GET", target, nil)
@@ -276,20 +549,26 @@ func LibboxTestOutbound(outboundJSON *C.char, targetURL *C.char, timeoutMS C.lon
 	}
 
 	// sing-box head requests might be blocked by some firewalls, but generate_204 usually works.
+	// Do returns once the response headers arrive, so this is time to first byte.
 	resp, err := client.Do(req)
 	if err != nil {
 		return C.CString(fmt.Sprintf("request error: %v", err))
 	}
+	latency := time.Since(start).Milliseconds()
 	defer resp.Body.Close()
 
-	if resp.StatusCode < 200 || resp.StatusCode >= 400 {
+	if resp.StatusCode != http.StatusOK && resp.StatusCode != http.StatusNoContent {
 		return C.CString(fmt.Sprintf("unexpected status code: %d", resp.StatusCode))
 	}
 
-	latency := time.Since(start).Milliseconds()
-	return C.CString(fmt.Sprintf("%d", latency))
+	// 0 reads as a failed test on the Rust side
+	connect := max(connectMS.Load(), 1)
+	return C.CString(fmt.Sprintf("{\"connect_ms\": %d, \"total_ms\": %d}", connect, max(latency, connect)))
 }
 
+// LibboxFetch GETs targetURL through a throwaway core holding only the given outbound
+// and returns a callResult carrying the response body.
+//
 //export LibboxFetch
 func LibboxFetch(outboundJSON *C.char, targetURL *C.char, timeoutMS C.longlong) *C.char {
 	configStr := C.GoString(outboundJSON)
@@ -298,6 +577,7 @@ func LibboxFetch(outboundJSON *C.char, targetURL *C.char, timeoutMS C.longlong)
 
 	ctx, cancel := context.WithTimeout(context.Background(), timeout)
 	defer cancel()
+	defer trackTest(cancel)()
 
 	// Ensure registries are initialized
 	ctx = include.Context(ctx)
@@ -314,7 +594,7 @@ func LibboxFetch(outboundJSON *C.char, targetURL *C.char, timeoutMS C.longlong)
 
 	var options option.Outbound
 	if err := sjson.UnmarshalContext(ctx, []byte(configStr), &options); err != nil {
-		return C.CString(fmt.Sprintf("decode config error: %v", err))
+		return errorResult("decode", err)
 	}
 	if options.Tag == "" {
 		options.Tag = "test-fetch"
@@ -324,9 +604,7 @@ func LibboxFetch(outboundJSON *C.char, targetURL *C.char, timeoutMS C.longlong)
 	boxOptions := box.Options{
 		Context: ctx,
 		Options: option.Options{
-			Log: &option.LogOptions{
-				Level: logLevel,
-			},
+			Log:       logOptions(logLevel),
 			Outbounds: []option.Outbound{options},
 		},
 	}
@@ -334,17 +612,17 @@ func LibboxFetch(outboundJSON *C.char, targetURL *C.char, timeoutMS C.longlong)
 	// box.New initializes everything but does not start anything until Start() is called.
 	tempInstance, err := box.New(boxOptions)
 	if err != nil {
-		return C.CString(fmt.Sprintf("create service error: %v", err))
+		return errorResult("create", err)
 	}
 	defer tempInstance.Close()
 
 	if err := tempInstance.Start(); err != nil {
-		return C.CString(fmt.Sprintf("start test service error: %v", err))
+		return errorResult("start", err)
 	}
 
 	out, ok := tempInstance.Outbound().Outbound(options.Tag)
 	if !ok {
-		return C.CString("outbound not found after creation")
+		return errorResult("create", fmt.Errorf("outbound %s not found after creation", options.Tag))
 	}
 
 	transport := &http.Transport{
@@ -362,21 +640,153 @@ func LibboxFetch(outboundJSON *C.char, targetURL *C.char, timeoutMS C.longlong)
 
 	req, err := http.NewRequestWithContext(ctx, "GET", target, nil)
 	if err != nil {
-		return C.CString(fmt.Sprintf("create request error: %v", err))
+		return errorResult("request", err)
 	}
 
 	resp, err := client.Do(req)
 	if err != nil {
-		return C.CString(fmt.Sprintf("request error: %v", err))
+		return errorResult("request", err)
 	}
 	defer resp.Body.Close()
 
 	body, err := io.ReadAll(resp.Body)
 	if err != nil {
-		return C.CString(fmt.Sprintf("read body error: %v", err))
+		return errorResult("read", err)
+	}
+
+	return okResult(string(body))
+}
+
+// In-flight test and fetch calls, canceled together by LibboxCancelTests.
+var (
+	runningMu    sync.Mutex
+	runningTests = map[int]context.CancelFunc{}
+	nextTestID   int
+)
+
+// trackTest registers the cancel func of a running test; call the result when done.
+func trackTest(cancel context.CancelFunc) func() {
+	runningMu.Lock()
+	defer runningMu.Unlock()
+	id := nextTestID
+	nextTestID++
+	runningTests[id] = cancel
+	return func() {
+		runningMu.Lock()
+		defer runningMu.Unlock()
+		delete(runningTests, id)
+	}
+}
+
+// LibboxCancelTests aborts every running test and fetch, then shuts the warm test
+// core down instead of leaving it for the idle timer.
+//
+//export LibboxCancelTests
+func LibboxCancelTests() {
+	runningMu.Lock()
+	for _, cancel := range runningTests {
+		cancel()
 	}
+	runningMu.Unlock()
 
-	return C.CString(string(body))
+	// A canceled batch returns promptly and releases the core
+	testMu.Lock()
+	defer testMu.Unlock()
+	closeTestInstanceLocked()
+}
+
+// testIdleTimeout is how long the test core stays warm after the last batch test.
+const testIdleTimeout = 5 * time.Second
+
+var (
+	testMu       sync.Mutex
+	testInstance *box.Box
+	testCancel   context.CancelFunc
+	testKey      string
+	testIdle     *time.Timer
+)
+
+// closeTestInstanceLocked tears down the warm test core. testMu must be held.
+func closeTestInstanceLocked() {
+	if testIdle != nil {
+		testIdle.Stop()
+		testIdle = nil
+	}
+	if testInstance != nil {
+		testInstance.Close()
+		testInstance = nil
+	}
+	if testCancel != nil {
+		testCancel()
+		testCancel = nil
+	}
+	testKey = ""
+}
+
+// testInstanceKey identifies what a test core can be reused for: the same outbounds,
+// in any order, tested against the same URL. Log level and cache path don't matter.
+func testInstanceKey(outbounds []map[string]interface{}, target string) (string, error) {
+	encoded := make([]string, 0, len(outbounds))
+	for _, out := range outbounds {
+		b, err := sjson.Marshal(out)
+		if err != nil {
+			return "", err
+		}
+		encoded = append(encoded, string(b))
+	}
+	sort.Strings(encoded)
+	key, err := sjson.Marshal(map[string]interface{}{"outbounds": encoded, "url": target})
+	return string(key), err
+}
+
+// warmTestInstanceLocked returns a started test core for configBytes, reusing the
+// current one when it was built for the same key. testMu must be held.
+func warmTestInstanceLocked(key string, configBytes []byte) (*box.Box, error) {
+	if testInstance != nil && testKey == key {
+		return testInstance, nil
+	}
+	closeTestInstanceLocked()
+
+	// The instance outlives a single call, so it gets its own context
+	instCtx, instCancel := context.WithCancel(context.Background())
+	instCtx = include.Context(instCtx)
+
+	var options option.Options
+	if err := sjson.UnmarshalContext(instCtx, configBytes, &options); err != nil {
+		instCancel()
+		return nil, fmt.Errorf("unmarshal options error: %v", err)
+	}
+
+	inst, err := box.New(box.Options{
+		Context: instCtx,
+		Options: options,
+	})
+	if err != nil {
+		instCancel()
+		return nil, fmt.Errorf("create service error: %v", err)
+	}
+	if err := inst.Start(); err != nil {
+		inst.Close()
+		instCancel()
+		return nil, fmt.Errorf("start test service error: %v", err)
+	}
+
+	testInstance = inst
+	testCancel = instCancel
+	testKey = key
+	return inst, nil
+}
+
+// scheduleTestIdleLocked (re)arms the idle teardown of the test core. testMu must be held.
+func scheduleTestIdleLocked() {
+	if testIdle != nil {
+		testIdle.Stop()
+	}
+	testIdle = time.AfterFunc(testIdleTimeout, func() {
+		testMu.Lock()
+		defer testMu.Unlock()
+		closeTestInstanceLocked()
+	})
 }
 
 //export LibboxTestBatch
@@ -387,6 +797,7 @@ func LibboxTestBatch(outboundsJSON *C.char, targetURL *C.char, timeoutMS C.longl
 
 	ctx, cancel := context.WithTimeout(context.Background(), timeout+2*time.Second)
 	defer cancel()
+	defer trackTest(cancel)()
 
 	ctx = include.Context(ctx)
 
@@ -394,6 +805,8 @@ func LibboxTestBatch(outboundsJSON *C.char, targetURL *C.char, timeoutMS C.longl
 	var wrapper struct {
 		Outbounds []map[string]interface{} `json:"outbounds"`
 		LogLevel  string                   `json:"log_level"`
+		// Cache file shared by test cores; the running core holds the lock on cache.db
+		CachePath string                   `json:"cache_path"`
 	}
 
 	var rawOutbounds []map[string]interface{}
@@ -408,7 +821,7 @@ func LibboxTestBatch(outboundsJSON *C.char, targetURL *C.char, timeoutMS C.longl
 	} else {
 		// Fallback: try unmarshal as array (backward compatibility)
 		if err := sjson.UnmarshalContext(ctx, []byte(configStr), &rawOutbounds); err != nil {
-			return C.CString(fmt.Sprintf("{\"error\": \"decode config error: %v\"}", err))
+			return errorJSON("decode config error: %v", err)
 		}
 	}
 
@@ -424,6 +837,11 @@ func LibboxTestBatch(outboundsJSON *C.char, targetURL *C.char, timeoutMS C.longl
 		}
 	}
 
+	instanceKey, err := testInstanceKey(rawOutbounds, target)
+	if err != nil {
+		return errorJSON("marshal outbounds error: %v", err)
+	}
+
 	// 3. Create URLTest Group Outbound
 	urlTestGroup := map[string]interface{}{
 		"type":      "urltest",
@@ -451,10 +869,13 @@ func LibboxTestBatch(outboundsJSON *C.char, targetURL *C.char, timeoutMS C.longl
 		})
 	}
 
+	logConfig := map[string]interface{}{"level": logLevel}
+	if logLevel == "disabled" {
+		logConfig = map[string]interface{}{"disabled": true}
+	}
+
 	fullConfig := map[string]interface{}{
-		"log": map[string]interface{}{
-			"level": logLevel,
-		},
+		"log":       logConfig,
 		"outbounds": rawOutbounds,
 		"dns": map[string]interface{}{
 			"servers": []map[string]interface{}{
@@ -476,32 +897,35 @@ func LibboxTestBatch(outboundsJSON *C.char, targetURL *C.char, timeoutMS C.longl
 			"auto_detect_interface": true,
 		},
 	}
+	if wrapper.CachePath != "" {
+		fullConfig["experimental"] = map[string]interface{}{
+			"cache_file": map[string]interface{}{
+				"enabled": true,
+				"path":    wrapper.CachePath,
+			},
+		}
+	}
 
 	configBytes, err := sjson.Marshal(fullConfig)
 	if err != nil {
-		return C.CString(fmt.Sprintf("{\"error\": \"marshal config error: %v\"}", err))
+		return errorJSON("marshal config error: %v", err)
 	}
 
-	var options option.Options
-	if err := sjson.UnmarshalContext(ctx, configBytes, &options); err != nil {
-		return C.CString(fmt.Sprintf("{\"error\": \"unmarshal options error: %v\"}", err))
-	}
+	// 5. Start Box, or reuse the warm one when the same nodes are tested again.
+	// Holding testMu for the whole test also serializes concurrent callers.
+	testMu.Lock()
+	defer testMu.Unlock()
 
-	// 5. Start Box
-	boxOptions := box.Options{
-		Context: ctx,
-		Options: options,
+	// Canceled while waiting for another batch; don't bring the core back up
+	if ctx.Err() != nil {
+		return errorJSON("%v", ctx.Err())
 	}
 
-	tempInstance, err := box.New(boxOptions)
+	tempInstance, err := warmTestInstanceLocked(instanceKey, configBytes)
 	if err != nil {
-		return C.CString(fmt.Sprintf("{\"error\": \"create service error: %v\"}", err))
-	}
-	defer tempInstance.Close()
-
-	if err := tempInstance.Start(); err != nil {
-		return C.CString(fmt.Sprintf("{\"error\": \"start test service error: %v\"}", err))
+		return errorJSON("%v", err)
 	}
+	defer scheduleTestIdleLocked()
 
 	// 6. Access the Group and Trigger Test
 	// We need to access the internal adapter.
@@ -509,7 +933,7 @@ func LibboxTestBatch(outboundsJSON *C.char, targetURL *C.char, timeoutMS C.longl
 	outboundManager := tempInstance.Outbound()
 	testGroup, ok := outboundManager.Outbound("global-test-group")
 	if !ok {
-		return C.CString("{\"error\": \"test group not found\"}")
+		return errorJSON("test group not found")
 	}
 
 	// We need to cast it to the *group.URLTest type to call URLTest method.
@@ -518,13 +942,14 @@ func LibboxTestBatch(outboundsJSON *C.char, targetURL *C.char, timeoutMS C.longl
 
 	urlTestInstance, ok := testGroup.(*group.URLTest)
 	if !ok {
-		return C.CString(fmt.Sprintf("{\"error\": \"invalid group type: %T\"}", testGroup))
+		return errorJSON("invalid group type: %T", testGroup)
 	}
 
 	// 7. Run Test via Native API
 	results, err := urlTestInstance.URLTest(ctx)
-	if err != nil {
-		return C.CString(fmt.Sprintf("{\"error\": \"url test failed: %v\"}", err))
+	// After a cancel, the nodes that finished are still worth reporting
+	if err != nil && len(results) == 0 {
+		return errorJSON("url test failed: %v", err)
 	}
 
 	// 8. Marshal Results
@@ -10,14 +10,22 @@ use std::sync::{Arc, Mutex};
 const SOCKET_PATH: &str = "/var/run/tunnet.sock";
 #[cfg(windows)]
 const PIPE_NAME: &str = r"\\.\pipe\tunnet";
-
+#[cfg(unix)]
+const TOKEN_PATH: &str = "/etc/tunnet/helper.token";
+use app_lib::HELPER_PROTOCOL_VERSION as PROTOCOL_VERSION;
 use app_lib::libbox;
+use subtle::ConstantTimeEq;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 
 #[derive(Serialize, Deserialize, Debug)]
 struct Request {
     command: String,
     payload: Option<String>,
+    #[serde(default)]
+    token: Option<String>,
+    // 0 means a client from before the handshake existed
+    #[serde(default)]
+    protocol_version: u32,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -26,6 +34,22 @@ struct Response {
     message: String,
 }
 
+/// Wire form of a `Response`, stamped with the protocol this helper speaks.
+#[derive(Serialize)]
+struct VersionedResponse<'a> {
+    #[serde(flatten)]
+    response: &'a Response,
+    protocol_version: u32,
+}
+
+fn encode_response(response: &Response) -> String {
+    serde_json::to_string(&VersionedResponse {
+        response,
+        protocol_version: PROTOCOL_VERSION,
+    })
+    .unwrap()
+}
+
 use std::fs::File;
 use std::io::BufWriter;
 
@@ -33,6 +57,8 @@ struct AppState {
     log_writer: Mutex<Option<BufWriter<File>>>,
     proxy_running: Mutex<bool>,
     libbox_log_file: Mutex<Option<File>>,
+    // Shared secret from install time; None keeps older installs working unauthenticated
+    auth_token: Option<String>,
 }
 
 fn log(state: &Arc<AppState>, msg: &str) {
@@ -208,6 +234,7 @@ async fn initialize_app_state() -> Result<Arc<AppState>, Box<dyn Error>> {
 
     println!("Tunnet Helper (Libbox) started");
 
+    // Read back by the app's get_helper_log (keep in sync with helper_client.rs)
     let log_path = if cfg!(windows) {
         PathBuf::from(std::env::var("ProgramData").unwrap_or("C:\\ProgramData".into()))
             .join("Tunnet")
@@ -223,12 +250,31 @@ async fn initialize_app_state() -> Result<Arc<AppState>, Box<dyn Error>> {
         .ok()
         .map(|f| BufWriter::new(f));
 
+    #[cfg(unix)]
+    let token_path = PathBuf::from(TOKEN_PATH);
+    #[cfg(windows)]
+    let token_path = std::env::current_exe()?
+        .parent()
+        .map(|p| p.join("helper.token"))
+        .unwrap_or_default();
+    let auth_token = fs::read_to_string(&token_path)
+        .ok()
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty());
+
     let app_state = Arc::new(AppState {
         log_writer: Mutex::new(log_file),
         proxy_running: Mutex::new(false),
         libbox_log_file: Mutex::new(None),
+        auth_token,
     });
 
+    if app_state.auth_token.is_some() {
+        log(&app_state, "IPC token loaded, requests must authenticate");
+    } else {
+        log(&app_state, "No IPC token found, accepting unauthenticated requests");
+    }
+
     // Verify Libbox linkage
     unsafe {
         let hello_ptr = libbox::LibboxHello();
@@ -416,6 +462,21 @@ fn main() -> Result<(), Box<dyn Error>> {
             }
         }
 
+        // Optional: install the IPC token, readable by SYSTEM and Administrators only
+        if let Some(token_src) = args.get(3) {
+            let token_dest = target_dir.join("helper.token");
+            match std::fs::copy(token_src, &token_dest) {
+                Ok(_) => {
+                    let _ = std::process::Command::new("icacls")
+                        .arg(&token_dest)
+                        .args(["/inheritance:r", "/grant:r", "*S-1-5-18:F", "*S-1-5-32-544:F"])
+                        .creation_flags(CREATE_NO_WINDOW)
+                        .output();
+                }
+                Err(e) => eprintln!("Failed to install IPC token: {}", e),
+            }
+        }
+
         // 4. Call service-install on the NEW binary
         let new_binary = target_dir.join("tunnet-helper.exe");
         println!("Executing service-install on new binary: {:?}", new_binary);
@@ -444,20 +505,77 @@ fn main() -> Result<(), Box<dyn Error>> {
 async fn main() -> Result<(), Box<dyn Error>> {
     let app_state = initialize_app_state().await?;
     let notify = Arc::new(tokio::sync::Notify::new());
-    // Setup signal handler for Unix to trigger notify?
-    // For now simpler to just run it.
-    // But we need to match signature.
+
+    // launchctl/systemd stop the helper with SIGTERM; turn it into an orderly shutdown so
+    // the core can remove its TUN interface and routes before the process exits.
+    let signal_notify = notify.clone();
+    tokio::spawn(async move {
+        use tokio::signal::unix::{signal, SignalKind};
+        let (Ok(mut term), Ok(mut int)) = (signal(SignalKind::terminate()), signal(SignalKind::interrupt())) else {
+            return;
+        };
+        tokio::select! {
+            _ = term.recv() => {}
+            _ = int.recv() => {}
+        }
+        signal_notify.notify_one();
+    });
+
     run_listener(app_state, notify).await
 }
 
+/// How long a running core gets to tear down on shutdown before the helper exits anyway.
+#[cfg(unix)]
+const SHUTDOWN_GRACE: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// How often the helper confirms that a core it believes is running still is.
+const HEALTH_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Check `proxy_running` against libbox and clear it when the core is gone,
+/// e.g. after a failed reload closed the instance. Returns the verified state.
+fn verify_running(state: &Arc<AppState>) -> bool {
+    if !*state.proxy_running.lock().unwrap() {
+        return false;
+    }
+    let problem = unsafe {
+        let err_ptr = libbox::LibboxHealth();
+        (!err_ptr.is_null()).then(|| CStr::from_ptr(err_ptr).to_string_lossy().into_owned())
+    };
+    match problem {
+        Some(reason) => {
+            log(state, &format!("Core is no longer serving ({}), marking proxy stopped", reason));
+            *state.proxy_running.lock().unwrap() = false;
+            *state.libbox_log_file.lock().unwrap() = None;
+            false
+        }
+        None => true,
+    }
+}
+
+fn spawn_health_monitor(state: Arc<AppState>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(HEALTH_CHECK_INTERVAL);
+        loop {
+            interval.tick().await;
+            let state = state.clone();
+            let _ = tokio::task::spawn_blocking(move || verify_running(&state)).await;
+        }
+    });
+}
+
 #[cfg(unix)]
 async fn run_listener(
     app_state: Arc<AppState>,
-    _shutdown: Arc<tokio::sync::Notify>,
+    shutdown: Arc<tokio::sync::Notify>,
 ) -> Result<(), Box<dyn Error>> {
     use tokio::net::UnixListener;
 
     if Path::new(SOCKET_PATH).exists() {
+        // Only reclaim the socket if nobody answers on it; a live helper keeps its socket
+        if std::os::unix::net::UnixStream::connect(SOCKET_PATH).is_ok() {
+            return Err("Another helper instance is already listening".into());
+        }
+        log(&app_state, "Removing stale socket left by a previous helper");
         fs::remove_file(SOCKET_PATH)?;
     }
 
@@ -467,9 +585,25 @@ async fn run_listener(
     }
 
     println!("Helper listening on Unix socket: {:?}", SOCKET_PATH);
+    spawn_health_monitor(app_state.clone());
 
     loop {
-        match listener.accept().await {
+        let accepted = tokio::select! {
+            accepted = listener.accept() => accepted,
+            _ = shutdown.notified() => {
+                log(&app_state, "Shutdown signal received. Stopping core and listener.");
+                if *app_state.proxy_running.lock().unwrap() {
+                    let state = app_state.clone();
+                    let stop = tokio::task::spawn_blocking(move || stop_libbox(&state));
+                    if tokio::time::timeout(SHUTDOWN_GRACE, stop).await.is_err() {
+                        log(&app_state, "Core did not stop within the grace period, exiting anyway");
+                    }
+                }
+                let _ = fs::remove_file(SOCKET_PATH);
+                return Ok(());
+            }
+        };
+        match accepted {
             Ok((mut stream, _)) => {
                 let state = app_state.clone();
                 tokio::spawn(async move {
@@ -484,7 +618,7 @@ async fn run_listener(
                                         message: format!("JSON error: {}", e),
                                     },
                                 };
-                                let response_str = serde_json::to_string(&response).unwrap();
+                                let response_str = encode_response(&response);
                                 let _ = stream.write_all(response_str.as_bytes()).await;
                             }
                         }
@@ -510,6 +644,7 @@ async fn run_listener(
         &format!("Helper listening on Named Pipe: {}", PIPE_NAME),
     );
     println!("Helper listening on Named Pipe: {}", PIPE_NAME);
+    spawn_health_monitor(app_state.clone());
 
     // Create the first Named Pipe instance with permissive security
     // This allows non-admin processes to connect to admin-created pipe
@@ -593,7 +728,7 @@ async fn handle_connection(
                         message: format!("JSON error: {}", e),
                     },
                 };
-                let mut response_str = serde_json::to_string(&response).unwrap();
+                let mut response_str = encode_response(&response);
                 response_str.push('\n'); // Append newline for delimiters
                                          // Sending response
 
@@ -754,6 +889,14 @@ struct StartPayload {
 fn start_libbox(payload: StartPayload, state: &Arc<AppState>) -> Response {
     log(state, "Start Libbox requested");
 
+    if let Err(e) = libbox::check_core_version(&libbox::core_version()) {
+        log(state, &e);
+        return Response {
+            status: "error".into(),
+            message: e,
+        };
+    }
+
     // We don't write config to file anymore, we pass it directly via memory!
     // But wait, the config might contain relative paths (geodatabase etc).
     // Sing-box usually resolves paths relative to Working Directory.
@@ -885,12 +1028,122 @@ fn stop_libbox(state: &Arc<AppState>) -> Response {
     }
 }
 
+fn reload_libbox(payload: StartPayload, state: &Arc<AppState>) -> Response {
+    log(state, "Reload Libbox requested");
+
+    if !*state.proxy_running.lock().unwrap() {
+        return Response {
+            status: "error".into(),
+            message: "Proxy not running".into(),
+        };
+    }
+
+    let c_config = match CString::new(payload.config) {
+        Ok(c) => c,
+        Err(_) => {
+            return Response {
+                status: "error".into(),
+                message: "Config contains null byte".into(),
+            }
+        }
+    };
+
+    // The log file stays open across reloads, so we only swap the instance
+    let reply = unsafe {
+        let ptr = libbox::LibboxReload(c_config.as_ptr());
+        if ptr.is_null() {
+            String::new()
+        } else {
+            CStr::from_ptr(ptr).to_string_lossy().into_owned()
+        }
+    };
+    if let Err(e) = libbox::parse_call_result(&reply) {
+        log(state, &format!("LibboxReload failed: {}", e));
+        // Only a config decode error leaves the old instance running
+        if e.kind != "decode" {
+            *state.proxy_running.lock().unwrap() = false;
+        }
+        return Response {
+            status: "error".into(),
+            message: e.to_string(),
+        };
+    }
+
+    log(state, "LibboxReload success");
+    Response {
+        status: "success".into(),
+        message: "Proxy reloaded via Libbox".into(),
+    }
+}
+
+fn helper_stats(state: &Arc<AppState>) -> Response {
+    if !*state.proxy_running.lock().unwrap() {
+        return Response {
+            status: "error".into(),
+            message: "Proxy not running".into(),
+        };
+    }
+
+    let raw = unsafe {
+        let ptr = libbox::LibboxStats();
+        if ptr.is_null() {
+            return Response {
+                status: "error".into(),
+                message: "No stats returned".into(),
+            };
+        }
+        CStr::from_ptr(ptr).to_string_lossy().into_owned()
+    };
+
+    match serde_json::from_str::<serde_json::Value>(&raw) {
+        Ok(v) => {
+            if let Some(err) = v.get("error").and_then(|e| e.as_str()) {
+                return Response {
+                    status: "error".into(),
+                    message: err.to_string(),
+                };
+            }
+            Response {
+                status: "success".into(),
+                message: raw,
+            }
+        }
+        Err(e) => Response {
+            status: "error".into(),
+            message: format!("Invalid stats: {}", e),
+        },
+    }
+}
+
 // We can remove kill_process_on_port or keep it as a no-op / fallback if user port is held by someone else?
 // But Libbox runs in-process. If Libbox fails to bind, it returns error.
 // We can't kill "ourself" to free port.
 // So we just return success/fail.
 
 fn handle_request(req: Request, state: &Arc<AppState>) -> Response {
+    if let Some(expected) = &state.auth_token {
+        // Constant time, so response timing doesn't reveal how much of a guess matched
+        let presented = req.token.as_deref().unwrap_or_default();
+        if !bool::from(presented.as_bytes().ct_eq(expected.as_bytes())) {
+            log(state, &format!("Rejected unauthenticated '{}' request", req.command));
+            return Response {
+                status: "error".into(),
+                message: "Unauthorized".into(),
+            };
+        }
+    }
+
+    // ping and version still answer, so a mismatched app can tell it needs a reinstall
+    if req.protocol_version != PROTOCOL_VERSION && !matches!(req.command.as_str(), "ping" | "version") {
+        return Response {
+            status: "error".into(),
+            message: format!(
+                "Client protocol {} does not match helper protocol {}, please reinstall the helper",
+                req.protocol_version, PROTOCOL_VERSION
+            ),
+        };
+    }
+
     match req.command.as_str() {
         "start" => {
             if let Some(payload_str) = req.payload {
@@ -908,9 +1161,25 @@ fn handle_request(req: Request, state: &Arc<AppState>) -> Response {
                 }
             }
         }
+        "reload" => {
+            if let Some(payload_str) = req.payload {
+                match serde_json::from_str::<StartPayload>(&payload_str) {
+                    Ok(payload) => reload_libbox(payload, state),
+                    Err(_) => Response {
+                        status: "error".into(),
+                        message: "Invalid payload".into(),
+                    },
+                }
+            } else {
+                Response {
+                    status: "error".into(),
+                    message: "Missing payload".into(),
+                }
+            }
+        }
         "stop" => stop_libbox(state),
         "status" => {
-            let running = *state.proxy_running.lock().unwrap();
+            let running = verify_running(state);
             Response {
                 status: if running { "running" } else { "stopped" }.into(),
                 message: if running {
@@ -922,11 +1191,23 @@ fn handle_request(req: Request, state: &Arc<AppState>) -> Response {
             }
         }
 
+        "stats" => helper_stats(state),
+
+        "ping" => Response {
+            status: "success".into(),
+            message: "pong".into(),
+        },
+
         "version" => Response {
             status: "success".into(),
             message: env!("CARGO_PKG_VERSION").into(),
         },
 
+        "core_version" => Response {
+            status: "success".into(),
+            message: libbox::core_version(),
+        },
+
         "kill_port" => Response {
             status: "success".into(),
             message: "Not needed in Libbox mode".into(),
@@ -26,6 +26,8 @@ pub enum ConfigMode {
 
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct LogConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub disabled: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub level: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -57,6 +59,9 @@ pub struct Inbound {
     pub auto_route: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub strict_route: Option<bool>,
+    // Linux only, requires nftables
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auto_redirect: Option<bool>,
     // Added for compatibility with Hiddify / P2P
     #[serde(skip_serializing_if = "Option::is_none")]
     pub endpoint_independent_nat: Option<bool>,
@@ -85,10 +90,16 @@ pub struct Outbound {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub server_port: Option<u16>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub server_ports: Option<Vec<String>>, // hysteria2 port hopping, "start:end"
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub method: Option<String>, // shadowsocks
     #[serde(skip_serializing_if = "Option::is_none")]
     pub password: Option<String>, // shadowsocks, trojan
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub plugin: Option<String>, // shadowsocks SIP003 plugin
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub plugin_opts: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub uuid: Option<String>, // vmess, vless
     #[serde(skip_serializing_if = "Option::is_none")]
     pub security: Option<String>, // vmess
@@ -106,6 +117,15 @@ pub struct Outbound {
     pub packet_encoding: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub connect_timeout: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tcp_fast_open: Option<bool>,
+    // Dial binding, takes precedence over route.auto_detect_interface for this outbound
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bind_interface: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub inet4_bind_address: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub multiplex: Option<MultiplexConfig>,
     // Hysteria2 / TUIC fields
     #[serde(skip_serializing_if = "Option::is_none")]
     pub up_mbps: Option<u32>,
@@ -131,6 +151,26 @@ pub struct Outbound {
     pub interval: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tolerance: Option<u16>,
+    /// Options Tunnet does not model, passed through to sing-box as-is
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MultiplexConfig {
+    pub enabled: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub protocol: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub brutal: Option<BrutalConfig>,
+}
+
+/// TCP Brutal over multiplex; the server needs the brutal kernel module
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BrutalConfig {
+    pub enabled: bool,
+    pub up_mbps: u32,
+    pub down_mbps: u32,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -148,6 +188,8 @@ pub struct TransportConfig {
     pub path: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub headers: Option<HashMap<String, String>>,
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -165,6 +207,18 @@ pub struct OutboundTls {
     pub reality: Option<RealityConfig>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub disable_sni: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ech: Option<EchConfig>,
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EchConfig {
+    pub enabled: bool,
+    /// PEM lines of the ECHConfigList; when absent sing-box fetches it from DNS HTTPS records
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub config: Option<Vec<String>>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -185,7 +239,8 @@ pub struct Route {
     pub rules: Vec<RouteRule>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub rule_set: Option<Vec<RuleSet>>,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    /// Outbound for connections no rule matched
+    #[serde(rename = "final", skip_serializing_if = "Option::is_none")]
     pub final_outbound: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub auto_detect_interface: Option<bool>,
@@ -210,6 +265,14 @@ pub struct RouteRule {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub port: Option<Vec<u16>>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub port_range: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub network: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub process_name: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub process_path: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub outbound: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub rule_set: Option<Vec<String>>,
@@ -217,6 +280,9 @@ pub struct RouteRule {
     pub ip_is_private: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub action: Option<String>,
+    /// `reject` action only: "default" answers with RST/ICMP, "drop" discards silently
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub method: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub sniff: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -256,6 +322,160 @@ pub struct ClashApiConfig {
     pub secret: Option<String>,
 }
 
+/// Bandwidth hint in Mbps from a node's `up`/`down` ("100", "100 Mbps", "100m").
+pub fn parse_mbps(value: &str) -> Option<u32> {
+    let value = value.trim();
+    let digits = value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len());
+    let unit = value[digits..].trim().to_lowercase();
+    if !matches!(unit.as_str(), "" | "m" | "mb" | "mbps" | "mb/s" | "mbit" | "mbit/s") {
+        return None;
+    }
+    value[..digits].parse().ok().filter(|mbps| *mbps > 0)
+}
+
+/// Bandwidths for Hysteria v1 nodes that declare none; sing-box refuses to start the
+/// outbound without both. Modest values, since overstating them causes loss.
+pub const HYSTERIA_DEFAULT_UP_MBPS: u32 = 10;
+pub const HYSTERIA_DEFAULT_DOWN_MBPS: u32 = 50;
+
+/// `Node::protocol` values `with_node_outbound` builds a proxy outbound for. ShadowTLS is
+/// left out: it only wraps a Shadowsocks outbound, which a single node does not carry.
+pub const NODE_PROTOCOLS: &[&str] = &[
+    "vmess", "vless", "shadowsocks", "ss", "trojan", "hysteria2", "hy2", "hysteria", "hy", "tuic",
+    "anytls",
+];
+
+/// Outbound types that run over QUIC and never open a TCP connection.
+pub const QUIC_OUTBOUND_TYPES: &[&str] = &["hysteria", "hysteria2", "tuic"];
+
+/// SIP003 options for simple-obfs ("obfs=http;obfs-host=example.com"), which sing-box
+/// runs natively as the `obfs-local` plugin. `None` without a mode.
+pub fn simple_obfs_opts(mode: Option<&str>, host: Option<&str>) -> Option<String> {
+    let mode = mode.filter(|m| !m.is_empty())?;
+    Some(match host.filter(|h| !h.is_empty()) {
+        Some(host) => format!("obfs={};obfs-host={}", mode, host),
+        None => format!("obfs={}", mode),
+    })
+}
+
+/// `multiplex` block enabling TCP Brutal, or `None` without both bandwidths.
+pub fn brutal_multiplex(up: Option<&str>, down: Option<&str>) -> Option<MultiplexConfig> {
+    let up_mbps = up.and_then(parse_mbps)?;
+    let down_mbps = down.and_then(parse_mbps)?;
+    Some(MultiplexConfig {
+        enabled: true,
+        protocol: Some("h2mux".to_string()),
+        brutal: Some(BrutalConfig {
+            enabled: true,
+            up_mbps,
+            down_mbps,
+        }),
+    })
+}
+
+/// Decode a base64 ECHConfigList as found in share links (standard or URL-safe, padded or not).
+pub fn decode_ech_config(config: &str) -> Result<Vec<u8>, String> {
+    use base64::{engine::general_purpose, Engine as _};
+    let trimmed: String = config.chars().filter(|c| !c.is_whitespace()).collect();
+    [
+        general_purpose::STANDARD,
+        general_purpose::URL_SAFE,
+        general_purpose::STANDARD_NO_PAD,
+        general_purpose::URL_SAFE_NO_PAD,
+    ]
+    .iter()
+    .find_map(|engine| engine.decode(&trimmed).ok())
+    .filter(|bytes| !bytes.is_empty())
+    .ok_or_else(|| "ECH config is not valid base64".to_string())
+}
+
+/// sing-box `tls.ech` block: `None` when ECH is off, otherwise the config wrapped in
+/// the PEM envelope sing-box expects (or no config, to resolve it via DNS).
+pub fn ech_config(enabled: bool, config: Option<&str>) -> Result<Option<EchConfig>, String> {
+    if !enabled {
+        return Ok(None);
+    }
+    let config = match config.map(str::trim).filter(|c| !c.is_empty()) {
+        Some(c) => {
+            use base64::{engine::general_purpose, Engine as _};
+            let encoded = general_purpose::STANDARD.encode(decode_ech_config(c)?);
+            let mut lines = vec!["-----BEGIN ECH CONFIGS-----".to_string()];
+            lines.extend(
+                encoded
+                    .as_bytes()
+                    .chunks(64)
+                    .map(|chunk| String::from_utf8_lossy(chunk).to_string()),
+            );
+            lines.push("-----END ECH CONFIGS-----".to_string());
+            Some(lines)
+        }
+        None => None,
+    };
+    Ok(Some(EchConfig {
+        enabled: true,
+        config,
+    }))
+}
+
+/// Recursively merge `patch` into `base`: objects merge key by key, anything else replaces.
+pub fn merge_json(base: &mut serde_json::Value, patch: &serde_json::Value) {
+    match (base, patch) {
+        (serde_json::Value::Object(base), serde_json::Value::Object(patch)) => {
+            for (key, value) in patch {
+                merge_json(base.entry(key.clone()).or_insert(serde_json::Value::Null), value);
+            }
+        }
+        (base, patch) => *base = patch.clone(),
+    }
+}
+
+impl Outbound {
+    /// Overlay user-supplied sing-box options (e.g. `{"tls": {"ech": {...}}}`) on the
+    /// generated outbound. Nested objects merge, so extras can extend modeled blocks.
+    /// The merge happens on the JSON and merged keys stay untyped in `extra`, so an
+    /// option Tunnet models differently (or only partly) still reaches sing-box.
+    pub fn with_extra(
+        mut self,
+        extra: &serde_json::Map<String, serde_json::Value>,
+    ) -> Result<Self, serde_json::Error> {
+        if extra.is_empty() {
+            return Ok(self);
+        }
+        // Keys the builder already kept untyped (Hysteria v1's string `obfs`) never
+        // round-trip through the typed fields
+        let mut untyped = std::mem::take(&mut self.extra);
+        let mut base = match serde_json::to_value(&self)? {
+            serde_json::Value::Object(map) => map,
+            _ => {
+                self.extra = untyped;
+                return Ok(self);
+            }
+        };
+        for (key, patch) in extra {
+            let mut value = untyped
+                .remove(key)
+                .or_else(|| base.remove(key))
+                .unwrap_or(serde_json::Value::Null);
+            merge_json(&mut value, patch);
+            // `type` and `tag` identify the outbound, so they stay typed
+            if key == "type" || key == "tag" {
+                base.insert(key.clone(), value);
+            } else {
+                untyped.insert(key.clone(), value);
+            }
+        }
+        let mut outbound: Outbound = serde_json::from_value(serde_json::Value::Object(base))?;
+        outbound.extra.extend(untyped);
+        Ok(outbound)
+    }
+}
+
+/// `external_controller` listen address: loopback unless the controller is shared with the LAN.
+pub fn clash_api_controller(port: u16, allow_lan: bool) -> String {
+    let host = if allow_lan { "0.0.0.0" } else { "127.0.0.1" };
+    format!("{}:{}", host, port)
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct CacheFileConfig {
     pub enabled: bool,
@@ -309,6 +529,70 @@ pub struct DnsRule {
     pub server: Option<String>,
 }
 
+/// Official sing-box rule-set download URLs (primary, CDN mirror) for a `geoip-*` / `geosite-*` tag
+pub fn remote_rule_set_urls(tag: &str) -> Option<(String, String)> {
+    let repo = if tag.starts_with("geoip-") {
+        "sing-geoip"
+    } else if tag.starts_with("geosite-") {
+        "sing-geosite"
+    } else {
+        return None;
+    };
+    Some((
+        format!("https://raw.githubusercontent.com/SagerNet/{}/rule-set/{}.srs", repo, tag),
+        format!("https://testingcf.jsdelivr.net/gh/SagerNet/{}@rule-set/{}.srs", repo, tag),
+    ))
+}
+
+/// Well-known `geosite-*` categories offered for autocomplete even before they are downloaded.
+pub const COMMON_GEOSITE_CATEGORIES: &[&str] = &[
+    "ads", "cn", "geolocation-cn", "geolocation-!cn", "private", "category-ads-all",
+    "google", "youtube", "netflix", "disney", "spotify", "telegram", "twitter", "facebook",
+    "instagram", "tiktok", "openai", "github", "apple", "microsoft", "amazon", "steam",
+    "bilibili",
+];
+
+/// Well-known `geoip-*` categories offered for autocomplete even before they are downloaded.
+pub const COMMON_GEOIP_CATEGORIES: &[&str] = &[
+    "cn", "private", "us", "jp", "hk", "tw", "sg", "kr", "de", "gb", "telegram", "google",
+    "netflix", "facebook", "twitter",
+];
+
+/// sing-box DNS strategy for the app's `dns_strategy` setting.
+/// Disabling IPv6 forces `ipv4_only` regardless of the preference.
+pub fn effective_dns_strategy(dns_strategy: &str, ipv6: bool) -> String {
+    if !ipv6 {
+        return "ipv4_only".to_string();
+    }
+    match dns_strategy {
+        "ipv4" => "prefer_ipv4",
+        "ipv6" => "prefer_ipv6",
+        "only4" => "ipv4_only",
+        "only6" => "ipv6_only",
+        s => s,
+    }
+    .to_string()
+}
+
+/// UDP packet encoding for vless/vmess outbounds. Nodes without a value get `xudp`;
+/// an explicit `none` (or empty string) leaves the field out entirely.
+pub fn effective_packet_encoding(packet_encoding: Option<&str>) -> Option<String> {
+    match packet_encoding.map(str::trim) {
+        None => Some("xudp".to_string()),
+        Some("") | Some("none") => None,
+        Some(p) => Some(p.to_string()),
+    }
+}
+
+/// `server_name` is dropped when SNI is disabled so sing-box sends no SNI at all.
+fn tls_server_name(server_name: Option<String>, disable_sni: Option<bool>) -> Option<String> {
+    if disable_sni == Some(true) {
+        None
+    } else {
+        server_name
+    }
+}
+
 impl SingBoxConfig {
     pub fn new(
         clash_api_port: Option<u16>,
@@ -403,10 +687,11 @@ impl SingBoxConfig {
         let dns = DnsConfig {
             servers,
             rules: vec![DnsRule {
-                inbound: Some(vec![match mode {
-                    ConfigMode::TunOnly => "tun-in".to_string(),
-                    _ => "mixed-in".to_string(),
-                }]),
+                inbound: Some(match mode {
+                    ConfigMode::TunOnly => vec!["tun-in".to_string()],
+                    ConfigMode::SystemProxyOnly => vec!["mixed-in".to_string()],
+                    ConfigMode::Combined => vec!["tun-in".to_string(), "mixed-in".to_string()],
+                }),
                 outbound: None,
                 domain: None,
                 domain_suffix: None,
@@ -440,11 +725,6 @@ impl SingBoxConfig {
             });
         }
 
-        route_rules.push(RouteRule {
-            outbound: Some(proxy_tag.to_string()),
-            ..Default::default()
-        });
-
         let mut experimental = ExperimentalConfig {
             cache_file: Some(CacheFileConfig {
                 enabled: true,
@@ -456,7 +736,7 @@ impl SingBoxConfig {
 
         if let Some(port) = clash_api_port {
             experimental.clash_api = Some(ClashApiConfig {
-                external_controller: format!("127.0.0.1:{}", port),
+                external_controller: clash_api_controller(port, false),
                 external_ui: None,
                 secret: None,
             });
@@ -464,6 +744,7 @@ impl SingBoxConfig {
 
         Self {
             log: Some(LogConfig {
+                disabled: None,
                 level: Some("info".to_string()),
                 output: None,
                 timestamp: Some(false),
@@ -474,7 +755,7 @@ impl SingBoxConfig {
             route: Some(Route {
                 rules: route_rules,
                 rule_set: None,
-                final_outbound: None,
+                final_outbound: Some(proxy_tag.to_string()),
                 auto_detect_interface: Some(true),
                 default_domain_resolver: Some("local".to_string()),
             }),
@@ -493,6 +774,7 @@ impl SingBoxConfig {
             reuse_addr: None,
             auto_route: None,
             strict_route: None,
+            auto_redirect: None,
             endpoint_independent_nat: None,
             address: None,
             route_address: None,
@@ -504,18 +786,43 @@ impl SingBoxConfig {
         self
     }
 
+    /// Linux TPROXY inbound (TCP and UDP). Traffic only arrives once an iptables/nftables
+    /// TPROXY rule and a matching `ip rule` route marked packets to it, and binding it
+    /// needs CAP_NET_ADMIN.
+    pub fn with_tproxy_inbound(self, port: u16) -> Self {
+        self.with_listener_inbound("tproxy", "tproxy-in", port)
+    }
+
+    /// Linux REDIRECT inbound (TCP only), fed by an iptables `nat` REDIRECT rule.
+    pub fn with_redirect_inbound(self, port: u16) -> Self {
+        self.with_listener_inbound("redirect", "redirect-in", port)
+    }
+
+    /// Listens on all addresses: traffic forwarded from the LAN is never delivered to a
+    /// loopback-bound socket.
+    fn with_listener_inbound(mut self, inbound_type: &str, tag: &str, port: u16) -> Self {
+        self.inbounds.push(Inbound {
+            inbound_type: inbound_type.to_string(),
+            tag: tag.to_string(),
+            listen: Some("0.0.0.0".to_string()),
+            listen_port: Some(port),
+            ..Default::default()
+        });
+        self
+    }
+
     pub fn with_tun_inbound(
         mut self,
         mtu: u16,
         stack: String,
-        ipv6_enabled: bool,
         strict_route: bool,
+        address: &str,
+        address_v6: Option<&str>,
+        interface_name: Option<String>,
+        route_exclude: Vec<String>,
     ) -> Self {
-        let addresses = if ipv6_enabled {
-            vec!["172.19.0.1/30".to_string(), "fd00::1/126".to_string()]
-        } else {
-            vec!["172.19.0.1/30".to_string()]
-        };
+        // An IPv6 address makes the interface dual-stack, so v6 traffic has a source to use
+        let addresses = std::iter::once(address).chain(address_v6).map(str::to_string).collect();
 
         self.inbounds.push(Inbound {
             inbound_type: "tun".to_string(),
@@ -527,30 +834,83 @@ impl SingBoxConfig {
             reuse_addr: None,
             auto_route: Some(true),
             strict_route: Some(strict_route),
+            auto_redirect: None,
             endpoint_independent_nat: None,
             address: Some(addresses),
             route_address: None,
-            route_exclude_address: None,
+            route_exclude_address: if route_exclude.is_empty() {
+                None
+            } else {
+                Some(route_exclude)
+            },
             stack: Some(stack),
-            interface_name: None,
+            interface_name: interface_name.filter(|n| !n.is_empty()),
             mtu: Some(mtu),
         });
         self
     }
 
+    /// Hand the `tun` inbound to a platform VPN service (Android `VpnService`, iOS
+    /// `NEPacketTunnelProvider`) that owns the device and its routes. The core only
+    /// receives the fd, so drop the options that would make it configure the interface.
+    #[cfg(mobile)]
+    pub fn with_external_tun(mut self) -> Self {
+        for inbound in self.inbounds.iter_mut().filter(|i| i.inbound_type == "tun") {
+            inbound.auto_route = None;
+            inbound.strict_route = None;
+            inbound.auto_redirect = None;
+            inbound.route_address = None;
+            inbound.route_exclude_address = None;
+            inbound.interface_name = None;
+        }
+        self
+    }
+
+    /// Apply user dial options to every proxy outbound. `None`/`false` leave sing-box defaults.
+    pub fn with_dial_options(mut self, connect_timeout_secs: Option<u32>, tcp_fast_open: bool) -> Self {
+        for outbound in self.outbounds.iter_mut() {
+            let is_proxy = !matches!(
+                outbound.outbound_type.as_str(),
+                "direct" | "block" | "dns" | "selector" | "urltest"
+            );
+            if !is_proxy {
+                continue;
+            }
+            if let Some(secs) = connect_timeout_secs {
+                outbound.connect_timeout = Some(format!("{}s", secs));
+            }
+            let is_quic = QUIC_OUTBOUND_TYPES.contains(&outbound.outbound_type.as_str());
+            if tcp_fast_open && !is_quic {
+                outbound.tcp_fast_open = Some(true);
+            }
+        }
+        self
+    }
+
     pub fn with_direct(self) -> Self {
         // No need to add an outbound for 'direct' if using action: "direct"
         // But we might still need it for detours or manual selection.
         // For compatibility with current implementation that expects a 'direct' tag:
-        self.with_direct_tag("direct")
+        self.with_direct_tag("direct", None, None)
     }
 
-    pub fn with_direct_tag(mut self, tag: &str) -> Self {
+    /// Add a direct outbound. `bind_interface`/`inet4_bind_address` pin its dials to
+    /// one NIC or source address; sing-box then skips `auto_detect_interface` for it,
+    /// which keeps direct traffic from looping back into the TUN on multi-homed hosts.
+    pub fn with_direct_tag(
+        mut self,
+        tag: &str,
+        bind_interface: Option<String>,
+        inet4_bind_address: Option<String>,
+    ) -> Self {
         self.outbounds.push(Outbound {
             outbound_type: "direct".to_string(),
             tag: tag.to_string(),
             server: None,
             server_port: None,
+            server_ports: None,
+            plugin: None,
+            plugin_opts: None,
             method: None,
             password: None,
             uuid: None,
@@ -559,6 +919,11 @@ impl SingBoxConfig {
             transport: None,
             tls: None,
             connect_timeout: Some("5s".to_string()), // Add this to avoid 'empty' error
+            tcp_fast_open: None,
+            bind_interface,
+            inet4_bind_address,
+            multiplex: None,
+            extra: Default::default(),
             flow: None,
             up_mbps: None,
             down_mbps: None,
@@ -590,7 +955,160 @@ impl SingBoxConfig {
         self
     }
 
-    #[allow(dead_code)]
+    /// The outbound for `node`, tagged `tag`. The one place a node's protocol is mapped to
+    /// sing-box; protocols outside `NODE_PROTOCOLS` get a direct outbound.
+    pub fn with_node_outbound(self, node: &crate::profile::Node, tag: &str) -> Self {
+        match node.protocol.as_str() {
+            "vmess" => {
+                let packet_encoding = effective_packet_encoding(node.packet_encoding.as_deref());
+                self.with_vmess_outbound(
+                    tag,
+                    node.server.clone(),
+                    node.port,
+                    node.uuid.clone().unwrap_or_default(),
+                    node.cipher.clone().unwrap_or("auto".to_string()),
+                    node.alter_id.unwrap_or(0),
+                    node.network.clone(),
+                    node.path.clone(),
+                    node.host.clone(),
+                    node.tls,
+                    node.insecure,
+                    node.sni.clone(),
+                    node.alpn.clone(),
+                    packet_encoding,
+                    node.fingerprint.clone(),
+                    node.disable_sni,
+                )
+            }
+            "vless" => {
+                let packet_encoding = effective_packet_encoding(node.packet_encoding.as_deref());
+                self.with_vless_outbound(
+                    tag,
+                    node.server.clone(),
+                    node.port,
+                    node.uuid.clone().unwrap_or_default(),
+                    node.flow.clone(),
+                    node.network.clone(),
+                    node.path.clone(),
+                    node.host.clone(),
+                    node.tls,
+                    node.insecure,
+                    node.sni.clone(),
+                    node.alpn.clone(),
+                    packet_encoding,
+                    node.fingerprint.clone(),
+                    node.public_key.clone(),
+                    node.short_id.clone(),
+                    node.disable_sni,
+                )
+            }
+            "shadowsocks" | "ss" => {
+                self.with_shadowsocks_outbound(
+                    tag,
+                    node.server.clone(),
+                    node.port,
+                    node.cipher
+                        .clone()
+                        .unwrap_or("chacha20-ietf-poly1305".to_string()),
+                    node.password.clone().unwrap_or_default(),
+                    node.obfs.clone(),
+                    node.host.clone(),
+                )
+            }
+            "trojan" => {
+                self.with_trojan_outbound(
+                    tag,
+                    node.server.clone(),
+                    node.port,
+                    node.password.clone().unwrap_or_default(),
+                    node.network.clone(),
+                    node.path.clone(),
+                    node.host.clone(),
+                    node.tls,
+                    node.insecure,
+                    node.sni.clone(),
+                    node.alpn.clone(),
+                    node.fingerprint.clone(),
+                    node.public_key.clone(),
+                    node.short_id.clone(),
+                    node.disable_sni,
+                )
+            }
+            "hysteria2" | "hy2" => {
+                // Hysteria2 runs Brutal natively whenever the bandwidths are set
+                let up_mbps = node.up.as_deref().and_then(parse_mbps);
+                let down_mbps = node.down.as_deref().and_then(parse_mbps);
+                self.with_hysteria2_outbound(
+                    tag,
+                    node.server.clone(),
+                    node.port,
+                    node.password.clone().unwrap_or_default(),
+                    node.sni.clone(),
+                    node.insecure,
+                    node.alpn.clone(),
+                    up_mbps,
+                    down_mbps,
+                    node.obfs.clone(),
+                    node.obfs_password.clone(),
+                    node.fingerprint.clone(),
+                    node.disable_sni,
+                    node.server_ports.clone(),
+                )
+            }
+            "hysteria" | "hy" => {
+                let up_mbps = node.up.as_deref().and_then(parse_mbps).unwrap_or(HYSTERIA_DEFAULT_UP_MBPS);
+                let down_mbps =
+                    node.down.as_deref().and_then(parse_mbps).unwrap_or(HYSTERIA_DEFAULT_DOWN_MBPS);
+                self.with_hysteria_outbound(
+                    tag,
+                    node.server.clone(),
+                    node.port,
+                    node.password.clone(),
+                    node.sni.clone(),
+                    node.insecure,
+                    node.alpn.clone(),
+                    up_mbps,
+                    down_mbps,
+                    node.obfs_password.clone(),
+                    node.disable_sni,
+                )
+            }
+            "tuic" => {
+                self.with_tuic_outbound(
+                    tag,
+                    node.server.clone(),
+                    node.port,
+                    node.uuid.clone().unwrap_or_default(),
+                    node.password.clone(),
+                    node.sni.clone(),
+                    node.insecure,
+                    node.alpn.clone(),
+                    None,
+                    None,
+                    None,
+                    None,
+                    node.fingerprint.clone(),
+                    node.disable_sni,
+                )
+            }
+            "anytls" => {
+                self.with_anytls_outbound(
+                    tag,
+                    node.server.clone(),
+                    node.port,
+                    node.password.clone().unwrap_or_default(),
+                    node.tls,
+                    node.insecure,
+                    node.sni.clone(),
+                    node.alpn.clone(),
+                    node.fingerprint.clone(),
+                    node.disable_sni,
+                )
+            }
+            _ => self.with_direct_tag(tag, None, None),
+        }
+    }
+
     pub fn with_shadowsocks_outbound(
         mut self,
         tag: &str,
@@ -598,12 +1116,18 @@ impl SingBoxConfig {
         port: u16,
         method: String,
         password: String,
+        obfs: Option<String>,
+        obfs_host: Option<String>,
     ) -> Self {
+        let plugin_opts = simple_obfs_opts(obfs.as_deref(), obfs_host.as_deref());
         self.outbounds.push(Outbound {
             outbound_type: "shadowsocks".to_string(),
             tag: tag.to_string(),
             server: Some(server),
             server_port: Some(port),
+            server_ports: None,
+            plugin: plugin_opts.as_ref().map(|_| "obfs-local".to_string()),
+            plugin_opts,
             method: Some(method),
             password: Some(password),
             uuid: None,
@@ -612,6 +1136,11 @@ impl SingBoxConfig {
             transport: None,
             tls: None,
             connect_timeout: None,
+            tcp_fast_open: None,
+            bind_interface: None,
+            inet4_bind_address: None,
+            multiplex: None,
+            extra: Default::default(),
             flow: None,
             up_mbps: None,
             down_mbps: None,
@@ -643,7 +1172,11 @@ impl SingBoxConfig {
         host: Option<String>,
         tls: bool,
         insecure: bool,
+        sni: Option<String>,
+        alpn: Option<Vec<String>>,
         packet_encoding: Option<String>,
+        fingerprint: Option<String>,
+        disable_sni: Option<bool>,
     ) -> Self {
         let mut transport_config = None;
         if let Some(t_type) = transport {
@@ -660,6 +1193,7 @@ impl SingBoxConfig {
                     transport_type: t_type,
                     path,
                     headers,
+                    extra: Default::default(),
                 });
             }
         }
@@ -669,6 +1203,9 @@ impl SingBoxConfig {
             tag: tag.to_string(),
             server: Some(server.clone()),
             server_port: Some(port),
+            server_ports: None,
+            plugin: None,
+            plugin_opts: None,
             method: None,
             password: None,
             uuid: Some(uuid),
@@ -676,20 +1213,30 @@ impl SingBoxConfig {
             alter_id: Some(alter_id),
             transport: transport_config,
             tls: if tls {
-                let sni = host.or(Some(server));
+                let sni = sni.filter(|s| !s.is_empty()).or(host).or(Some(server));
                 Some(OutboundTls {
                     enabled: true,
-                    server_name: sni,
+                    ech: None,
+                    extra: Default::default(),
+                    server_name: tls_server_name(sni, disable_sni),
                     insecure: Some(insecure),
-                    alpn: None,
-                    utls: None,
+                    alpn,
+                    utls: fingerprint.map(|f| UtlsConfig {
+                        enabled: true,
+                        fingerprint: f,
+                    }),
                     reality: None,
-                    disable_sni: None,
+                    disable_sni,
                 })
             } else {
                 None
             },
             connect_timeout: None,
+            tcp_fast_open: None,
+            bind_interface: None,
+            inet4_bind_address: None,
+            multiplex: None,
+            extra: Default::default(),
             flow: None,
             up_mbps: None,
             down_mbps: None,
@@ -726,6 +1273,7 @@ impl SingBoxConfig {
         fingerprint: Option<String>,
         public_key: Option<String>,
         short_id: Option<String>,
+        disable_sni: Option<bool>,
     ) -> Self {
         let mut transport_config = None;
         if let Some(t_type) = transport {
@@ -742,6 +1290,7 @@ impl SingBoxConfig {
                     transport_type: t_type,
                     path,
                     headers,
+                    extra: Default::default(),
                 });
             }
         }
@@ -751,6 +1300,9 @@ impl SingBoxConfig {
             tag: tag.to_string(),
             server: Some(server.clone()),
             server_port: Some(port),
+            server_ports: None,
+            plugin: None,
+            plugin_opts: None,
             method: None,
             password: None,
             uuid: Some(uuid),
@@ -761,7 +1313,9 @@ impl SingBoxConfig {
             tls: if tls {
                 Some(OutboundTls {
                     enabled: true,
-                    server_name: sni.or(host).or(Some(server)),
+                    ech: None,
+                    extra: Default::default(),
+                    server_name: tls_server_name(sni.or(host).or(Some(server)), disable_sni),
                     insecure: Some(insecure),
                     alpn,
                     utls: fingerprint.map(|f| UtlsConfig {
@@ -777,12 +1331,17 @@ impl SingBoxConfig {
                     } else {
                         None
                     },
-                    disable_sni: None,
+                    disable_sni,
                 })
             } else {
                 None
             },
             connect_timeout: None,
+            tcp_fast_open: None,
+            bind_interface: None,
+            inet4_bind_address: None,
+            multiplex: None,
+            extra: Default::default(),
             up_mbps: None,
             down_mbps: None,
             obfs: None,
@@ -814,12 +1373,19 @@ impl SingBoxConfig {
         obfs: Option<String>,
         obfs_password: Option<String>,
         fingerprint: Option<String>,
+        disable_sni: Option<bool>,
+        server_ports: Option<Vec<String>>,
     ) -> Self {
+        // sing-box rejects server_port alongside a hopping range
+        let server_ports = server_ports.filter(|p| !p.is_empty());
         self.outbounds.push(Outbound {
             outbound_type: "hysteria2".to_string(),
             tag: tag.to_string(),
             server: Some(server.clone()),
-            server_port: Some(port),
+            server_port: if server_ports.is_some() { None } else { Some(port) },
+            server_ports,
+            plugin: None,
+            plugin_opts: None,
             method: None,
             password: Some(password),
             uuid: None,
@@ -829,7 +1395,9 @@ impl SingBoxConfig {
             transport: None,
             tls: Some(OutboundTls {
                 enabled: true,
-                server_name: sni.or(Some(server)),
+                ech: None,
+                extra: Default::default(),
+                server_name: tls_server_name(sni.or(Some(server)), disable_sni),
                 insecure: Some(insecure),
                 alpn: if alpn.is_none() || alpn.as_ref().unwrap().is_empty() {
                     Some(vec!["h3".to_string()])
@@ -841,9 +1409,14 @@ impl SingBoxConfig {
                     fingerprint: f,
                 }),
                 reality: None,
-                disable_sni: None,
+                disable_sni,
             }),
             connect_timeout: None,
+            tcp_fast_open: None,
+            bind_interface: None,
+            inet4_bind_address: None,
+            multiplex: None,
+            extra: Default::default(),
             up_mbps: up,
             down_mbps: down,
             obfs: if obfs.is_some() && obfs_password.is_some() {
@@ -867,6 +1440,78 @@ impl SingBoxConfig {
         });
         self
     }
+
+    /// Hysteria (v1). `obfs` is the obfuscation password; sing-box requires both bandwidths.
+    pub fn with_hysteria_outbound(
+        mut self,
+        tag: &str,
+        server: String,
+        port: u16,
+        auth: Option<String>,
+        sni: Option<String>,
+        insecure: bool,
+        alpn: Option<Vec<String>>,
+        up: u32,
+        down: u32,
+        obfs: Option<String>,
+        disable_sni: Option<bool>,
+    ) -> Self {
+        let mut extra = serde_json::Map::new();
+        if let Some(auth) = auth.filter(|a| !a.is_empty()) {
+            extra.insert("auth_str".to_string(), serde_json::Value::String(auth));
+        }
+        if let Some(obfs) = obfs.filter(|o| !o.is_empty()) {
+            extra.insert("obfs".to_string(), serde_json::Value::String(obfs));
+        }
+        self.outbounds.push(Outbound {
+            outbound_type: "hysteria".to_string(),
+            tag: tag.to_string(),
+            server: Some(server.clone()),
+            server_port: Some(port),
+            server_ports: None,
+            plugin: None,
+            plugin_opts: None,
+            method: None,
+            password: None,
+            uuid: None,
+            security: None,
+            flow: None,
+            alter_id: None,
+            transport: None,
+            tls: Some(OutboundTls {
+                enabled: true,
+                ech: None,
+                extra: Default::default(),
+                server_name: tls_server_name(sni.or(Some(server)), disable_sni),
+                insecure: Some(insecure),
+                alpn,
+                utls: None,
+                reality: None,
+                disable_sni,
+            }),
+            connect_timeout: None,
+            tcp_fast_open: None,
+            bind_interface: None,
+            inet4_bind_address: None,
+            multiplex: None,
+            extra,
+            up_mbps: Some(up),
+            down_mbps: Some(down),
+            obfs: None,
+            congestion_controller: None,
+            udp_relay_mode: None,
+            zero_rtt_handshake: None,
+            heartbeat: None,
+            outbounds: None,
+            url: None,
+            interval: None,
+            tolerance: None,
+            packet_encoding: None,
+            domain_strategy: None,
+        });
+        self
+    }
+
     pub fn with_anytls_outbound(
         mut self,
         tag: &str,
@@ -885,6 +1530,9 @@ impl SingBoxConfig {
             tag: tag.to_string(),
             server: Some(server.clone()),
             server_port: Some(port),
+            server_ports: None,
+            plugin: None,
+            plugin_opts: None,
             method: None,
             password: Some(password),
             uuid: None,
@@ -895,7 +1543,9 @@ impl SingBoxConfig {
             tls: if tls {
                 Some(OutboundTls {
                     enabled: true,
-                    server_name: sni.or(Some(server)),
+                    ech: None,
+                    extra: Default::default(),
+                    server_name: tls_server_name(sni.or(Some(server)), disable_sni),
                     insecure: Some(insecure),
                     alpn,
                     utls: fingerprint.map(|f| UtlsConfig {
@@ -909,6 +1559,11 @@ impl SingBoxConfig {
                 None
             },
             connect_timeout: None,
+            tcp_fast_open: None,
+            bind_interface: None,
+            inet4_bind_address: None,
+            multiplex: None,
+            extra: Default::default(),
             up_mbps: None,
             down_mbps: None,
             obfs: None,
@@ -941,12 +1596,16 @@ impl SingBoxConfig {
         zero_rtt_handshake: Option<bool>,
         heartbeat: Option<String>,
         fingerprint: Option<String>,
+        disable_sni: Option<bool>,
     ) -> Self {
         self.outbounds.push(Outbound {
             outbound_type: "tuic".to_string(),
             tag: tag.to_string(),
             server: Some(server.clone()),
             server_port: Some(port),
+            server_ports: None,
+            plugin: None,
+            plugin_opts: None,
             method: None,
             password,
             uuid: Some(uuid),
@@ -956,7 +1615,9 @@ impl SingBoxConfig {
             transport: None,
             tls: Some(OutboundTls {
                 enabled: true,
-                server_name: sni.or(Some(server)),
+                ech: None,
+                extra: Default::default(),
+                server_name: tls_server_name(sni.or(Some(server)), disable_sni),
                 insecure: Some(insecure),
                 alpn: if alpn.is_none() || alpn.as_ref().unwrap().is_empty() {
                     Some(vec!["h3".to_string()])
@@ -968,9 +1629,14 @@ impl SingBoxConfig {
                     fingerprint: f,
                 }),
                 reality: None,
-                disable_sni: None,
+                disable_sni,
             }),
             connect_timeout: None,
+            tcp_fast_open: None,
+            bind_interface: None,
+            inet4_bind_address: None,
+            multiplex: None,
+            extra: Default::default(),
             up_mbps: None,
             down_mbps: None,
             obfs: None,
@@ -1004,6 +1670,7 @@ impl SingBoxConfig {
         fingerprint: Option<String>,
         public_key: Option<String>,
         short_id: Option<String>,
+        disable_sni: Option<bool>,
     ) -> Self {
         let mut transport_config = None;
         if let Some(t_type) = transport {
@@ -1020,6 +1687,7 @@ impl SingBoxConfig {
                     transport_type: t_type,
                     path,
                     headers,
+                    extra: Default::default(),
                 });
             }
         }
@@ -1029,6 +1697,9 @@ impl SingBoxConfig {
             tag: tag.to_string(),
             server: Some(server.clone()),
             server_port: Some(port),
+            server_ports: None,
+            plugin: None,
+            plugin_opts: None,
             method: None,
             password: Some(password),
             uuid: None,
@@ -1039,7 +1710,9 @@ impl SingBoxConfig {
             tls: if tls {
                 Some(OutboundTls {
                     enabled: true,
-                    server_name: sni.or(host).or(Some(server)),
+                    ech: None,
+                    extra: Default::default(),
+                    server_name: tls_server_name(sni.or(host).or(Some(server)), disable_sni),
                     insecure: Some(insecure),
                     alpn,
                     utls: fingerprint.map(|f| UtlsConfig {
@@ -1055,12 +1728,17 @@ impl SingBoxConfig {
                     } else {
                         None
                     },
-                    disable_sni: None,
+                    disable_sni,
                 })
             } else {
                 None
             },
             connect_timeout: None,
+            tcp_fast_open: None,
+            bind_interface: None,
+            inet4_bind_address: None,
+            multiplex: None,
+            extra: Default::default(),
             up_mbps: None,
             down_mbps: None,
             obfs: None,
@@ -1084,6 +1762,9 @@ impl SingBoxConfig {
             tag: tag.to_string(),
             server: None,
             server_port: None,
+            server_ports: None,
+            plugin: None,
+            plugin_opts: None,
             method: None,
             password: None,
             uuid: None,
@@ -1093,6 +1774,11 @@ impl SingBoxConfig {
             transport: None,
             tls: None,
             connect_timeout: None,
+            tcp_fast_open: None,
+            bind_interface: None,
+            inet4_bind_address: None,
+            multiplex: None,
+            extra: Default::default(),
             up_mbps: None,
             down_mbps: None,
             obfs: None,
@@ -1123,6 +1809,9 @@ impl SingBoxConfig {
             tag: tag.to_string(),
             server: None,
             server_port: None,
+            server_ports: None,
+            plugin: None,
+            plugin_opts: None,
             method: None,
             password: None,
             uuid: None,
@@ -1132,6 +1821,11 @@ impl SingBoxConfig {
             transport: None,
             tls: None,
             connect_timeout: None,
+            tcp_fast_open: None,
+            bind_interface: None,
+            inet4_bind_address: None,
+            multiplex: None,
+            extra: Default::default(),
             up_mbps: None,
             down_mbps: None,
             obfs: None,
@@ -1140,7 +1834,7 @@ impl SingBoxConfig {
             zero_rtt_handshake: None,
             heartbeat: None,
             outbounds: Some(outbounds),
-            url: url.or(Some("http://www.gstatic.com/generate_204".to_string())),
+            url: url.or(Some(crate::settings::DEFAULT_LATENCY_TEST_URL.to_string())),
             interval: interval.or(Some("10m".to_string())),
             tolerance: tolerance.or(Some(50)),
             packet_encoding: None,
@@ -0,0 +1,94 @@
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+
+/// Error type shared by the service layer and Tauri commands.
+/// Crosses the command boundary as `{ code, message }` so the frontend can branch on `code`.
+#[derive(Debug)]
+pub enum TunnetError {
+    NodeNotFound(String),
+    CoreUnavailable(String),
+    /// `suggested` is a nearby port that was free at the time of the check.
+    PortInUse { port: u16, suggested: Option<u16> },
+    HelperUnreachable(String),
+    ConfigInvalid(String),
+    /// A newer connect/disconnect request arrived while this one was queued.
+    Superseded,
+    Io(std::io::Error),
+    Other(String),
+}
+
+impl TunnetError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            TunnetError::NodeNotFound(_) => "node_not_found",
+            TunnetError::CoreUnavailable(_) => "core_unavailable",
+            TunnetError::PortInUse { .. } => "port_in_use",
+            TunnetError::HelperUnreachable(_) => "helper_unreachable",
+            TunnetError::ConfigInvalid(_) => "config_invalid",
+            TunnetError::Superseded => "superseded",
+            TunnetError::Io(_) => "io",
+            TunnetError::Other(_) => "other",
+        }
+    }
+}
+
+impl std::fmt::Display for TunnetError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TunnetError::NodeNotFound(id) => write!(f, "Node not found: {}", id),
+            TunnetError::CoreUnavailable(msg) => write!(f, "Core unavailable: {}", msg),
+            TunnetError::PortInUse { port, suggested: Some(free) } => {
+                write!(f, "Port {} is already in use (port {} is free)", port, free)
+            }
+            TunnetError::PortInUse { port, suggested: None } => {
+                write!(f, "Port {} is already in use", port)
+            }
+            TunnetError::HelperUnreachable(msg) => write!(f, "Helper unreachable: {}", msg),
+            TunnetError::ConfigInvalid(msg) => write!(f, "Invalid config: {}", msg),
+            TunnetError::Superseded => write!(f, "Superseded by a newer request"),
+            TunnetError::Io(e) => write!(f, "{}", e),
+            TunnetError::Other(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for TunnetError {}
+
+impl Serialize for TunnetError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut s = serializer.serialize_struct("TunnetError", 2)?;
+        s.serialize_field("code", self.code())?;
+        s.serialize_field("message", &self.to_string())?;
+        s.end()
+    }
+}
+
+// Untyped errors from older call sites still flow through `?`
+impl From<String> for TunnetError {
+    fn from(msg: String) -> Self {
+        TunnetError::Other(msg)
+    }
+}
+
+impl From<&str> for TunnetError {
+    fn from(msg: &str) -> Self {
+        TunnetError::Other(msg.to_string())
+    }
+}
+
+impl From<std::io::Error> for TunnetError {
+    fn from(e: std::io::Error) -> Self {
+        TunnetError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for TunnetError {
+    fn from(e: serde_json::Error) -> Self {
+        TunnetError::ConfigInvalid(e.to_string())
+    }
+}
+
+impl From<TunnetError> for String {
+    fn from(e: TunnetError) -> Self {
+        e.to_string()
+    }
+}
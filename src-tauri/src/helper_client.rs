@@ -5,16 +5,109 @@ const SOCKET_PATH: &str = "/var/run/tunnet.sock";
 #[cfg(windows)]
 const PIPE_NAME: &str = r"\\.\pipe\tunnet";
 
+/// IPC protocol revision, shared with bin/helper.rs; bump whenever commands or payloads
+/// change. App and helper must speak the same revision.
+pub const PROTOCOL_VERSION: u32 = 2;
+
+/// Root-only copy of the IPC token read by the helper (keep in sync with bin/helper.rs).
+/// On Windows it lives next to the installed service binary instead.
+#[cfg(unix)]
+pub const HELPER_TOKEN_PATH: &str = "/etc/tunnet/helper.token";
+
+/// Log file of the helper process itself (keep in sync with bin/helper.rs).
+pub fn helper_log_path() -> std::path::PathBuf {
+    if cfg!(windows) {
+        std::path::PathBuf::from(std::env::var("ProgramData").unwrap_or("C:\\ProgramData".into()))
+            .join("Tunnet")
+            .join("tunnet-helper.log")
+    } else {
+        std::path::PathBuf::from("/tmp/tunnet-helper.log")
+    }
+}
+
+/// Last `version` reply (version string, protocol revision). The helper binary only
+/// changes through `HelperInstaller`, which calls `forget_version`.
+static VERSION_CACHE: std::sync::Mutex<Option<(String, u32)>> = std::sync::Mutex::new(None);
+
+/// Drop the cached helper version after the helper was (re)installed or removed.
+pub fn forget_version() {
+    *VERSION_CACHE.lock().unwrap() = None;
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 struct Request {
     command: String,
     payload: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    token: Option<String>,
+    protocol_version: u32,
+}
+
+/// Per-user copy of the helper token. The helper keeps its own root-only copy.
+pub fn client_token_path() -> Option<std::path::PathBuf> {
+    #[cfg(unix)]
+    let base = std::env::var_os("HOME").map(|h| std::path::PathBuf::from(h).join(".tunnet"));
+    #[cfg(windows)]
+    let base = std::env::var_os("LOCALAPPDATA").map(|h| std::path::PathBuf::from(h).join("Tunnet"));
+    base.map(|b| b.join("helper.token"))
+}
+
+/// Generate a fresh token into a staging file next to the user copy. The installer hands
+/// it to the elevated step and calls `commit_token` only once that succeeded, so a
+/// cancelled install keeps the old client and helper copies matching.
+pub fn generate_token() -> Result<std::path::PathBuf, Box<dyn Error>> {
+    use std::io::Write;
+    let path = client_token_path().ok_or("Cannot determine token location")?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let staged = path.with_extension("token.new");
+    // `mode` only applies on create, so never reuse a leftover file
+    let _ = std::fs::remove_file(&staged);
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options
+        .open(&staged)?
+        .write_all(uuid::Uuid::new_v4().simple().to_string().as_bytes())?;
+    Ok(staged)
+}
+
+/// Replace the user copy with a token staged by `generate_token`.
+pub fn commit_token(staged: &std::path::Path) -> Result<(), Box<dyn Error>> {
+    let path = client_token_path().ok_or("Cannot determine token location")?;
+    std::fs::rename(staged, path)?;
+    Ok(())
+}
+
+fn read_token() -> Option<String> {
+    let token = std::fs::read_to_string(client_token_path()?).ok()?;
+    let token = token.trim();
+    (!token.is_empty()).then(|| token.to_string())
+}
+
+impl Request {
+    fn new(command: &str, payload: Option<String>) -> Self {
+        Self {
+            command: command.to_string(),
+            payload,
+            token: read_token(),
+            protocol_version: PROTOCOL_VERSION,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 struct Response {
     status: String,
     message: String,
+    // Helpers predating the handshake omit this and report 0
+    #[serde(default)]
+    protocol_version: u32,
 }
 
 #[derive(Serialize)]
@@ -25,6 +118,50 @@ struct StartPayload {
     log_path: String,
 }
 
+/// Cumulative traffic counters of the TUN instance running inside the helper.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HelperStats {
+    pub up: u64,
+    pub down: u64,
+}
+
+/// Why the helper could not be reached, so callers can pick between waiting, restarting and reinstalling.
+#[derive(Debug)]
+pub enum HelperError {
+    /// No socket at all: the helper is not installed or not started.
+    NotRunning,
+    /// The socket file exists but nothing accepts on it (helper crashed and left it behind).
+    StaleSocket,
+    Io(std::io::Error),
+}
+
+impl std::fmt::Display for HelperError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HelperError::NotRunning => write!(f, "Helper is not running"),
+            HelperError::StaleSocket => write!(f, "Helper socket is stale (helper crashed?)"),
+            HelperError::Io(e) => write!(f, "Helper IPC error: {}", e),
+        }
+    }
+}
+
+impl Error for HelperError {}
+
+impl From<std::io::Error> for HelperError {
+    fn from(e: std::io::Error) -> Self {
+        match e.kind() {
+            std::io::ErrorKind::NotFound => HelperError::NotRunning,
+            std::io::ErrorKind::ConnectionRefused => HelperError::StaleSocket,
+            _ => HelperError::Io(e),
+        }
+    }
+}
+
+/// Classify an error returned by `HelperClient`, if it came from the transport.
+pub fn helper_error_kind<'a>(e: &'a (dyn Error + 'static)) -> Option<&'a HelperError> {
+    e.downcast_ref::<HelperError>()
+}
+
 pub struct HelperClient;
 
 impl HelperClient {
@@ -58,7 +195,7 @@ impl HelperClient {
         use std::os::unix::net::UnixStream;
         use std::time::Duration;
 
-        let mut stream = UnixStream::connect(SOCKET_PATH)?;
+        let mut stream = UnixStream::connect(SOCKET_PATH).map_err(HelperError::from)?;
         stream.set_read_timeout(Some(Duration::from_millis(1500)))?;
         stream.set_write_timeout(Some(Duration::from_millis(1500)))?;
 
@@ -81,7 +218,8 @@ impl HelperClient {
         let mut file = std::fs::OpenOptions::new()
             .read(true)
             .write(true)
-            .open(PIPE_NAME)?;
+            .open(PIPE_NAME)
+            .map_err(HelperError::from)?;
 
         // Write the request with a newline delimiter
         let mut req_with_newline = req_str.to_string();
@@ -124,10 +262,26 @@ impl HelperClient {
         };
         let payload_str = serde_json::to_string(&payload)?;
 
-        let req = Request {
-            command: "start".to_string(),
-            payload: Some(payload_str),
+        let req = Request::new("start", Some(payload_str));
+        let resp = self.send_request(req)?;
+        if resp.status == "success" {
+            Ok(())
+        } else {
+            Err(resp.message.into())
+        }
+    }
+
+    /// Swap the running TUN instance's config in place, without tearing down the helper session.
+    pub fn reload_proxy(&self, config: String) -> Result<(), Box<dyn Error>> {
+        let payload = StartPayload {
+            config,
+            core_path: String::new(),
+            working_dir: String::new(),
+            log_path: String::new(),
         };
+        let payload_str = serde_json::to_string(&payload)?;
+
+        let req = Request::new("reload", Some(payload_str));
         let resp = self.send_request(req)?;
         if resp.status == "success" {
             Ok(())
@@ -137,10 +291,7 @@ impl HelperClient {
     }
 
     pub fn stop_proxy(&self) -> Result<(), Box<dyn Error>> {
-        let req = Request {
-            command: "stop".to_string(),
-            payload: None,
-        };
+        let req = Request::new("stop", None);
         let resp = self.send_request(req)?;
         if resp.status == "success" {
             Ok(())
@@ -150,21 +301,79 @@ impl HelperClient {
     }
 
     pub fn check_status(&self) -> Result<bool, Box<dyn Error>> {
-        let req = Request {
-            command: "status".to_string(),
-            payload: None,
-        };
+        let req = Request::new("status", None);
         // If connection fails, it returns Err, which means not running (or socket issue)
         let resp = self.send_request(req)?;
         Ok(resp.status == "running")
     }
 
-    pub fn get_version(&self) -> Result<String, Box<dyn Error>> {
-        let req = Request {
-            command: "version".to_string(),
-            payload: None,
+    pub fn get_stats(&self) -> Result<HelperStats, Box<dyn Error>> {
+        let req = Request::new("stats", None);
+        let resp = self.send_request(req)?;
+        if resp.status == "success" {
+            Ok(serde_json::from_str(&resp.message)?)
+        } else {
+            Err(resp.message.into())
+        }
+    }
+
+    /// sing-box version linked into the helper; fails on helpers predating the command.
+    pub fn core_version(&self) -> Result<String, Box<dyn Error>> {
+        let req = Request::new("core_version", None);
+        let resp = self.send_request(req)?;
+        if resp.status == "success" {
+            Ok(resp.message)
+        } else {
+            Err(resp.message.into())
+        }
+    }
+
+    /// Single-shot liveness probe, without the retry loop of `send_request`.
+    pub fn ping(&self) -> bool {
+        let req = Request::new("ping", None);
+        let Ok(req_str) = serde_json::to_string(&req) else {
+            return false;
         };
+        // Any reply counts, so helpers predating `ping` still register as alive
+        self.attempt_send(&req_str).is_ok()
+    }
+
+    /// Poll the helper until it answers a ping, or fail once `timeout` elapses.
+    pub fn wait_until_ready(&self, timeout: std::time::Duration) -> Result<(), Box<dyn Error>> {
+        let start = std::time::Instant::now();
+        loop {
+            if self.ping() {
+                return Ok(());
+            }
+            if start.elapsed() >= timeout {
+                return Err(format!("Helper not responding after {}s", timeout.as_secs()).into());
+            }
+            std::thread::sleep(std::time::Duration::from_millis(100));
+        }
+    }
+
+    fn query_version(&self) -> Result<(String, u32), Box<dyn Error>> {
+        let req = Request::new("version", None);
         let resp = self.send_request(req)?;
-        Ok(resp.message)
+        let reply = (resp.message, resp.protocol_version);
+        *VERSION_CACHE.lock().unwrap() = Some(reply.clone());
+        Ok(reply)
+    }
+
+    /// Protocol revision spoken by the installed helper (0 for helpers without the handshake).
+    /// Always asks the helper, since callers also use it as a liveness check; the reply
+    /// refreshes the cache behind `get_version`.
+    pub fn negotiate(&self) -> Result<u32, Box<dyn Error>> {
+        self.query_version().map(|(_, protocol)| protocol)
+    }
+
+    /// Version of the installed helper, asked once per helper install.
+    // Only the Windows service checks still compare exact versions
+    #[cfg_attr(not(windows), allow(dead_code))]
+    pub fn get_version(&self) -> Result<String, Box<dyn Error>> {
+        if let Some((version, _)) = VERSION_CACHE.lock().unwrap().clone() {
+            return Ok(version);
+        }
+        self.query_version().map(|(version, _)| version)
     }
 }
@@ -8,6 +8,8 @@ use std::os::windows::ffi::OsStrExt;
 
 const HELPER_LABEL: &str = "run.tunnet.helper";
 const HELPER_BIN_NAME: &str = "tunnet-helper";
+/// How long a freshly (re)installed helper gets to start answering IPC.
+const HELPER_READY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(15);
 
 #[cfg(target_os = "windows")]
 fn run_elevated(program: &str, args: &str) -> Result<(), Box<dyn Error>> {
@@ -93,6 +95,19 @@ fn run_elevated(program: &str, args: &str) -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+/// Run the elevated install step with a token staged by `generate_token`. The client copy
+/// is only replaced once the step succeeded; otherwise the staged token is dropped.
+fn with_staged_token(
+    token_path: &std::path::Path,
+    step: impl FnOnce() -> Result<(), Box<dyn Error>>,
+) -> Result<(), Box<dyn Error>> {
+    if let Err(e) = step() {
+        let _ = std::fs::remove_file(token_path);
+        return Err(e);
+    }
+    crate::helper_client::commit_token(token_path)
+}
+
 pub struct HelperInstaller<R: Runtime> {
     app_handle: AppHandle<R>,
 }
@@ -120,6 +135,7 @@ impl<R: Runtime> HelperInstaller<R> {
 
     #[cfg(target_os = "macos")]
     pub fn install(&self) -> Result<(), Box<dyn Error>> {
+        crate::helper_client::forget_version();
         use std::fs;
         // 1. Find binary path (handle dev vs production)
         // Note: resources are bundled into a 'resources' subdirectory due to tauri.conf.json structure
@@ -221,9 +237,16 @@ impl<R: Runtime> HelperInstaller<R> {
             "launchctl load -w '/Library/LaunchDaemons/{}.plist'",
             HELPER_LABEL
         );
+        // Fresh IPC token per install, staged until the elevated step succeeds; the helper's copy is root-only
+        let token_path = crate::helper_client::generate_token()?;
+        let cmd_token = format!(
+            "mkdir -p /etc/tunnet && install -m 600 -o root '{}' '{}'",
+            token_path.to_string_lossy(),
+            crate::helper_client::HELPER_TOKEN_PATH
+        );
 
         let script = format!(
-            "{} && {} && {} && {} && {} && {} && {} && {} && {}",
+            "{} && {} && {} && {} && {} && {} && {} && {} && {} && {}",
             cmd_unload,
             cmd_rm_bin,
             // Ensure directory exists
@@ -233,6 +256,7 @@ impl<R: Runtime> HelperInstaller<R> {
             cmd_chmod_bin,
             cmd_cp_plist,
             cmd_chown_plist,
+            cmd_token,
             cmd_load
         );
 
@@ -242,24 +266,29 @@ impl<R: Runtime> HelperInstaller<R> {
             script_escaped
         );
 
-        let output = Command::new("osascript")
-            .arg("-e")
-            .arg(apple_script)
-            .output()?;
-
-        if !output.status.success() {
-            return Err(format!(
-                "Installation failed: {}",
-                String::from_utf8_lossy(&output.stderr)
-            )
-            .into());
-        }
+        with_staged_token(&token_path, || {
+            let output = Command::new("osascript")
+                .arg("-e")
+                .arg(apple_script)
+                .output()?;
+
+            if !output.status.success() {
+                return Err(format!(
+                    "Installation failed: {}",
+                    String::from_utf8_lossy(&output.stderr)
+                )
+                .into());
+            }
+            Ok(())
+        })?;
 
+        crate::helper_client::HelperClient::new().wait_until_ready(HELPER_READY_TIMEOUT)?;
         Ok(())
     }
 
     #[cfg(target_os = "linux")]
     pub fn install(&self) -> Result<(), Box<dyn Error>> {
+        crate::helper_client::forget_version();
         use std::fs;
         // 1. Find binary path (handle dev vs production)
         let mut resource_path = self
@@ -314,12 +343,16 @@ WantedBy=multi-user.target
         let temp_service_path = std::env::temp_dir().join(format!("{}.service", HELPER_BIN_NAME));
         fs::write(&temp_service_path, service_content)?;
 
+        // Fresh IPC token per install, staged until the elevated step succeeds; the helper's copy is root-only
+        let token_path = crate::helper_client::generate_token()?;
+
         // 3. Construct install script
         let install_script = format!(
             r#"#!/bin/sh
 set -e
 install -D -m 755 "{}" "/usr/local/bin/{}"
 install -D -m 644 "{}" "/etc/systemd/system/{}.service"
+install -D -m 600 "{}" "{}"
 systemctl daemon-reload
 systemctl enable {}.service
 systemctl restart {}.service
@@ -328,31 +361,116 @@ systemctl restart {}.service
             HELPER_BIN_NAME,
             temp_service_path.to_string_lossy(),
             HELPER_BIN_NAME,
+            token_path.to_string_lossy(),
+            crate::helper_client::HELPER_TOKEN_PATH,
             HELPER_BIN_NAME,
             HELPER_BIN_NAME
         );
 
-        let temp_script_path = std::env::temp_dir().join("tunnet_install.sh");
-        fs::write(&temp_script_path, install_script)?;
+        with_staged_token(&token_path, || {
+            let temp_script_path = std::env::temp_dir().join("tunnet_install.sh");
+            fs::write(&temp_script_path, install_script)?;
+
+            // Make the script executable
+            Command::new("chmod")
+                .arg("+x")
+                .arg(&temp_script_path)
+                .output()?;
+
+            // 4. Run with pkexec
+            println!("Requesting elevation for installation...");
+            let output = Command::new("pkexec").arg(temp_script_path).output()?;
+
+            if !output.status.success() {
+                return Err(format!(
+                    "Installation failed: {}",
+                    String::from_utf8_lossy(&output.stderr)
+                )
+                .into());
+            }
+            Ok(())
+        })?;
+
+        crate::helper_client::HelperClient::new().wait_until_ready(HELPER_READY_TIMEOUT)?;
+        Ok(())
+    }
+
+    #[cfg(target_os = "macos")]
+    pub fn uninstall(&self) -> Result<(), Box<dyn Error>> {
+        crate::helper_client::forget_version();
+        let script = format!(
+            "launchctl unload '/Library/LaunchDaemons/{label}.plist' >/dev/null 2>&1 || true; \
+             rm -f '/Library/LaunchDaemons/{label}.plist' '/Library/PrivilegedHelperTools/{label}' '{token}' /var/run/tunnet.sock",
+            label = HELPER_LABEL,
+            token = crate::helper_client::HELPER_TOKEN_PATH
+        );
+
+        let script_escaped = script.replace("\\", "\\\\").replace("\"", "\\\"");
+        let apple_script = format!(
+            "do shell script \"{}\" with prompt \"Tunnet needs to remove the helper tool.\" with administrator privileges",
+            script_escaped
+        );
+
+        let output = Command::new("osascript")
+            .arg("-e")
+            .arg(apple_script)
+            .output()?;
+
+        if !output.status.success() {
+            return Err(format!(
+                "Uninstallation failed: {}",
+                String::from_utf8_lossy(&output.stderr)
+            )
+            .into());
+        }
+
+        if let Some(path) = crate::helper_client::client_token_path() {
+            let _ = std::fs::remove_file(path);
+        }
+
+        println!("Helper uninstalled successfully");
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    pub fn uninstall(&self) -> Result<(), Box<dyn Error>> {
+        crate::helper_client::forget_version();
+        use std::fs;
+
+        let uninstall_script = format!(
+            r#"#!/bin/sh
+systemctl disable --now {bin}.service >/dev/null 2>&1 || true
+rm -f "/etc/systemd/system/{bin}.service" "/usr/local/bin/{bin}" "{token}" /var/run/tunnet.sock
+systemctl daemon-reload
+"#,
+            bin = HELPER_BIN_NAME,
+            token = crate::helper_client::HELPER_TOKEN_PATH
+        );
+
+        let temp_script_path = std::env::temp_dir().join("tunnet_uninstall.sh");
+        fs::write(&temp_script_path, uninstall_script)?;
 
-        // Make the script executable
         Command::new("chmod")
             .arg("+x")
             .arg(&temp_script_path)
             .output()?;
 
-        // 4. Run with pkexec
-        println!("Requesting elevation for installation...");
+        println!("Requesting elevation for uninstallation...");
         let output = Command::new("pkexec").arg(temp_script_path).output()?;
 
         if !output.status.success() {
             return Err(format!(
-                "Installation failed: {}",
+                "Uninstallation failed: {}",
                 String::from_utf8_lossy(&output.stderr)
             )
             .into());
         }
 
+        if let Some(path) = crate::helper_client::client_token_path() {
+            let _ = fs::remove_file(path);
+        }
+
+        println!("Helper uninstalled successfully");
         Ok(())
     }
 
@@ -387,6 +505,7 @@ systemctl restart {}.service
 
     #[cfg(target_os = "windows")]
     pub fn install(&self) -> Result<(), Box<dyn Error>> {
+        crate::helper_client::forget_version();
         use std::fs;
 
         println!("Starting Windows Helper Service installation...");
@@ -445,29 +564,27 @@ systemctl restart {}.service
         let resource_helper_str = resource_path.to_str().ok_or("Invalid resource path")?;
         let install_dir_str = install_dir.to_str().ok_or("Invalid install dir")?;
 
-        // Command: <resource_helper> service-update <install_dir>
-        let args = format!("service-update \"{}\"", install_dir_str);
+        // Fresh IPC token per install; service-update restricts the helper's copy to SYSTEM/Admins
+        let token_path = crate::helper_client::generate_token()?;
 
-        run_elevated(resource_helper_str, &args)?;
-
-        // 4. Verify service started successfully
-        println!("Verifying service status...");
-        std::thread::sleep(std::time::Duration::from_secs(3));
-
-        let query_output = Command::new("sc.exe")
-            .args(&["query", "TunnetHelper"])
-            .output()?;
-
-        let stdout = String::from_utf8_lossy(&query_output.stdout);
-        if !stdout.contains("RUNNING") && !stdout.contains("START_PENDING") {
-            return Err(format!("Service failed to start. Status:\n{}", stdout).into());
-        }
+        // Command: <resource_helper> service-update <install_dir> <token_file>
+        let args = format!(
+            "service-update \"{}\" \"{}\"",
+            install_dir_str,
+            token_path.to_string_lossy()
+        );
 
-        println!("Service is running");
+        with_staged_token(&token_path, || run_elevated(resource_helper_str, &args))?;
 
-        // 11. Wait for service to be responsive
+        // 4. Wait for the service to answer on its pipe
         println!("Waiting for service to start...");
-        std::thread::sleep(std::time::Duration::from_secs(2));
+        if let Err(e) = crate::helper_client::HelperClient::new().wait_until_ready(HELPER_READY_TIMEOUT) {
+            let query_output = Command::new("sc.exe")
+                .args(&["query", "TunnetHelper"])
+                .output()?;
+            let stdout = String::from_utf8_lossy(&query_output.stdout);
+            return Err(format!("Service failed to start ({}). Status:\n{}", e, stdout).into());
+        }
 
         println!("Windows Helper Service installed successfully!");
         Ok(())
@@ -475,6 +592,7 @@ systemctl restart {}.service
 
     #[cfg(target_os = "windows")]
     pub fn uninstall(&self) -> Result<(), Box<dyn Error>> {
+        crate::helper_client::forget_version();
         // 1. Stop the service
         let _ = Command::new("sc.exe")
             .args(["stop", "TunnetHelper"])
@@ -0,0 +1,282 @@
+//! Exit-IP geolocation lookups. Each provider answers with its own JSON shape, so
+//! responses go through an adapter picked from the endpoint URL.
+
+use serde::Serialize;
+use serde_json::Value;
+
+/// Rate-limited but needs no key; users can point `ip_check_url` elsewhere.
+pub const DEFAULT_IP_CHECK_URL: &str = "http://ip-api.com/json";
+/// Tried after the configured endpoint when probing node locations.
+pub const FALLBACK_IP_CHECK_URL: &str = "https://ipwho.is/";
+
+/// Provider-independent result of an IP lookup.
+#[derive(Debug, Serialize, Clone, Default, PartialEq)]
+pub struct IpLookup {
+    pub ip: String,
+    pub country: String,
+    pub country_code: String,
+    pub city: String,
+    pub lat: f64,
+    pub lon: f64,
+    pub isp: String,
+}
+
+impl IpLookup {
+    pub fn into_location(self, latency: u64) -> crate::profile::LocationInfo {
+        crate::profile::LocationInfo {
+            ip: self.ip,
+            country: self.country,
+            country_code: self.country_code.to_uppercase(),
+            city: self.city,
+            lat: self.lat,
+            lon: self.lon,
+            isp: self.isp,
+            latency,
+        }
+    }
+}
+
+/// Maps one provider's response body onto `IpLookup`.
+pub trait IpInfoProvider {
+    /// `None` when the body reports a failed lookup or lacks an address.
+    fn parse(&self, body: &Value) -> Option<IpLookup>;
+}
+
+fn text(body: &Value, key: &str) -> String {
+    body[key].as_str().unwrap_or_default().to_string()
+}
+
+/// http://ip-api.com/json
+struct IpApi;
+
+impl IpInfoProvider for IpApi {
+    fn parse(&self, body: &Value) -> Option<IpLookup> {
+        if body["status"] != "success" {
+            return None;
+        }
+        Some(IpLookup {
+            ip: text(body, "query"),
+            country: text(body, "country"),
+            country_code: text(body, "countryCode"),
+            city: text(body, "city"),
+            lat: body["lat"].as_f64().unwrap_or_default(),
+            lon: body["lon"].as_f64().unwrap_or_default(),
+            isp: text(body, "isp"),
+        })
+    }
+}
+
+/// https://ipwho.is/
+struct IpWhoIs;
+
+impl IpInfoProvider for IpWhoIs {
+    fn parse(&self, body: &Value) -> Option<IpLookup> {
+        if body["success"] != true {
+            return None;
+        }
+        Some(IpLookup {
+            ip: text(body, "ip"),
+            country: text(body, "country"),
+            country_code: text(body, "country_code"),
+            city: text(body, "city"),
+            lat: body["latitude"].as_f64().unwrap_or_default(),
+            lon: body["longitude"].as_f64().unwrap_or_default(),
+            isp: text(&body["connection"], "isp"),
+        })
+    }
+}
+
+/// https://ipinfo.io/json: only a country code, coordinates as "lat,lon", ISP in `org`.
+struct IpInfoIo;
+
+impl IpInfoProvider for IpInfoIo {
+    fn parse(&self, body: &Value) -> Option<IpLookup> {
+        let (lat, lon) = body["loc"]
+            .as_str()
+            .and_then(|loc| loc.split_once(','))
+            .map(|(lat, lon)| (lat.trim().parse().unwrap_or_default(), lon.trim().parse().unwrap_or_default()))
+            .unwrap_or_default();
+        let org = text(body, "org");
+        // "AS13335 Cloudflare, Inc." -> "Cloudflare, Inc."
+        let isp = match org.split_once(' ') {
+            Some((asn, name)) if asn.starts_with("AS") => name.to_string(),
+            _ => org,
+        };
+        Some(IpLookup {
+            ip: text(body, "ip"),
+            country: text(body, "country"),
+            country_code: text(body, "country"),
+            city: text(body, "city"),
+            lat,
+            lon,
+            isp,
+        })
+        .filter(|l| !l.ip.is_empty())
+    }
+}
+
+/// Anything else (ifconfig.co, self-hosted echo services): common field names.
+struct Generic;
+
+impl IpInfoProvider for Generic {
+    fn parse(&self, body: &Value) -> Option<IpLookup> {
+        let first = |keys: &[&str]| {
+            keys.iter()
+                .map(|k| text(body, k))
+                .find(|v| !v.is_empty())
+                .unwrap_or_default()
+        };
+        let number = |keys: &[&str]| keys.iter().find_map(|k| body[*k].as_f64()).unwrap_or_default();
+        Some(IpLookup {
+            ip: first(&["ip", "query", "ip_addr"]),
+            country: first(&["country", "country_name"]),
+            country_code: first(&["country_code", "countryCode", "country_iso"]),
+            city: first(&["city"]),
+            lat: number(&["lat", "latitude"]),
+            lon: number(&["lon", "longitude"]),
+            isp: first(&["isp", "asn_org", "org"]),
+        })
+        .filter(|l| !l.ip.is_empty())
+    }
+}
+
+/// Adapter for the endpoint's host.
+pub fn provider_for(url: &str) -> Box<dyn IpInfoProvider> {
+    let host = reqwest::Url::parse(url)
+        .ok()
+        .and_then(|u| u.host_str().map(|h| h.to_lowercase()))
+        .unwrap_or_default();
+    let is = |domain: &str| host == domain || host.ends_with(&format!(".{}", domain));
+    if is("ip-api.com") {
+        Box::new(IpApi)
+    } else if is("ipwho.is") {
+        Box::new(IpWhoIs)
+    } else if is("ipinfo.io") {
+        Box::new(IpInfoIo)
+    } else {
+        Box::new(Generic)
+    }
+}
+
+/// Parse the body `url` returned; `None` for non-JSON or failed lookups.
+pub fn parse_response(url: &str, body: &str) -> Option<IpLookup> {
+    let value: Value = serde_json::from_str(body).ok()?;
+    provider_for(url).parse(&value)
+}
+
+/// Answers for a random subdomain with the resolver that asked for it, so every
+/// request forces a fresh lookup through whatever resolver the system really uses.
+const DNS_ECHO_DOMAIN: &str = "edns.ip-api.com";
+
+pub fn dns_echo_url() -> String {
+    format!("http://{}.{}/json", uuid::Uuid::new_v4().simple(), DNS_ECHO_DOMAIN)
+}
+
+/// English country names as geolocation services spell them, for answers that carry
+/// no ISO code. Names missing here leave the code empty.
+const COUNTRY_CODES: &[(&str, &str)] = &[
+    ("argentina", "AR"), ("australia", "AU"), ("austria", "AT"), ("bangladesh", "BD"),
+    ("belgium", "BE"), ("brazil", "BR"), ("bulgaria", "BG"), ("cambodia", "KH"),
+    ("canada", "CA"), ("chile", "CL"), ("china", "CN"), ("colombia", "CO"),
+    ("croatia", "HR"), ("cyprus", "CY"), ("czechia", "CZ"), ("czech republic", "CZ"),
+    ("denmark", "DK"), ("egypt", "EG"), ("estonia", "EE"), ("finland", "FI"),
+    ("france", "FR"), ("germany", "DE"), ("greece", "GR"), ("hong kong", "HK"),
+    ("hungary", "HU"), ("iceland", "IS"), ("india", "IN"), ("indonesia", "ID"),
+    ("iran", "IR"), ("ireland", "IE"), ("israel", "IL"), ("italy", "IT"),
+    ("japan", "JP"), ("kazakhstan", "KZ"), ("kenya", "KE"), ("latvia", "LV"),
+    ("lithuania", "LT"), ("luxembourg", "LU"), ("macao", "MO"), ("macau", "MO"),
+    ("malaysia", "MY"), ("mexico", "MX"), ("moldova", "MD"), ("mongolia", "MN"),
+    ("netherlands", "NL"), ("the netherlands", "NL"), ("new zealand", "NZ"),
+    ("nigeria", "NG"), ("norway", "NO"), ("pakistan", "PK"), ("peru", "PE"),
+    ("philippines", "PH"), ("poland", "PL"), ("portugal", "PT"), ("romania", "RO"),
+    ("russia", "RU"), ("russian federation", "RU"), ("saudi arabia", "SA"),
+    ("serbia", "RS"), ("singapore", "SG"), ("slovakia", "SK"), ("slovenia", "SI"),
+    ("south africa", "ZA"), ("south korea", "KR"), ("korea", "KR"),
+    ("republic of korea", "KR"), ("spain", "ES"), ("sweden", "SE"),
+    ("switzerland", "CH"), ("taiwan", "TW"), ("thailand", "TH"), ("turkey", "TR"),
+    ("türkiye", "TR"), ("ukraine", "UA"), ("united arab emirates", "AE"),
+    ("united kingdom", "GB"), ("united states", "US"), ("united states of america", "US"),
+    ("vietnam", "VN"), ("viet nam", "VN"),
+];
+
+/// ISO 3166-1 alpha-2 code for an English country name, e.g. "Japan" -> "JP".
+pub fn country_code_for_name(name: &str) -> Option<&'static str> {
+    let name = name.trim().to_lowercase();
+    COUNTRY_CODES.iter().find(|(n, _)| *n == name).map(|(_, code)| *code)
+}
+
+/// A DNS resolver seen by the echo service.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct ResolverInfo {
+    pub ip: String,
+    pub country: String,
+    /// Mapped from `country`; empty when the name is not known
+    pub country_code: String,
+    pub isp: String,
+}
+
+/// `{"dns":{"geo":"Netherlands - Cloudflare, Inc.","ip":"162.158.1.1"}}`
+pub fn parse_dns_echo(body: &str) -> Option<ResolverInfo> {
+    let value: Value = serde_json::from_str(body).ok()?;
+    let dns = &value["dns"];
+    let ip = text(dns, "ip");
+    if ip.is_empty() {
+        return None;
+    }
+    let geo = text(dns, "geo");
+    let (country, isp) = geo.split_once(" - ").unwrap_or((geo.as_str(), ""));
+    Some(ResolverInfo {
+        ip,
+        country: country.trim().to_string(),
+        country_code: country_code_for_name(country).unwrap_or_default().to_string(),
+        isp: isp.trim().to_string(),
+    })
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct DnsLeakReport {
+    /// ISO code of the exit country
+    pub exit_country: String,
+    pub resolvers: Vec<ResolverInfo>,
+    pub leak: bool,
+    /// Why `leak` is set, one entry per suspicious resolver
+    pub reasons: Vec<String>,
+}
+
+fn is_local_address(ip: &str) -> bool {
+    match ip.parse::<std::net::IpAddr>() {
+        Ok(std::net::IpAddr::V4(v4)) => {
+            v4.is_private() || v4.is_loopback() || v4.is_link_local() || v4.is_unspecified()
+        }
+        Ok(std::net::IpAddr::V6(v6)) => {
+            v6.is_loopback() || v6.is_unspecified() || (v6.segments()[0] & 0xfe00) == 0xfc00
+        }
+        Err(_) => false,
+    }
+}
+
+/// A resolver on a local network, or in another country than the exit, means lookups
+/// are not going through the proxy. Countries are compared by ISO code; an unknown exit
+/// or resolver country skips the comparison.
+pub fn assess_dns_leak(exit_country: &str, resolvers: Vec<ResolverInfo>) -> DnsLeakReport {
+    let mut reasons = Vec::new();
+    for r in &resolvers {
+        if is_local_address(&r.ip) {
+            reasons.push(format!("Resolver {} is on the local network", r.ip));
+        } else if !exit_country.is_empty()
+            && !r.country_code.is_empty()
+            && !r.country_code.eq_ignore_ascii_case(exit_country)
+        {
+            reasons.push(format!(
+                "Resolver {} is in {}, the exit is in {}",
+                r.ip, r.country, exit_country
+            ));
+        }
+    }
+    DnsLeakReport {
+        exit_country: exit_country.to_string(),
+        resolvers,
+        leak: !reasons.is_empty(),
+        reasons,
+    }
+}
@@ -1,8 +1,10 @@
 #![allow(unexpected_cfgs)]
 
 mod config;
+mod error;
 mod helper_client;
 mod installer;
+mod ip_check;
 pub mod libbox;
 mod manager;
 mod profile;
@@ -10,6 +12,10 @@ mod service;
 
 pub mod settings;
 
+/// IPC protocol revision, also compiled into the helper binary
+pub use helper_client::PROTOCOL_VERSION as HELPER_PROTOCOL_VERSION;
+
+use error::TunnetError;
 use service::ProxyService;
 use std::sync::OnceLock;
 use tauri::{Manager, State};
@@ -57,7 +63,7 @@ async fn start_proxy(
     node: Option<profile::Node>,
     tun: Option<bool>,
     routing: Option<String>,
-) -> Result<service::ProxyStatus, String> {
+) -> Result<service::ProxyStatus, TunnetError> {
     log::info!(
         "IPC: start_proxy received tun={:?}, routing={:?}",
         tun,
@@ -78,7 +84,7 @@ async fn start_proxy(
 #[tauri::command]
 async fn stop_proxy(
     service: State<'_, ProxyService<tauri::Wry>>,
-) -> Result<service::ProxyStatus, String> {
+) -> Result<service::ProxyStatus, TunnetError> {
     service.stop_proxy(true).await;
     Ok(service.get_status())
 }
@@ -87,25 +93,74 @@ async fn stop_proxy(
 async fn import_subscription(
     url: String,
     name: Option<String>,
+    use_proxy: Option<bool>,
+    service: State<'_, ProxyService<tauri::Wry>>,
+) -> Result<service::ImportResult, TunnetError> {
+    service
+        .import_subscription(&url, name, use_proxy.unwrap_or(false))
+        .await
+}
+
+#[tauri::command]
+fn cleanup_orphans(service: State<'_, ProxyService<tauri::Wry>>) -> Vec<u32> {
+    service.cleanup_orphans()
+}
+
+#[tauri::command]
+fn cancel_import(service: State<'_, ProxyService<tauri::Wry>>) -> bool {
+    service.cancel_import()
+}
+
+#[tauri::command]
+async fn import_from_file(
+    path: String,
     service: State<'_, ProxyService<tauri::Wry>>,
-) -> Result<String, String> {
-    service.import_subscription(&url, name).await
+) -> Result<service::ImportResult, TunnetError> {
+    service.import_from_file(&path)
+}
+
+#[tauri::command]
+async fn import_links(
+    text: String,
+    service: State<'_, ProxyService<tauri::Wry>>,
+) -> Result<service::LinkImportResult, TunnetError> {
+    service.import_links(&text)
 }
 
 #[tauri::command]
 async fn get_nodes(
     service: State<'_, ProxyService<tauri::Wry>>,
-) -> Result<Vec<crate::profile::Node>, String> {
+) -> Result<Vec<crate::profile::Node>, TunnetError> {
     service.get_nodes()
 }
 
 #[tauri::command]
-async fn check_ip(
+async fn get_stale_nodes(
+    days: u64,
     service: State<'_, ProxyService<tauri::Wry>>,
-) -> Result<serde_json::Value, String> {
+) -> Result<Vec<crate::profile::Node>, TunnetError> {
+    service.get_stale_nodes(days)
+}
+
+#[tauri::command]
+async fn get_nodes_sorted(
+    by: crate::profile::NodeSortKey,
+    service: State<'_, ProxyService<tauri::Wry>>,
+) -> Result<Vec<crate::profile::Node>, TunnetError> {
+    service.get_nodes_sorted(by)
+}
+
+/// Client whose requests leave the machine the way proxied traffic does.
+fn proxied_client(service: &ProxyService<tauri::Wry>) -> Result<reqwest::Client, TunnetError> {
     let client_builder = reqwest::Client::builder().timeout(std::time::Duration::from_secs(10));
 
-    let client = if service.is_tun_mode() {
+    // With tun_with_mixed the mixed port is up next to TUN, so ask through it explicitly
+    // rather than relying on the tunnel capturing the app's own traffic
+    let tun_with_mixed = service
+        .get_app_settings()
+        .map(|s| s.tun_with_mixed)
+        .unwrap_or(false);
+    let client = if service.is_tun_mode() && !tun_with_mixed {
         client_builder
             .no_proxy()
             .build()
@@ -122,31 +177,164 @@ async fn check_ip(
             .build()
             .map_err(|e| e.to_string())?
     };
+    Ok(client)
+}
 
-    let res = client
-        .get("http://ip-api.com/json")
+/// Exit IP and location as seen through the proxy.
+async fn lookup_exit_ip(
+    service: &ProxyService<tauri::Wry>,
+) -> Result<ip_check::IpLookup, TunnetError> {
+    let client = proxied_client(service)?;
+    let url = service
+        .get_app_settings()
+        .map(|s| s.ip_check_url)
+        .unwrap_or_else(|_| ip_check::DEFAULT_IP_CHECK_URL.to_string());
+    let body = client
+        .get(&url)
         .send()
         .await
+        .map_err(|e| e.to_string())?
+        .text()
+        .await
         .map_err(|e| e.to_string())?;
 
-    let json: serde_json::Value = res.json().await.map_err(|e| e.to_string())?;
-    Ok(json)
+    ip_check::parse_response(&url, &body)
+        .ok_or_else(|| format!("Unexpected IP check response from {}", url).into())
+}
+
+#[tauri::command]
+async fn check_ip(
+    service: State<'_, ProxyService<tauri::Wry>>,
+) -> Result<ip_check::IpLookup, TunnetError> {
+    lookup_exit_ip(&service).await
+}
+
+/// Resolve a few random names through the proxy and collect the resolvers that asked.
+async fn lookup_resolvers(
+    service: &ProxyService<tauri::Wry>,
+) -> Result<Vec<ip_check::ResolverInfo>, TunnetError> {
+    const LOOKUPS: usize = 3;
+
+    let client = proxied_client(service)?;
+    let mut resolvers: Vec<ip_check::ResolverInfo> = Vec::new();
+    for _ in 0..LOOKUPS {
+        let body = match client.get(ip_check::dns_echo_url()).send().await {
+            Ok(res) => res.text().await.unwrap_or_default(),
+            Err(e) => {
+                log::warn!("DNS leak lookup failed: {}", e);
+                continue;
+            }
+        };
+        if let Some(resolver) = ip_check::parse_dns_echo(&body) {
+            if !resolvers.contains(&resolver) {
+                resolvers.push(resolver);
+            }
+        }
+    }
+    if resolvers.is_empty() {
+        return Err("The DNS echo service did not answer".into());
+    }
+    Ok(resolvers)
+}
+
+/// Country of the active node from its last location probe.
+fn active_node_country(service: &ProxyService<tauri::Wry>) -> Option<String> {
+    service
+        .get_status()
+        .node
+        .and_then(|n| n.location)
+        .map(|l| l.country_code)
+        .filter(|c| !c.is_empty())
+}
+
+/// Check where the resolvers used through the proxy sit compared with the exit node.
+#[tauri::command]
+async fn dns_leak_test(
+    service: State<'_, ProxyService<tauri::Wry>>,
+) -> Result<ip_check::DnsLeakReport, TunnetError> {
+    if !service.is_proxy_running() {
+        return Err("Connect first to run a DNS leak test".into());
+    }
+    let resolvers = lookup_resolvers(&service).await?;
+
+    // The probed location of the active node, else a live lookup of the exit
+    let exit_country = match active_node_country(&service) {
+        Some(country) => country,
+        None => lookup_exit_ip(&service).await.map(|l| l.country_code).unwrap_or_default(),
+    };
+    Ok(ip_check::assess_dns_leak(&exit_country, resolvers))
+}
+
+/// Everything the connection health card shows, in one round trip. The exit and DNS
+/// lookups run side by side and only while connected.
+#[tauri::command]
+async fn connection_diagnostics(
+    service: State<'_, ProxyService<tauri::Wry>>,
+) -> Result<service::ConnectionDiagnostics, TunnetError> {
+    let status = service.get_status();
+    let settings = service.get_app_settings()?;
+    // Desktop TUN mode keeps the local mixed instance; mobile only with tun_with_mixed
+    let mixed_up = !status.tun_mode || cfg!(desktop) || settings.tun_with_mixed;
+    let mut diagnostics = service::ConnectionDiagnostics {
+        is_running: status.is_running,
+        tun_mode: status.tun_mode,
+        mixed_port: (status.is_running && mixed_up).then_some(settings.mixed_port),
+        routing_mode: status.routing_mode,
+        node: status.node,
+        exit: None,
+        exit_error: None,
+        dns: None,
+        dns_error: None,
+    };
+    if !diagnostics.is_running {
+        return Ok(diagnostics);
+    }
+
+    let (exit, resolvers) = tokio::join!(lookup_exit_ip(&service), lookup_resolvers(&service));
+    let exit_country = active_node_country(&service)
+        .or_else(|| exit.as_ref().ok().map(|l| l.country_code.clone()))
+        .unwrap_or_default();
+    match exit {
+        Ok(lookup) => diagnostics.exit = Some(lookup),
+        Err(e) => diagnostics.exit_error = Some(e.to_string()),
+    }
+    match resolvers {
+        Ok(resolvers) => diagnostics.dns = Some(ip_check::assess_dns_leak(&exit_country, resolvers)),
+        Err(e) => diagnostics.dns_error = Some(e.to_string()),
+    }
+    Ok(diagnostics)
+}
+
+#[tauri::command]
+async fn check_node_ip(
+    node_id: String,
+    service: State<'_, ProxyService<tauri::Wry>>,
+) -> Result<ip_check::IpLookup, TunnetError> {
+    service.check_node_ip(&node_id).await
 }
 
 #[tauri::command]
 async fn add_node(
     node: crate::profile::Node,
     service: State<'_, ProxyService<tauri::Wry>>,
-) -> Result<(), String> {
+) -> Result<(), TunnetError> {
     service.add_node(node).await
 }
 
+#[tauri::command]
+async fn add_node_from_outbound_json(
+    json: String,
+    service: State<'_, ProxyService<tauri::Wry>>,
+) -> Result<crate::profile::Node, TunnetError> {
+    service.add_node_from_outbound_json(&json).await
+}
+
 #[tauri::command]
 async fn update_node(
     id: String,
     node: crate::profile::Node,
     service: State<'_, ProxyService<tauri::Wry>>,
-) -> Result<(), String> {
+) -> Result<(), TunnetError> {
     // Current frontend passes ID, but update_node in service takes Node.
     // Ensure Node has the ID
     let mut n = node;
@@ -158,7 +346,7 @@ async fn update_node(
 async fn delete_node(
     id: String,
     service: State<'_, ProxyService<tauri::Wry>>,
-) -> Result<(), String> {
+) -> Result<(), TunnetError> {
     service.delete_node(&id)
 }
 
@@ -166,20 +354,49 @@ async fn delete_node(
 async fn check_node_locations(
     node_ids: Vec<String>,
     service: State<'_, ProxyService<tauri::Wry>>,
-) -> Result<(), String> {
+) -> Result<(), TunnetError> {
     service.probe_nodes_location(node_ids).await
 }
 
+#[tauri::command]
+async fn resolve_node_country(
+    node_id: String,
+    service: State<'_, ProxyService<tauri::Wry>>,
+) -> Result<String, TunnetError> {
+    service.resolve_node_country(node_id).await
+}
+
 use installer::HelperInstaller;
 
 #[tauri::command]
-async fn install_helper(app: tauri::AppHandle) -> Result<(), String> {
-    let installer = HelperInstaller::new(app);
-    installer.install().map_err(|e| e.to_string())
+async fn install_helper(app: tauri::AppHandle) -> Result<(), TunnetError> {
+    // Installing blocks until the new helper answers over IPC
+    tauri::async_runtime::spawn_blocking(move || {
+        HelperInstaller::new(app).install().map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| TunnetError::Other(e.to_string()))?
+    .map_err(TunnetError::Other)
+}
+
+#[tauri::command]
+async fn uninstall_helper(
+    app: tauri::AppHandle,
+    service: State<'_, ProxyService<tauri::Wry>>,
+) -> Result<(), TunnetError> {
+    // The helper may be running the TUN instance; tear everything down first
+    service.stop_proxy(true).await;
+    // Uninstalling blocks on the osascript/pkexec password prompt
+    tauri::async_runtime::spawn_blocking(move || {
+        HelperInstaller::new(app).uninstall().map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| TunnetError::Other(e.to_string()))?
+    .map_err(TunnetError::Other)
 }
 
 #[tauri::command]
-async fn check_helper(app: tauri::AppHandle) -> Result<bool, String> {
+async fn check_helper(app: tauri::AppHandle) -> Result<bool, TunnetError> {
     let installer = HelperInstaller::new(app);
     let installed = installer.is_installed();
 
@@ -187,25 +404,64 @@ async fn check_helper(app: tauri::AppHandle) -> Result<bool, String> {
         return Ok(false);
     }
 
-    // Binary exists, now check if it's running/responsive via IPC
+    // Helper IPC blocks, for seconds while waiting on a restarting helper
+    tauri::async_runtime::spawn_blocking(helper_responsive)
+        .await
+        .map_err(|e| TunnetError::Other(e.to_string()))
+}
+
+/// Whether the installed helper answers over IPC with our protocol and a supported core.
+fn helper_responsive() -> bool {
     let client = helper_client::HelperClient::new();
-    match client.get_version() {
-        Ok(v) => {
-            // Version 1.1.0+ supports reload (SIGHUP)
-            Ok(v == env!("CARGO_PKG_VERSION")) // Bound to specific version for update
-        }
-        Err(_) => {
-            // Helper installed but not responsive (crashed, stopped, or stale socket)
+    match client.negotiate() {
+        Ok(protocol) => helper_supported(&client, protocol),
+        Err(e) => {
+            // A stale socket means the helper crashed; launchd/systemd restart it on their own,
+            // so give the supervisor a chance before falling back to a full reinstall
+            if let Some(helper_client::HelperError::StaleSocket) =
+                helper_client::helper_error_kind(e.as_ref())
+            {
+                log::warn!("Helper socket is stale, waiting for the helper to restart...");
+                if client
+                    .wait_until_ready(std::time::Duration::from_secs(8))
+                    .is_ok()
+                {
+                    if let Ok(protocol) = client.negotiate() {
+                        return helper_supported(&client, protocol);
+                    }
+                }
+            }
+            // Helper installed but not responsive (crashed or stopped)
             // Treat as not installed so we trigger the repair flow
-            Ok(false)
+            false
         }
     }
 }
 
+/// Whether a helper that negotiated `protocol` can serve us as installed.
+fn helper_supported(client: &helper_client::HelperClient, protocol: u32) -> bool {
+    // A helper speaking another protocol revision must be reinstalled
+    if protocol != helper_client::PROTOCOL_VERSION {
+        return false;
+    }
+    // So does one whose core would reject our configs; reinstalling brings the bundled one
+    match client.core_version() {
+        Ok(version) => match libbox::check_core_version(&version) {
+            Ok(()) => true,
+            Err(e) => {
+                log::warn!("Installed helper: {}", e);
+                false
+            }
+        },
+        // Helpers from before the command run the core they were built with
+        Err(_) => true,
+    }
+}
+
 #[tauri::command]
 async fn get_rules(
     service: State<'_, ProxyService<tauri::Wry>>,
-) -> Result<Vec<crate::profile::Rule>, String> {
+) -> Result<Vec<crate::profile::Rule>, TunnetError> {
     service.get_rules()
 }
 
@@ -213,7 +469,7 @@ async fn get_rules(
 async fn save_rules(
     rules: Vec<crate::profile::Rule>,
     service: State<'_, ProxyService<tauri::Wry>>,
-) -> Result<(), String> {
+) -> Result<(), TunnetError> {
     service.save_rules(rules).await
 }
 
@@ -221,7 +477,7 @@ async fn save_rules(
 async fn add_rule(
     rule: crate::profile::Rule,
     service: State<'_, ProxyService<tauri::Wry>>,
-) -> Result<(), String> {
+) -> Result<(), TunnetError> {
     service.add_rule(rule).await
 }
 
@@ -229,7 +485,7 @@ async fn add_rule(
 async fn update_rule(
     rule: crate::profile::Rule,
     service: State<'_, ProxyService<tauri::Wry>>,
-) -> Result<(), String> {
+) -> Result<(), TunnetError> {
     service.update_rule(rule).await
 }
 
@@ -237,22 +493,49 @@ async fn update_rule(
 async fn delete_rule(
     id: String,
     service: State<'_, ProxyService<tauri::Wry>>,
-) -> Result<(), String> {
+) -> Result<(), TunnetError> {
     service.delete_rule(&id).await
 }
 
+#[tauri::command]
+async fn move_rule(
+    id: String,
+    new_index: usize,
+    service: State<'_, ProxyService<tauri::Wry>>,
+) -> Result<(), TunnetError> {
+    service.move_rule(&id, new_index).await
+}
+
+#[tauri::command]
+async fn move_node(
+    id: String,
+    new_index: usize,
+    service: State<'_, ProxyService<tauri::Wry>>,
+) -> Result<(), TunnetError> {
+    service.move_node(&id, new_index)
+}
+
+#[tauri::command]
+async fn simulate_route(
+    host: String,
+    port: u16,
+    service: State<'_, ProxyService<tauri::Wry>>,
+) -> Result<crate::profile::RouteDecision, TunnetError> {
+    service.simulate_route(&host, port)
+}
+
 #[tauri::command]
 async fn url_test(
     node_id: String,
     service: State<'_, ProxyService<tauri::Wry>>,
-) -> Result<u64, String> {
+) -> Result<service::LatencyResult, TunnetError> {
     service.url_test(node_id).await
 }
 
 #[tauri::command]
 async fn get_app_settings(
     service: State<'_, ProxyService<tauri::Wry>>,
-) -> Result<crate::settings::AppSettings, String> {
+) -> Result<crate::settings::AppSettings, TunnetError> {
     service.get_app_settings()
 }
 
@@ -260,14 +543,70 @@ async fn get_app_settings(
 async fn save_app_settings(
     settings: crate::settings::AppSettings,
     service: State<'_, ProxyService<tauri::Wry>>,
-) -> Result<(), String> {
+) -> Result<(), TunnetError> {
     service.save_app_settings(settings).await
 }
 
+#[tauri::command]
+fn get_clash_api_info(service: State<'_, ProxyService<tauri::Wry>>) -> service::ClashApiInfo {
+    service.get_clash_api_info()
+}
+
+#[tauri::command]
+fn list_geo_categories(
+    kind: String,
+    service: State<'_, ProxyService<tauri::Wry>>,
+) -> Result<Vec<String>, TunnetError> {
+    service.list_geo_categories(&kind)
+}
+
+#[tauri::command]
+async fn update_settings(
+    settings: crate::settings::AppSettings,
+    apply: Option<bool>,
+    service: State<'_, ProxyService<tauri::Wry>>,
+) -> Result<service::SettingsUpdate, TunnetError> {
+    service.update_settings(settings, apply.unwrap_or(true)).await
+}
+
+#[tauri::command]
+fn get_recent_logs(service: tauri::State<'_, service::ProxyService<tauri::Wry>>) -> Vec<service::LogEvent> {
+    service.get_recent_logs()
+}
+
+#[tauri::command]
+fn get_core_log_path(service: State<'_, ProxyService<tauri::Wry>>) -> String {
+    service.get_core_log_path()
+}
+
+#[tauri::command]
+fn read_core_log(
+    tail_lines: usize,
+    service: State<'_, ProxyService<tauri::Wry>>,
+) -> Result<Vec<String>, TunnetError> {
+    service.read_core_log(tail_lines)
+}
+
+#[tauri::command]
+fn get_helper_log(
+    tail_lines: usize,
+    service: State<'_, ProxyService<tauri::Wry>>,
+) -> Result<Vec<String>, TunnetError> {
+    service.read_helper_log(tail_lines)
+}
+
+#[tauri::command]
+async fn set_tun_bypass_cidrs(
+    cidrs: Vec<String>,
+    service: State<'_, ProxyService<tauri::Wry>>,
+) -> Result<(), TunnetError> {
+    service.set_tun_bypass_cidrs(cidrs).await
+}
+
 #[tauri::command]
 async fn get_groups(
     service: State<'_, ProxyService<tauri::Wry>>,
-) -> Result<Vec<crate::profile::Group>, String> {
+) -> Result<Vec<crate::profile::Group>, TunnetError> {
     service.get_groups()
 }
 
@@ -275,7 +614,7 @@ async fn get_groups(
 async fn save_groups(
     groups: Vec<crate::profile::Group>,
     service: State<'_, ProxyService<tauri::Wry>>,
-) -> Result<(), String> {
+) -> Result<(), TunnetError> {
     service.save_groups(groups).await
 }
 
@@ -283,7 +622,7 @@ async fn save_groups(
 async fn add_group(
     group: crate::profile::Group,
     service: State<'_, ProxyService<tauri::Wry>>,
-) -> Result<(), String> {
+) -> Result<(), TunnetError> {
     service.add_group(group).await
 }
 
@@ -291,7 +630,7 @@ async fn add_group(
 async fn update_group(
     group: crate::profile::Group,
     service: State<'_, ProxyService<tauri::Wry>>,
-) -> Result<(), String> {
+) -> Result<(), TunnetError> {
     service.update_group(group).await
 }
 
@@ -299,7 +638,7 @@ async fn update_group(
 async fn delete_group(
     id: String,
     service: State<'_, ProxyService<tauri::Wry>>,
-) -> Result<(), String> {
+) -> Result<(), TunnetError> {
     service.delete_group(&id).await
 }
 
@@ -309,14 +648,14 @@ async fn ensure_auto_group(
     name: String,
     references: Vec<String>,
     group_type: String, // "selector" or "url-test"
-) -> Result<String, String> {
+) -> Result<String, TunnetError> {
     let gt = match group_type.as_str() {
         "url-test" => crate::profile::GroupType::UrlTest {
             interval: 600, // Default 10 min
             tolerance: 50,
         },
         "selector" => crate::profile::GroupType::Selector,
-        _ => return Err("Invalid group type".to_string()),
+        _ => return Err("Invalid group type".into()),
     };
     service.ensure_auto_group(name, references, gt)
 }
@@ -325,7 +664,7 @@ async fn ensure_auto_group(
 async fn get_group_alive_nodes(
     service: State<'_, ProxyService<tauri::Wry>>,
     group_id: String,
-) -> Result<Vec<service::ProxyNodeStatus>, String> {
+) -> Result<Vec<service::ProxyNodeStatus>, TunnetError> {
     service.get_group_nodes(&group_id).await
 }
 
@@ -334,14 +673,14 @@ async fn select_group_node(
     service: State<'_, ProxyService<tauri::Wry>>,
     group_id: String,
     node_name: String,
-) -> Result<(), String> {
+) -> Result<(), TunnetError> {
     service.select_group_node(&group_id, &node_name).await
 }
 
 #[tauri::command]
 async fn get_connections(
     service: State<'_, ProxyService<tauri::Wry>>,
-) -> Result<service::ConnectionsResponse, String> {
+) -> Result<service::ConnectionsResponse, TunnetError> {
     service.get_connections().await
 }
 
@@ -349,19 +688,19 @@ async fn get_connections(
 async fn close_connection(
     id: String,
     service: State<'_, ProxyService<tauri::Wry>>,
-) -> Result<(), String> {
+) -> Result<(), TunnetError> {
     service.close_connection(&id).await
 }
 
 #[tauri::command]
 async fn close_all_connections(
     service: State<'_, ProxyService<tauri::Wry>>,
-) -> Result<(), String> {
+) -> Result<(), TunnetError> {
     service.close_all_connections().await
 }
 
 #[tauri::command]
-async fn open_main_window(app: tauri::AppHandle) -> Result<(), String> {
+async fn open_main_window(app: tauri::AppHandle) -> Result<(), TunnetError> {
     #[cfg(desktop)]
     {
         let window = if let Some(w) = app.get_webview_window("main") {
@@ -412,7 +751,7 @@ fn create_main_window(app: &tauri::AppHandle) -> tauri::Result<tauri::WebviewWin
 async fn quit_app(
     _app: tauri::AppHandle,
     service: State<'_, ProxyService<tauri::Wry>>,
-) -> Result<(), String> {
+) -> Result<(), TunnetError> {
     log::info!("Preparing to quit: Stopping proxy service...");
     // 真正等待服务停止完成 (stop_proxy(true) will join the task)
     service.stop_proxy(true).await;
@@ -427,7 +766,7 @@ fn final_exit(app: tauri::AppHandle) {
 }
 
 #[tauri::command]
-async fn hide_tray_window(app: tauri::AppHandle) -> Result<(), String> {
+async fn hide_tray_window(app: tauri::AppHandle) -> Result<(), TunnetError> {
     #[cfg(desktop)]
     if let Some(window) = app.get_webview_window("tray") {
         window.hide().map_err(|e| e.to_string())?;
@@ -439,14 +778,48 @@ async fn hide_tray_window(app: tauri::AppHandle) -> Result<(), String> {
 async fn set_routing_mode_command(
     service: State<'_, ProxyService<tauri::Wry>>,
     mode: String,
-) -> Result<(), String> {
+) -> Result<(), TunnetError> {
     service.set_routing_mode(&mode).await
 }
 
+/// Mobile TUN entry point: the Android/iOS VPN service owns the interface and hands
+/// over its file descriptor.
+#[tauri::command]
+async fn start_mobile(
+    fd: i32,
+    node: Option<crate::profile::Node>,
+    routing: Option<String>,
+    service: State<'_, ProxyService<tauri::Wry>>,
+) -> Result<service::ProxyStatus, TunnetError> {
+    #[cfg(mobile)]
+    {
+        let routing = routing.unwrap_or_else(|| "rule".to_string());
+        service.start_mobile_proxy(fd, node, &routing)?;
+        Ok(service.get_status())
+    }
+    #[cfg(desktop)]
+    {
+        let _ = (fd, node, routing, service);
+        Err("Mobile TUN is only available on Android and iOS".into())
+    }
+}
+
+#[tauri::command]
+fn get_resumable_session(
+    service: State<'_, ProxyService<tauri::Wry>>,
+) -> Option<service::LastSession> {
+    service.get_resumable_session()
+}
+
+#[tauri::command]
+fn discard_last_session(service: State<'_, ProxyService<tauri::Wry>>) {
+    service.clear_last_session();
+}
+
 #[tauri::command]
 async fn get_proxy_status(
     service: State<'_, ProxyService<tauri::Wry>>,
-) -> Result<service::ProxyStatus, String> {
+) -> Result<service::ProxyStatus, TunnetError> {
     Ok(service.get_status())
 }
 
@@ -693,6 +1066,9 @@ pub fn run() {
                 service.maybe_auto_connect().await;
             });
 
+            // Scheduled subscription updates
+            app.state::<ProxyService<tauri::Wry>>().start_subscription_scheduler();
+
             // System Tray Setup
             #[cfg(desktop)]
             {
@@ -912,25 +1288,52 @@ pub fn run() {
             start_proxy,
             stop_proxy,
             import_subscription,
+            cancel_import,
+            import_from_file,
+            import_links,
+            cleanup_orphans,
             get_nodes,
+            get_nodes_sorted,
+            get_stale_nodes,
             check_ip,
+            dns_leak_test,
+            connection_diagnostics,
+            check_node_ip,
             add_node,
+            add_node_from_outbound_json,
             update_node,
             delete_node,
             install_helper,
+            uninstall_helper,
             check_helper,
             get_profiles,
+            get_profile_usage,
             delete_profile,
             update_subscription_profile,
             check_node_locations,
+            test_profile,
+            cancel_tests,
+            resolve_node_country,
             get_rules,
             save_rules,
             add_rule,
             update_rule,
             delete_rule,
+            simulate_route,
+            move_rule,
+            move_node,
             url_test,
             get_app_settings,
             save_app_settings,
+            update_settings,
+            list_geo_categories,
+            get_clash_api_info,
+            preview_config,
+            set_tun_bypass_cidrs,
+            get_recent_logs,
+            get_core_log_path,
+            read_core_log,
+            get_helper_log,
             // Group Commands
             ensure_auto_group,
             get_groups,
@@ -946,6 +1349,9 @@ pub fn run() {
             hide_tray_window,
             set_routing_mode_command,
             get_proxy_status,
+            get_resumable_session,
+            start_mobile,
+            discard_last_session,
             final_exit,
             edit_profile,
             check_node_pings,
@@ -953,6 +1359,8 @@ pub fn run() {
             refresh_geodata,
             restart_app,
             get_node_link,
+            node_qr_code,
+            profile_qr_code,
             poll_traffic,
             tray_heartbeat,
             main_heartbeat,
@@ -961,9 +1369,14 @@ pub fn run() {
             export_profile_content,
             export_group_content,
             export_all_nodes,
+            export_node_config,
+            export_profile_config,
+            export_clash_yaml,
                     export_singbox_config,
             export_tunnet_backup,
             import_tunnet_backup,
+            export_backup,
+            import_backup,
             decode_qr,
             get_connections,
             close_connection,
@@ -1020,10 +1433,18 @@ pub fn run() {
         });
 }
 
+#[tauri::command]
+fn get_profile_usage(
+    id: String,
+    service: State<'_, ProxyService<tauri::Wry>>,
+) -> Result<crate::profile::ProfileUsage, TunnetError> {
+    service.get_profile_usage(&id)
+}
+
 #[tauri::command]
 async fn get_profiles(
     service: State<'_, ProxyService<tauri::Wry>>,
-) -> Result<Vec<crate::profile::Profile>, String> {
+) -> Result<Vec<crate::profile::Profile>, TunnetError> {
     service.get_profiles()
 }
 
@@ -1031,7 +1452,7 @@ async fn get_profiles(
 async fn delete_profile(
     service: State<'_, ProxyService<tauri::Wry>>,
     id: String,
-) -> Result<(), String> {
+) -> Result<(), TunnetError> {
     service.delete_profile(&id).await
 }
 
@@ -1043,13 +1464,15 @@ async fn edit_profile(
     url: Option<String>,
     update_interval: Option<u64>,
     clear_interval: Option<bool>,
-) -> Result<(), String> {
+    user_agent: Option<String>,
+) -> Result<(), TunnetError> {
     service.edit_profile(
         &id,
         &name,
         url,
         update_interval,
         clear_interval.unwrap_or(false),
+        user_agent,
     )
 }
 
@@ -1057,28 +1480,44 @@ async fn edit_profile(
 async fn update_subscription_profile(
     service: State<'_, ProxyService<tauri::Wry>>,
     id: String,
-) -> Result<Vec<String>, String> {
-    service.update_subscription_profile(&id).await
+    use_proxy: Option<bool>,
+) -> Result<Vec<String>, TunnetError> {
+    service
+        .update_subscription_profile(&id, use_proxy.unwrap_or(false))
+        .await
 }
 
 #[tauri::command]
 async fn check_node_pings(
     service: State<'_, ProxyService<tauri::Wry>>,
     node_ids: Vec<String>,
-) -> Result<(), String> {
+) -> Result<(), TunnetError> {
     service.probe_nodes_latency(node_ids).await
 }
 
+#[tauri::command]
+async fn test_profile(
+    profile_id: String,
+    service: State<'_, ProxyService<tauri::Wry>>,
+) -> Result<Vec<crate::profile::Node>, TunnetError> {
+    service.test_profile(&profile_id).await
+}
+
+#[tauri::command]
+fn cancel_tests(service: State<'_, ProxyService<tauri::Wry>>) {
+    service.cancel_tests()
+}
+
 #[tauri::command]
 async fn get_group_status(
     service: State<'_, ProxyService<tauri::Wry>>,
     group_id: String,
-) -> Result<String, String> {
+) -> Result<String, TunnetError> {
     service.get_group_status(&group_id).await
 }
 
 #[tauri::command]
-async fn refresh_geodata(service: State<'_, ProxyService<tauri::Wry>>) -> Result<(), String> {
+async fn refresh_geodata(service: State<'_, ProxyService<tauri::Wry>>) -> Result<(), TunnetError> {
     service.refresh_geodata().await
 }
 
@@ -1091,16 +1530,38 @@ fn restart_app(app: tauri::AppHandle) {
 async fn get_node_link(
     service: State<'_, ProxyService<tauri::Wry>>,
     id: String,
-) -> Result<String, String> {
+) -> Result<String, TunnetError> {
     service.export_node_link(id)
 }
 
+#[tauri::command]
+async fn node_qr_code(
+    service: State<'_, ProxyService<tauri::Wry>>,
+    id: String,
+    format: Option<String>,
+    size: Option<u32>,
+    ec_level: Option<String>,
+) -> Result<String, TunnetError> {
+    service.node_qr_code(id, format, size, ec_level)
+}
+
+#[tauri::command]
+async fn profile_qr_code(
+    service: State<'_, ProxyService<tauri::Wry>>,
+    id: String,
+    format: Option<String>,
+    size: Option<u32>,
+    ec_level: Option<String>,
+) -> Result<String, TunnetError> {
+    service.profile_qr_code(id, format, size, ec_level)
+}
+
 #[tauri::command]
 async fn export_profile_content(
     service: State<'_, ProxyService<tauri::Wry>>,
     id: String,
     format: String,
-) -> Result<String, String> {
+) -> Result<String, TunnetError> {
     service.export_profile_content(id, format)
 }
 
@@ -1109,7 +1570,7 @@ async fn export_group_content(
     service: State<'_, ProxyService<tauri::Wry>>,
     id: String,
     format: String,
-) -> Result<String, String> {
+) -> Result<String, TunnetError> {
     service.export_group_content(id, format)
 }
 
@@ -1118,29 +1579,67 @@ async fn export_node_content(
     service: State<'_, ProxyService<tauri::Wry>>,
     id: String,
     format: String,
-) -> Result<String, String> {
+) -> Result<String, TunnetError> {
     service.export_node_content(id, format)
 }
 
+#[tauri::command]
+async fn export_node_config(
+    service: State<'_, ProxyService<tauri::Wry>>,
+    id: String,
+) -> Result<String, TunnetError> {
+    service.export_node_config(id)
+}
+
+#[tauri::command]
+async fn export_profile_config(
+    service: State<'_, ProxyService<tauri::Wry>>,
+    id: String,
+) -> Result<String, TunnetError> {
+    service.export_profile_config(id)
+}
+
+#[tauri::command]
+async fn export_clash_yaml(
+    service: State<'_, ProxyService<tauri::Wry>>,
+    id: String,
+) -> Result<String, TunnetError> {
+    service.export_clash_yaml(id)
+}
+
 #[tauri::command]
 async fn export_all_nodes(
     service: State<'_, ProxyService<tauri::Wry>>,
     format: String,
-) -> Result<String, String> {
+) -> Result<String, TunnetError> {
     service.export_all_nodes(format)
 }
 
+#[tauri::command]
+fn preview_config(
+    service: State<'_, ProxyService<tauri::Wry>>,
+    node: Option<profile::Node>,
+    tun: Option<bool>,
+    routing_mode: Option<String>,
+) -> Result<String, TunnetError> {
+    service.preview_config(
+        node,
+        tun.unwrap_or(false),
+        routing_mode.as_deref().unwrap_or("rule"),
+    )
+}
+
 #[tauri::command]
 async fn export_singbox_config(
     service: State<'_, ProxyService<tauri::Wry>>,
-) -> Result<String, String> {
+) -> Result<String, TunnetError> {
     service.export_singbox_config()
 }
 
 #[tauri::command]
 async fn export_tunnet_backup(
     service: State<'_, ProxyService<tauri::Wry>>,
-) -> Result<String, String> {
+) -> Result<String, TunnetError> {
     service.export_tunnet_backup()
 }
 
@@ -1148,15 +1647,33 @@ async fn export_tunnet_backup(
 async fn import_tunnet_backup(
     service: State<'_, ProxyService<tauri::Wry>>,
     json: String,
-) -> Result<(), String> {
-    service.import_tunnet_backup(json).await
+    merge: Option<bool>,
+) -> Result<(), TunnetError> {
+    service.import_tunnet_backup(json, merge.unwrap_or(false)).await
+}
+
+#[tauri::command]
+async fn export_backup(
+    service: State<'_, ProxyService<tauri::Wry>>,
+    path: String,
+) -> Result<(), TunnetError> {
+    service.export_backup(&path)
+}
+
+#[tauri::command]
+async fn import_backup(
+    service: State<'_, ProxyService<tauri::Wry>>,
+    path: String,
+    merge: Option<bool>,
+) -> Result<(), TunnetError> {
+    service.import_backup(&path, merge.unwrap_or(false)).await
 }
 
 #[tauri::command]
 async fn decode_qr(
     service: State<'_, ProxyService<tauri::Wry>>,
     path: String,
-) -> Result<String, String> {
+) -> Result<String, TunnetError> {
     service.decode_qr(&path)
 }
 
@@ -5,7 +5,13 @@ use std::os::raw::c_char;
 extern "C" {
     pub fn LibboxStart(config: *const c_char, log_fd: i64) -> *const c_char;
     pub fn LibboxStop() -> *const c_char;
+    pub fn LibboxReload(config: *const c_char) -> *const c_char;
+    pub fn LibboxStats() -> *const c_char;
+    pub fn LibboxHealth() -> *const c_char;
+    pub fn LibboxCheck(config: *const c_char) -> *const c_char;
     pub fn LibboxHello() -> *const c_char;
+    pub fn LibboxVersion() -> *const c_char;
+    pub fn LibboxGeoIPLookup(ip: *const c_char, rule_set_dir: *const c_char) -> *const c_char;
     pub fn LibboxTestOutbound(
         outbound_json: *const c_char,
         target_url: *const c_char,
@@ -21,5 +27,88 @@ extern "C" {
         target_url: *const c_char,
         timeout_ms: i64,
     ) -> *const c_char;
+    pub fn LibboxCancelTests();
     pub fn LibboxStartMobile(fd: i32, config: *const c_char, log_fd: i64) -> *const c_char;
 }
+
+/// Failure of a core call that replies with a structured result
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct CallError {
+    /// The step that failed: "decode", "create", "start", "request", ...
+    pub kind: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for CallError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} error: {}", self.kind, self.message)
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct CallResult {
+    ok: bool,
+    #[serde(default)]
+    kind: String,
+    #[serde(default)]
+    message: String,
+    #[serde(default)]
+    body: String,
+}
+
+/// Decode the `{"ok", "kind", "message", "body"}` reply of `LibboxFetch` and friends
+/// into the body or the failure. Anything else is reported as-is with kind "unknown".
+pub fn parse_call_result(raw: &str) -> Result<String, CallError> {
+    match serde_json::from_str::<CallResult>(raw) {
+        Ok(r) if r.ok => Ok(r.body),
+        Ok(r) => Err(CallError {
+            kind: r.kind,
+            message: r.message,
+        }),
+        Err(_) => Err(CallError {
+            kind: "unknown".to_string(),
+            message: raw.to_string(),
+        }),
+    }
+}
+
+/// Core releases whose config schema `build_config` targets: from the minimum up to,
+/// not including, the maximum. Minor releases move fields around (e.g. the DNS server
+/// format), so a core outside the range would reject or misread the generated config.
+pub const MIN_CORE_VERSION: (u32, u32, u32) = (1, 13, 0);
+pub const MAX_CORE_VERSION: (u32, u32, u32) = (1, 14, 0);
+
+/// sing-box version of the linked core; on Windows `libbox.dll` can be swapped by hand.
+pub fn core_version() -> String {
+    unsafe {
+        let ptr = LibboxVersion();
+        if ptr.is_null() {
+            return String::new();
+        }
+        std::ffi::CStr::from_ptr(ptr).to_string_lossy().into_owned()
+    }
+}
+
+/// `1.13.6`, `v1.13.0-beta.2` -> (1, 13, 6), (1, 13, 0). Pre-release tags are ignored.
+pub fn parse_core_version(version: &str) -> Option<(u32, u32, u32)> {
+    let version = version.trim().trim_start_matches('v');
+    let release = version.split(['-', '+']).next()?;
+    let mut parts = release.split('.').map(|p| p.parse::<u32>().ok());
+    let major = parts.next()??;
+    let minor = parts.next()??;
+    let patch = parts.next().unwrap_or(Some(0))?;
+    Some((major, minor, patch))
+}
+
+pub fn check_core_version(version: &str) -> Result<(), String> {
+    let fmt = |(a, b, c): (u32, u32, u32)| format!("{}.{}.{}", a, b, c);
+    let supported = format!(">= {}, < {}", fmt(MIN_CORE_VERSION), fmt(MAX_CORE_VERSION));
+    match parse_core_version(version) {
+        Some(v) if (MIN_CORE_VERSION..MAX_CORE_VERSION).contains(&v) => Ok(()),
+        Some(_) => Err(format!(
+            "sing-box core {} is not supported (needs {}), reinstall Tunnet to restore the bundled core",
+            version, supported
+        )),
+        None => Err(format!("Unrecognized sing-box core version '{}'", version)),
+    }
+}
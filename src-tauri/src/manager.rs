@@ -3,10 +3,24 @@ use reqwest::Client;
 
 use std::fs;
 use std::path::{Path, PathBuf};
-use tauri::{AppHandle, Manager, Runtime};
+use tauri::{AppHandle, Emitter, Manager, Runtime};
 use chrono::Local;
+use crate::error::TunnetError;
 
 const SETTINGS_FILENAME: &str = "settings.json";
+/// Extra attempts after a transient subscription fetch failure
+const SUBSCRIPTION_FETCH_RETRIES: u32 = 2;
+const SUBSCRIPTION_RETRY_BACKOFF: std::time::Duration = std::time::Duration::from_secs(1);
+/// Younger temp files may still belong to a test running right now
+const STALE_TEMP_FILE_AGE: std::time::Duration = std::time::Duration::from_secs(5 * 60);
+
+/// Scratch files of latency/location tests; older builds removed them only on success
+/// and gave every test batch its own `probe_cache_*.db`.
+pub fn is_test_temp_file(name: &str) -> bool {
+    name == "probe_config.json"
+        || (name.starts_with("url_test_") && (name.ends_with(".json") || name.ends_with(".log")))
+        || (name.starts_with("probe_cache_") && name.ends_with(".db"))
+}
 
 pub struct CoreManager<R: Runtime> {
     app: AppHandle<R>,
@@ -17,7 +31,7 @@ impl<R: Runtime> CoreManager<R> {
         Self { app }
     }
 
-    pub async fn ensure_databases(&self) -> Result<(), String> {
+    pub async fn ensure_databases(&self) -> Result<(), TunnetError> {
         let mut app_local_data = self
             .app
             .path()
@@ -60,7 +74,7 @@ impl<R: Runtime> CoreManager<R> {
         Ok(())
     }
 
-    fn extract_from_resources(&self, name: &str, dest: &Path) -> Result<(), String> {
+    fn extract_from_resources(&self, name: &str, dest: &Path) -> Result<(), TunnetError> {
         let resource_path = self
             .app
             .path()
@@ -73,7 +87,7 @@ impl<R: Runtime> CoreManager<R> {
             return Err(format!(
                 "Resource {} not found at {:?}",
                 name, resource_path
-            ));
+            ).into());
         }
 
         if let Some(parent) = dest.parent() {
@@ -90,26 +104,39 @@ impl<R: Runtime> CoreManager<R> {
         &self,
         url: &str,
         name: Option<String>,
-    ) -> Result<(crate::profile::Profile, crate::profile::ParsedContent), String> {
+        user_agent: &str,
+        proxy_port: Option<u16>,
+    ) -> Result<(crate::profile::Profile, crate::profile::ParsedContent), TunnetError> {
+        self.fetch_subscription_with_progress(url, name, user_agent, proxy_port, &|_, _| {})
+            .await
+    }
+
+    /// Same as `fetch_subscription`, reporting each stage ("fetching", "parsing",
+    /// "found" with the node count) through `progress`.
+    /// With `proxy_port` the request goes through the local mixed inbound.
+    pub async fn fetch_subscription_with_progress(
+        &self,
+        url: &str,
+        name: Option<String>,
+        user_agent: &str,
+        proxy_port: Option<u16>,
+        progress: &(dyn Fn(&str, usize) + Send + Sync),
+    ) -> Result<(crate::profile::Profile, crate::profile::ParsedContent), TunnetError> {
         let url = url.trim();
         if url.starts_with("http://") || url.starts_with("https://") {
-            let client = Client::new();
-            // Use sing-box User-Agent to get full node list and subscription info
-            let res = client
-                .get(url)
-                .header("User-Agent", "sing-box")
-                .header("Cache-Control", "no-cache")
-                .header("Pragma", "no-cache")
-                .send()
-                .await
-                .map_err(|e| e.to_string())?;
-
-            if !res.status().is_success() {
-                return Err(format!(
-                    "Subscription server returned error: {}",
-                    res.status()
-                ));
+            progress("fetching", 0);
+            let settings = self.load_settings().unwrap_or_default();
+            let mut builder = Client::builder()
+                .connect_timeout(std::time::Duration::from_secs(settings.subscription_connect_timeout_secs))
+                .timeout(std::time::Duration::from_secs(settings.subscription_timeout_secs));
+            if let Some(port) = proxy_port {
+                info!("Fetching subscription through local proxy on port {}", port);
+                let proxy = reqwest::Proxy::all(format!("http://127.0.0.1:{}", port))
+                    .map_err(|e| e.to_string())?;
+                builder = builder.proxy(proxy);
             }
+            let client = builder.build().map_err(|e| e.to_string())?;
+            let res = Self::get_subscription_with_retry(&client, url, user_agent).await?;
 
             let mut profile = crate::profile::Profile {
                 id: uuid::Uuid::new_v4().to_string(),
@@ -123,6 +150,13 @@ impl<R: Runtime> CoreManager<R> {
                 web_page_url: None,
                 update_interval: None,
                 header_update_interval: None,
+                user_agent: None,
+                last_updated: Some(
+                    std::time::SystemTime::now()
+                        .duration_since(std::time::UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_secs(),
+                ),
             };
 
             // Parse Subscription-Userinfo
@@ -169,59 +203,125 @@ impl<R: Runtime> CoreManager<R> {
                 }
             }
 
-            // Extract name from Content-Disposition if not provided or default
+            // Use the provider's name if the user did not pick one
             if profile.name == "New Subscription" {
-                if let Some(cd_val) = res.headers().get("content-disposition") {
-                    if let Ok(cd_str) = cd_val.to_str() {
-                        // Look for filename*=UTF-8''... or filename="..."
-                        if let Some(idx) = cd_str.find("filename*=") {
-                            let part = &cd_str[idx + 10..];
-                            if let Some(val) = part.split(';').next() {
-                                let val = val.trim().trim_matches('"');
-                                if val.to_uppercase().starts_with("UTF-8''") {
-                                    let encoded = &val[7..];
-                                    if let Ok(decoded) = urlencoding::decode(encoded) {
-                                        profile.name = decoded.to_string();
-                                    }
-                                }
-                            }
-                        } else if let Some(idx) = cd_str.find("filename=") {
-                            let part = &cd_str[idx + 9..];
-                            if let Some(val) = part.split(';').next() {
-                                let val = val.trim().trim_matches('"');
-                                if let Ok(decoded) = urlencoding::decode(val) {
-                                    profile.name = decoded.to_string();
-                                }
-                            }
-                        }
-                    }
+                if let Some(title) = subscription_name_from_headers(res.headers()) {
+                    profile.name = title;
                 }
             }
 
             let text = res.text().await.map_err(|e| e.to_string())?;
-            let parsed = crate::profile::parser::parse_subscription_full(&text);
+            progress("parsing", 0);
+            // Large lists take a while to parse; keep it off the async workers
+            let parsed = tokio::task::spawn_blocking(move || {
+                crate::profile::parser::parse_subscription_full(&text)
+            })
+            .await
+            .map_err(|e| e.to_string())?;
             profile.nodes = parsed.nodes.clone();
+            progress("found", profile.nodes.len());
             Ok((profile, parsed))
         } else {
             // Treat as raw content/link (e.g. vmess://, ss://, or base64)
-            let parsed = crate::profile::parser::parse_subscription_full(url);
-            Ok((
-                crate::profile::Profile {
-                    id: uuid::Uuid::new_v4().to_string(),
-                    name: name.unwrap_or("Local Import".to_string()),
-                    url: None, // Raw import usually has no update URL
-                    nodes: parsed.nodes.clone(),
-                    upload: None,
-                    download: None,
-                    total: None,
-                    expire: None,
-                    web_page_url: None,
-                    update_interval: None,
-                    header_update_interval: None,
-                },
-                parsed,
-            ))
+            progress("parsing", 0);
+            let result = Self::profile_from_content(
+                url,
+                name.unwrap_or("Local Import".to_string()),
+            );
+            progress("found", result.0.nodes.len());
+            Ok(result)
+        }
+    }
+
+    /// GET a subscription, retrying connection failures, timeouts and 5xx/429 with
+    /// a doubling backoff. Non-success statuses are returned as errors with a body snippet.
+    async fn get_subscription_with_retry(
+        client: &Client,
+        url: &str,
+        user_agent: &str,
+    ) -> Result<reqwest::Response, TunnetError> {
+        let mut backoff = SUBSCRIPTION_RETRY_BACKOFF;
+        let mut attempt = 0;
+        loop {
+            let res = client
+                .get(url)
+                .header("User-Agent", user_agent)
+                .header("Cache-Control", "no-cache")
+                .header("Pragma", "no-cache")
+                .send()
+                .await;
+
+            let transient = match &res {
+                Ok(r) => is_transient_status(r.status()),
+                Err(e) => e.is_connect() || e.is_timeout() || e.is_request(),
+            };
+            if transient && attempt < SUBSCRIPTION_FETCH_RETRIES {
+                attempt += 1;
+                match &res {
+                    Ok(r) => info!("Subscription fetch returned {}, retrying ({}/{})", r.status(), attempt, SUBSCRIPTION_FETCH_RETRIES),
+                    Err(e) => info!("Subscription fetch failed: {}, retrying ({}/{})", e, attempt, SUBSCRIPTION_FETCH_RETRIES),
+                }
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+                continue;
+            }
+
+            let res = res.map_err(|e| {
+                if e.is_timeout() {
+                    format!("Subscription server timed out: {}", e)
+                } else {
+                    format!("Failed to reach subscription server: {}", e)
+                }
+            })?;
+            let status = res.status();
+            if !status.is_success() {
+                let body = res.text().await.unwrap_or_default();
+                return Err(http_error_message(status, &body).into());
+            }
+            return Ok(res);
+        }
+    }
+
+    /// Build a local (non-updatable) profile from raw subscription content
+    pub fn profile_from_content(
+        content: &str,
+        name: String,
+    ) -> (crate::profile::Profile, crate::profile::ParsedContent) {
+        let parsed = crate::profile::parser::parse_subscription_full(content);
+        (
+            crate::profile::Profile {
+                id: uuid::Uuid::new_v4().to_string(),
+                name,
+                url: None, // Raw import usually has no update URL
+                nodes: parsed.nodes.clone(),
+                upload: None,
+                download: None,
+                total: None,
+                expire: None,
+                web_page_url: None,
+                update_interval: None,
+                header_update_interval: None,
+                last_updated: None,
+                user_agent: None,
+            },
+            parsed,
+        )
+    }
+
+    /// Parse a data file, falling back to its `.bak` copy when it is corrupted. A
+    /// recovery is logged and reported to the UI via `config-recovered`.
+    fn parse_or_recover<T: serde::de::DeserializeOwned>(
+        &self,
+        path: &Path,
+        content: &str,
+    ) -> Result<T, serde_json::Error> {
+        let (value, recovered) = parse_json_with_backup(path, content)?;
+        if recovered {
+            let file = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+            log::warn!("{} was corrupted, restored the previous version from its backup", file);
+            let _ = self.app.emit("config-recovered", file);
         }
+        Ok(value)
     }
 
     pub fn get_profiles_path(&self) -> PathBuf {
@@ -238,26 +338,26 @@ impl<R: Runtime> CoreManager<R> {
         base.join("profiles_v2.json")
     }
 
-    pub fn save_profiles(&self, profiles: &[crate::profile::Profile]) -> Result<(), String> {
-        let path = self.get_profiles_path();
-        if let Some(parent) = path.parent() {
-            if !parent.exists() {
-                fs::create_dir_all(parent).map_err(|e| e.to_string())?;
-            }
-        }
-        let json = serde_json::to_string_pretty(profiles).map_err(|e| e.to_string())?;
-        fs::write(&path, json).map_err(|e| e.to_string())?;
-        Ok(())
+    pub fn save_profiles(&self, profiles: &[crate::profile::Profile]) -> Result<(), TunnetError> {
+        save_json_with_backup(&self.get_profiles_path(), profiles)
     }
 
-    pub fn load_profiles(&self) -> Result<Vec<crate::profile::Profile>, String> {
+    pub fn load_profiles(&self) -> Result<Vec<crate::profile::Profile>, TunnetError> {
         let path = self.get_profiles_path();
         if !path.exists() {
-            return Ok(vec![]);
+            return self.migrate_legacy_profiles();
         }
         let content = fs::read_to_string(&path).map_err(|e| e.to_string())?;
-        match serde_json::from_str::<Vec<crate::profile::Profile>>(&content) {
-            Ok(profiles) => Ok(profiles),
+        match self.parse_or_recover::<Vec<crate::profile::Profile>>(&path, &content) {
+            Ok(mut profiles) => {
+                for profile in &mut profiles {
+                    for node in &mut profile.nodes {
+                        node.profile_id = Some(profile.id.clone());
+                        node.profile_name = Some(profile.name.clone());
+                    }
+                }
+                Ok(profiles)
+            }
             Err(e) => {
                 log::error!(
                     "Failed to parse profiles_v2.json: {}. Falling back to empty.",
@@ -268,6 +368,31 @@ impl<R: Runtime> CoreManager<R> {
         }
     }
 
+    /// One-time upgrade from the v1 `profiles.json`. The legacy file is renamed once
+    /// converted, so this only runs while neither the v2 file nor a migrated copy exist.
+    fn migrate_legacy_profiles(&self) -> Result<Vec<crate::profile::Profile>, TunnetError> {
+        let legacy = self.get_profiles_path().with_file_name("profiles.json");
+        if !legacy.exists() {
+            return Ok(vec![]);
+        }
+        let content = fs::read_to_string(&legacy).map_err(|e| e.to_string())?;
+        let Some(mut profiles) = crate::profile::profiles_from_legacy(&content) else {
+            log::error!("Legacy profiles.json is not in a known format, leaving it untouched");
+            return Ok(vec![]);
+        };
+        self.save_profiles(&profiles)?;
+        fs::rename(&legacy, legacy.with_extension("json.migrated")).map_err(|e| e.to_string())?;
+        info!("Migrated {} profiles from legacy profiles.json", profiles.len());
+
+        for profile in &mut profiles {
+            for node in &mut profile.nodes {
+                node.profile_id = Some(profile.id.clone());
+                node.profile_name = Some(profile.name.clone());
+            }
+        }
+        Ok(profiles)
+    }
+
     pub fn get_rules_path(&self) -> PathBuf {
         let mut base = self
             .app
@@ -282,26 +407,22 @@ impl<R: Runtime> CoreManager<R> {
         base.join("rules.json")
     }
 
-    pub fn save_rules(&self, rules: &[crate::profile::Rule]) -> Result<(), String> {
-        let path = self.get_rules_path();
-        if let Some(parent) = path.parent() {
-            if !parent.exists() {
-                fs::create_dir_all(parent).map_err(|e| e.to_string())?;
-            }
-        }
-        let json = serde_json::to_string_pretty(rules).map_err(|e| e.to_string())?;
-        fs::write(&path, json).map_err(|e| e.to_string())?;
-        Ok(())
+    pub fn save_rules(&self, rules: &[crate::profile::Rule]) -> Result<(), TunnetError> {
+        save_json_with_backup(&self.get_rules_path(), rules)
     }
 
-    pub fn load_rules(&self) -> Result<Vec<crate::profile::Rule>, String> {
+    pub fn load_rules(&self) -> Result<Vec<crate::profile::Rule>, TunnetError> {
         let path = self.get_rules_path();
         if !path.exists() {
             return Ok(self.default_rules());
         }
         let content = fs::read_to_string(&path).map_err(|e| e.to_string())?;
-        match serde_json::from_str::<Vec<crate::profile::Rule>>(&content) {
-            Ok(rules) => Ok(rules),
+        match self.parse_or_recover::<Vec<crate::profile::Rule>>(&path, &content) {
+            Ok(mut rules) => {
+                // Rules saved before the type names were unified still load
+                rules.iter_mut().for_each(crate::profile::Rule::normalize);
+                Ok(rules)
+            }
             Err(e) => {
                 log::error!(
                     "Failed to parse rules.json: {}. Falling back to default rules.",
@@ -322,6 +443,8 @@ impl<R: Runtime> CoreManager<R> {
                 value: "true".to_string(),
                 policy: "DIRECT".to_string(),
                 enabled: true,
+                port: None,
+                network: None,
             },
             crate::profile::Rule {
                 id: "ads-1".to_string(),
@@ -330,6 +453,8 @@ impl<R: Runtime> CoreManager<R> {
                 value: "geosite:geosite-ads".to_string(),
                 policy: "REJECT".to_string(),
                 enabled: true,
+                port: None,
+                network: None,
             },
             crate::profile::Rule {
                 id: "cn-1".to_string(),
@@ -338,6 +463,8 @@ impl<R: Runtime> CoreManager<R> {
                 value: "geosite:geosite-cn".to_string(),
                 policy: "DIRECT".to_string(),
                 enabled: true,
+                port: None,
+                network: None,
             },
             crate::profile::Rule {
                 id: "cn-2".to_string(),
@@ -346,6 +473,8 @@ impl<R: Runtime> CoreManager<R> {
                 value: "geoip-cn".to_string(),
                 policy: "DIRECT".to_string(),
                 enabled: true,
+                port: None,
+                network: None,
             },
             crate::profile::Rule {
                 id: "final-policy".to_string(),
@@ -354,6 +483,8 @@ impl<R: Runtime> CoreManager<R> {
                 value: "default".to_string(),
                 policy: "PROXY".to_string(),
                 enabled: true,
+                port: None,
+                network: None,
             },
         ]
     }
@@ -372,25 +503,17 @@ impl<R: Runtime> CoreManager<R> {
         base.join("groups.json")
     }
 
-    pub fn save_groups(&self, groups: &[crate::profile::Group]) -> Result<(), String> {
-        let path = self.get_groups_path();
-        if let Some(parent) = path.parent() {
-            if !parent.exists() {
-                fs::create_dir_all(parent).map_err(|e| e.to_string())?;
-            }
-        }
-        let json = serde_json::to_string_pretty(groups).map_err(|e| e.to_string())?;
-        fs::write(&path, json).map_err(|e| e.to_string())?;
-        Ok(())
+    pub fn save_groups(&self, groups: &[crate::profile::Group]) -> Result<(), TunnetError> {
+        save_json_with_backup(&self.get_groups_path(), groups)
     }
 
-    pub fn load_groups(&self) -> Result<Vec<crate::profile::Group>, String> {
+    pub fn load_groups(&self) -> Result<Vec<crate::profile::Group>, TunnetError> {
         let path = self.get_groups_path();
         if !path.exists() {
             return Ok(vec![]);
         }
         let content = fs::read_to_string(&path).map_err(|e| e.to_string())?;
-        match serde_json::from_str::<Vec<crate::profile::Group>>(&content) {
+        match self.parse_or_recover::<Vec<crate::profile::Group>>(&path, &content) {
             Ok(groups) => Ok(groups),
             Err(e) => {
                 log::error!("Failed to parse groups.json: {}. Falling back to empty.", e);
@@ -422,7 +545,42 @@ impl<R: Runtime> CoreManager<R> {
         app_local_data
     }
 
-    pub fn backup_data(&self) -> Result<(), String> {
+    /// Delete stale test scratch files left behind by failed runs. Returns how many went.
+    pub fn cleanup_test_temp_files(&self) -> usize {
+        let mut dirs = vec![self.get_app_data_dir()];
+        if let Ok(local) = self.app.path().app_local_data_dir() {
+            if !dirs.contains(&local) {
+                dirs.push(local);
+            }
+        }
+
+        let mut removed = 0;
+        for dir in dirs {
+            let Ok(entries) = fs::read_dir(&dir) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                if !is_test_temp_file(&entry.file_name().to_string_lossy()) {
+                    continue;
+                }
+                let stale = entry
+                    .metadata()
+                    .and_then(|m| m.modified())
+                    .ok()
+                    .and_then(|t| t.elapsed().ok())
+                    .is_some_and(|age| age >= STALE_TEMP_FILE_AGE);
+                if stale && fs::remove_file(entry.path()).is_ok() {
+                    removed += 1;
+                }
+            }
+        }
+        if removed > 0 {
+            info!("Removed {} stale test temp files", removed);
+        }
+        removed
+    }
+
+    pub fn backup_data(&self) -> Result<(), TunnetError> {
         let app_data_dir = self.get_app_data_dir();
         let backup_root = app_data_dir.join("backups");
         let timestamp = Local::now().format("%Y%m%d_%H%M%S").to_string();
@@ -443,7 +601,7 @@ impl<R: Runtime> CoreManager<R> {
         Ok(())
     }
 
-    pub fn migrate_data(&self, mut settings: crate::settings::AppSettings) -> Result<crate::settings::AppSettings, String> {
+    pub fn migrate_data(&self, mut settings: crate::settings::AppSettings) -> Result<crate::settings::AppSettings, TunnetError> {
         if settings.config_version >= 2 {
             return Ok(settings);
         }
@@ -468,25 +626,17 @@ impl<R: Runtime> CoreManager<R> {
         Ok(settings)
     }
 
-    pub fn save_settings(&self, settings: &crate::settings::AppSettings) -> Result<(), String> {
-        let path = self.get_settings_path();
-        if let Some(parent) = path.parent() {
-            if !parent.exists() {
-                fs::create_dir_all(parent).map_err(|e| e.to_string())?;
-            }
-        }
-        let json = serde_json::to_string_pretty(settings).map_err(|e| e.to_string())?;
-        fs::write(&path, json).map_err(|e| e.to_string())?;
-        Ok(())
+    pub fn save_settings(&self, settings: &crate::settings::AppSettings) -> Result<(), TunnetError> {
+        save_json_with_backup(&self.get_settings_path(), settings)
     }
 
-    pub fn load_settings(&self) -> Result<crate::settings::AppSettings, String> {
+    pub fn load_settings(&self) -> Result<crate::settings::AppSettings, TunnetError> {
         let path = self.get_settings_path();
         if !path.exists() {
             return Ok(crate::settings::AppSettings::default());
         }
         let content = fs::read_to_string(&path).map_err(|e| e.to_string())?;
-        let settings = match serde_json::from_str::<crate::settings::AppSettings>(&content) {
+        let settings = match self.parse_or_recover::<crate::settings::AppSettings>(&path, &content) {
             Ok(s) => s,
             Err(e) => {
                 log::error!(
@@ -505,3 +655,134 @@ impl<R: Runtime> CoreManager<R> {
         }
     }
 }
+
+/// Sibling that `write_atomic` stages new contents in, e.g. `profiles_v2.json.tmp`.
+pub fn temp_path_for(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".tmp");
+    path.with_file_name(name)
+}
+
+/// Write to a temp file in the same directory, flush it to disk and rename it over
+/// `path`. A crash mid-write leaves the previous file intact instead of a truncated one.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+
+    let tmp = temp_path_for(path);
+    let mut file = fs::File::create(&tmp)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    drop(file);
+    fs::rename(&tmp, path)
+}
+
+/// Previous version of a data file, kept by `save_json_with_backup`.
+pub fn backup_path_for(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".bak");
+    path.with_file_name(name)
+}
+
+/// Serialize `value` over `path` atomically, first keeping the current file as `.bak`.
+/// A current file that is not valid JSON is not rotated, so it never replaces a good backup.
+pub fn save_json_with_backup<T: serde::Serialize + ?Sized>(
+    path: &Path,
+    value: &T,
+) -> Result<(), TunnetError> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let json = serde_json::to_string_pretty(value).map_err(|e| e.to_string())?;
+    if let Ok(current) = fs::read(path) {
+        if serde_json::from_slice::<serde_json::Value>(&current).is_ok() {
+            fs::write(backup_path_for(path), current).map_err(|e| e.to_string())?;
+        }
+    }
+    write_atomic(path, json.as_bytes()).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Move rule `id` of `rules` to `new_index` and save the result to `path`.
+pub fn save_moved_rule(
+    path: &Path,
+    mut rules: Vec<crate::profile::Rule>,
+    id: &str,
+    new_index: usize,
+) -> Result<(), TunnetError> {
+    if !crate::profile::move_by_id(&mut rules, id, new_index, |r| &r.id) {
+        return Err("Rule not found".into());
+    }
+    save_json_with_backup(path, &rules)
+}
+
+/// Parse `content` read from `path`, or the `.bak` next to it when that fails. The
+/// flag tells whether the backup was used; the primary's error is returned if both fail.
+pub fn parse_json_with_backup<T: serde::de::DeserializeOwned>(
+    path: &Path,
+    content: &str,
+) -> Result<(T, bool), serde_json::Error> {
+    match serde_json::from_str(content) {
+        Ok(value) => Ok((value, false)),
+        Err(e) => fs::read_to_string(backup_path_for(path))
+            .ok()
+            .and_then(|backup| serde_json::from_str(&backup).ok())
+            .map(|value| (value, true))
+            .ok_or(e),
+    }
+}
+
+/// Statuses worth retrying: server errors and rate limiting.
+pub fn is_transient_status(status: reqwest::StatusCode) -> bool {
+    status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+}
+
+/// Error text for a non-success subscription response, with the start of the body
+/// since providers often explain the failure there (expired, quota exceeded, ...).
+pub fn http_error_message(status: reqwest::StatusCode, body: &str) -> String {
+    const SNIPPET_CHARS: usize = 200;
+    let body = body.split_whitespace().collect::<Vec<_>>().join(" ");
+    if body.is_empty() {
+        return format!("Subscription server returned error: {}", status);
+    }
+    let mut snippet: String = body.chars().take(SNIPPET_CHARS).collect();
+    if body.chars().count() > SNIPPET_CHARS {
+        snippet.push_str("...");
+    }
+    format!("Subscription server returned error: {}: {}", status, snippet)
+}
+
+/// Profile name announced by a subscription server, from `profile-title`
+/// (plain or `base64:`-prefixed) or else the `Content-Disposition` filename.
+pub fn subscription_name_from_headers(headers: &reqwest::header::HeaderMap) -> Option<String> {
+    use base64::{engine::general_purpose, Engine as _};
+
+    if let Some(title) = headers.get("profile-title").and_then(|v| v.to_str().ok()) {
+        let title = title.trim();
+        let decoded = match title.strip_prefix("base64:") {
+            Some(b64) => general_purpose::STANDARD
+                .decode(b64.trim())
+                .ok()
+                .and_then(|bytes| String::from_utf8(bytes).ok()),
+            None => Some(title.to_string()),
+        };
+        if let Some(name) = decoded.map(|n| n.trim().to_string()).filter(|n| !n.is_empty()) {
+            return Some(name);
+        }
+    }
+
+    let cd_str = headers.get("content-disposition")?.to_str().ok()?;
+    // Look for filename*=UTF-8''... or filename="..."
+    let name = if let Some(idx) = cd_str.find("filename*=") {
+        let val = cd_str[idx + 10..].split(';').next()?.trim().trim_matches('"');
+        if !val.to_uppercase().starts_with("UTF-8''") {
+            return None;
+        }
+        urlencoding::decode(&val[7..]).ok()?.to_string()
+    } else if let Some(idx) = cd_str.find("filename=") {
+        let val = cd_str[idx + 9..].split(';').next()?.trim().trim_matches('"');
+        urlencoding::decode(val).ok()?.to_string()
+    } else {
+        return None;
+    };
+    Some(name).filter(|n| !n.trim().is_empty())
+}
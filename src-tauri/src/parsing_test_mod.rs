@@ -2,6 +2,65 @@
 mod tests {
     use crate::profile::parser::parse_subscription;
 
+    /// Config inputs with no profiles or groups and fixed paths, routing by `rules`.
+    fn test_sources(rules: &[crate::profile::Rule]) -> crate::service::ConfigSources<'_> {
+        crate::service::ConfigSources {
+            profiles: &[],
+            groups: &[],
+            rules,
+            data_dir: std::path::Path::new("/tmp/tunnet"),
+            local_rule_sets: Default::default(),
+            core_log_path: "/tmp/tunnet/logs/core.log".into(),
+            session_clash_secret: "session",
+        }
+    }
+
+    /// An enabled rule without port or network conditions.
+    fn rule(id: &str, rule_type: &str, value: &str, policy: &str) -> crate::profile::Rule {
+        crate::profile::Rule {
+            id: id.to_string(),
+            description: None,
+            rule_type: rule_type.to_string(),
+            value: value.to_string(),
+            policy: policy.to_string(),
+            enabled: true,
+            port: None,
+            network: None,
+        }
+    }
+
+    /// The trojan node the routing tests connect through.
+    fn trojan_node() -> crate::profile::Node {
+        crate::profile::Node {
+            id: "node-1".to_string(),
+            name: "Node".to_string(),
+            protocol: "trojan".to_string(),
+            server: "203.0.113.7".to_string(),
+            port: 443,
+            password: Some("pw".to_string()),
+            ..Default::default()
+        }
+    }
+
+    /// Subscription profile `p1` holding `nodes`, without usage or update info.
+    fn profile(nodes: Vec<crate::profile::Node>) -> crate::profile::Profile {
+        crate::profile::Profile {
+            id: "p1".to_string(),
+            name: "Sub".to_string(),
+            url: None,
+            upload: None,
+            download: None,
+            total: None,
+            expire: None,
+            web_page_url: None,
+            update_interval: None,
+            header_update_interval: None,
+            last_updated: None,
+            user_agent: None,
+            nodes,
+        }
+    }
+
     #[test]
     fn test_parse_vless() {
         let uri = "vless://uuid@example.com:443?security=tls&type=ws&path=/&host=example.com&flow=xtls-rprx-vision&sni=example.com&alpn=h2,http/1.1#TestNode";
@@ -28,6 +87,233 @@ mod tests {
         assert_eq!(node.obfs, Some("salamander".to_string()));
     }
 
+    #[test]
+    fn test_link_round_trip_all_protocols() {
+        use crate::profile::Node;
+
+        let s = |v: &str| Some(v.to_string());
+        let alpn = Some(vec!["h2".to_string(), "http/1.1".to_string()]);
+        let cases = vec![
+            Node {
+                name: "vmess ws".to_string(),
+                protocol: "vmess".to_string(),
+                server: "vm.example.com".to_string(),
+                port: 443,
+                uuid: s("b831381d-6324-4d53-ad4f-8cda48b30811"),
+                cipher: s("aes-128-gcm"),
+                tls: true,
+                network: s("ws"),
+                path: s("/ws?ed=2048"),
+                host: s("cdn.example.com"),
+                sni: s("sni.example.com"),
+                alpn: alpn.clone(),
+                alter_id: Some(64),
+                ..Default::default()
+            },
+            Node {
+                name: "vless reality".to_string(),
+                protocol: "vless".to_string(),
+                server: "vl.example.com".to_string(),
+                port: 8443,
+                uuid: s("b831381d-6324-4d53-ad4f-8cda48b30811"),
+                tls: true,
+                network: s("grpc"),
+                path: s("svc"),
+                host: s("h.example.com"),
+                sni: s("www.apple.com"),
+                flow: s("xtls-rprx-vision"),
+                fingerprint: s("chrome"),
+                public_key: s("pbkKey"),
+                short_id: s("6ba85179"),
+                alpn: alpn.clone(),
+                packet_encoding: s("xudp"),
+                insecure: true,
+                ..Default::default()
+            },
+            Node {
+                name: "hy2".to_string(),
+                protocol: "hysteria2".to_string(),
+                server: "hy2.example.com".to_string(),
+                port: 443,
+                password: s("p@ss"),
+                tls: true,
+                sni: s("hy2.example.com"),
+                insecure: true,
+                obfs: s("salamander"),
+                obfs_password: s("ob fs"),
+                alpn: alpn.clone(),
+                ..Default::default()
+            },
+            Node {
+                name: "tuic".to_string(),
+                protocol: "tuic".to_string(),
+                server: "tuic.example.com".to_string(),
+                port: 443,
+                uuid: s("b831381d-6324-4d53-ad4f-8cda48b30811"),
+                password: s("secret"),
+                tls: true,
+                sni: s("tuic.example.com"),
+                insecure: true,
+                alpn: Some(vec!["h3".to_string()]),
+                ..Default::default()
+            },
+            Node {
+                name: "trojan ws".to_string(),
+                protocol: "trojan".to_string(),
+                server: "tj.example.com".to_string(),
+                port: 443,
+                password: s("secret"),
+                tls: true,
+                network: s("ws"),
+                path: s("/tj"),
+                host: s("cdn.example.com"),
+                sni: s("tj.example.com"),
+                insecure: true,
+                alpn: alpn.clone(),
+                ..Default::default()
+            },
+            Node {
+                name: "ss".to_string(),
+                protocol: "shadowsocks".to_string(),
+                server: "ss.example.com".to_string(),
+                port: 8388,
+                cipher: s("2022-blake3-aes-128-gcm"),
+                password: s("a:b+c/d="),
+                ..Default::default()
+            },
+            Node {
+                name: "anytls".to_string(),
+                protocol: "anytls".to_string(),
+                server: "any.example.com".to_string(),
+                port: 443,
+                password: s("secret"),
+                tls: true,
+                sni: s("any.example.com"),
+                fingerprint: s("firefox"),
+                insecure: true,
+                ..Default::default()
+            },
+            Node {
+                name: "hysteria".to_string(),
+                protocol: "hysteria".to_string(),
+                server: "hy.example.com".to_string(),
+                port: 443,
+                password: s("auth"),
+                sni: s("hy.example.com"),
+                insecure: true,
+                up: s("50"),
+                down: s("100"),
+                obfs: s("xplus"),
+                ..Default::default()
+            },
+            Node {
+                name: "shadowtls".to_string(),
+                protocol: "shadowtls".to_string(),
+                server: "stls.example.com".to_string(),
+                port: 443,
+                password: s("secret"),
+                sni: s("www.bing.com"),
+                flow: s("3"),
+                ..Default::default()
+            },
+        ];
+
+        for original in cases {
+            let link = original.to_link();
+            let nodes = parse_subscription(&link);
+            assert_eq!(nodes.len(), 1, "{} did not re-parse: {}", original.name, link);
+            let parsed = &nodes[0];
+            let ctx = format!("{} ({})", original.name, link);
+
+            assert_eq!(parsed.protocol, original.protocol, "{}", ctx);
+            assert_eq!(parsed.name, original.name, "{}", ctx);
+            assert_eq!(parsed.server, original.server, "{}", ctx);
+            assert_eq!(parsed.port, original.port, "{}", ctx);
+            assert_eq!(parsed.uuid, original.uuid, "{}", ctx);
+            assert_eq!(parsed.password, original.password, "{}", ctx);
+            assert_eq!(parsed.path, original.path, "{}", ctx);
+            assert_eq!(parsed.host, original.host, "{}", ctx);
+            assert_eq!(parsed.sni, original.sni, "{}", ctx);
+            assert_eq!(parsed.flow, original.flow, "{}", ctx);
+            assert_eq!(parsed.fingerprint, original.fingerprint, "{}", ctx);
+            assert_eq!(parsed.public_key, original.public_key, "{}", ctx);
+            assert_eq!(parsed.short_id, original.short_id, "{}", ctx);
+            assert_eq!(parsed.obfs, original.obfs, "{}", ctx);
+            assert_eq!(parsed.obfs_password, original.obfs_password, "{}", ctx);
+            assert_eq!(parsed.up, original.up, "{}", ctx);
+            assert_eq!(parsed.down, original.down, "{}", ctx);
+            assert_eq!(parsed.alpn, original.alpn, "{}", ctx);
+            assert_eq!(parsed.insecure, original.insecure, "{}", ctx);
+            assert_eq!(parsed.tls, original.tls, "{}", ctx);
+            assert_eq!(parsed.cipher, original.cipher, "{}", ctx);
+            if original.network.is_some() {
+                assert_eq!(parsed.network, original.network, "{}", ctx);
+            }
+            if original.protocol == "vmess" {
+                assert_eq!(parsed.alter_id, original.alter_id, "{}", ctx);
+            }
+            if original.protocol == "vless" {
+                assert_eq!(parsed.packet_encoding, original.packet_encoding, "{}", ctx);
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_names_with_fragments() {
+        use base64::{engine::general_purpose, Engine as _};
+
+        // Encoded emoji, spaces and '#' in the remark
+        let uri = "vless://uuid@example.com:443?security=tls#%F0%9F%87%AF%F0%9F%87%B5%20Tokyo%20%2301";
+        assert_eq!(parse_subscription(uri)[0].name, "🇯🇵 Tokyo #01");
+
+        // Unencoded '#' inside the remark belongs to the name
+        let uri = "trojan://pw@example.com:443?sni=example.com#HK #2 香港";
+        let node = parse_subscription(uri).remove(0);
+        assert_eq!(node.name, "HK #2 香港");
+        assert_eq!(node.sni.as_deref(), Some("example.com"));
+
+        let uri = "ss://YWVzLTEyOC1nY206cHc@example.com:8388#A%20B#C";
+        assert_eq!(parse_subscription(uri)[0].name, "A B#C");
+
+        // Empty remark falls back to the protocol default
+        let uri = "hysteria2://pw@example.com:443#";
+        assert_eq!(parse_subscription(uri)[0].name, "Hysteria2 Node");
+
+        // vmess links with a trailing remark still decode, and the remark fills in a missing `ps`
+        let json = r#"{"v":"2","add":"example.com","port":"443","id":"uuid","net":"tcp"}"#;
+        let uri = format!("vmess://{}#My%20Vmess", general_purpose::STANDARD.encode(json));
+        let node = parse_subscription(&uri).remove(0);
+        assert_eq!(node.server, "example.com");
+        assert_eq!(node.name, "My Vmess");
+    }
+
+    #[test]
+    fn test_parse_ipv6_and_missing_port() {
+        let uri = "vless://uuid@[2001:db8::1]:8443?security=tls&sni=example.com#v6";
+        let node = parse_subscription(uri).remove(0);
+        assert_eq!(node.server, "2001:db8::1");
+        assert_eq!(node.port, 8443);
+        // Exported links keep the brackets so they parse back the same
+        assert!(node.to_link().contains("@[2001:db8::1]:8443"));
+        assert_eq!(parse_subscription(&node.to_link())[0].server, "2001:db8::1");
+
+        let node = parse_subscription("trojan://pw@example.com?sni=example.com#no-port").remove(0);
+        assert_eq!(node.server, "example.com");
+        assert_eq!(node.port, 443);
+
+        let node = parse_subscription("hysteria2://pw@[2001:db8::2]#bracketed").remove(0);
+        assert_eq!((node.server.as_str(), node.port), ("2001:db8::2", 443));
+
+        let node = parse_subscription("vless://uuid@example.com:443/?security=tls#slash").remove(0);
+        assert_eq!(node.port, 443);
+
+        // Garbage ports are rejected instead of defaulting
+        assert!(parse_subscription("trojan://pw@example.com:http#bad").is_empty());
+        assert!(parse_subscription("trojan://pw@example.com:0#zero").is_empty());
+        // Shadowsocks has no customary port
+        assert!(parse_subscription("ss://YWVzLTEyOC1nY206cHc@example.com#ss").is_empty());
+    }
+
     #[test]
     fn test_parse_shadowrocket_vless() {
         let uri = "vless://OjYxOGQzNDk2LTM0OTctNGNhMC1mYWZhLWViMGExMmU1N2JkNkBseXJhLnJ1bjo0NDM?path=/pass&remarks=bypasswall(vultr)&obfsParam=%7B%22Host%22:%22lyra.run%22%7D&obfs=websocket&tls=1&udp=1";
@@ -45,6 +331,1099 @@ mod tests {
         assert_eq!(node.tls, true);
     }
 
+    #[test]
+    fn test_parse_vmess_alter_id() {
+        use base64::{engine::general_purpose, Engine as _};
+
+        let json = r#"{"v":"2","ps":"Legacy","add":"example.com","port":"443","id":"b831381d-6324-4d53-ad4f-8cda48b30811","aid":64,"net":"tcp","tls":""}"#;
+        let uri = format!("vmess://{}", general_purpose::STANDARD.encode(json));
+        let nodes = parse_subscription(&uri);
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(nodes[0].alter_id, Some(64));
+
+        // The exported link keeps the value
+        let again = parse_subscription(&nodes[0].to_link());
+        assert_eq!(again[0].alter_id, Some(64));
+    }
+
+    #[test]
+    fn test_vmess_packet_encoding_serialization() {
+        use crate::config::{effective_packet_encoding, ConfigMode, SingBoxConfig};
+
+        let outbound_json = |packet_encoding: Option<&str>| {
+            let cfg = SingBoxConfig::new(None, ConfigMode::Combined, "", "ipv4", "proxy")
+                .with_vmess_outbound(
+                    "n1",
+                    "example.com".to_string(),
+                    443,
+                    "b831381d-6324-4d53-ad4f-8cda48b30811".to_string(),
+                    "auto".to_string(),
+                    0,
+                    None,
+                    None,
+                    None,
+                    false,
+                    false,
+                    None,
+                    None,
+                    effective_packet_encoding(packet_encoding),
+                    None,
+                    None,
+                );
+            serde_json::to_value(&cfg.outbounds[0]).unwrap()
+        };
+
+        assert_eq!(outbound_json(None)["packet_encoding"], "xudp");
+        assert_eq!(outbound_json(Some("packetaddr"))["packet_encoding"], "packetaddr");
+        assert!(outbound_json(Some("none")).get("packet_encoding").is_none());
+    }
+
+    #[test]
+    fn test_disable_sni_omits_server_name() {
+        use crate::config::{ConfigMode, SingBoxConfig};
+
+        let tls_json = |disable_sni: Option<bool>| {
+            let cfg = SingBoxConfig::new(None, ConfigMode::Combined, "", "ipv4", "proxy")
+                .with_trojan_outbound(
+                    "n1",
+                    "203.0.113.7".to_string(),
+                    443,
+                    "pw".to_string(),
+                    None,
+                    None,
+                    None,
+                    true,
+                    true,
+                    Some("example.com".to_string()),
+                    None,
+                    None,
+                    None,
+                    None,
+                    disable_sni,
+                );
+            serde_json::to_value(&cfg.outbounds[0]).unwrap()["tls"].clone()
+        };
+
+        let with_sni = tls_json(None);
+        assert_eq!(with_sni["server_name"], "example.com");
+        assert!(with_sni.get("disable_sni").is_none());
+
+        let without_sni = tls_json(Some(true));
+        assert!(without_sni.get("server_name").is_none());
+        assert_eq!(without_sni["disable_sni"], true);
+    }
+
+    #[test]
+    fn test_vmess_alpn_reaches_outbound() {
+        use base64::{engine::general_purpose, Engine as _};
+
+        let json = r#"{"v":"2","ps":"CDN","add":"cdn.example.com","port":443,"id":"b831381d-6324-4d53-ad4f-8cda48b30811","aid":"0","net":"ws","path":"/ws","host":"cdn.example.com","tls":"tls","alpn":"h2,http/1.1"}"#;
+        let uri = format!("vmess://{}", general_purpose::STANDARD.encode(json));
+        let node = parse_subscription(&uri).remove(0);
+        let alpn = vec!["h2".to_string(), "http/1.1".to_string()];
+        assert_eq!(node.alpn, Some(alpn.clone()));
+
+        let cfg = crate::config::SingBoxConfig::new(None, crate::config::ConfigMode::Combined, "", "ipv4", "proxy")
+            .with_vmess_outbound(
+                &node.id,
+                node.server.clone(),
+                node.port,
+                node.uuid.clone().unwrap_or_default(),
+                "auto".to_string(),
+                0,
+                node.network.clone(),
+                node.path.clone(),
+                node.host.clone(),
+                node.tls,
+                node.insecure,
+                node.sni.clone(),
+                node.alpn.clone(),
+                None,
+                None,
+                None,
+            );
+        let out = serde_json::to_value(&cfg.outbounds[0]).unwrap();
+        assert_eq!(out["tls"]["alpn"], serde_json::json!(alpn));
+    }
+
+    #[test]
+    fn test_parse_vmess_security() {
+        use base64::{engine::general_purpose, Engine as _};
+
+        let json = r#"{"v":"2","ps":"Gcm","add":"example.com","port":443,"id":"b831381d-6324-4d53-ad4f-8cda48b30811","aid":"0","scy":"aes-128-gcm","net":"tcp","tls":""}"#;
+        let uri = format!("vmess://{}", general_purpose::STANDARD.encode(json));
+        let node = parse_subscription(&uri).remove(0);
+        assert_eq!(node.cipher.as_deref(), Some("aes-128-gcm"));
+        assert!(node.validate().is_ok());
+        assert_eq!(parse_subscription(&node.to_link())[0].cipher.as_deref(), Some("aes-128-gcm"));
+
+        // Links without `scy` keep the previous default
+        let json = r#"{"v":"2","ps":"Auto","add":"example.com","port":443,"id":"b831381d-6324-4d53-ad4f-8cda48b30811","net":"tcp"}"#;
+        let uri = format!("vmess://{}", general_purpose::STANDARD.encode(json));
+        assert_eq!(parse_subscription(&uri)[0].cipher.as_deref(), Some("auto"));
+    }
+
+    #[test]
+    fn test_dial_options_apply_to_proxy_outbounds() {
+        use crate::config::{ConfigMode, SingBoxConfig};
+
+        let cfg = SingBoxConfig::new(None, ConfigMode::Combined, "", "ipv4", "proxy")
+            .with_shadowsocks_outbound("ss", "example.com".to_string(), 8388, "aes-128-gcm".to_string(), "pw".to_string(), None, None)
+            .with_direct()
+            .with_dial_options(Some(15), true);
+        let ss = cfg.outbounds.iter().find(|o| o.tag == "ss").unwrap();
+        assert_eq!(ss.connect_timeout.as_deref(), Some("15s"));
+        assert_eq!(ss.tcp_fast_open, Some(true));
+        let direct = cfg.outbounds.iter().find(|o| o.tag == "direct").unwrap();
+        assert_eq!(direct.connect_timeout.as_deref(), Some("5s"));
+        assert_eq!(direct.tcp_fast_open, None);
+
+        // QUIC protocols, Hysteria v1 included, have no TCP connection to speed up
+        let cfg = SingBoxConfig::new(None, ConfigMode::Combined, "", "ipv4", "proxy")
+            .with_hysteria_outbound("hy", "example.com".to_string(), 443, None, None, false, None, 10, 50, None, None)
+            .with_dial_options(None, true);
+        assert!(cfg.outbounds.iter().find(|o| o.tag == "hy").unwrap().tcp_fast_open.is_none());
+
+        // Unset options keep the previous output
+        let cfg = SingBoxConfig::new(None, ConfigMode::Combined, "", "ipv4", "proxy")
+            .with_shadowsocks_outbound("ss", "example.com".to_string(), 8388, "aes-128-gcm".to_string(), "pw".to_string(), None, None)
+            .with_dial_options(None, false);
+        assert!(cfg.outbounds[0].connect_timeout.is_none());
+        assert!(cfg.outbounds[0].tcp_fast_open.is_none());
+    }
+
+    #[test]
+    fn test_direct_outbound_bind() {
+        use crate::config::{ConfigMode, SingBoxConfig};
+
+        let cfg = SingBoxConfig::new(None, ConfigMode::Combined, "", "ipv4", "proxy")
+            .with_direct_tag("direct", Some("en0".to_string()), Some("192.168.1.10".to_string()));
+        let json = serde_json::to_value(&cfg.outbounds[0]).unwrap();
+        assert_eq!(json["bind_interface"], "en0");
+        assert_eq!(json["inet4_bind_address"], "192.168.1.10");
+
+        let cfg = SingBoxConfig::new(None, ConfigMode::Combined, "", "ipv4", "proxy").with_direct();
+        let json = serde_json::to_value(&cfg.outbounds[0]).unwrap();
+        assert!(json.get("bind_interface").is_none());
+
+        let settings = crate::settings::AppSettings {
+            direct_bind_address: Some("not-an-ip".to_string()),
+            ..Default::default()
+        };
+        assert!(settings.validate().is_err());
+    }
+
+    #[test]
+    fn test_subscription_name_from_headers() {
+        use crate::manager::subscription_name_from_headers;
+        use reqwest::header::{HeaderMap, HeaderValue};
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "content-disposition",
+            HeaderValue::from_static("attachment; filename*=UTF-8''My%20Provider"),
+        );
+        assert_eq!(subscription_name_from_headers(&headers).as_deref(), Some("My Provider"));
+
+        // profile-title wins over the filename; "5Lit6L2s5py65Zy6" is base64 for "中转机场"
+        headers.insert("profile-title", HeaderValue::from_static("base64:5Lit6L2s5py65Zy6"));
+        assert_eq!(subscription_name_from_headers(&headers).as_deref(), Some("中转机场"));
+
+        headers.insert("profile-title", HeaderValue::from_static("Plain Title"));
+        assert_eq!(subscription_name_from_headers(&headers).as_deref(), Some("Plain Title"));
+
+        assert!(subscription_name_from_headers(&HeaderMap::new()).is_none());
+    }
+
+    #[test]
+    fn test_subscription_http_errors() {
+        use crate::manager::{http_error_message, is_transient_status};
+        use reqwest::StatusCode;
+
+        assert!(is_transient_status(StatusCode::BAD_GATEWAY));
+        assert!(is_transient_status(StatusCode::TOO_MANY_REQUESTS));
+        assert!(!is_transient_status(StatusCode::NOT_FOUND));
+        assert!(!is_transient_status(StatusCode::FORBIDDEN));
+
+        assert_eq!(
+            http_error_message(StatusCode::FORBIDDEN, "  subscription\n expired "),
+            "Subscription server returned error: 403 Forbidden: subscription expired"
+        );
+        assert_eq!(
+            http_error_message(StatusCode::NOT_FOUND, ""),
+            "Subscription server returned error: 404 Not Found"
+        );
+        let long = http_error_message(StatusCode::INTERNAL_SERVER_ERROR, &"x".repeat(500));
+        assert!(long.ends_with("..."));
+        assert!(long.len() < 300);
+    }
+
+    #[test]
+    fn test_settings_connection_changes() {
+        use crate::settings::AppSettings;
+
+        let old = AppSettings::default();
+        let mut new = old.clone();
+        new.theme = Some("dark".to_string());
+        new.launch_at_login = true;
+        assert!(new.connection_changes(&old).is_empty());
+
+        new.mixed_port = 7890;
+        new.dns_servers = "1.1.1.1".to_string();
+        assert_eq!(new.connection_changes(&old), vec!["mixed_port", "dns_servers"]);
+
+        // The core log level lives in the generated config too
+        let debug = AppSettings { log_level: "debug".to_string(), ..old.clone() };
+        assert_eq!(debug.connection_changes(&old), vec!["log_level"]);
+
+        // Desktop keeps its mixed instance next to TUN, so this needs no restart there
+        let with_mixed = AppSettings { tun_with_mixed: true, ..old.clone() };
+        assert_eq!(with_mixed.connection_changes(&old).is_empty(), cfg!(desktop));
+    }
+
+    #[test]
+    fn test_clash_api_controller_settings() {
+        use crate::config::clash_api_controller;
+        use crate::settings::AppSettings;
+
+        assert_eq!(clash_api_controller(9090, false), "127.0.0.1:9090");
+        assert_eq!(clash_api_controller(9090, true), "0.0.0.0:9090");
+
+        let mut settings = AppSettings::default();
+        assert!(settings.validate().is_ok());
+        settings.clash_api_port = Some(settings.mixed_port);
+        assert!(settings.validate().is_err());
+        settings.clash_api_port = Some(9090);
+        settings.clash_api_secret = Some("has space".to_string());
+        assert!(settings.validate().is_err());
+        settings.clash_api_secret = Some("pinned".to_string());
+        assert!(settings.validate().is_ok());
+    }
+
+    #[test]
+    fn test_outbound_extra_passthrough() {
+        use crate::settings::AppSettings;
+
+        let config = r#"{"outbounds":[{
+            "type":"trojan","tag":"ech-node","server":"example.com","server_port":443,
+            "password":"pw","tcp_multi_path":true,"detour":"elsewhere",
+            "tls":{"enabled":true,"server_name":"example.com","ech":{"enabled":true}}
+        }]}"#;
+        let nodes = parse_subscription(config);
+        assert_eq!(nodes.len(), 1);
+        let extra = serde_json::Value::Object(nodes[0].extra.clone());
+        assert_eq!(
+            extra,
+            serde_json::json!({"tcp_multi_path": true, "tls": {"ech": {"enabled": true}}})
+        );
+
+        // Extras merge into the generated outbound without clobbering modeled fields
+        let outbound = crate::service::node_to_outbound(&nodes[0], &AppSettings::default());
+        let json = serde_json::to_value(&outbound).unwrap();
+        assert_eq!(json["tcp_multi_path"], serde_json::json!(true));
+        assert_eq!(json["tls"]["ech"]["enabled"], serde_json::json!(true));
+        assert_eq!(json["tls"]["server_name"], serde_json::json!("example.com"));
+        assert!(json.get("detour").is_none());
+    }
+
+    #[test]
+    fn test_ech_links_and_outbound() {
+        use crate::settings::AppSettings;
+
+        let nodes = parse_subscription(
+            "vless://uuid@example.com:443?security=tls&sni=example.com&ech=AEX%2BDQBBpQAgACB4#ECH\n\
+             trojan://pw@example.com:443?sni=example.com&ech=1#DNS",
+        );
+        assert_eq!(nodes.len(), 2);
+        assert!(nodes[0].ech);
+        assert_eq!(nodes[0].ech_config.as_deref(), Some("AEX+DQBBpQAgACB4"));
+        assert!(nodes[1].ech && nodes[1].ech_config.is_none());
+        assert!(nodes[0].to_link().contains("ech=AEX%2BDQBBpQAgACB4"));
+        assert!(nodes[1].to_link().contains("ech=1"));
+
+        let settings = AppSettings::default();
+        let tls = |node: &crate::profile::Node| {
+            serde_json::to_value(crate::service::node_to_outbound(node, &settings)).unwrap()["tls"].clone()
+        };
+        assert_eq!(
+            tls(&nodes[0])["ech"]["config"],
+            serde_json::json!(["-----BEGIN ECH CONFIGS-----", "AEX+DQBBpQAgACB4", "-----END ECH CONFIGS-----"])
+        );
+        assert_eq!(tls(&nodes[1])["ech"], serde_json::json!({"enabled": true}));
+
+        // Disabled ECH omits the block; a broken config fails validation
+        let mut node = nodes[0].clone();
+        node.ech = false;
+        assert!(tls(&node).get("ech").is_none());
+        node.ech_config = Some("not base64!".to_string());
+        assert!(node.validate().is_err());
+    }
+
+    #[test]
+    fn test_brutal_multiplex_serialization() {
+        use crate::settings::AppSettings;
+
+        let settings = AppSettings::default();
+        let outbound = |node: &crate::profile::Node| {
+            serde_json::to_value(crate::service::node_to_outbound(node, &settings)).unwrap()
+        };
+
+        let mut node = parse_subscription("trojan://pw@example.com:443?sni=example.com#Brutal")
+            .pop()
+            .unwrap();
+        node.up = Some("50 Mbps".to_string());
+        node.down = Some("200".to_string());
+        assert!(outbound(&node).get("multiplex").is_none());
+
+        node.brutal = true;
+        assert_eq!(
+            outbound(&node)["multiplex"],
+            serde_json::json!({
+                "enabled": true,
+                "protocol": "h2mux",
+                "brutal": {"enabled": true, "up_mbps": 50, "down_mbps": 200}
+            })
+        );
+
+        // Missing bandwidth leaves multiplex off rather than sending a zero rate
+        node.down = None;
+        assert!(outbound(&node).get("multiplex").is_none());
+
+        let mut hy2 = parse_subscription("hysteria2://pw@example.com:443#HY2").pop().unwrap();
+        hy2.up = Some("30 Mbps".to_string());
+        hy2.down = Some("100m".to_string());
+        let json = outbound(&hy2);
+        assert_eq!(json["up_mbps"], 30);
+        assert_eq!(json["down_mbps"], 100);
+    }
+
+    #[test]
+    fn test_test_temp_file_names() {
+        use crate::manager::is_test_temp_file;
+
+        assert!(is_test_temp_file("url_test_3f2a.json"));
+        assert!(is_test_temp_file("url_test_3f2a.log"));
+        assert!(is_test_temp_file("probe_config.json"));
+        assert!(is_test_temp_file("probe_cache_9c1e.db"));
+        assert!(!is_test_temp_file("cache.db"));
+        assert!(!is_test_temp_file("cache_test.db"));
+        assert!(!is_test_temp_file("config.json"));
+        assert!(!is_test_temp_file("url_test_notes.txt"));
+        assert!(!is_test_temp_file("profiles_v2.json"));
+    }
+
+    #[test]
+    fn test_ip_check_providers() {
+        use crate::ip_check::parse_response;
+
+        let ip_api = parse_response(
+            "http://ip-api.com/json",
+            r#"{"status":"success","query":"1.2.3.4","country":"Japan","countryCode":"JP","city":"Tokyo","lat":35.6,"lon":139.7,"isp":"ExampleNet"}"#,
+        )
+        .unwrap();
+        assert_eq!((ip_api.ip.as_str(), ip_api.country_code.as_str()), ("1.2.3.4", "JP"));
+        assert!(parse_response("http://ip-api.com/json", r#"{"status":"fail","message":"quota"}"#).is_none());
+
+        let ipinfo = parse_response(
+            "https://ipinfo.io/json",
+            r#"{"ip":"5.6.7.8","city":"Berlin","country":"DE","loc":"52.52,13.40","org":"AS3320 Deutsche Telekom AG"}"#,
+        )
+        .unwrap();
+        assert_eq!(ipinfo.country_code, "DE");
+        assert_eq!(ipinfo.isp, "Deutsche Telekom AG");
+        assert_eq!((ipinfo.lat, ipinfo.lon), (52.52, 13.40));
+
+        // Unknown hosts such as ifconfig.co fall back to common field names
+        let generic = parse_response(
+            "https://ifconfig.co/json",
+            r#"{"ip":"9.9.9.9","country":"Switzerland","country_iso":"CH","latitude":47.1,"longitude":8.5,"asn_org":"Quad9"}"#,
+        )
+        .unwrap();
+        assert_eq!((generic.country_code.as_str(), generic.isp.as_str()), ("CH", "Quad9"));
+        assert!(parse_response("https://example.com/ip", "1.2.3.4").is_none());
+    }
+
+    #[test]
+    fn test_parse_mixed_links_and_base64() {
+        use base64::{engine::general_purpose, Engine as _};
+
+        let nested = general_purpose::STANDARD.encode(
+            "trojan://pass@t.example.com:443#Nested%20Trojan\nss://YWVzLTEyOC1nY206cGFzcw@s.example.com:8388#Nested%20SS",
+        );
+        let content = format!(
+            "vless://uuid@v.example.com:443?security=tls#Plain%20Vless\n{}\nnot a link\nhysteria2://pw@h.example.com:443#Plain%20Hy2\n",
+            nested
+        );
+        let nodes = parse_subscription(&content);
+        let names: Vec<&str> = nodes.iter().map(|n| n.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec!["Plain Vless", "Nested Trojan", "Nested SS", "Plain Hy2"]
+        );
+    }
+
+    #[test]
+    fn test_sort_nodes_untested_last() {
+        use crate::profile::{sort_nodes, LocationInfo, Node, NodeSortKey};
+
+        let node = |name: &str, ping: Option<u64>, country: &str| Node {
+            name: name.to_string(),
+            ping,
+            location: (!country.is_empty()).then(|| LocationInfo {
+                country: country.to_string(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let names = |nodes: &[Node]| nodes.iter().map(|n| n.name.clone()).collect::<Vec<_>>();
+        let mut nodes = vec![
+            node("untested", None, "Japan"),
+            node("slow", Some(300), "Japan"),
+            node("failed", Some(0), ""),
+            node("fast", Some(40), "Germany"),
+        ];
+
+        sort_nodes(&mut nodes, NodeSortKey::Latency);
+        assert_eq!(names(&nodes), ["fast", "slow", "untested", "failed"]);
+
+        sort_nodes(&mut nodes, NodeSortKey::Country);
+        assert_eq!(names(&nodes), ["fast", "slow", "untested", "failed"]);
+
+        sort_nodes(&mut nodes, NodeSortKey::Name);
+        assert_eq!(names(&nodes), ["failed", "fast", "slow", "untested"]);
+    }
+
+    #[test]
+    fn test_legacy_profiles_migration() {
+        use crate::profile::profiles_from_legacy;
+
+        let listed = profiles_from_legacy(
+            r#"[{"id":"p1","name":"Sub","url":"https://example.com/sub","nodes":[{"id":"n1","name":"A","protocol":"trojan","server":"a.example.com","port":443}]}]"#,
+        )
+        .unwrap();
+        assert_eq!((listed[0].id.as_str(), listed[0].nodes[0].id.as_str()), ("p1", "n1"));
+
+        // Flat node lists (optionally wrapped) become one local profile with fresh ids
+        let flat = profiles_from_legacy(
+            r#"{"profiles":[{"name":"B","protocol":"vmess","server":"b.example.com","port":80}]}"#,
+        )
+        .unwrap();
+        assert_eq!(flat.len(), 1);
+        assert!(flat[0].url.is_none());
+        assert!(!flat[0].id.is_empty() && !flat[0].nodes[0].id.is_empty());
+
+        assert!(profiles_from_legacy("not json").is_none());
+    }
+
+    #[test]
+    fn test_atomic_write_survives_interrupted_write() {
+        use crate::manager::{temp_path_for, write_atomic};
+
+        let dir = std::env::temp_dir().join(format!("tunnet_atomic_{}", uuid::Uuid::new_v4().simple()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("profiles_v2.json");
+
+        write_atomic(&path, b"[\"saved\"]").unwrap();
+        assert!(!temp_path_for(&path).exists());
+
+        // A crash while writing the next version only ever leaves a partial temp file
+        std::fs::write(temp_path_for(&path), b"[\"trunc").unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"[\"saved\"]");
+
+        // The next save replaces the leftover temp file and then the real one
+        write_atomic(&path, b"[\"next\"]").unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"[\"next\"]");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_backup_recovers_corrupted_file() {
+        use crate::manager::{backup_path_for, parse_json_with_backup, save_json_with_backup};
+
+        let dir = std::env::temp_dir().join(format!("tunnet_backup_{}", uuid::Uuid::new_v4().simple()));
+        let path = dir.join("rules.json");
+
+        save_json_with_backup(&path, &vec!["first"]).unwrap();
+        save_json_with_backup(&path, &vec!["second"]).unwrap();
+        assert_eq!(std::fs::read_to_string(backup_path_for(&path)).unwrap(), "[\n  \"first\"\n]");
+
+        // Corrupted primary: the backup is used and reported as such
+        std::fs::write(&path, "[\"sec").unwrap();
+        let (rules, recovered) = parse_json_with_backup::<Vec<String>>(&path, "[\"sec").unwrap();
+        assert_eq!((rules, recovered), (vec!["first".to_string()], true));
+
+        // Saving over the corrupted file keeps the good backup
+        save_json_with_backup(&path, &vec!["third"]).unwrap();
+        let backup = std::fs::read_to_string(backup_path_for(&path)).unwrap();
+        assert_eq!(parse_json_with_backup::<Vec<String>>(&path, &backup).unwrap().0, ["first"]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_dns_leak_assessment() {
+        use crate::ip_check::{assess_dns_leak, parse_dns_echo};
+
+        let remote = parse_dns_echo(r#"{"dns":{"geo":"Japan - Example DNS","ip":"203.0.113.53"}}"#).unwrap();
+        assert_eq!((remote.country.as_str(), remote.isp.as_str()), ("Japan", "Example DNS"));
+        assert_eq!(remote.country_code, "JP");
+        assert!(parse_dns_echo(r#"{"dns":{}}"#).is_none());
+
+        // The exit country is an ISO code, the resolver's a name mapped to one
+        assert!(!assess_dns_leak("JP", vec![remote.clone()]).leak);
+        assert!(!assess_dns_leak("jp", vec![remote.clone()]).leak);
+        assert!(assess_dns_leak("DE", vec![remote.clone()]).leak);
+        // No known exit country: only local resolvers count
+        assert!(!assess_dns_leak("", vec![remote.clone()]).leak);
+        // Nor does a resolver country without a known code
+        let unknown = parse_dns_echo(r#"{"dns":{"geo":"Atlantis - Example DNS","ip":"203.0.113.54"}}"#).unwrap();
+        assert!(!assess_dns_leak("JP", vec![unknown]).leak);
+
+        let local = parse_dns_echo(r#"{"dns":{"geo":"Japan","ip":"192.168.1.1"}}"#).unwrap();
+        let report = assess_dns_leak("JP", vec![remote, local]);
+        assert!(report.leak);
+        assert_eq!(report.reasons.len(), 1);
+    }
+
+    #[test]
+    fn test_rapid_toggles_keep_only_last_request() {
+        use crate::error::TunnetError;
+        use crate::service::RequestGenerations;
+        use std::sync::Arc;
+
+        let requests = Arc::new(RequestGenerations::default());
+        let start_lock = Arc::new(tokio::sync::Mutex::new(()));
+
+        tauri::async_runtime::block_on(async {
+            // A start is running and holds the lock
+            let running = start_lock.lock().await;
+
+            // Start, stop, start clicked meanwhile, each queued behind the running one
+            let mut queued = Vec::new();
+            for (i, action) in ["start", "stop", "start"].into_iter().enumerate() {
+                let (tickets, lock) = (requests.clone(), start_lock.clone());
+                queued.push(tokio::spawn(async move {
+                    tickets.acquire(&lock).await.map(|_guard| action)
+                }));
+                // Keep the tickets in click order
+                while requests.current() < i as u64 + 1 {
+                    tokio::task::yield_now().await;
+                }
+            }
+
+            drop(running);
+            let mut results = Vec::new();
+            for handle in queued {
+                results.push(handle.await.unwrap());
+            }
+
+            // The earlier start and the stop report superseded; only the last start runs
+            assert!(matches!(results[0], Err(TunnetError::Superseded)));
+            assert!(matches!(results[1], Err(TunnetError::Superseded)));
+            assert_eq!(results[2].as_ref().ok(), Some(&"start"));
+            assert_eq!(TunnetError::Superseded.code(), "superseded");
+
+            // A request arriving after the burst runs again
+            assert!(requests.acquire(&start_lock).await.is_ok());
+        });
+    }
+
+    #[test]
+    fn test_cancel_tests_ends_current_epoch() {
+        use crate::service::RequestGenerations;
+
+        // Work started in the current epoch ends once the epoch moves on (cancel_tests)
+        let epochs = RequestGenerations::default();
+        let epoch = epochs.current();
+        assert!(epochs.is_current(epoch));
+        epochs.next();
+        assert!(!epochs.is_current(epoch));
+    }
+
+    #[test]
+    fn test_node_test_history_and_staleness() {
+        use crate::profile::Node;
+
+        const DAY: u64 = 86400;
+        let mut node = Node::default();
+        assert!(!node.is_stale(7, 100 * DAY));
+
+        node.record_test(Some(120), 10 * DAY);
+        assert_eq!((node.last_ok, node.fail_count), (Some(10 * DAY), 0));
+
+        // A recent success tolerates a couple of failures, an old one does not
+        node.record_test(Some(0), 11 * DAY);
+        node.record_test(None, 12 * DAY);
+        assert_eq!((node.ping, node.fail_count), (Some(0), 2));
+        assert!(!node.is_stale(7, 12 * DAY));
+        assert!(node.is_stale(7, 20 * DAY));
+        // A huge window never overflows, it just never expires
+        assert!(!node.is_stale(u64::MAX, 20 * DAY));
+
+        node.record_test(None, 13 * DAY);
+        assert!(node.is_stale(7, 13 * DAY));
+
+        node.record_test(Some(80), 14 * DAY);
+        assert!(!node.is_stale(7, 30 * DAY));
+    }
+
+    #[test]
+    fn test_outbound_with_extra() {
+        use crate::config::{Outbound, OutboundTls};
+        use serde_json::json;
+
+        let outbound = Outbound {
+            outbound_type: "vmess".to_string(),
+            tag: "n".to_string(),
+            server: Some("example.com".to_string()),
+            tls: Some(OutboundTls {
+                enabled: true,
+                server_name: Some("example.com".to_string()),
+                insecure: None,
+                alpn: None,
+                utls: None,
+                reality: None,
+                disable_sni: None,
+                ech: None,
+                extra: Default::default(),
+            }),
+            ..Default::default()
+        };
+        let extra = |v: serde_json::Value| v.as_object().unwrap().clone();
+
+        // Nested objects merge into the generated block, unknown keys pass through
+        let merged = outbound
+            .clone()
+            .with_extra(&extra(json!({"tls": {"ech": {"enabled": true}}, "udp_over_tcp": true})))
+            .unwrap();
+        let value = serde_json::to_value(&merged).unwrap();
+        assert_eq!(value["tls"], json!({"enabled": true, "server_name": "example.com", "ech": {"enabled": true}}));
+        assert_eq!(value["udp_over_tcp"], true);
+        assert_eq!(merged.server.as_deref(), Some("example.com"));
+
+        // A block the typed outbound could not hold on its own still comes through
+        let plain = Outbound { tls: None, ..outbound };
+        let merged = plain.with_extra(&extra(json!({"tls": {"ech": {"enabled": true}}}))).unwrap();
+        let value = serde_json::to_value(&merged).unwrap();
+        assert_eq!(value["tls"], json!({"ech": {"enabled": true}}));
+        assert_eq!(value["type"], "vmess");
+    }
+
+    #[test]
+    fn test_raw_outbound_node() {
+        use crate::profile::Node;
+        use crate::service::node_to_outbound;
+
+        let json = r#"{
+            "type": "ssh",
+            "tag": "jump host",
+            "server": "198.51.100.4",
+            "server_port": 2222,
+            "user": "root",
+            "private_key_path": "~/.ssh/id_ed25519"
+        }"#;
+        let node = Node::from_outbound_json(json).unwrap();
+        assert_eq!(node.protocol, "raw");
+        assert_eq!((node.name.as_str(), node.server.as_str(), node.port), ("jump host", "198.51.100.4", 2222));
+        assert!(node.validate().is_ok());
+
+        // Emitted verbatim apart from the tag, which becomes the node id
+        let outbound = node_to_outbound(&node, &crate::settings::AppSettings::default());
+        assert_eq!(outbound.outbound_type, "ssh");
+        assert_eq!(outbound.tag, node.id);
+        let value = serde_json::to_value(&outbound).unwrap();
+        assert_eq!(value["user"], "root");
+        assert_eq!(value["private_key_path"], "~/.ssh/id_ed25519");
+
+        assert!(Node::from_outbound_json(r#"{"type": "direct"}"#).is_err());
+        assert!(Node::from_outbound_json(r#"{"server": "198.51.100.4"}"#).is_err());
+        assert!(Node::from_outbound_json("[1, 2]").is_err());
+    }
+
+    #[test]
+    fn test_core_version_range() {
+        use crate::libbox::{check_core_version, parse_core_version};
+
+        assert_eq!(parse_core_version("1.13.6"), Some((1, 13, 6)));
+        assert_eq!(parse_core_version("v1.13.0-beta.2"), Some((1, 13, 0)));
+        assert_eq!(parse_core_version("1.13"), Some((1, 13, 0)));
+        assert_eq!(parse_core_version("unknown"), None);
+
+        assert!(check_core_version("1.13.6").is_ok());
+        assert!(check_core_version("1.12.14").is_err());
+        assert!(check_core_version("1.14.0-alpha.1").is_err());
+        assert!(check_core_version("").is_err());
+    }
+
+    #[test]
+    fn test_parse_call_result() {
+        use crate::libbox::parse_call_result;
+
+        assert_eq!(parse_call_result(r#"{"ok": true, "body": "{\"ip\": \"1.2.3.4\"}"}"#).unwrap(), r#"{"ip": "1.2.3.4"}"#);
+        assert_eq!(parse_call_result(r#"{"ok": true}"#).unwrap(), "");
+        let err = parse_call_result(r#"{"ok": false, "kind": "request", "message": "timeout"}"#).unwrap_err();
+        assert_eq!((err.kind.as_str(), err.message.as_str()), ("request", "timeout"));
+        // A body that merely looks like an error is still a body
+        assert_eq!(parse_call_result(r#"{"ok": true, "body": "request error"}"#).unwrap(), "request error");
+        assert_eq!(parse_call_result("garbage").unwrap_err().kind, "unknown");
+    }
+
+    #[test]
+    fn test_clash_yaml_round_trip() {
+        use crate::profile::parser::parse_subscription_full;
+        use crate::profile::{nodes_to_clash_yaml, Node};
+
+        let yaml = r#"
+proxies:
+  - name: vmess-ws
+    type: vmess
+    server: v.example.com
+    port: 443
+    uuid: 8c3a2f6e-1b7d-4c9e-a5f0-2d6b9e8c1a47
+    alterId: 0
+    cipher: auto
+    tls: true
+    servername: cdn.example.com
+    network: ws
+    ws-opts:
+      path: /ray
+      headers:
+        Host: cdn.example.com
+  - name: ss
+    type: ss
+    server: 198.51.100.7
+    port: 8388
+    cipher: aes-256-gcm
+    password: secret
+  - name: trojan-grpc
+    type: trojan
+    server: t.example.com
+    port: 443
+    password: pass
+    sni: t.example.com
+    network: grpc
+    grpc-opts:
+      grpc-service-name: tun
+    skip-cert-verify: true
+  - name: vless-reality
+    type: vless
+    server: r.example.com
+    port: 443
+    uuid: 5f2e9a1c-7b3d-4e8f-9a6c-1d2b3c4e5f60
+    flow: xtls-rprx-vision
+    tls: true
+    servername: www.microsoft.com
+    client-fingerprint: chrome
+    reality-opts:
+      public-key: jNXHt1yRo0vDuchQlIP6Z0ZvjT3KtzVI-T4E7RoLJS0
+      short-id: 6ba85179e30d4fc2
+  - name: hy2
+    type: hysteria2
+    server: h.example.com
+    port: 8443
+    password: hy
+    sni: h.example.com
+    obfs: salamander
+    obfs-password: cry
+    up: 30 Mbps
+    down: 100
+  - name: hy2
+    type: hysteria2
+    server: h2.example.com
+    port: 8443
+    password: hy
+"#;
+        let first = parse_subscription_full(yaml).nodes;
+        assert_eq!(first.len(), 6);
+        assert_eq!(first[1].protocol, "ss");
+        assert!(first[2].tls, "Trojan is TLS-only in Clash");
+        assert_eq!(first[4].down.as_deref(), Some("100"));
+
+        let exported = nodes_to_clash_yaml(&first).unwrap();
+        let second = parse_subscription_full(&exported).nodes;
+        assert_eq!(second.len(), first.len());
+        // Clash refers to proxies by name, so the duplicate is renamed
+        assert_eq!(second[5].name, "hy2 2");
+
+        for (a, b) in first.iter().zip(&second).take(5) {
+            assert_eq!(a.name, b.name);
+            assert_eq!(
+                (&a.protocol, &a.server, a.port, &a.uuid, &a.cipher, &a.password, a.tls),
+                (&b.protocol, &b.server, b.port, &b.uuid, &b.cipher, &b.password, b.tls)
+            );
+            assert_eq!((&a.network, &a.path, &a.host, &a.sni), (&b.network, &b.path, &b.host, &b.sni));
+            assert_eq!(
+                (&a.flow, &a.fingerprint, &a.public_key, &a.short_id, a.insecure, a.alter_id),
+                (&b.flow, &b.fingerprint, &b.public_key, &b.short_id, b.insecure, b.alter_id)
+            );
+            assert_eq!((&a.obfs, &a.obfs_password, &a.up, &a.down), (&b.obfs, &b.obfs_password, &b.up, &b.down));
+        }
+        assert_eq!(second[0].path.as_deref(), Some("/ray"));
+        assert_eq!(second[2].path.as_deref(), Some("tun"));
+        assert_eq!(second[3].short_id.as_deref(), Some("6ba85179e30d4fc2"));
+
+        // Protocols without a Clash type are left out rather than failing the export
+        let tuic = Node {
+            protocol: "tuic".to_string(),
+            ..Default::default()
+        };
+        assert!(tuic.to_clash_proxy().is_none());
+    }
+
+    #[test]
+    fn test_hysteria2_port_hopping() {
+        use crate::service::node_to_outbound;
+        let settings = crate::settings::AppSettings::default();
+
+        let nodes = parse_subscription("hysteria2://pw@h.example.com:443?mport=20000-50000&sni=h.example.com#Hop");
+        let node = &nodes[0];
+        assert_eq!(node.port, 443);
+        assert_eq!(node.server_ports, Some(vec!["20000:50000".to_string()]));
+
+        let outbound = node_to_outbound(node, &settings);
+        assert_eq!(outbound.server_ports, Some(vec!["20000:50000".to_string()]));
+        // sing-box refuses server_port next to server_ports
+        assert_eq!(outbound.server_port, None);
+        assert!(node.to_link().contains("mport=20000-50000"));
+
+        // Ranges in the authority, as the Hysteria2 URI scheme writes them
+        let nodes = parse_subscription("hy2://pw@[2001:db8::1]:443,20000-30000/?insecure=1#V6");
+        assert_eq!(nodes[0].server, "2001:db8::1");
+        assert_eq!(nodes[0].port, 443);
+        assert_eq!(
+            nodes[0].server_ports,
+            Some(vec!["20000:30000".to_string(), "443:443".to_string()])
+        );
+
+        // No range keeps the single port
+        let nodes = parse_subscription("hysteria2://pw@h.example.com:8443#Plain");
+        assert_eq!(nodes[0].server_ports, None);
+        let outbound = node_to_outbound(&nodes[0], &settings);
+        assert_eq!((outbound.server_port, outbound.server_ports), (Some(8443), None));
+    }
+
+    #[test]
+    fn test_shadowsocks_simple_obfs() {
+        use crate::profile::parser::parse_subscription_full;
+        use crate::service::node_to_outbound;
+        let settings = crate::settings::AppSettings::default();
+
+        // SIP003 plugin parameter on a SIP002 link
+        let link = "ss://YWVzLTI1Ni1nY206c2VjcmV0@198.51.100.9:8388/?plugin=obfs-local%3Bobfs%3Dhttp%3Bobfs-host%3Dwww.bing.com#Obfs";
+        let nodes = parse_subscription(link);
+        assert_eq!(nodes[0].obfs.as_deref(), Some("http"));
+        assert_eq!(nodes[0].host.as_deref(), Some("www.bing.com"));
+
+        let outbound = node_to_outbound(&nodes[0], &settings);
+        assert_eq!(outbound.plugin.as_deref(), Some("obfs-local"));
+        assert_eq!(outbound.plugin_opts.as_deref(), Some("obfs=http;obfs-host=www.bing.com"));
+        let value = serde_json::to_value(&outbound).unwrap();
+        assert_eq!(value["plugin_opts"], "obfs=http;obfs-host=www.bing.com");
+
+        // The exported link carries the plugin back
+        let again = parse_subscription(&nodes[0].to_link());
+        assert_eq!((again[0].obfs.as_deref(), again[0].host.as_deref()), (Some("http"), Some("www.bing.com")));
+
+        // Clash plugin/plugin-opts
+        let yaml = "proxies:\n  - {name: c, type: ss, server: 198.51.100.9, port: 8388, cipher: aes-256-gcm, password: s, plugin: obfs, plugin-opts: {mode: tls}}\n";
+        let node = &parse_subscription_full(yaml).nodes[0];
+        let outbound = node_to_outbound(node, &settings);
+        assert_eq!(outbound.plugin_opts.as_deref(), Some("obfs=tls"));
+
+        // Plain nodes and unsupported plugins emit no plugin
+        let plain = parse_subscription("ss://YWVzLTI1Ni1nY206c2VjcmV0@198.51.100.9:8388/?plugin=v2ray-plugin%3Bmode%3Dwebsocket#V2");
+        assert_eq!(plain[0].obfs, None);
+        assert_eq!(node_to_outbound(&plain[0], &settings).plugin, None);
+    }
+
+    #[test]
+    fn test_probe_outbound_matches_live_config() {
+        use crate::config::ConfigMode;
+        use crate::profile::Node;
+        use crate::service::{build_config, node_to_outbound, ConfigSources};
+        use crate::settings::AppSettings;
+
+        let vmess = Node {
+            id: "vmess-node".to_string(),
+            name: "VMess".to_string(),
+            protocol: "vmess".to_string(),
+            server: "v.example.com".to_string(),
+            port: 443,
+            uuid: Some("b831381d-6324-4d53-ad4f-8cda48b30811".to_string()),
+            tls: true,
+            network: Some("ws".to_string()),
+            host: Some("cdn.example.com".to_string()),
+            sni: Some("sni.example.com".to_string()),
+            fingerprint: Some("chrome".to_string()),
+            ..Default::default()
+        };
+        let anytls = Node {
+            id: "anytls-node".to_string(),
+            protocol: "anytls".to_string(),
+            server: "a.example.com".to_string(),
+            port: 443,
+            password: Some("pw".to_string()),
+            tls: true,
+            disable_sni: Some(true),
+            ..Default::default()
+        };
+        let profiles = vec![profile(vec![vmess.clone(), anytls.clone()])];
+        let sources = ConfigSources { profiles: &profiles, ..test_sources(&[]) };
+        let settings = AppSettings { ipv6: false, ..AppSettings::default() };
+        let cfg = build_config(Some(&anytls), ConfigMode::SystemProxyOnly, "global", &settings, None, &sources);
+
+        // The probes dial exactly what the live config would
+        for node in [&vmess, &anytls] {
+            let live = cfg.outbounds.iter().find(|o| o.tag == node.id).expect("node in live config");
+            let probe = node_to_outbound(node, &settings);
+            assert_eq!(serde_json::to_value(live).unwrap(), serde_json::to_value(&probe).unwrap());
+            assert_eq!(probe.domain_strategy.as_deref(), Some("ipv4_only"));
+        }
+
+        // VMess takes the node's SNI and fingerprint instead of the Host header
+        let tls = node_to_outbound(&vmess, &settings).tls.unwrap();
+        assert_eq!(tls.server_name.as_deref(), Some("sni.example.com"));
+        assert_eq!(tls.utls.map(|u| u.fingerprint).as_deref(), Some("chrome"));
+        let tls = node_to_outbound(&anytls, &settings).tls.unwrap();
+        assert_eq!(tls.disable_sni, Some(true));
+    }
+
+    #[test]
+    fn test_node_outbound_per_protocol() {
+        use crate::config::{ConfigMode, SingBoxConfig, NODE_PROTOCOLS};
+        use crate::profile::Node;
+
+        let outbound = |protocol: &str, node: Node| {
+            let node = Node {
+                protocol: protocol.to_string(),
+                server: "203.0.113.20".to_string(),
+                port: 8443,
+                ..node
+            };
+            let cfg = SingBoxConfig::new(None, ConfigMode::Combined, "", "ipv4", "proxy").with_node_outbound(&node, "t");
+            assert_eq!(cfg.outbounds.len(), 1);
+            let value = serde_json::to_value(&cfg.outbounds[0]).unwrap();
+            assert_eq!((value["tag"].as_str(), value["server_port"].as_u64()), (Some("t"), Some(8443)), "{}", protocol);
+            value
+        };
+        let uuid = Some("b831381d-6324-4d53-ad4f-8cda48b30811".to_string());
+        let password = Some("pw".to_string());
+
+        // Every listed protocol, aliases included, gets a real outbound
+        for protocol in NODE_PROTOCOLS {
+            let value = outbound(protocol, Node { uuid: uuid.clone(), password: password.clone(), ..Default::default() });
+            assert_ne!(value["type"], "direct", "{}", protocol);
+        }
+
+        let vmess = outbound("vmess", Node { uuid: uuid.clone(), ..Default::default() });
+        assert_eq!((&vmess["type"], &vmess["security"]), (&"vmess".into(), &"auto".into()));
+        let vless = outbound("vless", Node { uuid: uuid.clone(), flow: Some("xtls-rprx-vision".to_string()), ..Default::default() });
+        assert_eq!(vless["flow"], "xtls-rprx-vision");
+        let ss = outbound("ss", Node { password: password.clone(), ..Default::default() });
+        assert_eq!((&ss["type"], &ss["method"]), (&"shadowsocks".into(), &"chacha20-ietf-poly1305".into()));
+        let trojan = outbound("trojan", Node { password: password.clone(), tls: true, ..Default::default() });
+        assert_eq!((&trojan["password"], &trojan["tls"]["enabled"]), (&"pw".into(), &true.into()));
+        let hy2 = outbound("hy2", Node { password: password.clone(), up: Some("50 Mbps".to_string()), ..Default::default() });
+        assert_eq!((&hy2["type"], &hy2["up_mbps"]), (&"hysteria2".into(), &50.into()));
+        let tuic = outbound("tuic", Node { uuid: uuid.clone(), password: password.clone(), ..Default::default() });
+        assert_eq!(tuic["uuid"], "b831381d-6324-4d53-ad4f-8cda48b30811");
+        let anytls = outbound("anytls", Node { password: password.clone(), tls: true, ..Default::default() });
+        assert_eq!(anytls["type"], "anytls");
+
+        // Hysteria v1 from a link: auth, obfs password and bandwidths
+        let link = "hysteria://203.0.113.20:8443?auth=secret&peer=h.example.com&upmbps=20&downmbps=100&obfs=xplus&obfsParam=mask&alpn=h3#V1";
+        let node = parse_subscription(link).remove(0);
+        let hy = outbound("hysteria", node.clone());
+        assert_eq!((&hy["type"], &hy["auth_str"], &hy["obfs"]), (&"hysteria".into(), &"secret".into(), &"mask".into()));
+        assert_eq!((&hy["up_mbps"], &hy["down_mbps"]), (&20.into(), &100.into()));
+        assert_eq!(hy["tls"]["server_name"], "h.example.com");
+        assert_eq!(parse_subscription(&node.to_link())[0].obfs_password.as_deref(), Some("mask"));
+        // Without bandwidths the defaults stand in, since sing-box requires both
+        let hy = outbound("hysteria", Node { up: None, down: None, ..node });
+        assert_eq!((&hy["up_mbps"], &hy["down_mbps"]), (&10.into(), &50.into()));
+
+        // ShadowTLS needs an inner protocol a node does not carry, so it stays direct
+        assert_eq!(outbound("shadowtls", Node { password, ..Default::default() })["type"], "direct");
+    }
+
+    #[test]
+    fn test_hysteria_v1_obfs_with_extra() {
+        use crate::service::node_to_outbound;
+        use crate::settings::AppSettings;
+
+        // The v1 obfs string is untyped, so it must not fail the typed `obfs` on merge
+        let link = "hysteria://203.0.113.20:8443?auth=secret&upmbps=20&downmbps=100&obfs=xplus&obfsParam=mask#V1";
+        let mut node = parse_subscription(link).remove(0);
+        node.extra.insert("recv_window_conn".to_string(), 15728640.into());
+        node.extra.insert("obfs".to_string(), "override".into());
+
+        let value = serde_json::to_value(node_to_outbound(&node, &AppSettings::default())).unwrap();
+        assert_eq!(value["type"], "hysteria");
+        assert_eq!(value["recv_window_conn"], 15728640);
+        assert_eq!(value["obfs"], "override");
+        assert_eq!(value["auth_str"], "secret");
+
+        node.extra.remove("obfs");
+        let value = serde_json::to_value(node_to_outbound(&node, &AppSettings::default())).unwrap();
+        assert_eq!((&value["obfs"], &value["recv_window_conn"]), (&"mask".into(), &15728640.into()));
+    }
+
+    #[test]
+    fn test_import_reject_drop_rules() {
+        use crate::profile::parser::parse_subscription_full;
+
+        let singbox = r#"{"route":{"rules":[
+            {"domain_suffix":["ads.example.com"],"action":"reject","method":"drop"},
+            {"domain":["tracker.example.com"],"action":"reject"},
+            {"domain":["old.example.com"],"outbound":"block"}
+        ]}}"#;
+        let policies: Vec<String> = parse_subscription_full(singbox)
+            .rules
+            .into_iter()
+            .map(|r| r.policy)
+            .collect();
+        assert_eq!(policies, vec!["REJECT_DROP", "REJECT", "REJECT"]);
+
+        let clash = "proxies: []\nrules:\n  - DOMAIN-SUFFIX,ads.example.com,REJECT-DROP\n";
+        let parsed = parse_subscription_full(clash);
+        assert_eq!(parsed.rules[0].policy, "REJECT_DROP");
+    }
+
+    #[test]
+    fn test_profile_usage() {
+        use crate::profile::Profile;
+
+        let now = 1_700_000_000;
+        let mut profile = Profile {
+            url: Some("https://example.com/sub".to_string()),
+            upload: Some(10 * 1024),
+            download: Some(80 * 1024),
+            total: Some(100 * 1024),
+            expire: Some(now + 10 * 86400 + 60),
+            ..profile(vec![])
+        };
+
+        let usage = profile.usage(now);
+        assert_eq!(usage.used_bytes, 90 * 1024);
+        assert_eq!(usage.percent, Some(90.0));
+        assert_eq!(usage.expires_in_days, Some(10));
+        assert!(usage.needs_warning());
+
+        profile.download = Some(20 * 1024);
+        profile.expire = Some(now + 2 * 86400);
+        let usage = profile.usage(now);
+        assert_eq!(usage.expires_in_days, Some(2));
+        assert!(usage.needs_warning());
+
+        // Unlimited plans without expiry never warn
+        profile.total = None;
+        profile.expire = None;
+        let usage = profile.usage(now);
+        assert_eq!(usage.percent, None);
+        assert!(!usage.needs_warning());
+    }
+
     #[test]
     fn test_parse_shadowsocks() {
         // Standard SIP002 format (aes-128-gcm:test)
@@ -71,4 +1450,464 @@ mod tests {
         assert_eq!(node2.port, 1234);
         assert_eq!(node2.name, "Example2");
     }
+
+    #[test]
+    fn test_reconcile_nodes() {
+        use crate::profile::{reconcile_nodes, Node};
+
+        let node = |id: &str, name: &str, server: &str| Node {
+            id: id.to_string(),
+            name: name.to_string(),
+            protocol: "trojan".to_string(),
+            server: server.to_string(),
+            port: 443,
+            ..Default::default()
+        };
+
+        let mut kept = node("old-a", "A", "a.example.com");
+        kept.ping = Some(120);
+        let old = vec![
+            kept,
+            node("old-b", "B", "b.example.com"),
+            node("old-c", "C", "c.example.com"),
+        ];
+        let new = vec![
+            node("new-a", "A", "a.example.com"),
+            node("new-b", "B renamed", "b.example.com"),
+            node("new-d", "D", "d.example.com"),
+        ];
+
+        let nodes = reconcile_nodes(&old, new);
+        assert_eq!(nodes.len(), 3);
+        // Unchanged node keeps its id and probe data
+        assert_eq!(nodes[0].id, "old-a");
+        assert_eq!(nodes[0].ping, Some(120));
+        // Renamed node keeps its id but takes the new name
+        assert_eq!(nodes[1].id, "old-b");
+        assert_eq!(nodes[1].name, "B renamed");
+        // Added node keeps its fresh id, removed node is gone
+        assert_eq!(nodes[2].id, "new-d");
+        assert!(!nodes.iter().any(|n| n.id == "old-c"));
+    }
+
+    #[test]
+    fn test_node_validate_required_fields() {
+        use crate::profile::Node;
+
+        let base = |protocol: &str| Node {
+            protocol: protocol.to_string(),
+            server: "example.com".to_string(),
+            port: 443,
+            ..Default::default()
+        };
+
+        // Missing server and port are always reported
+        let empty = Node {
+            protocol: "trojan".to_string(),
+            password: Some("pw".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(empty.validate().unwrap_err().len(), 2);
+
+        for protocol in ["vmess", "vless", "tuic"] {
+            assert!(base(protocol).validate().is_err(), "{} without uuid", protocol);
+            let mut n = base(protocol);
+            n.uuid = Some("618d3496-3497-4ca0-fafa-eb0a12e57bd6".to_string());
+            assert!(n.validate().is_ok(), "{} with uuid", protocol);
+        }
+
+        for protocol in ["trojan", "anytls", "hysteria2"] {
+            assert!(base(protocol).validate().is_err(), "{} without password", protocol);
+            let mut n = base(protocol);
+            n.password = Some("pw".to_string());
+            assert!(n.validate().is_ok(), "{} with password", protocol);
+        }
+
+        let mut ss = base("shadowsocks");
+        assert_eq!(ss.validate().unwrap_err().len(), 2);
+        ss.password = Some("pw".to_string());
+        ss.cipher = Some("aes-128-gcm".to_string());
+        assert!(ss.validate().is_ok());
+    }
+
+    #[test]
+    fn test_rule_validate() {
+        use crate::profile::Rule;
+
+        assert!(rule("r", "IP_CIDR", "10.0.0.0/8", "PROXY").validate().is_ok());
+        assert!(rule("r", "IP_CIDR", "2001:db8::/32", "PROXY").validate().is_ok());
+        assert!(rule("r", "IP_CIDR", "10.0.0.0/33", "PROXY").validate().is_err());
+        assert!(rule("r", "IP_CIDR", "not-an-ip", "PROXY").validate().is_err());
+        assert!(rule("r", "GEOIP", "geoip-cn", "PROXY").validate().is_ok());
+        assert!(rule("r", "GEOIP", "GeoIP CN", "PROXY").validate().is_err());
+        assert!(rule("r", "DOMAIN", "geosite:geosite-cn", "PROXY").validate().is_ok());
+        // Only geoip-*/geosite-* rule sets can be resolved, so other tags are refused up front
+        assert!(rule("r", "GEOIP", "geosite-cn", "PROXY").validate().is_err());
+        assert!(rule("r", "DOMAIN", "geosite:my-list", "PROXY").validate().is_err());
+        assert!(rule("r", "DOMAIN_SUFFIX", "", "PROXY").validate().is_err());
+        assert!(rule("r", "FINAL", "", "PROXY").validate().is_ok());
+
+        // Rules saved with the Clash-style type names are still valid and get renamed
+        let mut legacy = rule("r", "DOMAIN-SUFFIX", "example.com", "PROXY");
+        assert!(legacy.validate().is_ok());
+        legacy.normalize();
+        assert_eq!(legacy.rule_type, "DOMAIN_SUFFIX");
+        let mut legacy = rule("r", "IP-CIDR", "10.0.0.0/8", "PROXY");
+        assert!(legacy.validate().is_ok());
+        legacy.normalize();
+        assert_eq!(legacy.rule_type, "IP_CIDR");
+        assert!(rule("r", "IP-CIDR", "not-an-ip", "PROXY").validate().is_err());
+
+        let web = |port: &str, network: &str| Rule {
+            port: Some(port.to_string()),
+            network: Some(network.to_string()),
+            ..rule("r", "DOMAIN_SUFFIX", "example.com", "PROXY")
+        };
+        assert!(web("443", "tcp").validate().is_ok());
+        assert!(web("0", "tcp").validate().is_err());
+        assert!(web("443", "icmp").validate().is_err());
+        assert!(Rule { port: Some("80".to_string()), ..rule("r", "PORT", "443", "PROXY") }.validate().is_err());
+        assert!(Rule { network: Some("udp".to_string()), ..rule("r", "FINAL", "", "PROXY") }.validate().is_err());
+    }
+
+    #[test]
+    fn test_parse_port_spec() {
+        use crate::profile::parse_port_spec;
+
+        let (ports, ranges) = parse_port_spec("80, 443,1000-2000").unwrap();
+        assert_eq!(ports, vec![80, 443]);
+        assert_eq!(ranges, vec!["1000:2000".to_string()]);
+
+        // sing-box style separator is accepted too
+        let (ports, ranges) = parse_port_spec("5000:6000").unwrap();
+        assert!(ports.is_empty());
+        assert_eq!(ranges, vec!["5000:6000".to_string()]);
+
+        assert!(parse_port_spec("").is_err());
+        assert!(parse_port_spec("0").is_err());
+        assert!(parse_port_spec("70000").is_err());
+        assert!(parse_port_spec("2000-1000").is_err());
+        assert!(parse_port_spec("http").is_err());
+    }
+
+    #[test]
+    fn test_simulate_route_first_match_wins() {
+        use crate::config::ConfigMode;
+        use crate::profile::{simulate_route, RouteDecision, Rule};
+        use crate::service::build_config_with_origins;
+        use crate::settings::AppSettings;
+
+        let node = trojan_node();
+        // Decide through the same route rules the generated config carries
+        let simulate = |rules: &[Rule], mode: ConfigMode, routing: &str, settings: &AppSettings, host: &str, port: u16| -> RouteDecision {
+            let sources = test_sources(rules);
+            let (cfg, origins) = build_config_with_origins(Some(&node), mode, routing, settings, None, &sources);
+            let inbound = if mode == ConfigMode::TunOnly { "tun-in" } else { "mixed-in" };
+            simulate_route(cfg.route.as_ref().unwrap(), &origins, rules, inbound, host, port)
+        };
+        let settings = AppSettings::default();
+        let proxy = ConfigMode::SystemProxyOnly;
+
+        let suffix_first = vec![
+            rule("suffix", "DOMAIN_SUFFIX", "google.com", "PROXY"),
+            rule("keyword", "DOMAIN_KEYWORD", "google", "DIRECT"),
+            rule("final", "FINAL", "", "DIRECT"),
+        ];
+        let d = simulate(&suffix_first, proxy, "rule", &settings, "www.google.com", 443);
+        assert_eq!(d.policy, "PROXY");
+        assert_eq!(d.rule_id.as_deref(), Some("suffix"));
+
+        // Same rules with the keyword first: the keyword now wins
+        let keyword_first = vec![
+            suffix_first[1].clone(),
+            suffix_first[0].clone(),
+            suffix_first[2].clone(),
+        ];
+        let d = simulate(&keyword_first, proxy, "rule", &settings, "www.google.com", 443);
+        assert_eq!(d.policy, "DIRECT");
+        assert_eq!(d.rule_id.as_deref(), Some("keyword"));
+
+        // Suffix must match on a label boundary, so this falls through to FINAL
+        let d = simulate(&suffix_first[..1], proxy, "rule", &settings, "notgoogle.com", 443);
+        assert_eq!(d.policy, "PROXY");
+        assert_eq!(d.rule_id, None);
+        let d = simulate(&suffix_first, proxy, "rule", &settings, "example.org", 443);
+        assert_eq!((d.policy.as_str(), d.rule_id.as_deref()), ("DIRECT", Some("final")));
+
+        // A port condition narrows the domain match
+        let mut port_limited = suffix_first.clone();
+        port_limited[0].port = Some("80".to_string());
+        let d = simulate(&port_limited, proxy, "rule", &settings, "www.google.com", 443);
+        assert_eq!(d.rule_id.as_deref(), Some("keyword"));
+        let d = simulate(&port_limited, proxy, "rule", &settings, "www.google.com", 80);
+        assert_eq!(d.rule_id.as_deref(), Some("suffix"));
+
+        // An unknown policy is reported as the proxy it falls back to
+        let missing = vec![rule("bad", "DOMAIN", "example.org", "missing-group")];
+        let d = simulate(&missing, proxy, "rule", &settings, "example.org", 443);
+        assert_eq!((d.policy.as_str(), d.rule_id.as_deref()), ("PROXY", Some("bad")));
+
+        // DNS is only hijacked on the TUN inbound, ahead of user rules
+        let d = simulate(&suffix_first, ConfigMode::TunOnly, "rule", &settings, "www.google.com", 53);
+        assert_eq!(d.policy, "HIJACK_DNS");
+        let d = simulate(&suffix_first, proxy, "rule", &settings, "www.google.com", 53);
+        assert_eq!(d.rule_id.as_deref(), Some("suffix"));
+
+        // Routing modes skip user rules, but the node's own server still bypasses the proxy
+        let d = simulate(&suffix_first, proxy, "direct", &settings, "www.google.com", 443);
+        assert_eq!((d.policy.as_str(), d.rule_id), ("DIRECT", None));
+        let d = simulate(&suffix_first, proxy, "global", &settings, "example.org", 443);
+        assert_eq!(d.policy, "PROXY");
+        let d = simulate(&suffix_first, proxy, "global", &settings, "203.0.113.7", 443);
+        assert_eq!(d.policy, "DIRECT");
+
+        // IPv6 destinations are rejected in any mode once IPv6 is off or only4 is chosen
+        let only4 = AppSettings { dns_strategy: "only4".to_string(), ..AppSettings::default() };
+        let no_v6 = AppSettings { ipv6: false, ..AppSettings::default() };
+        for settings in [&only4, &no_v6] {
+            let d = simulate(&suffix_first, proxy, "global", settings, "[2001:db8::1]", 443);
+            assert_eq!(d.policy, "REJECT");
+            let d = simulate(&suffix_first, proxy, "global", settings, "192.0.2.1", 443);
+            assert_eq!(d.policy, "PROXY");
+        }
+    }
+
+    #[test]
+    fn test_build_config_routing() {
+        use crate::config::ConfigMode;
+        use crate::profile::Rule;
+        use crate::service::build_config;
+        use crate::settings::AppSettings;
+
+        let node = trojan_node();
+        let rules = vec![
+            rule("cn", "DOMAIN_SUFFIX", "example.cn", "DIRECT"),
+            rule("bad", "DOMAIN", "example.org", "missing-group"),
+            Rule {
+                port: Some("443".to_string()),
+                network: Some("tcp".to_string()),
+                ..rule("web", "DOMAIN_SUFFIX", "example.com", "DIRECT")
+            },
+            rule("final", "FINAL", "", "REJECT"),
+        ];
+        let sources = test_sources(&rules);
+        let settings = AppSettings::default();
+        let build = |mode: ConfigMode, routing: &str, settings: &AppSettings| {
+            build_config(Some(&node), mode, routing, settings, Some(9090), &sources)
+        };
+
+        // Rule mode: the node's IP bypass comes first, user rules keep their order,
+        // unknown policies fall back to proxy and FINAL becomes the catch-all
+        let cfg = build(ConfigMode::SystemProxyOnly, "rule", &settings);
+        let route_rules = &cfg.route.as_ref().unwrap().rules;
+        assert_eq!(route_rules[0].ip_cidr, Some(vec!["203.0.113.7".to_string()]));
+        assert_eq!(route_rules[0].outbound.as_deref(), Some("direct"));
+        let cn = route_rules.iter().position(|r| r.domain_suffix == Some(vec!["example.cn".to_string()])).unwrap();
+        let bad = route_rules.iter().position(|r| r.domain == Some(vec!["example.org".to_string()])).unwrap();
+        assert!(cn < bad);
+        assert_eq!(route_rules[cn].outbound.as_deref(), Some("direct"));
+        assert_eq!(route_rules[bad].outbound.as_deref(), Some("proxy"));
+        // Extra conditions land on the same rule, which sing-box matches as an AND
+        let web = route_rules.iter().find(|r| r.domain_suffix == Some(vec!["example.com".to_string()])).unwrap();
+        assert_eq!(web.port, Some(vec![443]));
+        assert_eq!(web.network, Some(vec!["tcp".to_string()]));
+        assert_eq!(web.outbound.as_deref(), Some("direct"));
+        // Rejecting is an action, which `final` cannot name, so it stays a catch-all rule
+        let last = route_rules.last().unwrap();
+        assert_eq!((last.outbound.as_deref(), last.action.as_deref()), (None, Some("reject")));
+        assert_eq!(cfg.route.as_ref().unwrap().final_outbound, None);
+        assert!(!route_rules.iter().any(|r| r.action.as_deref() == Some("sniff")));
+        let clash_api = cfg.experimental.as_ref().and_then(|e| e.clash_api.as_ref()).unwrap();
+        assert_eq!(clash_api.secret.as_deref(), Some("session"));
+
+        // Global and direct modes ignore user rules and send everything to `final`
+        for (routing, policy) in [("global", "proxy"), ("direct", "direct")] {
+            let cfg = build(ConfigMode::SystemProxyOnly, routing, &settings);
+            let route = cfg.route.as_ref().unwrap();
+            assert!(!route.rules.iter().any(|r| r.domain_suffix.is_some()), "{}", routing);
+            assert!(route.rules.iter().all(|r| r.outbound.as_deref() != Some(policy)), "{}", routing);
+            assert_eq!(route.final_outbound.as_deref(), Some(policy));
+            let value = serde_json::to_value(route).unwrap();
+            assert_eq!(value["final"], policy);
+        }
+
+        // A FINAL rule pointing at an outbound becomes `final` rather than a rule
+        let to_direct = vec![rule("final", "FINAL", "", "DIRECT")];
+        let direct_sources = test_sources(&to_direct);
+        let cfg = build_config(Some(&node), ConfigMode::SystemProxyOnly, "rule", &settings, Some(9090), &direct_sources);
+        let route = cfg.route.as_ref().unwrap();
+        assert_eq!(route.final_outbound.as_deref(), Some("direct"));
+        assert!(route.rules.iter().all(|r| r.action.as_deref() != Some("reject")));
+
+        // TUN: sniffing follows the DNS hijack, and disabling IPv6 rejects ::/0 ahead of `final`
+        let no_v6 = AppSettings { ipv6: false, ..AppSettings::default() };
+        let cfg = build(ConfigMode::TunOnly, "global", &no_v6);
+        let route_rules = &cfg.route.as_ref().unwrap().rules;
+        let sniff = route_rules.iter().position(|r| r.action.as_deref() == Some("sniff")).unwrap();
+        let hijack = route_rules
+            .iter()
+            .position(|r| r.action.as_deref() == Some("hijack-dns"))
+            .expect("TUN config with dns_hijack on must hijack DNS");
+        assert!(hijack < sniff);
+        let v6 = route_rules.last().unwrap();
+        assert_eq!(v6.ip_cidr, Some(vec!["::/0".to_string()]));
+        assert_eq!(v6.action.as_deref(), Some("reject"));
+
+        // TUN + mixed: both inbounds, and DNS from either goes to the configured servers
+        let cfg = build(ConfigMode::Combined, "global", &settings);
+        let tags: Vec<&str> = cfg.inbounds.iter().map(|i| i.tag.as_str()).collect();
+        assert_eq!(tags, ["tun-in", "mixed-in"]);
+        let dns_inbounds = cfg.dns.as_ref().unwrap().rules.last().unwrap().inbound.clone();
+        assert_eq!(dns_inbounds, Some(vec!["tun-in".to_string(), "mixed-in".to_string()]));
+        assert_eq!(cfg.inbounds[0].strict_route, Some(true));
+
+        // Turning strict_route off is written out explicitly, not left to the core default
+        let loose = AppSettings { strict_route: false, ..AppSettings::default() };
+        let cfg = build(ConfigMode::TunOnly, "global", &loose);
+        assert_eq!(cfg.inbounds[0].strict_route, Some(false));
+
+        // auto_redirect needs the system stack, and is never emitted off Linux
+        let redirect = AppSettings { tun_auto_redirect: true, ..AppSettings::default() };
+        let cfg = build(ConfigMode::TunOnly, "global", &redirect);
+        assert_eq!(cfg.inbounds[0].auto_redirect, None);
+        let redirect = AppSettings { tun_stack: "system".to_string(), ..redirect };
+        let cfg = build(ConfigMode::TunOnly, "global", &redirect);
+        assert_eq!(cfg.inbounds[0].auto_redirect, cfg!(target_os = "linux").then_some(true));
+
+        // Transparent proxy inbounds exist on Linux only: TPROXY in the privileged TUN
+        // instance, REDIRECT next to mixed-in, both reachable from forwarded traffic
+        let tproxy = AppSettings {
+            transparent_proxy: crate::settings::TransparentProxyMode::Tproxy,
+            ..AppSettings::default()
+        };
+        let cfg = build(ConfigMode::SystemProxyOnly, "global", &tproxy);
+        let types: Vec<&str> = cfg.inbounds.iter().map(|i| i.inbound_type.as_str()).collect();
+        assert_eq!(types, ["mixed"]);
+        let cfg = build(ConfigMode::TunOnly, "global", &tproxy);
+        let types: Vec<&str> = cfg.inbounds.iter().map(|i| i.inbound_type.as_str()).collect();
+        if cfg!(target_os = "linux") {
+            assert_eq!(types, ["tun", "tproxy"]);
+            assert_eq!(cfg.inbounds[1].listen.as_deref(), Some("0.0.0.0"));
+            assert_eq!(cfg.inbounds[1].listen_port, Some(tproxy.transparent_proxy_port));
+        } else {
+            assert_eq!(types, ["tun"]);
+        }
+
+        let redirect = AppSettings {
+            transparent_proxy: crate::settings::TransparentProxyMode::Redirect,
+            ..AppSettings::default()
+        };
+        let cfg = build(ConfigMode::SystemProxyOnly, "global", &redirect);
+        let types: Vec<&str> = cfg.inbounds.iter().map(|i| i.inbound_type.as_str()).collect();
+        if cfg!(target_os = "linux") {
+            assert_eq!(types, ["mixed", "redirect"]);
+            assert_eq!(cfg.inbounds[1].listen.as_deref(), Some("0.0.0.0"));
+            let dns_inbounds = cfg.dns.as_ref().unwrap().rules.last().unwrap().inbound.clone();
+            assert_eq!(dns_inbounds, Some(vec!["mixed-in".to_string(), "redirect-in".to_string()]));
+        } else {
+            assert_eq!(types, ["mixed"]);
+        }
+        let cfg = build(ConfigMode::TunOnly, "global", &redirect);
+        assert!(!cfg.inbounds.iter().any(|i| i.inbound_type == "redirect"));
+    }
+
+    #[test]
+    fn test_move_rule_order_is_persisted() {
+        use crate::manager::{save_json_with_backup, save_moved_rule};
+        use crate::profile::Rule;
+
+        let rules: Vec<Rule> = ["a", "b", "c", "d"]
+            .iter()
+            .map(|id| rule(id, "DOMAIN", &format!("{}.example.com", id), "PROXY"))
+            .collect();
+
+        let dir = std::env::temp_dir().join(format!("tunnet_move_{}", uuid::Uuid::new_v4().simple()));
+        let path = dir.join("rules.json");
+        save_json_with_backup(&path, &rules).unwrap();
+        let reload = || serde_json::from_str::<Vec<Rule>>(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        let saved_ids = || reload().into_iter().map(|r| r.id).collect::<Vec<_>>();
+
+        // Each move starts from what rules.json holds, like move_rule, and is written back
+        save_moved_rule(&path, reload(), "d", 0).unwrap();
+        assert_eq!(saved_ids(), ["d", "a", "b", "c"]);
+        save_moved_rule(&path, reload(), "a", 99).unwrap();
+        assert_eq!(saved_ids(), ["d", "b", "c", "a"]);
+
+        // An unknown id leaves the file alone
+        assert!(save_moved_rule(&path, reload(), "missing", 0).is_err());
+        assert_eq!(saved_ids(), ["d", "b", "c", "a"]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_upsert_by_id() {
+        use crate::profile::upsert_by_id;
+
+        let mut items = vec![("a", 1), ("b", 2)];
+        upsert_by_id(&mut items, vec![("c", 3), ("a", 10)], |i| i.0);
+        assert_eq!(items, vec![("a", 10), ("b", 2), ("c", 3)]);
+    }
+
+    #[test]
+    fn test_ipv6_toggle_shapes_config() {
+        use crate::config::{effective_dns_strategy, ConfigMode, SingBoxConfig};
+
+        assert_eq!(effective_dns_strategy("ipv4", true), "prefer_ipv4");
+        assert_eq!(effective_dns_strategy("only6", true), "ipv6_only");
+        assert_eq!(effective_dns_strategy("ipv6", false), "ipv4_only");
+
+        let tun_addresses = |address_v6: Option<&str>| {
+            let cfg = SingBoxConfig::new(None, ConfigMode::TunOnly, "", "ipv4", "proxy")
+                .with_tun_inbound(1500, "gvisor".to_string(), true, "172.19.0.1/30", address_v6, None, Vec::new());
+            cfg.inbounds[0].address.clone().unwrap()
+        };
+        assert!(tun_addresses(Some("fd00::1/126")).iter().any(|a| a.contains(':')));
+        assert!(tun_addresses(None).iter().all(|a| !a.contains(':')));
+
+        // The full config follows the IPv6 toggle with both families
+        use crate::service::build_config;
+        let sources = test_sources(&[]);
+        let tun_config = |settings: &crate::settings::AppSettings| {
+            let cfg = build_config(None, ConfigMode::TunOnly, "global", settings, None, &sources);
+            cfg.inbounds[0].address.clone().unwrap()
+        };
+        let settings = crate::settings::AppSettings::default();
+        assert_eq!(tun_config(&settings), ["172.19.0.1/30", "fdfe:dcba:9876::1/126"]);
+        let only4 = crate::settings::AppSettings { dns_strategy: "only4".to_string(), ..settings.clone() };
+        assert_eq!(tun_config(&only4), ["172.19.0.1/30"]);
+        let v4_only = crate::settings::AppSettings { ipv6: false, ..settings };
+        assert_eq!(tun_config(&v4_only), ["172.19.0.1/30"]);
+    }
+
+    #[test]
+    fn test_tun_settings_validation() {
+        use crate::settings::AppSettings;
+
+        let mut settings = AppSettings::default();
+        assert!(settings.validate().is_ok());
+
+        settings.tun_address = "10.233.0.1/24".to_string();
+        settings.tun_interface_name = Some("tunnet0".to_string());
+        assert!(settings.validate().is_ok());
+
+        settings.tun_address = "10.233.0.1".to_string();
+        assert!(settings.validate().is_err());
+        settings.tun_address = "10.233.0.300/24".to_string();
+        assert!(settings.validate().is_err());
+
+        settings.tun_address = "10.233.0.1/24".to_string();
+        settings.tun_address_v6 = "fd12:3456::1/64".to_string();
+        assert!(settings.validate().is_ok());
+        settings.tun_address_v6 = "10.233.0.1/24".to_string();
+        assert!(settings.validate().is_err());
+        settings.tun_address_v6 = "fd12:3456::1/127".to_string();
+        assert!(settings.validate().is_err());
+        settings.tun_address_v6 = "fd12:3456::1/64".to_string();
+
+        settings.tun_interface_name = Some("a-very-long-interface".to_string());
+        assert!(settings.validate().is_err());
+        // An unrelated bad value doesn't send the TUN addresses back to the defaults
+        assert!(settings.validate_tun().is_ok());
+    }
 }
@@ -1,3 +1,4 @@
+use crate::error::TunnetError;
 use crate::manager::CoreManager;
 use log::{debug, error, info, warn};
 use std::collections::{HashSet, VecDeque};
@@ -12,6 +13,9 @@ use crate::libbox;
 #[cfg(target_os = "windows")]
 use std::os::windows::process::CommandExt;
 
+/// Bump when the backup bundle layout changes incompatibly
+const BACKUP_SCHEMA_VERSION: u32 = 1;
+
 #[derive(serde::Serialize, Clone, Debug)]
 pub struct ProxyStatus {
     pub is_running: bool,
@@ -26,6 +30,20 @@ pub struct ProxyStatus {
     pub running_settings: Option<crate::settings::AppSettings>,
     pub starting: bool,
 }
+/// Snapshot for the connection health card; see `connection_diagnostics`.
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct ConnectionDiagnostics {
+    pub is_running: bool,
+    pub tun_mode: bool,
+    /// Local mixed port when it is accepting connections
+    pub mixed_port: Option<u16>,
+    pub routing_mode: String,
+    pub node: Option<crate::profile::Node>,
+    pub exit: Option<crate::ip_check::IpLookup>,
+    pub exit_error: Option<String>,
+    pub dns: Option<crate::ip_check::DnsLeakReport>,
+    pub dns_error: Option<String>,
+}
 #[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
 pub struct ProxyNodeStatus {
     pub name: String,
@@ -38,12 +56,80 @@ pub struct ProxyNodeStatus {
     pub delay: Option<u16>,
     pub now: Option<String>, // currently selected node name for selector
 }
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct ImportResult {
+    pub profile_id: String,
+    pub duplicates_removed: usize,
+}
+/// Outcome of `import_links`: the new profile plus how many nodes each scheme contributed.
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct LinkImportResult {
+    pub profile_id: String,
+    pub duplicates_removed: usize,
+    pub protocols: std::collections::BTreeMap<String, usize>,
+}
+/// Payload of the `import-progress` event.
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct ImportProgress {
+    pub stage: String,
+    pub nodes: usize,
+}
+
+/// What was running when the proxy last started; only present on disk while connected,
+/// so finding it at launch means the previous run ended without a clean stop.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct LastSession {
+    pub node_id: Option<String>,
+    pub tun_mode: bool,
+    pub routing_mode: String,
+    pub started_at: u64,
+}
+
+/// Result of `url_test`: time to get a connection through the proxy, and to the first
+/// response byte of the test request (which adds TLS and HTTP with the target).
+/// `total_ms` is the Clash-style delay batch tests record, so it is what `ping` stores.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct LatencyResult {
+    pub connect_ms: u64,
+    pub total_ms: u64,
+}
+
+/// Where an external dashboard can reach the running core's Clash API.
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct ClashApiInfo {
+    pub controller_url: Option<String>,
+    pub helper_controller_url: Option<String>,
+    pub secret: String,
+    pub allow_lan: bool,
+}
+
+/// Result of `update_settings`: the connection-affecting fields that changed and
+/// whether the running proxy was reconfigured to apply them.
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct SettingsUpdate {
+    pub changed: Vec<String>,
+    pub applied: bool,
+}
+
+/// Upper bound for fetching and parsing one subscription.
+const IMPORT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(90);
+
 #[derive(serde::Serialize, Clone, Debug)]
 pub struct LogEvent {
     pub source: String, // "local" or "helper"
     pub message: String,
 }
 
+/// Lifecycle state broadcast to the frontend via the `proxy-state` event.
+#[derive(serde::Serialize, Clone, Debug)]
+#[serde(tag = "state", rename_all = "lowercase")]
+pub enum ProxyState {
+    Starting,
+    Connected,
+    Stopped,
+    Failed { reason: String },
+}
+
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
 pub struct ConnectionMetadata {
     pub network: String,
@@ -96,7 +182,13 @@ pub struct ProxyService<R: Runtime> {
     latest_routing_mode: Mutex<String>,
     clash_api_port: Mutex<Option<u16>>,
     helper_api_port: Mutex<Option<u16>>,
+    /// Random controller secret for this app session, used unless the user pins one
+    session_clash_secret: String,
+    /// Secret written into the running configs; sent with every Clash API request
+    clash_api_secret: Mutex<String>,
     start_lock: tokio::sync::Mutex<()>, // Ensure serialized start operations
+    /// Start/stop requests waiting on `start_lock` skip themselves once superseded
+    start_requests: RequestGenerations,
     internal_client: reqwest::Client,
     active_network_services: std::sync::Arc<std::sync::Mutex<Vec<String>>>,
     local_log_fd: Mutex<Option<i64>>,
@@ -107,6 +199,977 @@ pub struct ProxyService<R: Runtime> {
     last_wake_up_time: std::sync::Arc<std::sync::atomic::AtomicI64>,
     latest_traffic: std::sync::Arc<std::sync::Mutex<(u64, u64)>>,
     latest_logs: std::sync::Arc<std::sync::Mutex<VecDeque<LogEvent>>>,
+    /// History of core log lines for late-opened log panels; unlike `latest_logs`
+    /// it is not drained by polling.
+    recent_logs: std::sync::Arc<std::sync::Mutex<VecDeque<LogEvent>>>,
+    /// Cancels the subscription import in flight, if any
+    import_cancel: Mutex<Option<std::sync::Arc<tokio::sync::Notify>>>,
+    /// Bundled databases were checked this session; later starts skip the check
+    databases_verified: std::sync::atomic::AtomicBool,
+    /// Moved on by `cancel_tests`; batch tests stop once the epoch they started in is over
+    test_epoch: std::sync::Arc<RequestGenerations>,
+    /// Held across the load-modify-save of test results so concurrent tests don't
+    /// overwrite each other's
+    test_results_lock: Mutex<()>,
+}
+
+/// One ticket per connect/disconnect request. Only the newest ticket is current, so a
+/// request still queued on `start_lock` can tell a later click made it obsolete.
+#[derive(Debug, Default)]
+pub struct RequestGenerations(std::sync::atomic::AtomicU64);
+
+impl RequestGenerations {
+    pub fn next(&self) -> u64 {
+        self.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1
+    }
+
+    pub fn is_current(&self, ticket: u64) -> bool {
+        self.0.load(std::sync::atomic::Ordering::SeqCst) == ticket
+    }
+
+    /// The newest ticket, for work that should stop once anyone calls `next`.
+    pub fn current(&self) -> u64 {
+        self.0.load(std::sync::atomic::Ordering::SeqCst)
+    }
+
+    /// Takes a ticket and waits for `lock`. Fails with `Superseded` if another request
+    /// took a ticket while this one was queued.
+    pub async fn acquire<'a>(
+        &self,
+        lock: &'a tokio::sync::Mutex<()>,
+    ) -> Result<tokio::sync::MutexGuard<'a, ()>, TunnetError> {
+        let ticket = self.next();
+        let guard = lock.lock().await;
+        if !self.is_current(ticket) {
+            return Err(TunnetError::Superseded);
+        }
+        Ok(guard)
+    }
+}
+
+/// `latency-test-progress` event, one per tested node. `latency` is `None` on failure.
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct LatencyProgress {
+    pub node_id: String,
+    pub latency: Option<u64>,
+    pub done: usize,
+    pub total: usize,
+}
+
+const RECENT_LOG_LINES: usize = 500;
+
+fn port_available(listen: &str, port: u16) -> bool {
+    std::net::TcpListener::bind((listen, port)).is_ok()
+}
+
+/// First free port after `port`, for suggesting an alternative to the user.
+fn next_free_port(listen: &str, port: u16) -> Option<u16> {
+    (port.saturating_add(1)..=port.saturating_add(100)).find(|p| port_available(listen, *p))
+}
+
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+fn push_recent_log(recent: &std::sync::Mutex<VecDeque<LogEvent>>, event: &LogEvent) {
+    if let Ok(mut logs) = recent.lock() {
+        if logs.len() >= RECENT_LOG_LINES {
+            logs.pop_front();
+        }
+        logs.push_back(event.clone());
+    }
+}
+
+/// Last `tail_lines` lines of a log file, oldest first; a missing file reads as empty.
+fn read_log_tail(path: &std::path::Path, tail_lines: usize) -> Result<Vec<String>, TunnetError> {
+    let content = match std::fs::read(path) {
+        Ok(c) => c,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    // The helper and the core write whatever bytes they get, so don't fail on bad UTF-8
+    let content = String::from_utf8_lossy(&content);
+    let lines: Vec<&str> = content.lines().collect();
+    let start = lines.len().saturating_sub(tail_lines);
+    Ok(lines[start..].iter().map(|l| l.to_string()).collect())
+}
+
+/// Everything `build_config` would otherwise read from disk or app state.
+pub struct ConfigSources<'a> {
+    pub profiles: &'a [crate::profile::Profile],
+    pub groups: &'a [crate::profile::Group],
+    pub rules: &'a [crate::profile::Rule],
+    /// App data directory, home of the cache files and the controller UI
+    pub data_dir: &'a std::path::Path,
+    /// `.srs` files on disk keyed by rule-set tag
+    pub local_rule_sets: std::collections::HashMap<String, std::path::PathBuf>,
+    pub core_log_path: std::path::PathBuf,
+    /// Controller secret used unless the settings pin one
+    pub session_clash_secret: &'a str,
+}
+
+/// Directory the privileged helper reads staged databases from. The helper runs as
+/// another user (root, or SYSTEM on Windows with its own temp dir), so this must be
+/// a location both sides can reach rather than the app's private data directory.
+/// On Unix the directory is per user and private (0700); the helper reads it as root.
+#[cfg(windows)]
+fn staging_dir() -> std::path::PathBuf {
+    std::env::var_os("ProgramData")
+        .map(std::path::PathBuf::from)
+        .unwrap_or_else(|| std::path::PathBuf::from(r"C:\ProgramData"))
+        .join("Tunnet")
+        .join("staging")
+}
+
+#[cfg(not(windows))]
+fn staging_dir() -> std::path::PathBuf {
+    let uid = unsafe { libc::getuid() };
+    std::env::temp_dir().join(format!("tunnet-staging-{}", uid))
+}
+
+/// Create the staging directory, refusing one another account created first (it could
+/// swap the staged rule sets the root helper loads), then drop files staged earlier so
+/// removed rule sets don't linger.
+fn prepare_staging_dir(staging: &std::path::Path) -> Result<(), String> {
+    std::fs::create_dir_all(staging)
+        .map_err(|e| format!("Failed to create staging dir {:?}: {}", staging, e))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{MetadataExt, PermissionsExt};
+        let meta = std::fs::symlink_metadata(staging)
+            .map_err(|e| format!("Failed to inspect staging dir {:?}: {}", staging, e))?;
+        if !meta.is_dir() || meta.uid() != unsafe { libc::getuid() } {
+            return Err(format!(
+                "Staging dir {:?} is not a directory owned by the current user",
+                staging
+            ));
+        }
+        std::fs::set_permissions(staging, std::fs::Permissions::from_mode(0o700))
+            .map_err(|e| format!("Failed to restrict staging dir {:?}: {}", staging, e))?;
+    }
+    if let Ok(entries) = std::fs::read_dir(staging) {
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if name.ends_with(".srs") || name == "cache.db" {
+                let _ = std::fs::remove_file(entry.path());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// `.srs` files in `dirs` keyed by tag; earlier directories win.
+fn find_rule_set_files(
+    dirs: &[&std::path::Path],
+) -> std::collections::HashMap<String, std::path::PathBuf> {
+    let mut found = std::collections::HashMap::new();
+    for dir in dirs {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if let Some(tag) = name.strip_suffix(".srs") {
+                found.entry(tag.to_string()).or_insert_with(|| entry.path());
+            }
+        }
+    }
+    found
+}
+
+/// Build the sing-box config for one instance. Pure: all inputs come from the
+/// arguments, so routing behavior can be unit-tested without an app handle.
+pub fn build_config(
+    node_opt: Option<&crate::profile::Node>,
+    mode: crate::config::ConfigMode,
+    routing_mode: &str,
+    settings: &crate::settings::AppSettings,
+    clash_api_port: Option<u16>,
+    sources: &ConfigSources,
+) -> crate::config::SingBoxConfig {
+    build_config_with_origins(node_opt, mode, routing_mode, settings, clash_api_port, sources).0
+}
+
+/// `build_config`, also reporting which user rule each route rule came from so
+/// `simulate_route` can walk exactly the rules the core will see.
+pub fn build_config_with_origins(
+    node_opt: Option<&crate::profile::Node>,
+    mode: crate::config::ConfigMode,
+    routing_mode: &str,
+    settings: &crate::settings::AppSettings,
+    clash_api_port: Option<u16>,
+    sources: &ConfigSources,
+) -> (crate::config::SingBoxConfig, crate::profile::RuleOrigins) {
+    let tun_mode =
+        mode == crate::config::ConfigMode::TunOnly || mode == crate::config::ConfigMode::Combined;
+    let app_local_data = sources.data_dir;
+    let mut cfg = crate::config::SingBoxConfig::new(
+        clash_api_port,
+        mode,
+        &settings.dns_servers,
+        &settings.dns_strategy,
+        "proxy",
+    );
+
+    // Synchronize log level with app settings
+    if let Some(log) = &mut cfg.log {
+        let level = settings.log_level.to_lowercase();
+        info!("Configuring SingBox log level: {}", level);
+        if level == "disabled" {
+            log.disabled = Some(true);
+            log.level = None;
+        } else {
+            log.level = Some(level);
+        }
+
+        // The helper's output is already captured into helper.log, so only the local core
+        // is redirected to a file.
+        if settings.core_log_file && mode != crate::config::ConfigMode::TunOnly {
+            log.output = Some(sources.core_log_path.to_string_lossy().to_string());
+        }
+    }
+
+    // Synchronize DNS strategy with app settings
+    if let Some(dns) = &mut cfg.dns {
+        dns.strategy = Some(crate::config::effective_dns_strategy(
+            &settings.dns_strategy,
+            settings.ipv6,
+        ));
+
+        // Enable Split DNS for CN domains in Rule mode to improve domestic access speed
+        if routing_mode != "global" && routing_mode != "direct" {
+            dns.rules.insert(
+                0,
+                crate::config::DnsRule {
+                    rule_set: Some(vec!["geosite-cn".to_string()]),
+                    server: Some("local".to_string()),
+                    inbound: None,
+                    outbound: None,
+                    domain: None,
+                    domain_suffix: None,
+                    domain_keyword: None,
+                    ip_cidr: None,
+                },
+            );
+        }
+    }
+
+    if tun_mode {
+        // CRITICAL FIX: To prevent IPv6 leak, we must enable IPv6 address for TUN
+        // even if dns_strategy is "prefer_ipv4". Only the IPv6 toggle or "only4" turn it off.
+        let ipv6_enabled = settings.ipv6 && settings.dns_strategy != "only4";
+        // Force a safe MTU for maximum compatibility, especially with DoH/CDN nodes
+        let mut mtu = settings.tun_mtu;
+        if mtu > 1500 || mtu == 0 {
+            mtu = 1500;
+        }
+        // Settings are validated on save, but hand-edited files can still slip through
+        let (tun_address, tun_address_v6) = if settings.validate_tun().is_ok() {
+            (
+                settings.tun_address.clone(),
+                settings.tun_address_v6.clone(),
+            )
+        } else {
+            warn!("Invalid TUN settings, falling back to the default addresses");
+            let defaults = crate::settings::AppSettings::default();
+            (defaults.tun_address, defaults.tun_address_v6)
+        };
+        let bypass_cidrs: Vec<String> = settings
+            .tun_bypass_cidrs
+            .iter()
+            .filter(|cidr| match crate::profile::validate_cidr(cidr) {
+                Ok(()) => true,
+                Err(e) => {
+                    warn!("Skipping TUN bypass entry: {}", e);
+                    false
+                }
+            })
+            .cloned()
+            .collect();
+        cfg = cfg.with_tun_inbound(
+            mtu,
+            settings.tun_stack.clone(),
+            settings.strict_route,
+            &tun_address,
+            ipv6_enabled.then_some(tun_address_v6.as_str()),
+            settings.tun_interface_name.clone(),
+            bypass_cidrs,
+        );
+        // sing-box only implements auto_redirect on Linux, and pairs it with the system stack
+        if cfg!(target_os = "linux") && settings.tun_auto_redirect && settings.tun_stack == "system"
+        {
+            if let Some(inbound) = cfg.inbounds.last_mut() {
+                inbound.auto_redirect = Some(true);
+            }
+        }
+    }
+
+    let listen = if settings.allow_lan {
+        "0.0.0.0"
+    } else {
+        "127.0.0.1"
+    };
+
+    if mode != crate::config::ConfigMode::TunOnly {
+        cfg = cfg.with_mixed_inbound(settings.mixed_port, "mixed-in", false);
+        if let Some(inbound) = cfg.inbounds.last_mut() {
+            inbound.listen = Some(listen.to_string());
+            inbound.reuse_addr = Some(true);
+        }
+    }
+
+    // Both inbounds are kernel features sing-box only builds on Linux. TPROXY needs
+    // CAP_NET_ADMIN, so it only joins the helper's TUN instance; REDIRECT works
+    // unprivileged and sits next to mixed-in.
+    if cfg!(target_os = "linux") {
+        let port = settings.transparent_proxy_port;
+        let privileged = mode == crate::config::ConfigMode::TunOnly;
+        let inbound_count = cfg.inbounds.len();
+        cfg = match settings.transparent_proxy {
+            crate::settings::TransparentProxyMode::Tproxy if privileged => {
+                cfg.with_tproxy_inbound(port)
+            }
+            crate::settings::TransparentProxyMode::Redirect if !privileged => {
+                cfg.with_redirect_inbound(port)
+            }
+            _ => cfg,
+        };
+        if cfg.inbounds.len() > inbound_count {
+            let tag = cfg.inbounds.last().map(|i| i.tag.clone());
+            // Its lookups should use the same servers as the mixed inbound's
+            let dns_rule = cfg.dns.as_mut().and_then(|dns| {
+                dns.rules.iter_mut().find(|r| {
+                    r.inbound
+                        .as_ref()
+                        .is_some_and(|i| i.iter().any(|t| t == "mixed-in"))
+                })
+            });
+            if let (Some(rule), Some(tag)) = (dns_rule, tag) {
+                rule.inbound.get_or_insert_with(Vec::new).push(tag);
+            }
+        }
+    }
+
+    // 1. Add required system outbounds and database paths
+    cfg = cfg
+        .with_direct_tag(
+            "direct",
+            settings
+                .direct_bind_interface
+                .clone()
+                .filter(|i| !i.is_empty()),
+            settings
+                .direct_bind_address
+                .clone()
+                .filter(|a| !a.is_empty()),
+        )
+        .with_block();
+
+    if let Some(route) = &mut cfg.route {
+        let geoip_path = sources.local_rule_sets.get("geoip-cn");
+        let geosite_path = sources.local_rule_sets.get("geosite-cn");
+        let geosite_ads_path = sources.local_rule_sets.get("geosite-category-ads-all");
+
+        route.rule_set = Some(vec![
+            if let Some(path) = geoip_path {
+                crate::config::RuleSet {
+                    rule_set_type: "local".to_string(),
+                    tag: "geoip-cn".to_string(),
+                    format: "binary".to_string(),
+                    path: Some(path.to_string_lossy().to_string()),
+                    url: None,
+                    download_detour: None,
+                    update_interval: None,
+                }
+            } else {
+                crate::config::RuleSet {
+                    rule_set_type: "remote".to_string(),
+                    tag: "geoip-cn".to_string(),
+                    format: "binary".to_string(),
+                    path: None,
+                    url: Some("https://raw.githubusercontent.com/SagerNet/sing-geoip/rule-set/geoip-cn.srs".to_string()),
+                    download_detour: Some("direct".to_string()),
+                    update_interval: Some("1d".to_string()),
+                }
+            },
+            if let Some(path) = geosite_path {
+                crate::config::RuleSet {
+                    rule_set_type: "local".to_string(),
+                    tag: "geosite-cn".to_string(),
+                    format: "binary".to_string(),
+                    path: Some(path.to_string_lossy().to_string()),
+                    url: None,
+                    download_detour: None,
+                    update_interval: None,
+                }
+            } else {
+                crate::config::RuleSet {
+                    rule_set_type: "remote".to_string(),
+                    tag: "geosite-cn".to_string(),
+                    format: "binary".to_string(),
+                    path: None,
+                    url: Some("https://raw.githubusercontent.com/SagerNet/sing-geosite/rule-set/geosite-cn.srs".to_string()),
+                    download_detour: Some("direct".to_string()),
+                    update_interval: Some("1d".to_string()),
+                }
+            },
+            if let Some(path) = geosite_ads_path {
+                crate::config::RuleSet {
+                    rule_set_type: "local".to_string(),
+                    tag: "geosite-ads".to_string(),
+                    format: "binary".to_string(),
+                    path: Some(path.to_string_lossy().to_string()),
+                    url: None,
+                    download_detour: None,
+                    update_interval: None,
+                }
+            } else {
+                crate::config::RuleSet {
+                    rule_set_type: "remote".to_string(),
+                    tag: "geosite-ads".to_string(),
+                    format: "binary".to_string(),
+                    path: None,
+                    url: Some("https://raw.githubusercontent.com/SagerNet/sing-geosite/rule-set/geosite-category-ads-all.srs".to_string()),
+                    download_detour: Some("direct".to_string()),
+                    update_interval: Some("1d".to_string()),
+                }
+            },
+        ]);
+    }
+
+    // 2. Load Resources (Profiles/Groups)
+    let profiles = sources.profiles;
+    let groups = sources.groups;
+
+    // Track valid outbound tags to prevent "dependency not found" errors
+    let mut valid_tags = HashSet::new();
+    valid_tags.insert("direct".to_string());
+    valid_tags.insert("block".to_string());
+
+    // 3. Add ALL Nodes as Outbounds
+    // We iterate all profiles and their nodes
+    for profile in profiles {
+        for node in &profile.nodes {
+            let tag = node.id.clone(); // Use UUID as tag
+            if outbound_supported(node) {
+                let outbound = node_to_outbound(node, settings);
+                cfg.outbounds.push(outbound);
+                valid_tags.insert(tag);
+            }
+        }
+    }
+
+    // 4. Add Group Outbounds
+    for group in groups {
+        let mut member_tags = Vec::new();
+
+        match &group.source {
+            crate::profile::GroupSource::Static { node_ids } => {
+                // Filter valid nodes
+                for pid in node_ids {
+                    if valid_tags.contains(pid) {
+                        member_tags.push(pid.clone());
+                    } else {
+                        debug!(
+                            "Skipping invalid node dependency '{}' in group '{}'",
+                            pid, group.id
+                        );
+                    }
+                }
+            }
+            crate::profile::GroupSource::Filter { criteria } => {
+                // Logic: Iterate all nodes, check match
+                for profile in profiles {
+                    for node in &profile.nodes {
+                        if criteria.matches(node) {
+                            member_tags.push(node.id.clone());
+                        }
+                    }
+                }
+            }
+        }
+
+        // If group is empty, we must handle it. Singbox fails with empty selector?
+        // Let's add 'block' if empty to prevent crash
+        // Prevent crash on empty groups: if no members, fallback to direct
+        if member_tags.is_empty() {
+            member_tags.push("direct".to_string());
+        }
+
+        match group.group_type {
+            crate::profile::GroupType::Selector => {
+                // move selected to front
+                let mut tags = member_tags.clone();
+                if let Some(selected) = &group.selected {
+                    if let Some(pos) = tags.iter().position(|x| x == selected) {
+                        let val = tags.remove(pos);
+                        tags.insert(0, val);
+                    }
+                }
+                cfg = cfg.with_selector_outbound(&group.id, tags);
+            }
+            crate::profile::GroupType::UrlTest {
+                interval,
+                tolerance,
+            } => {
+                cfg = cfg.with_urltest_outbound(
+                    &group.id,
+                    member_tags,
+                    Some(settings.latency_test_url.clone()),
+                    Some(format!("{}s", interval)),
+                    Some(tolerance as u16),
+                );
+            }
+        }
+        valid_tags.insert(group.id.clone());
+    }
+
+    // 5. Add MAIN 'proxy' outbound
+    // This is what the dashboard "Select Server" controls.
+    // For backward compatibility and immediate effect, 'proxy' tag should point to the selected node.
+    // We create a Selector `proxy` that contains [selected_node_id].
+    // This acts as an alias.
+
+    let mut proxy_target = "direct".to_string(); // Fallback
+    if let Some(node) = &node_opt {
+        // Verify this node ID exists in our generated outbounds (it should)
+        // But 'node_opt' might be a standalone object if not from profile?
+        // Usually it's from the list.
+        proxy_target = node.id.clone();
+
+        // Check if we already added a vmess/etc outbound for this ID.
+        if !valid_tags.contains(&proxy_target) {
+            info!("Manual node addition safety net for: {}", node.name);
+            // It might be a temp node? Add it manually (legacy behavior fallback)
+            if outbound_supported(node) {
+                // Use the helper to add node with the custom tag pointing to actual node
+                // But here tag is 'proxy_target' which is node.id
+                let mut outbound = node_to_outbound(node, settings);
+                outbound.tag = proxy_target.clone();
+                cfg.outbounds.push(outbound);
+                valid_tags.insert(proxy_target.clone());
+            } else {
+                proxy_target = "direct".to_string();
+            }
+        }
+    }
+
+    // Define 'proxy' as a Selector wrapping the target, or just direct alias?
+    // Singbox doesn't have "Alias".
+    // We use a Selector with 1 item.
+    // This allows 'proxy' to be used in rules.
+    cfg = cfg.with_selector_outbound("proxy", vec![proxy_target]);
+    valid_tags.insert("proxy".to_string());
+
+    // Apply Rules and Routing Mode. Each rule is paired with the id of the user rule it came from.
+    let mut final_rules: Vec<(Option<String>, crate::config::RouteRule)> = Vec::new();
+    let mut final_rule_id = None;
+
+    // 1. DNS Hijack (ABSOLUTE PRIORITY)
+    if settings.dns_hijack {
+        if let Some(route) = &cfg.route {
+            if let Some(dns_rule) = route
+                .rules
+                .iter()
+                .find(|r| r.action == Some("hijack-dns".to_string()))
+            {
+                final_rules.push((None, dns_rule.clone()));
+            }
+        }
+    }
+
+    // 2. Sniffing Rule (MUST follow Hijack so port 53 is caught first)
+    if tun_mode {
+        final_rules.push((
+            None,
+            crate::config::RouteRule {
+                inbound: Some(vec!["tun-in".to_string()]),
+                action: Some("sniff".to_string()),
+                ..Default::default()
+            },
+        ));
+    }
+
+    // (Removed early IPv6 reject rule to allow user rules and global proxy to take precedence)
+
+    let mut default_policy = "proxy".to_string(); // Default fallback
+
+    match routing_mode {
+        "global" => {
+            default_policy = "proxy".to_string();
+            // In Global mode, also make DNS go through proxy for safety
+            if let Some(dns) = &mut cfg.dns {
+                for server in &mut dns.servers {
+                    if server.tag == "google" {
+                        server.detour = Some("proxy".to_string());
+                    }
+                }
+            }
+        }
+        "direct" => {
+            default_policy = "direct".to_string();
+            // In Direct mode, also make DNS direct
+            if let Some(dns) = &mut cfg.dns {
+                for server in &mut dns.servers {
+                    if server.tag == "google" {
+                        server.detour = Some("direct".to_string());
+                    }
+                }
+            }
+        }
+        _ => {
+            // "rule" mode
+            let user_rules = sources.rules;
+            info!(
+                "Loaded {} user rules for config generation",
+                user_rules.len()
+            );
+            for rule in user_rules {
+                if !rule.enabled {
+                    continue;
+                }
+
+                if rule.rule_type == "FINAL" {
+                    let mut policy = match rule.policy.as_str() {
+                        "PROXY" => "proxy".to_string(),
+                        "DIRECT" => "direct".to_string(),
+                        "REJECT" => "reject".to_string(),
+                        "REJECT_DROP" => "reject-drop".to_string(),
+                        _ => rule.policy.clone(), // Likely a Group ID
+                    };
+                    // Validation
+                    if policy != "reject"
+                        && policy != "reject-drop"
+                        && !valid_tags.contains(&policy)
+                    {
+                        warn!("Invalid FINAL policy '{}', falling back to 'proxy'", policy);
+                        policy = "proxy".to_string();
+                    }
+                    default_policy = policy;
+                    final_rule_id = Some(rule.id.clone());
+                    continue;
+                }
+
+                let (mut outbound_tag, action, method) = match rule.policy.as_str() {
+                    "PROXY" => (Some("proxy".to_string()), None, None),
+                    "DIRECT" => (Some("direct".to_string()), None, None),
+                    "REJECT" => (None, Some("reject".to_string()), None),
+                    // Silent drop: no RST/ICMP, the client just times out
+                    "REJECT_DROP" => (None, Some("reject".to_string()), Some("drop".to_string())),
+                    _ => (Some(rule.policy.clone()), None, None), // Assume it's a Group ID or Valid Tag
+                };
+
+                // Validation
+                if let Some(ref tag) = outbound_tag {
+                    if !valid_tags.contains(tag) {
+                        warn!(
+                            "Invalid policy '{}' in rule '{}', falling back to 'proxy'",
+                            tag, rule.id
+                        );
+                        outbound_tag = Some("proxy".to_string());
+                    }
+                }
+
+                let mut route_rule = crate::config::RouteRule {
+                    outbound: outbound_tag,
+                    action,
+                    method,
+                    ..Default::default()
+                };
+
+                match rule.rule_type.as_str() {
+                    "DOMAIN" => {
+                        if rule.value.starts_with("geosite:") {
+                            let val = rule.value.replace("geosite:", "");
+                            route_rule.rule_set = Some(vec![val]);
+                        } else {
+                            route_rule.domain = Some(vec![rule.value.clone()]);
+                        }
+                    }
+                    "DOMAIN_SUFFIX" => {
+                        route_rule.domain_suffix = Some(vec![rule.value.clone()]);
+                    }
+                    "DOMAIN_KEYWORD" => {
+                        route_rule.domain_keyword = Some(vec![rule.value.clone()]);
+                    }
+                    "IP_CIDR" => {
+                        route_rule.ip_cidr = Some(vec![rule.value.clone()]);
+                    }
+                    "GEOIP" => {
+                        let val = rule.value.replace("geoip:", "");
+                        route_rule.rule_set = Some(vec![val]);
+                    }
+                    "IP_IS_PRIVATE" => {
+                        route_rule.ip_is_private = Some(true);
+                    }
+                    "PORT" | "PORT_RANGE" => match crate::profile::parse_port_spec(&rule.value) {
+                        Ok((ports, ranges)) => {
+                            route_rule.port = (!ports.is_empty()).then_some(ports);
+                            route_rule.port_range = (!ranges.is_empty()).then_some(ranges);
+                        }
+                        Err(e) => {
+                            warn!("Skipping invalid port rule '{}': {}", rule.id, e);
+                            continue;
+                        }
+                    },
+                    // Only effective when sing-box can see the owning process (TUN or local system proxy)
+                    "PROCESS_NAME" => {
+                        route_rule.process_name = Some(vec![rule.value.clone()]);
+                    }
+                    "PROCESS_PATH" => {
+                        route_rule.process_path = Some(vec![rule.value.clone()]);
+                    }
+                    _ => {}
+                }
+                if let Err(e) = rule.apply_constraints(&mut route_rule) {
+                    warn!("Skipping rule '{}' with invalid conditions: {}", rule.id, e);
+                    continue;
+                }
+
+                final_rules.push((Some(rule.id.clone()), route_rule));
+            }
+        }
+    }
+    // Make sure every rule_set referenced by a user rule is declared, preferring a
+    // cached .srs file and falling back to the remote rule-set. Rules pointing at a
+    // tag we cannot resolve are dropped instead of failing the whole config.
+    if let Some(route) = &mut cfg.route {
+        let rule_sets = route.rule_set.get_or_insert_with(Vec::new);
+        final_rules.retain(|(_, r)| {
+            let Some(tags) = &r.rule_set else { return true };
+            for tag in tags {
+                if rule_sets.iter().any(|rs| &rs.tag == tag) {
+                    continue;
+                }
+                if let Some(local) = sources.local_rule_sets.get(tag) {
+                    rule_sets.push(crate::config::RuleSet {
+                        rule_set_type: "local".to_string(),
+                        tag: tag.clone(),
+                        format: "binary".to_string(),
+                        path: Some(local.to_string_lossy().to_string()),
+                        ..Default::default()
+                    });
+                } else if let Some((url, _)) = crate::config::remote_rule_set_urls(tag) {
+                    rule_sets.push(crate::config::RuleSet {
+                        rule_set_type: "remote".to_string(),
+                        tag: tag.clone(),
+                        format: "binary".to_string(),
+                        url: Some(url),
+                        download_detour: Some("direct".to_string()),
+                        update_interval: Some("1d".to_string()),
+                        ..Default::default()
+                    });
+                } else {
+                    warn!("Dropping rule referencing unknown rule set '{}'", tag);
+                    return false;
+                }
+            }
+            true
+        });
+    }
+
+    // IPv6 Fallback: Only reject IPv6 traffic if IPv6 is disabled or the user chose "Only IPv4".
+    // For "Prefer IPv4", we allow it to fall through to the proxy/direct fallback,
+    // which now has 'domain_strategy: prefer_ipv4' to handle it gracefully.
+    if !settings.ipv6 || settings.dns_strategy == "only4" {
+        final_rules.push((
+            None,
+            crate::config::RouteRule {
+                ip_cidr: Some(vec!["::/0".to_string()]),
+                action: Some("reject".to_string()),
+                ..Default::default()
+            },
+        ));
+    }
+
+    // 3. Route whatever no rule matched
+    // Validate ultimate default_policy too (just in case no rule set it or it was invalid)
+    if default_policy != "reject"
+        && default_policy != "reject-drop"
+        && !valid_tags.contains(&default_policy)
+    {
+        default_policy = "proxy".to_string();
+    }
+
+    // `final` only names an outbound, so rejecting by default still takes a catch-all rule
+    let final_outbound = match default_policy.as_str() {
+        "reject" | "reject-drop" => {
+            final_rules.push((
+                final_rule_id.clone(),
+                crate::config::RouteRule {
+                    action: Some("reject".to_string()),
+                    method: (default_policy == "reject-drop").then(|| "drop".to_string()),
+                    ..Default::default()
+                },
+            ));
+            None
+        }
+        _ => Some(default_policy.clone()),
+    };
+
+    let (rule_origins, final_rules): (Vec<_>, Vec<_>) = final_rules.into_iter().unzip();
+    let mut origins = crate::profile::RuleOrigins {
+        rules: rule_origins,
+        final_rule: final_rule_id,
+    };
+    if let Some(route) = &mut cfg.route {
+        route.rules = final_rules;
+        route.final_outbound = final_outbound;
+        let rule_count = route.rules.len();
+        info!(
+            "Config generated: rules={}, mode={}, default_policy={}",
+            rule_count, routing_mode, default_policy
+        );
+        // Log DNS detour if exists
+        if let Some(dns) = &cfg.dns {
+            if let Some(google_server) = dns.servers.iter().find(|s| s.tag == "google") {
+                info!("DNS google detour: {:?}", google_server.detour);
+            }
+        }
+    }
+    // 6. Set Cache File to avoid writing to src-tauri in dev
+    let cache_name = if mode == crate::config::ConfigMode::TunOnly {
+        "cache_tun.db"
+    } else {
+        "cache.db"
+    };
+    let clash_api_config = if let Some(port) = clash_api_port {
+        let secret = settings
+            .clash_api_secret
+            .clone()
+            .filter(|s| !s.is_empty())
+            .unwrap_or_else(|| sources.session_clash_secret.to_string());
+        Some(crate::config::ClashApiConfig {
+            external_controller: crate::config::clash_api_controller(
+                port,
+                settings.clash_api_allow_lan,
+            ),
+            external_ui: Some(app_local_data.join("ui").to_string_lossy().to_string()),
+            secret: Some(secret),
+        })
+    } else {
+        cfg.experimental.and_then(|e| e.clash_api) // Preserve clash_api if already set and no new port provided
+    };
+
+    cfg.experimental = Some(crate::config::ExperimentalConfig {
+        cache_file: Some(crate::config::CacheFileConfig {
+            enabled: true,
+            path: app_local_data
+                .join(cache_name)
+                .to_string_lossy()
+                .to_string(),
+        }),
+        clash_api: clash_api_config,
+    });
+
+    // --- Final Stage: Robust Proxy Bypass (Routing Loop Prevention) ---
+    // Scan ALL outbounds to find their server IPs and ensure they are direct-routed.
+    // This is done last to catch all nodes across all profiles/groups/selectors.
+    let mut all_bypass_ips = Vec::new();
+    for outbound in &cfg.outbounds {
+        if let Some(server) = &outbound.server {
+            // Heuristic: if it's an IP address, we must bypass it.
+            // If it's a domain, sing-box's auto_detect_interface handles it better than a raw route.
+            if server.parse::<std::net::IpAddr>().is_ok() {
+                all_bypass_ips.push(server.clone());
+            }
+        }
+    }
+
+    if !all_bypass_ips.is_empty() {
+        if let Some(route) = &mut cfg.route {
+            info!(
+                "Injecting {} proxy server IP bypass rules",
+                all_bypass_ips.len()
+            );
+            route.rules.insert(
+                0,
+                crate::config::RouteRule {
+                    ip_cidr: Some(all_bypass_ips),
+                    outbound: Some("direct".to_string()),
+                    ..Default::default()
+                },
+            );
+            origins.rules.insert(0, None);
+        }
+    }
+    // ------------------------------------------------------------------
+
+    (cfg, origins)
+}
+
+/// Whether `node_to_outbound` builds a real outbound for `node` rather than a direct
+/// stand-in. Live configs and latency tests both go by this, so a node that tests fine
+/// is also one that can be selected.
+pub fn outbound_supported(node: &crate::profile::Node) -> bool {
+    crate::config::NODE_PROTOCOLS.contains(&node.protocol.as_str())
+        || node.protocol == crate::profile::RAW_OUTBOUND_PROTOCOL
+}
+
+/// How proxy outbounds resolve their server's domain under the DNS settings.
+fn outbound_domain_strategy(settings: &crate::settings::AppSettings) -> Option<String> {
+    match settings.dns_strategy.as_str() {
+        _ if !settings.ipv6 => Some("ipv4_only".to_string()),
+        "ipv4" | "only4" => Some("prefer_ipv4".to_string()),
+        "ipv6" | "only6" => Some("prefer_ipv6".to_string()),
+        _ => None,
+    }
+}
+
+/// sing-box outbound for `node`, tagged with its id. The live config, latency and
+/// location probes and `url_test` all build outbounds here, so they dial identically.
+pub fn node_to_outbound(
+    node: &crate::profile::Node,
+    settings: &crate::settings::AppSettings,
+) -> crate::config::Outbound {
+    if node.protocol == crate::profile::RAW_OUTBOUND_PROTOCOL {
+        // Pasted by the user and emitted as-is; an unusable one blocks rather than going direct
+        return node.raw_outbound().unwrap_or_else(|e| {
+            warn!("Node '{}': {}", node.name, e);
+            crate::config::Outbound {
+                outbound_type: "block".to_string(),
+                tag: node.id.clone(),
+                ..Default::default()
+            }
+        });
+    }
+
+    let mut cfg = crate::config::SingBoxConfig::new(None, crate::config::ConfigMode::Combined, &settings.dns_servers, &settings.dns_strategy, "proxy")
+        .with_node_outbound(node, &node.id)
+        .with_dial_options(settings.connect_timeout_secs, settings.tcp_fast_open);
+    let mut outbound = cfg.outbounds.pop().unwrap();
+    if outbound.outbound_type != "direct" {
+        outbound.domain_strategy = outbound_domain_strategy(settings);
+    }
+    if node.brutal
+        && matches!(outbound.outbound_type.as_str(), "vmess" | "vless" | "trojan" | "shadowsocks")
+    {
+        if node.flow.as_deref().is_some_and(|f| !f.is_empty()) {
+            warn!("Node '{}': TCP Brutal needs multiplex, which does not work with flow", node.name);
+        } else {
+            outbound.multiplex = crate::config::brutal_multiplex(node.up.as_deref(), node.down.as_deref());
+            if outbound.multiplex.is_none() {
+                warn!("Node '{}': TCP Brutal needs both upload and download bandwidth", node.name);
+            }
+        }
+    }
+    // REALITY replaces the TLS handshake, so ECH does not apply there
+    if let Some(tls) = outbound.tls.as_mut().filter(|t| t.reality.is_none()) {
+        match crate::config::ech_config(node.ech || settings.ech_enabled, node.ech_config.as_deref()) {
+            Ok(ech) => tls.ech = ech,
+            Err(e) => warn!("Ignoring ECH config of node '{}': {}", node.name, e),
+        }
+    }
+    match outbound.clone().with_extra(&node.extra) {
+        Ok(merged) => merged,
+        Err(e) => {
+            warn!("Ignoring invalid extra options on node '{}': {}", node.name, e);
+            outbound
+        }
+    }
 }
 
 impl<R: Runtime> ProxyService<R> {
@@ -119,6 +1182,7 @@ impl<R: Runtime> ProxyService<R> {
             .unwrap_or_default();
 
         let initial_tun_mode = manager.load_settings().map(|s| s.tun_mode).unwrap_or(false);
+        let session_clash_secret = uuid::Uuid::new_v4().simple().to_string();
 
         Self {
             app: app.clone(),
@@ -128,7 +1192,10 @@ impl<R: Runtime> ProxyService<R> {
             latest_routing_mode: Mutex::new("rule".to_string()),
             clash_api_port: Mutex::new(None),
             helper_api_port: Mutex::new(None),
+            clash_api_secret: Mutex::new(session_clash_secret.clone()),
+            session_clash_secret,
             start_lock: tokio::sync::Mutex::new(()),
+            start_requests: RequestGenerations::default(),
             manager,
             internal_client,
             active_network_services: std::sync::Arc::new(std::sync::Mutex::new(Vec::new())),
@@ -140,6 +1207,11 @@ impl<R: Runtime> ProxyService<R> {
             last_wake_up_time: std::sync::Arc::new(std::sync::atomic::AtomicI64::new(0)),
             latest_traffic: std::sync::Arc::new(std::sync::Mutex::new((0, 0))),
             latest_logs: std::sync::Arc::new(std::sync::Mutex::new(VecDeque::with_capacity(200))),
+            recent_logs: std::sync::Arc::new(std::sync::Mutex::new(VecDeque::with_capacity(RECENT_LOG_LINES))),
+            import_cancel: Mutex::new(None),
+            databases_verified: std::sync::atomic::AtomicBool::new(false),
+            test_epoch: Default::default(),
+            test_results_lock: Mutex::new(()),
         }
     }
 
@@ -159,6 +1231,14 @@ impl<R: Runtime> ProxyService<R> {
         }
     }
 
+    pub fn get_recent_logs(&self) -> Vec<LogEvent> {
+        if let Ok(logs) = self.recent_logs.lock() {
+            logs.iter().cloned().collect()
+        } else {
+            Vec::new()
+        }
+    }
+
     pub fn trigger_wake_up(&self) {
         let now = std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
@@ -181,6 +1261,36 @@ impl<R: Runtime> ProxyService<R> {
         });
         
         self.warmup_network_cache();
+        self.manager.cleanup_test_temp_files();
+        self.cleanup_orphans();
+    }
+
+    /// Kill `sing-box` processes started from `<app_local_data>/bin`. Older releases ran the
+    /// core as a child process from there; the core now runs in-process (and the TUN
+    /// instance in the helper, stopped above), so any such process survived a crash and
+    /// still holds the mixed port or the TUN interface. Returns the killed pids.
+    pub fn cleanup_orphans(&self) -> Vec<u32> {
+        let Ok(bin_dir) = self.app.path().app_local_data_dir().map(|d| d.join("bin")) else {
+            return vec![];
+        };
+        let mut system = sysinfo::System::new();
+        system.refresh_processes();
+
+        let mut killed = Vec::new();
+        for (pid, process) in system.processes() {
+            let name = process.name().trim_end_matches(".exe");
+            let from_bin_dir = process.exe().is_some_and(|exe| exe.starts_with(&bin_dir));
+            if name != "sing-box" || !from_bin_dir {
+                continue;
+            }
+            if process.kill() {
+                warn!("Killed orphaned sing-box process {}", pid);
+                killed.push(pid.as_u32());
+            } else {
+                warn!("Failed to kill orphaned sing-box process {}", pid);
+            }
+        }
+        killed
     }
 
     pub async fn maybe_auto_connect(&self) {
@@ -198,8 +1308,17 @@ impl<R: Runtime> ProxyService<R> {
             return;
         }
 
+        // After a crash the frontend offers to resume the exact session instead
+        if let Some(session) = self.read_resumable_session() {
+            info!(
+                "Previous session (target={:?}, tun={}, mode={}) ended unexpectedly; waiting for resume.",
+                session.node_id, session.tun_mode, session.routing_mode
+            );
+            return;
+        }
+
         info!("Auto-connect enabled. Checking for active target...");
-        if let Some(target_id) = settings.active_target_id {
+        if let Some(target_id) = settings.active_target_id.clone() {
             // Set starting state
             self.is_starting.store(true, std::sync::atomic::Ordering::SeqCst);
 
@@ -217,17 +1336,44 @@ impl<R: Runtime> ProxyService<R> {
                 }
             };
 
-            let node = nodes.into_iter().find(|n| n.id == target_id);
-            
+            // The target may be a node or a group (groups are passed as pseudo-nodes)
+            let node = nodes.into_iter().find(|n| n.id == target_id).or_else(|| {
+                self.get_groups().ok().and_then(|groups| {
+                    groups.into_iter().find(|g| g.id == target_id).map(|g| crate::profile::Node {
+                        id: g.id,
+                        name: g.name,
+                        protocol: "group".to_string(),
+                        ..Default::default()
+                    })
+                })
+            });
+
+            let node = match node {
+                Some(n) => n,
+                None => {
+                    warn!("Auto-connect target {} no longer exists, skipping.", target_id);
+                    let mut settings = settings;
+                    settings.active_target_id = None;
+                    if let Err(e) = self.manager.save_settings(&settings) {
+                        error!("Failed to clear stale active_target_id: {}", e);
+                    }
+                    self.is_starting.store(false, std::sync::atomic::Ordering::SeqCst);
+                    self.app.emit("proxy-transition", serde_json::json!({ "state": "idle" })).ok();
+                    return;
+                }
+            };
+
             // Wait a bit to ensure system network is ready?
             tokio::time::sleep(tokio::time::Duration::from_millis(1000)).await;
 
             info!("Triggering auto-connect with target_id: {}", target_id);
             let mode = settings.routing_mode.clone().unwrap_or("rule".to_string());
-            if let Err(e) = self.start_proxy(node, settings.tun_mode, mode).await {
-                error!("Auto-connect failed: {}", e);
-            } else {
-                info!("Auto-connect successful.");
+            match self.start_proxy(Some(node), settings.tun_mode, mode).await {
+                Err(TunnetError::Superseded) => {
+                    info!("Auto-connect superseded by a user request.");
+                }
+                Err(e) => error!("Auto-connect failed: {}", e),
+                Ok(()) => info!("Auto-connect successful."),
             }
             
             // Reset starting state
@@ -240,21 +1386,91 @@ impl<R: Runtime> ProxyService<R> {
         }
     }
 
-    pub async fn start_proxy(
+    pub async fn start_proxy(
+        &self,
+        node_opt: Option<crate::profile::Node>,
+        tun_mode: bool,
+        // mode: "global" | "rule" | "direct"
+        routing_mode: String,
+    ) -> Result<(), TunnetError> {
+        info!("start_proxy: acquiring lock...");
+        // A later connect/disconnect is queued behind us; only the last click matters
+        let _lock = self
+            .start_requests
+            .acquire(&self.start_lock)
+            .await
+            .inspect_err(|_| info!("start_proxy: superseded by a newer request, skipping"))?;
+        self.emit_state(ProxyState::Starting);
+
+        let session = LastSession {
+            node_id: node_opt.as_ref().map(|n| n.id.clone()),
+            tun_mode,
+            routing_mode: routing_mode.to_lowercase(),
+            started_at: unix_now(),
+        };
+        let result = self
+            .start_proxy_locked(node_opt, tun_mode, routing_mode)
+            .await;
+        match &result {
+            Ok(_) => {
+                self.write_last_session(&session);
+                self.emit_state(ProxyState::Connected)
+            }
+            Err(e) => {
+                // The previous instance was stopped before the failure, so nothing is left to resume
+                self.clear_last_session();
+                self.is_starting.store(false, std::sync::atomic::Ordering::SeqCst);
+                self.emit_state(ProxyState::Failed { reason: e.to_string() });
+            }
+        }
+        result
+    }
+
+    /// Run the sing-box config check on a generated config file.
+    fn check_config_file(path: &std::path::Path) -> Result<(), TunnetError> {
+        let config_str = std::fs::read_to_string(path)?;
+        let c_config = CString::new(config_str).map_err(|_| "Config holds null bytes")?;
+        let reply = unsafe {
+            let ptr = libbox::LibboxCheck(c_config.as_ptr());
+            if ptr.is_null() {
+                return Err("Core returned no check result".into());
+            }
+            CStr::from_ptr(ptr).to_string_lossy().into_owned()
+        };
+        libbox::parse_call_result(&reply).map(|_| ()).map_err(|e| {
+            error!("Config check failed for {:?}: {}", path, e);
+            TunnetError::ConfigInvalid(e.to_string())
+        })
+    }
+
+    fn emit_state(&self, state: ProxyState) {
+        debug!("Proxy state: {:?}", state);
+        let _ = self.app.emit("proxy-state", state);
+    }
+
+    /// Body of `start_proxy`; the caller must hold `start_lock`.
+    async fn start_proxy_locked(
         &self,
         node_opt: Option<crate::profile::Node>,
         tun_mode: bool,
-        // mode: "global" | "rule" | "direct"
         routing_mode: String,
-    ) -> Result<(), String> {
-        info!("start_proxy: acquiring lock...");
-        let _lock = self.start_lock.lock().await;
+    ) -> Result<(), TunnetError> {
         info!("start_proxy: lock acquired, checking download...");
         // Mark as starting so frontend shows loading state even during restart
         self.is_starting.store(true, std::sync::atomic::Ordering::SeqCst);
 
         info!("start_proxy: download check done, ensuring DBs...");
-        self.manager.ensure_databases().await?;
+        // Both only do real work when files are missing, so run them side by side
+        let databases = async {
+            if !self.databases_verified.load(std::sync::atomic::Ordering::SeqCst) {
+                self.manager.ensure_databases().await?;
+                self.databases_verified
+                    .store(true, std::sync::atomic::Ordering::SeqCst);
+            }
+            Ok::<(), TunnetError>(())
+        };
+        let (databases, ()) = tokio::join!(databases, self.ensure_rule_sets());
+        databases?;
         let core_path = std::path::PathBuf::new();
         let routing_mode = routing_mode.to_lowercase();
         let node_name = node_opt.as_ref().map(|n| n.name.as_str()).unwrap_or("None");
@@ -306,6 +1522,17 @@ impl<R: Runtime> ProxyService<R> {
             }
         }
 
+        // Remember the selected target so auto-connect can restore it after a restart
+        if let Some(node) = &node_opt {
+            if settings.active_target_id.as_deref() != Some(node.id.as_str()) {
+                info!("start_proxy: updating persisted active_target_id to {}", node.id);
+                settings.active_target_id = Some(node.id.clone());
+                if let Err(e) = self.manager.save_settings(&settings) {
+                    error!("Failed to persist active_target_id update: {}", e);
+                }
+            }
+        }
+
         // Synchronize log level
         self.apply_log_level(&settings.log_level);
 
@@ -339,9 +1566,35 @@ impl<R: Runtime> ProxyService<R> {
         // Reduced from 200ms to 50ms for optimization
         tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
 
-        // Allocate a dynamic port for Clash API with retries
-        let mut clash_port = None;
+        // Fail fast when another application holds the mixed port on a cold start; the core
+        // would only report it as a generic startup error. Restarts leave the port to the
+        // core's own retry loop, since our previous listener may still be closing.
+        let listen = if settings.allow_lan { "0.0.0.0" } else { "127.0.0.1" };
+        if !is_running && !tun_mode && !port_available(listen, settings.mixed_port) {
+            let suggested = next_free_port(listen, settings.mixed_port);
+            warn!(
+                "Mixed port {} is in use by another process (suggested: {:?})",
+                settings.mixed_port, suggested
+            );
+            return Err(TunnetError::PortInUse {
+                port: settings.mixed_port,
+                suggested,
+            });
+        }
+
+        // Use the pinned Clash API port if free, otherwise allocate a dynamic one with retries
+        let clash_listen = if settings.clash_api_allow_lan { "0.0.0.0" } else { "127.0.0.1" };
+        let mut clash_port = settings.clash_api_port.filter(|p| {
+            let free = port_available(clash_listen, *p);
+            if !free {
+                warn!("Configured Clash API port {} is in use, allocating a dynamic one", p);
+            }
+            free
+        });
         for _ in 0..3 {
+            if clash_port.is_some() {
+                break;
+            }
             if let Ok(l) = std::net::TcpListener::bind("127.0.0.1:0") {
                 if let Ok(addr) = l.local_addr() {
                     clash_port = Some(addr.port());
@@ -426,6 +1679,19 @@ impl<R: Runtime> ProxyService<R> {
             clash_port,
         )?;
 
+        // Catch config mistakes up front instead of as an opaque startup failure
+        if settings.preflight_check {
+            Self::check_config_file(&config_file_path)?;
+            if tun_mode {
+                Self::check_config_file(&helper_config_path)?;
+            }
+        }
+
+        // A new session starts with an empty history
+        if let Ok(mut logs) = self.recent_logs.lock() {
+            logs.clear();
+        }
+
         // Loop for retrying startup if port is temporarily held (TIME_WAIT race)
         let max_retries = 60;
         let mut last_error = String::new();
@@ -459,6 +1725,7 @@ impl<R: Runtime> ProxyService<R> {
                 let log_running_clone = self.log_running.clone();
                 let last_wake_clone = self.last_wake_up_time.clone();
                 let latest_logs_clone = self.latest_logs.clone();
+                let recent_logs_clone = self.recent_logs.clone();
                 std::thread::spawn(move || {
                     let reader = BufReader::new(reader);
                     // Experiment: Slight delay to prevent IPC flooding during boot/wake bursts
@@ -486,16 +1753,18 @@ impl<R: Runtime> ProxyService<R> {
                                     std::thread::sleep(std::time::Duration::from_millis(100));
                                 } else {
                                     for msg in log_batch.drain(..) {
+                                        let event = LogEvent {
+                                            source: "local".to_string(),
+                                            message: msg,
+                                        };
+                                        push_recent_log(&recent_logs_clone, &event);
                                         // REFACTOR: Instead of pushing via IPC (emit_to), we buffer for polling
                                         if let Ok(mut logs_guard) = latest_logs_clone.lock() {
                                             let logs: &mut VecDeque<LogEvent> = &mut *logs_guard;
                                             if logs.len() >= 200 {
                                                 logs.pop_front();
                                             }
-                                            logs.push_back(LogEvent {
-                                                source: "local".to_string(),
-                                                message: msg,
-                                            });
+                                            logs.push_back(event);
                                         }
                                     }
                                     last_emit = std::time::Instant::now();
@@ -511,13 +1780,14 @@ impl<R: Runtime> ProxyService<R> {
                 let config_str =
                     std::fs::read_to_string(&config_file_path).map_err(|e| e.to_string())?;
                 let c_config = CString::new(config_str).map_err(|_| "Config holds null bytes")?;
+                libbox::check_core_version(&libbox::core_version())?;
 
                 unsafe {
                     let err_ptr = libbox::LibboxStart(c_config.as_ptr(), log_fd);
                     if !err_ptr.is_null() {
                         let err_msg = CStr::from_ptr(err_ptr).to_string_lossy().into_owned();
                         error!("Local LibboxStart failed: {}", err_msg);
-                        return Err(err_msg);
+                        return Err(err_msg.into());
                     }
                 }
                 *self.local_proxy_running.lock().unwrap() = true;
@@ -559,6 +1829,21 @@ impl<R: Runtime> ProxyService<R> {
                         attempt
                     );
                     let client = crate::helper_client::HelperClient::new();
+                    // Fail fast with a clear message instead of burning the request retries
+                    let ready = tauri::async_runtime::spawn_blocking(|| {
+                        crate::helper_client::HelperClient::new()
+                            .wait_until_ready(std::time::Duration::from_secs(3))
+                            .map_err(|e| e.to_string())
+                    })
+                    .await
+                    .unwrap_or_else(|e| Err(e.to_string()));
+                    if let Err(e) = ready {
+                        unsafe {
+                            libbox::LibboxStop();
+                        }
+                        *self.local_proxy_running.lock().unwrap() = false;
+                        return Err(TunnetError::HelperUnreachable(e));
+                    }
                     let helper_config_str =
                         std::fs::read_to_string(&helper_config_path).map_err(|e| e.to_string())?;
 
@@ -579,6 +1864,7 @@ impl<R: Runtime> ProxyService<R> {
                     let log_path_clone = helper_log_path.clone();
                     let log_running_clone = self.log_running.clone();
                     let latest_logs_clone = self.latest_logs.clone();
+                    let recent_logs_clone = self.recent_logs.clone();
                     tauri::async_runtime::spawn(async move {
                         info!("Helper log tailer started for {:?}", log_path_clone);
                         
@@ -618,16 +1904,18 @@ impl<R: Runtime> ProxyService<R> {
                                     Ok(_) => {
                                         let message = line.trim();
                                         if !message.is_empty() {
+                                            let event = LogEvent {
+                                                source: "helper".to_string(),
+                                                message: message.to_string(),
+                                            };
+                                            push_recent_log(&recent_logs_clone, &event);
                                             // REFACTOR: Instead of pushing via IPC (emit_to), we buffer for polling
                                             if let Ok(mut logs_guard) = latest_logs_clone.lock() {
                                                 let logs: &mut VecDeque<LogEvent> = &mut *logs_guard;
                                                 if logs.len() >= 200 {
                                                     logs.pop_front();
                                                 }
-                                                logs.push_back(LogEvent {
-                                                    source: "helper".to_string(),
-                                                    message: message.to_string(),
-                                                });
+                                                logs.push_back(event);
                                             }
                                         }
                                     }
@@ -664,7 +1952,7 @@ impl<R: Runtime> ProxyService<R> {
                             libbox::LibboxStop();
                         }
                         *self.local_proxy_running.lock().unwrap() = false;
-                        return Err(e);
+                        return Err(TunnetError::HelperUnreachable(e));
                     }
                 }
 
@@ -701,14 +1989,31 @@ impl<R: Runtime> ProxyService<R> {
                         settings.mixed_port
                     );
                     self.stop_proxy_internal(false, retain_system_proxy).await;
-                    return Err(format!("Proxy port {} not responding", settings.mixed_port));
+                    return Err(format!("Proxy port {} not responding", settings.mixed_port).into());
                 }
 
                 if let Some(p) = clash_port {
                     if !self.wait_for_port(p, 2000).await {
                         error!("Clash API port {} is not responding.", p);
                         self.stop_proxy_internal(false, retain_system_proxy).await;
-                        return Err(format!("Clash API port {} not responding", p));
+                        return Err(format!("Clash API port {} not responding", p).into());
+                    }
+                }
+
+                // The helper may accept the start request and then exit right away
+                // (e.g. TUN setup failure). Treat that as a failed start.
+                if tun_mode {
+                    let alive = tauri::async_runtime::spawn_blocking(|| {
+                        crate::helper_client::HelperClient::new().check_status().unwrap_or(false)
+                    })
+                    .await
+                    .unwrap_or(false);
+                    if !alive {
+                        error!("TUN instance exited prematurely after startup.");
+                        self.stop_proxy_internal(false, retain_system_proxy).await;
+                        return Err(TunnetError::HelperUnreachable(
+                            "TUN instance exited prematurely, check helper logs".to_string(),
+                        ));
                     }
                 }
 
@@ -725,7 +2030,7 @@ impl<R: Runtime> ProxyService<R> {
                     });
                 }
 
-                Ok(())
+                Ok::<(), TunnetError>(())
             }
             .await;
 
@@ -739,10 +2044,8 @@ impl<R: Runtime> ProxyService<R> {
                     return Ok(());
                 }
                 Err(e) => {
-                    last_error = e.clone();
-                    if e.contains("address already in use")
-                        || e.contains("bind: address already in use")
-                    {
+                    last_error = e.to_string();
+                    if last_error.contains("address already in use") {
                         warn!(
                             "Startup attempt {} failed: {}. Retrying in 500ms...",
                             attempt, e
@@ -757,13 +2060,19 @@ impl<R: Runtime> ProxyService<R> {
         }
 
         self.is_starting.store(false, std::sync::atomic::Ordering::SeqCst);
-        Err(format!(
+        // Only bind failures are retried, so running out of attempts means the port stayed taken
+        error!(
             "Failed to start dual-instance proxy after {} attempts. Last error: {}",
             max_retries, last_error
-        ))
+        );
+        let listen = if settings.allow_lan { "0.0.0.0" } else { "127.0.0.1" };
+        Err(TunnetError::PortInUse {
+            port: settings.mixed_port,
+            suggested: next_free_port(listen, settings.mixed_port),
+        })
     }
 
-    pub async fn get_group_nodes(&self, group_id: &str) -> Result<Vec<ProxyNodeStatus>, String> {
+    pub async fn get_group_nodes(&self, group_id: &str) -> Result<Vec<ProxyNodeStatus>, TunnetError> {
         let _lock = self.start_lock.lock().await;
         if !self.is_proxy_running() {
             // Fallback: Calculate members from config without live status
@@ -781,19 +2090,11 @@ impl<R: Runtime> ProxyService<R> {
 
             let member_ids = match &group.source {
                 crate::profile::GroupSource::Static { node_ids } => node_ids.clone(),
-                crate::profile::GroupSource::Filter { criteria } => {
-                    let keywords = criteria.keywords.as_deref().unwrap_or(&[]);
-                    all_nodes
-                        .iter()
-                        .filter(|n| {
-                            if keywords.is_empty() {
-                                return true;
-                            }
-                            keywords.iter().any(|k| n.name.contains(k))
-                        })
-                        .map(|n| n.id.clone())
-                        .collect()
-                }
+                crate::profile::GroupSource::Filter { criteria } => all_nodes
+                    .iter()
+                    .filter(|n| criteria.matches(n))
+                    .map(|n| n.id.clone())
+                    .collect(),
             };
 
             let status_list = member_ids
@@ -830,6 +2131,7 @@ impl<R: Runtime> ProxyService<R> {
         let all_resp = self
             .internal_client
             .get(&all_url)
+            .bearer_auth(self.clash_secret())
             .send()
             .await
             .map_err(|e| format!("Failed to fetch group nodes from Clash API: {}", e))?
@@ -898,10 +2200,10 @@ impl<R: Runtime> ProxyService<R> {
         Ok(status_list)
     }
 
-    pub async fn select_group_node(&self, group_id: &str, node_name: &str) -> Result<(), String> {
+    pub async fn select_group_node(&self, group_id: &str, node_name: &str) -> Result<(), TunnetError> {
         let _lock = self.start_lock.lock().await;
         if !self.is_proxy_running() {
-            return Err("Proxy is not running. Start proxy to select nodes.".to_string());
+            return Err("Proxy is not running. Start proxy to select nodes.".into());
         }
         let port = self
             .ensure_clash_port()
@@ -918,7 +2220,13 @@ impl<R: Runtime> ProxyService<R> {
                 port,
                 urlencoding::encode(group_id)
             );
-            let resp = self.internal_client.put(&url).json(&payload).send().await;
+            let resp = self
+                .internal_client
+                .put(&url)
+                .bearer_auth(self.clash_secret())
+                .json(&payload)
+                .send()
+                .await;
 
             match resp {
                 Ok(res) if res.status().is_success() => {
@@ -1006,10 +2314,10 @@ impl<R: Runtime> ProxyService<R> {
             tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
         }
 
-        Err(last_err)
+        Err(last_err.into())
     }
 
-    pub async fn get_group_status(&self, group_id: &str) -> Result<String, String> {
+    pub async fn get_group_status(&self, group_id: &str) -> Result<String, TunnetError> {
         let _lock = self.start_lock.lock().await;
         let port = self
             .ensure_clash_port()
@@ -1024,7 +2332,12 @@ impl<R: Runtime> ProxyService<R> {
 
         let mut last_err = String::new();
         for _ in 0..3 {
-            let resp = self.internal_client.get(&url).send().await;
+            let resp = self
+                .internal_client
+                .get(&url)
+                .bearer_auth(self.clash_secret())
+                .send()
+                .await;
             match resp {
                 Ok(res) if res.status().is_success() => {
                     if let Ok(json) = res.json::<serde_json::Value>().await {
@@ -1048,10 +2361,10 @@ impl<R: Runtime> ProxyService<R> {
             tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
         }
 
-        Err(last_err)
+        Err(last_err.into())
     }
 
-    pub fn export_node_link(&self, node_id: String) -> Result<String, String> {
+    pub fn export_node_link(&self, node_id: String) -> Result<String, TunnetError> {
         // 1. Load profiles to find the node
         let profiles = self.manager.load_profiles().map_err(|e| e.to_string())?;
         
@@ -1061,10 +2374,90 @@ impl<R: Runtime> ProxyService<R> {
             }
         }
         
-        Err("Node not found".to_string())
+        Err(TunnetError::NodeNotFound(node_id.to_string()))
+    }
+
+    /// Render a node share link as a QR code (`svg` markup or base64 `png`)
+    pub fn node_qr_code(
+        &self,
+        node_id: String,
+        format: Option<String>,
+        size: Option<u32>,
+        ec_level: Option<String>,
+    ) -> Result<String, TunnetError> {
+        let link = self.export_node_link(node_id)?;
+        if link.is_empty() {
+            return Err("Node protocol does not support share links".into());
+        }
+        Self::render_qr(&link, format, size, ec_level)
+    }
+
+    /// Render the base64 subscription body of a whole profile as a QR code
+    pub fn profile_qr_code(
+        &self,
+        profile_id: String,
+        format: Option<String>,
+        size: Option<u32>,
+        ec_level: Option<String>,
+    ) -> Result<String, TunnetError> {
+        let content = self.export_profile_content(profile_id, "base64".to_string())?;
+        Self::render_qr(&content, format, size, ec_level)
     }
 
-    pub fn export_all_nodes(&self, format: String) -> Result<String, String> {
+    fn render_qr(
+        data: &str,
+        format: Option<String>,
+        size: Option<u32>,
+        ec_level: Option<String>,
+    ) -> Result<String, TunnetError> {
+        use qrcode::{EcLevel, QrCode};
+
+        let ec = match ec_level.as_deref().map(|s| s.to_uppercase()).as_deref() {
+            Some("L") => EcLevel::L,
+            Some("Q") => EcLevel::Q,
+            Some("H") => EcLevel::H,
+            _ => EcLevel::M,
+        };
+        let size = size.unwrap_or(256).clamp(64, 2048);
+
+        let code = QrCode::with_error_correction_level(data.as_bytes(), ec)
+            .map_err(|e| format!("Failed to generate QR code: {}", e))?;
+
+        match format.as_deref().unwrap_or("svg") {
+            "svg" => Ok(code
+                .render::<qrcode::render::svg::Color>()
+                .min_dimensions(size, size)
+                .build()),
+            "png" => {
+                // Draw modules manually with a 4-module quiet zone
+                let width = code.width() as u32;
+                let total = width + 8;
+                let scale = (size / total).max(1);
+                let dim = total * scale;
+                let colors = code.to_colors();
+                let img = image::GrayImage::from_fn(dim, dim, |x, y| {
+                    let (mx, my) = (x / scale, y / scale);
+                    if mx < 4 || my < 4 || mx >= width + 4 || my >= width + 4 {
+                        return image::Luma([255u8]);
+                    }
+                    let idx = ((my - 4) * width + (mx - 4)) as usize;
+                    match colors[idx] {
+                        qrcode::Color::Dark => image::Luma([0u8]),
+                        qrcode::Color::Light => image::Luma([255u8]),
+                    }
+                });
+
+                let mut buf = std::io::Cursor::new(Vec::new());
+                img.write_to(&mut buf, image::ImageOutputFormat::Png)
+                    .map_err(|e| format!("Failed to encode QR code: {}", e))?;
+                use base64::{engine::general_purpose, Engine as _};
+                Ok(general_purpose::STANDARD.encode(buf.into_inner()))
+            }
+            other => Err(format!("Unsupported QR code format: {}", other).into()),
+        }
+    }
+
+    pub fn export_all_nodes(&self, format: String) -> Result<String, TunnetError> {
         let profiles = self.manager.load_profiles().map_err(|e| e.to_string())?;
         let mut all_nodes = Vec::new();
         for p in profiles {
@@ -1074,14 +2467,14 @@ impl<R: Runtime> ProxyService<R> {
         self.export_nodes_content(all_nodes, format)
     }
 
-    pub fn export_profile_content(&self, profile_id: String, format: String) -> Result<String, String> {
+    pub fn export_profile_content(&self, profile_id: String, format: String) -> Result<String, TunnetError> {
         let profiles = self.manager.load_profiles().map_err(|e| e.to_string())?;
         let profile = profiles.iter().find(|p| p.id == profile_id).ok_or("Profile not found")?;
 
         self.export_nodes_content(profile.nodes.clone(), format)
     }
 
-    pub fn export_node_content(&self, node_id: String, format: String) -> Result<String, String> {
+    pub fn export_node_content(&self, node_id: String, format: String) -> Result<String, TunnetError> {
         let profiles = self.manager.load_profiles().map_err(|e| e.to_string())?;
         let node = profiles.iter()
             .flat_map(|p| &p.nodes)
@@ -1091,7 +2484,7 @@ impl<R: Runtime> ProxyService<R> {
         self.export_nodes_content(vec![node.clone()], format)
     }
 
-    fn export_nodes_content(&self, nodes: Vec<crate::profile::Node>, format: String) -> Result<String, String> {
+    fn export_nodes_content(&self, nodes: Vec<crate::profile::Node>, format: String) -> Result<String, TunnetError> {
         match format.as_str() {
             "sip002" | "base64" => {
                 let mut links = String::new();
@@ -1119,13 +2512,14 @@ impl<R: Runtime> ProxyService<R> {
                 // Generate standard Sing-box config using helper function
                 self.build_singbox_config_from_nodes(nodes)
             }
-            _ => Err("Unknown format".to_string())
+            "clash" | "yaml" => Ok(crate::profile::nodes_to_clash_yaml(&nodes)?),
+            _ => Err("Unknown format".into())
         }
     }
 
     // Helper function to build Sing-box config from a list of nodes
     // Reuses the same ID-to-tag mapping logic as export_singbox_config
-    fn build_singbox_config_from_nodes(&self, nodes: Vec<crate::profile::Node>) -> Result<String, String> {
+    fn build_singbox_config_from_nodes(&self, nodes: Vec<crate::profile::Node>) -> Result<String, TunnetError> {
         // Build ID to tag mapping with collision handling
         let mut id_to_tag = std::collections::HashMap::new();
         let mut used_tags = std::collections::HashSet::new();
@@ -1177,16 +2571,30 @@ impl<R: Runtime> ProxyService<R> {
             experimental: None,
         };
 
-        serde_json::to_string_pretty(&config).map_err(|e| e.to_string())
+        serde_json::to_string_pretty(&config).map_err(TunnetError::from)
+    }
+
+    /// Export a single node as a `{ "outbounds": [...] }` sing-box snippet
+    pub fn export_node_config(&self, node_id: String) -> Result<String, TunnetError> {
+        self.export_node_content(node_id, "sing-box".to_string())
+    }
+
+    /// Export every node of a profile as a `{ "outbounds": [...] }` sing-box snippet
+    pub fn export_profile_config(&self, profile_id: String) -> Result<String, TunnetError> {
+        self.export_profile_content(profile_id, "sing-box".to_string())
+    }
+
+    pub fn export_clash_yaml(&self, profile_id: String) -> Result<String, TunnetError> {
+        self.export_profile_content(profile_id, "clash".to_string())
     }
 
-    pub fn export_group_content(&self, group_id: String, format: String) -> Result<String, String> {
+    pub fn export_group_content(&self, group_id: String, format: String) -> Result<String, TunnetError> {
         let nodes = self.resolve_group_nodes(&group_id)?;
         self.export_nodes_content(nodes, format)
     }
 
     // New Helper: Resolve all nodes for a group (System or User-defined)
-    fn resolve_group_nodes(&self, group_id: &str) -> Result<Vec<crate::profile::Node>, String> {
+    fn resolve_group_nodes(&self, group_id: &str) -> Result<Vec<crate::profile::Node>, TunnetError> {
         // 1. Get Group Definition (try persisted, then generate implicit/system ones if missing)
         let groups = self.get_groups()?; 
         let group = groups.iter().find(|g| g.id == group_id).ok_or("Group not found")?;
@@ -1204,18 +2612,14 @@ impl<R: Runtime> ProxyService<R> {
                 all_nodes.into_iter().filter(|n| node_ids.contains(&n.id)).collect()
             }
             crate::profile::GroupSource::Filter { criteria } => {
-                let keywords = criteria.keywords.as_deref().unwrap_or(&[]);
-                all_nodes.into_iter().filter(|n| {
-                    if keywords.is_empty() { return true; }
-                     keywords.iter().any(|k| n.name.contains(k))
-                }).collect()
+                all_nodes.into_iter().filter(|n| criteria.matches(n)).collect()
             }
         };
 
         Ok(member_nodes)
     }
 
-    pub fn export_singbox_config(&self) -> Result<String, String> {
+    pub fn export_singbox_config(&self) -> Result<String, TunnetError> {
         let settings = self.manager.load_settings().unwrap_or_default();
         let mode = if settings.tun_mode {
             crate::config::ConfigMode::Combined
@@ -1325,10 +2729,9 @@ impl<R: Runtime> ProxyService<R> {
                     }
                 }
                 crate::profile::GroupSource::Filter { criteria } => {
-                    let keywords = criteria.keywords.as_deref().unwrap_or(&[]);
                     for profile in &profiles {
                         for node in &profile.nodes {
-                            if keywords.is_empty() || keywords.iter().any(|k| node.name.contains(k)) {
+                            if criteria.matches(node) {
                                 if let Some(tag) = id_to_tag.get(&node.id) {
                                     members.push(tag.clone());
                                 }
@@ -1353,7 +2756,7 @@ impl<R: Runtime> ProxyService<R> {
                 };
 
                 if let crate::profile::GroupType::UrlTest { interval, tolerance } = group.group_type {
-                    outbound.url = Some("https://www.gstatic.com/generate_204".to_string());
+                    outbound.url = Some(settings.latency_test_url.clone());
                     outbound.interval = Some(format!("{}s", interval));
                     outbound.tolerance = Some(tolerance as u16);
                 }
@@ -1397,6 +2800,10 @@ impl<R: Runtime> ProxyService<R> {
                 
                 // Normalize policy: Tunnet uses uppercase DIRECT/REJECT, sing-box uses direct/block
                 let policy_lower = rule.policy.to_lowercase();
+                if policy_lower == "reject_drop" {
+                    route_rule.action = Some("reject".to_string());
+                    route_rule.method = Some("drop".to_string());
+                }
                 let policy_tag = if policy_lower == "direct" {
                     "direct".to_string()
                 } else if policy_lower == "reject" {
@@ -1408,8 +2815,13 @@ impl<R: Runtime> ProxyService<R> {
                      })
                 };
 
-                route_rule.outbound = Some(policy_tag);
-                
+                if route_rule.action.is_none() {
+                    route_rule.outbound = Some(policy_tag);
+                }
+                if rule.apply_constraints(&mut route_rule).is_err() {
+                    continue;
+                }
+
                 match rule.rule_type.as_str() {
                     "DOMAIN" => route_rule.domain = Some(vec![rule.value]),
                     "DOMAIN-SUFFIX" | "DOMAIN_SUFFIX" => route_rule.domain_suffix = Some(vec![rule.value]),
@@ -1417,6 +2829,15 @@ impl<R: Runtime> ProxyService<R> {
                     "IP-CIDR" | "IP_CIDR" => route_rule.ip_cidr = Some(vec![rule.value]),
                     // Fix: rule.value (e.g. "geoip-cn") matches the remote rule-set tag, no need to prepend "geoip-"
                     "GEOIP" => route_rule.rule_set = Some(vec![rule.value.to_lowercase()]),
+                    "PORT" | "PORT_RANGE" => match crate::profile::parse_port_spec(&rule.value) {
+                        Ok((ports, ranges)) => {
+                            route_rule.port = (!ports.is_empty()).then_some(ports);
+                            route_rule.port_range = (!ranges.is_empty()).then_some(ranges);
+                        }
+                        Err(_) => continue,
+                    },
+                    "PROCESS_NAME" => route_rule.process_name = Some(vec![rule.value]),
+                    "PROCESS_PATH" => route_rule.process_path = Some(vec![rule.value]),
                     _ => continue,
                 }
                 // Fix: Push to end to preserve order (was insert(0) which reversed it)
@@ -1424,16 +2845,17 @@ impl<R: Runtime> ProxyService<R> {
             }
         }
 
-        serde_json::to_string_pretty(&cfg).map_err(|e| e.to_string())
+        serde_json::to_string_pretty(&cfg).map_err(TunnetError::from)
     }
 
-    pub fn export_tunnet_backup(&self) -> Result<String, String> {
+    pub fn export_tunnet_backup(&self) -> Result<String, TunnetError> {
         let profiles = self.manager.load_profiles().map_err(|e| e.to_string())?;
         let groups = self.manager.load_groups().map_err(|e| e.to_string())?;
         let rules = self.manager.load_rules().map_err(|e| e.to_string())?;
         let settings = self.manager.load_settings().map_err(|e| e.to_string())?;
 
         let backup = serde_json::json!({
+            "schema_version": BACKUP_SCHEMA_VERSION,
             "version": env!("CARGO_PKG_VERSION"),
             "profiles": profiles,
             "groups": groups,
@@ -1441,30 +2863,82 @@ impl<R: Runtime> ProxyService<R> {
             "settings": settings,
         });
 
-        serde_json::to_string_pretty(&backup).map_err(|e| e.to_string())
+        serde_json::to_string_pretty(&backup).map_err(TunnetError::from)
     }
 
-    pub async fn import_tunnet_backup(&self, json: String) -> Result<(), String> {
+    /// Restore a backup bundle. Every section is validated before anything is written.
+    /// With `merge`, entries are upserted by id and local settings are kept;
+    /// otherwise the existing data is replaced.
+    pub async fn import_tunnet_backup(&self, json: String, merge: bool) -> Result<(), TunnetError> {
         let v: serde_json::Value = serde_json::from_str(&json).map_err(|e| e.to_string())?;
-        
-        if let Some(profiles) = v.get("profiles") {
-            let p: Vec<crate::profile::Profile> = serde_json::from_value(profiles.clone()).map_err(|e| e.to_string())?;
+
+        // Backups created before schema versioning have no schema_version field
+        let schema_version = v.get("schema_version").and_then(|v| v.as_u64()).unwrap_or(1);
+        if schema_version > BACKUP_SCHEMA_VERSION as u64 {
+            return Err(format!(
+                "Backup schema version {} is newer than supported version {}",
+                schema_version, BACKUP_SCHEMA_VERSION
+            ).into());
+        }
+
+        let profiles: Option<Vec<crate::profile::Profile>> = v
+            .get("profiles")
+            .map(|p| serde_json::from_value(p.clone()).map_err(|e| format!("Invalid profiles: {}", e)))
+            .transpose()?;
+        let groups: Option<Vec<crate::profile::Group>> = v
+            .get("groups")
+            .map(|g| serde_json::from_value(g.clone()).map_err(|e| format!("Invalid groups: {}", e)))
+            .transpose()?;
+        let rules: Option<Vec<crate::profile::Rule>> = v
+            .get("rules")
+            .map(|r| serde_json::from_value(r.clone()).map_err(|e| format!("Invalid rules: {}", e)))
+            .transpose()?;
+        let settings: Option<crate::settings::AppSettings> = v
+            .get("settings")
+            .map(|s| serde_json::from_value(s.clone()).map_err(|e| format!("Invalid settings: {}", e)))
+            .transpose()?;
+
+        if profiles.is_none() && groups.is_none() && rules.is_none() && settings.is_none() {
+            return Err("Backup contains no data".into());
+        }
+
+        if let Some(p) = profiles {
+            let p = if merge {
+                let mut current = self.manager.load_profiles().unwrap_or_default();
+                crate::profile::upsert_by_id(&mut current, p, |i| &i.id);
+                current
+            } else {
+                p
+            };
             self.manager.save_profiles(&p).map_err(|e| e.to_string())?;
         }
-        
-        if let Some(groups) = v.get("groups") {
-            let g: Vec<crate::profile::Group> = serde_json::from_value(groups.clone()).map_err(|e| e.to_string())?;
+
+        if let Some(g) = groups {
+            let g = if merge {
+                let mut current = self.manager.load_groups().unwrap_or_default();
+                crate::profile::upsert_by_id(&mut current, g, |i| &i.id);
+                current
+            } else {
+                g
+            };
             self.manager.save_groups(&g).map_err(|e| e.to_string())?;
         }
-        
-        if let Some(rules) = v.get("rules") {
-            let r: Vec<crate::profile::Rule> = serde_json::from_value(rules.clone()).map_err(|e| e.to_string())?;
+
+        if let Some(r) = rules {
+            let r = if merge {
+                let mut current = self.manager.load_rules().unwrap_or_default();
+                crate::profile::upsert_by_id(&mut current, r, |i| &i.id);
+                current
+            } else {
+                r
+            };
             self.manager.save_rules(&r).map_err(|e| e.to_string())?;
         }
-        
-        if let Some(settings) = v.get("settings") {
-            let s: crate::settings::AppSettings = serde_json::from_value(settings.clone()).map_err(|e| e.to_string())?;
-            self.manager.save_settings(&s).map_err(|e| e.to_string())?;
+
+        if let Some(s) = settings {
+            if !merge {
+                self.manager.save_settings(&s).map_err(|e| e.to_string())?;
+            }
         }
 
         // Trigger updates
@@ -1476,10 +2950,22 @@ impl<R: Runtime> ProxyService<R> {
         Ok(())
     }
 
-    pub async fn get_connections(&self) -> Result<ConnectionsResponse, String> {
+    pub fn export_backup(&self, path: &str) -> Result<(), TunnetError> {
+        let content = self.export_tunnet_backup()?;
+        std::fs::write(path, content)
+            .map_err(|e| format!("Failed to write backup: {}", e).into())
+    }
+
+    pub async fn import_backup(&self, path: &str, merge: bool) -> Result<(), TunnetError> {
+        let content =
+            std::fs::read_to_string(path).map_err(|e| format!("Failed to read backup: {}", e))?;
+        self.import_tunnet_backup(content, merge).await
+    }
+
+    pub async fn get_connections(&self) -> Result<ConnectionsResponse, TunnetError> {
         let _lock = self.start_lock.lock().await; // Ensure we don't query while restarting
         if !self.is_proxy_running() {
-            return Err("Proxy is not running".to_string());
+            return Err("Proxy is not running".into());
         }
 
         let mut ports = Vec::new();
@@ -1493,7 +2979,7 @@ impl<R: Runtime> ProxyService<R> {
         }
 
         if ports.is_empty() {
-             return Err("Clash API port not available".to_string());
+             return Err("Clash API port not available".into());
         }
 
         let mut combined_connections = Vec::new();
@@ -1502,7 +2988,7 @@ impl<R: Runtime> ProxyService<R> {
 
         for (port, label) in ports {
             let url = format!("http://127.0.0.1:{}/connections", port);
-            match self.internal_client.get(&url).send().await {
+            match self.internal_client.get(&url).bearer_auth(self.clash_secret()).send().await {
                 Ok(resp) => {
                     if resp.status().is_success() {
                         if let Ok(mut data) = resp.json::<ConnectionsResponse>().await {
@@ -1557,11 +3043,11 @@ impl<R: Runtime> ProxyService<R> {
         Ok(final_response)
     }
 
-    pub async fn close_connection(&self, id: &str) -> Result<(), String> {
+    pub async fn close_connection(&self, id: &str) -> Result<(), TunnetError> {
         let _lock = self.start_lock.lock().await;
 
         if !self.is_proxy_running() {
-             return Err("Proxy is not running".to_string());
+             return Err("Proxy is not running".into());
         }
 
         let mut ports = Vec::new();
@@ -1573,7 +3059,7 @@ impl<R: Runtime> ProxyService<R> {
         }
 
         if ports.is_empty() {
-            return Err("Clash API port not available".to_string());
+            return Err("Clash API port not available".into());
         }
 
         let mut last_error = String::from("Connection not found");
@@ -1581,7 +3067,7 @@ impl<R: Runtime> ProxyService<R> {
 
         for port in ports {
             let url = format!("http://127.0.0.1:{}/connections/{}", port, id);
-            match self.internal_client.delete(&url).send().await {
+            match self.internal_client.delete(&url).bearer_auth(self.clash_secret()).send().await {
                 Ok(resp) => {
                     if resp.status().is_success() || resp.status() == reqwest::StatusCode::NO_CONTENT {
                         closed = true;
@@ -1604,15 +3090,15 @@ impl<R: Runtime> ProxyService<R> {
         if closed {
             Ok(())
         } else {
-            Err(last_error)
+            Err(last_error.into())
         }
     }
 
-    pub async fn close_all_connections(&self) -> Result<(), String> {
+    pub async fn close_all_connections(&self) -> Result<(), TunnetError> {
         let _lock = self.start_lock.lock().await;
 
         if !self.is_proxy_running() {
-             return Err("Proxy is not running".to_string());
+             return Err("Proxy is not running".into());
         }
 
         let mut ports = Vec::new();
@@ -1624,14 +3110,14 @@ impl<R: Runtime> ProxyService<R> {
         }
 
         if ports.is_empty() {
-             return Err("Clash API port not available".to_string());
+             return Err("Clash API port not available".into());
         }
 
         let mut combined_error = String::new();
 
         for port in ports {
             let url = format!("http://127.0.0.1:{}/connections", port);
-            match self.internal_client.delete(&url).send().await {
+            match self.internal_client.delete(&url).bearer_auth(self.clash_secret()).send().await {
                 Ok(resp) => {
                     if !resp.status().is_success() && resp.status() != reqwest::StatusCode::NO_CONTENT {
                          combined_error.push_str(&format!("Port {}: API Error {}; ", port, resp.status()));
@@ -1646,7 +3132,29 @@ impl<R: Runtime> ProxyService<R> {
         if combined_error.is_empty() {
             Ok(())
         } else {
-            Err(combined_error)
+            Err(combined_error.into())
+        }
+    }
+
+    fn clash_secret(&self) -> String {
+        self.clash_api_secret.lock().unwrap().clone()
+    }
+
+    /// Controller URLs and secret of the running core, for embedded or external dashboards.
+    pub fn get_clash_api_info(&self) -> ClashApiInfo {
+        let allow_lan = self
+            .running_settings
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|s| s.clash_api_allow_lan)
+            .unwrap_or(false);
+        let helper_port = *self.helper_api_port.lock().unwrap();
+        ClashApiInfo {
+            controller_url: self.ensure_clash_port().map(|p| format!("http://127.0.0.1:{}", p)),
+            helper_controller_url: helper_port.map(|p| format!("http://127.0.0.1:{}", p)),
+            secret: self.clash_secret(),
+            allow_lan,
         }
     }
 
@@ -1685,6 +3193,12 @@ impl<R: Runtime> ProxyService<R> {
 
         if let Ok(content) = std::fs::read_to_string(&config_file_path) {
             if let Ok(json) = serde_json::from_str::<serde_json::Value>(&content) {
+                if let Some(secret) = json
+                    .pointer("/experimental/clash_api/secret")
+                    .and_then(|v| v.as_str())
+                {
+                    *self.clash_api_secret.lock().unwrap() = secret.to_string();
+                }
                 if let Some(controller) = json
                     .get("experimental")
                     .and_then(|e| e.get("clash_api"))
@@ -1745,7 +3259,7 @@ impl<R: Runtime> ProxyService<R> {
     /// Ensure the Windows Helper Service is running.
     /// Checks if the TunnetHelper service exists and is running, starts it if necessary.
     #[cfg(target_os = "windows")]
-    fn ensure_windows_helper(&self) -> Result<(), String> {
+    fn ensure_windows_helper(&self) -> Result<(), TunnetError> {
         info!("Checking Windows Helper Service status...");
         
         // 1. Check if service exists and get its status
@@ -1758,7 +3272,7 @@ impl<R: Runtime> ProxyService<R> {
         
         // 2. If service doesn't exist, return error (user needs to install it)
         if !output.status.success() || !output_str.contains("TunnetHelper") {
-            return Err("TunnetHelper service is not installed. Please reinstall the application.".to_string());
+            return Err(TunnetError::HelperUnreachable("TunnetHelper service is not installed. Please reinstall the application.".to_string()));
         }
         
         // 3. Check if service is running
@@ -1782,7 +3296,7 @@ impl<R: Runtime> ProxyService<R> {
                                  error!("Failed to update helper service: {}", e);
                                  // Return error or warn? 
                                  // Ideally fail the start so logic works
-                                 return Err(format!("Failed to update helper service: {}", e));
+                                 return Err(format!("Failed to update helper service: {}", e).into());
                              }
                              info!("Helper service updated successfully.");
                              return Ok(());
@@ -1811,26 +3325,19 @@ impl<R: Runtime> ProxyService<R> {
             if stderr.contains("already been started") {
                 info!("Service was already started");
             } else {
-                return Err(format!("Failed to start service: {}", stderr));
+                return Err(format!("Failed to start service: {}", stderr).into());
             }
         }
         
         info!("Waiting for service to be ready...");
         
         // 5. Wait for service to be ready and responsive
-        let client = crate::helper_client::HelperClient::new();
-        for i in 0..20 {
-            std::thread::sleep(std::time::Duration::from_millis(500));
-            match client.check_status() {
-                Ok(_) => {
-                    info!("TunnetHelper service is ready after {}ms", (i + 1) * 500);
-                    return Ok(());
-                }
-                Err(_) => continue,
-            }
-        }
-        
-        Err("Service started but not responding within timeout (10s)".to_string())
+        let started = std::time::Instant::now();
+        crate::helper_client::HelperClient::new()
+            .wait_until_ready(std::time::Duration::from_secs(10))
+            .map_err(|e| TunnetError::HelperUnreachable(format!("service started but not responding: {}", e)))?;
+        info!("TunnetHelper service is ready after {}ms", started.elapsed().as_millis());
+        Ok(())
     }
     
     /// Find the helper executable and DLL directory paths
@@ -1838,7 +3345,7 @@ impl<R: Runtime> ProxyService<R> {
     /// NOTE: This function is kept for potential future use (e.g., development mode)
     #[cfg(target_os = "windows")]
     #[allow(dead_code)]
-    fn find_helper_and_dll_paths(&self) -> Result<(std::path::PathBuf, std::path::PathBuf), String> {
+    fn find_helper_and_dll_paths(&self) -> Result<(std::path::PathBuf, std::path::PathBuf), TunnetError> {
         // Use Tauri's resource_dir which points to:
         // - Dev mode: target/debug
         // - Release/bundled mode: app_dir
@@ -1848,608 +3355,234 @@ impl<R: Runtime> ProxyService<R> {
         
         let bin_dir = resource_dir.join("resources").join("bin");
         let helper = bin_dir.join("tunnet-helper.exe");
-        let dll = bin_dir.join("libbox.dll");
-        
-        info!("Resource dir: {:?}", resource_dir);
-        info!("Looking for helper: {:?} (exists: {})", helper, helper.exists());
-        info!("Looking for DLL: {:?} (exists: {})", dll, dll.exists());
-        
-        if !helper.exists() {
-            return Err(format!("tunnet-helper.exe not found at {:?}", helper));
-        }
-        if !dll.exists() {
-            return Err(format!("libbox.dll not found at {:?}", dll));
-        }
-        
-        Ok((helper, bin_dir))
-    }
-
-    fn write_config(
-        &self,
-        node_opt: Option<&crate::profile::Node>,
-        mode: crate::config::ConfigMode,
-        _routing_mode: &str,
-        settings: &crate::settings::AppSettings,
-        clash_api_port: Option<u16>,
-    ) -> Result<(), String> {
-        let tun_mode = mode == crate::config::ConfigMode::TunOnly
-            || mode == crate::config::ConfigMode::Combined;
-        let app_local_data = self.app.path().app_local_data_dir().unwrap();
-        let mut cfg = crate::config::SingBoxConfig::new(clash_api_port, mode, &settings.dns_servers, &settings.dns_strategy, "proxy");
-
-
-        // Synchronize log level with app settings
-        if let Some(log) = &mut cfg.log {
-            let level = settings.log_level.to_lowercase();
-            info!("Configuring SingBox log level: {}", level);
-            log.level = Some(level);
-        }
-
-        // Synchronize DNS strategy with app settings
-        if let Some(dns) = &mut cfg.dns {
-            let strategy = match settings.dns_strategy.as_str() {
-                "ipv4" => "prefer_ipv4".to_string(),
-                "ipv6" => "prefer_ipv6".to_string(),
-                "only4" => "ipv4_only".to_string(),
-                "only6" => "ipv6_only".to_string(),
-                s => s.to_string(),
-            };
-            dns.strategy = Some(strategy);
-
-            // Enable Split DNS for CN domains in Rule mode to improve domestic access speed
-            if _routing_mode != "global" && _routing_mode != "direct" {
-                dns.rules.insert(
-                    0,
-                    crate::config::DnsRule {
-                        rule_set: Some(vec!["geosite-cn".to_string()]),
-                        server: Some("local".to_string()),
-                        inbound: None,
-                        outbound: None,
-                        domain: None,
-                        domain_suffix: None,
-                        domain_keyword: None,
-                        ip_cidr: None,
-                    },
-                );
-            }
-        }
-
-        if tun_mode {
-            // CRITICAL FIX: To prevent IPv6 leak, we must enable IPv6 address for TUN
-            // even if dns_strategy is "prefer_ipv4". Only disable if explicitly "only4".
-            let ipv6_enabled = settings.dns_strategy != "only4";
-            // Force a safe MTU for maximum compatibility, especially with DoH/CDN nodes
-            let mut mtu = settings.tun_mtu;
-            if mtu > 1500 || mtu == 0 {
-                mtu = 1500;
-            }
-            cfg = cfg.with_tun_inbound(mtu, settings.tun_stack.clone(), ipv6_enabled, settings.strict_route);
-        }
-
-        let listen = if settings.allow_lan {
-            "0.0.0.0"
-        } else {
-            "127.0.0.1"
-        };
-
-        if mode != crate::config::ConfigMode::TunOnly {
-            cfg = cfg.with_mixed_inbound(settings.mixed_port, "mixed-in", false);
-            if let Some(inbound) = cfg.inbounds.last_mut() {
-                inbound.listen = Some(listen.to_string());
-                inbound.reuse_addr = Some(true);
-            }
-        }
-
-        // 1. Add required system outbounds and database paths
-        cfg = cfg.with_direct().with_block();
-
-        if let Some(route) = &mut cfg.route {
-            let app_local_data = self.app.path().app_local_data_dir().unwrap();
-            let resource_dir = self.app.path().resource_dir().unwrap().join("resources");
-
-            // Check order: 1. app_local_data (manual updates), 2. resources (bundled)
-            let geoip_path = if app_local_data.join("geoip-cn.srs").exists() {
-                Some(app_local_data.join("geoip-cn.srs"))
-            } else if resource_dir.join("geoip-cn.srs").exists() {
-                Some(resource_dir.join("geoip-cn.srs"))
-            } else {
-                None
-            };
-
-            let geosite_path = if app_local_data.join("geosite-cn.srs").exists() {
-                Some(app_local_data.join("geosite-cn.srs"))
-            } else if resource_dir.join("geosite-cn.srs").exists() {
-                Some(resource_dir.join("geosite-cn.srs"))
-            } else {
-                None
-            };
-            let geosite_ads_path = if app_local_data.join("geosite-category-ads-all.srs").exists() {
-                Some(app_local_data.join("geosite-category-ads-all.srs"))
-            } else if resource_dir.join("geosite-category-ads-all.srs").exists() {
-                Some(resource_dir.join("geosite-category-ads-all.srs"))
-            } else {
-                None
-            };
-
-            route.rule_set = Some(vec![
-                if let Some(path) = geoip_path {
-                    crate::config::RuleSet {
-                        rule_set_type: "local".to_string(),
-                        tag: "geoip-cn".to_string(),
-                        format: "binary".to_string(),
-                        path: Some(path.to_string_lossy().to_string()),
-                        url: None,
-                        download_detour: None,
-                        update_interval: None,
-                    }
-                } else {
-                    crate::config::RuleSet {
-                        rule_set_type: "remote".to_string(),
-                        tag: "geoip-cn".to_string(),
-                        format: "binary".to_string(),
-                        path: None,
-                        url: Some("https://raw.githubusercontent.com/SagerNet/sing-geoip/rule-set/geoip-cn.srs".to_string()),
-                        download_detour: Some("direct".to_string()),
-                        update_interval: Some("1d".to_string()),
-                    }
-                },
-                if let Some(path) = geosite_path {
-                    crate::config::RuleSet {
-                        rule_set_type: "local".to_string(),
-                        tag: "geosite-cn".to_string(),
-                        format: "binary".to_string(),
-                        path: Some(path.to_string_lossy().to_string()),
-                        url: None,
-                        download_detour: None,
-                        update_interval: None,
-                    }
-                } else {
-                    crate::config::RuleSet {
-                        rule_set_type: "remote".to_string(),
-                        tag: "geosite-cn".to_string(),
-                        format: "binary".to_string(),
-                        path: None,
-                        url: Some("https://raw.githubusercontent.com/SagerNet/sing-geosite/rule-set/geosite-cn.srs".to_string()),
-                        download_detour: Some("direct".to_string()),
-                        update_interval: Some("1d".to_string()),
-                    }
-                },
-                if let Some(path) = geosite_ads_path {
-                    crate::config::RuleSet {
-                        rule_set_type: "local".to_string(),
-                        tag: "geosite-ads".to_string(),
-                        format: "binary".to_string(),
-                        path: Some(path.to_string_lossy().to_string()),
-                        url: None,
-                        download_detour: None,
-                        update_interval: None,
-                    }
-                } else {
-                    crate::config::RuleSet {
-                        rule_set_type: "remote".to_string(),
-                        tag: "geosite-ads".to_string(),
-                        format: "binary".to_string(),
-                        path: None,
-                        url: Some("https://raw.githubusercontent.com/SagerNet/sing-geosite/rule-set/geosite-category-ads-all.srs".to_string()),
-                        download_detour: Some("direct".to_string()),
-                        update_interval: Some("1d".to_string()),
-                    }
-                },
-            ]);
-        }
-
-        // 2. Load Resources (Profiles/Groups)
-        let profiles = self.manager.load_profiles().unwrap_or_default();
-        let groups = self.get_groups().unwrap_or_default(); // Uses the new dynamic get_groups
-
-        // Track valid outbound tags to prevent "dependency not found" errors
-        let mut valid_tags = HashSet::new();
-        valid_tags.insert("direct".to_string());
-        valid_tags.insert("block".to_string());
-
-        // 3. Add ALL Nodes as Outbounds
-        // We iterate all profiles and their nodes
-        for profile in &profiles {
-            for node in &profile.nodes {
-                let tag = node.id.clone(); // Use UUID as tag
-                let is_supported = match node.protocol.as_str() {
-                    "vmess" | "vless" | "shadowsocks" | "ss" | "trojan" | "hysteria2" | "hy2"
-                    | "tuic" => true,
-                    _ => false,
-                };
-
-                if is_supported {
-                    let outbound = self.node_to_outbound(node);
-                    cfg.outbounds.push(outbound);
-                    valid_tags.insert(tag);
-                }
-            }
-        }
-
-        // 4. Add Group Outbounds
-        for group in &groups {
-            let mut member_tags = Vec::new();
-
-            match &group.source {
-                crate::profile::GroupSource::Static { node_ids } => {
-                    // Filter valid nodes
-                    for pid in node_ids {
-                        if valid_tags.contains(pid) {
-                            member_tags.push(pid.clone());
-                        } else {
-                            debug!(
-                                "Skipping invalid node dependency '{}' in group '{}'",
-                                pid, group.id
-                            );
-                        }
-                    }
-                }
-                crate::profile::GroupSource::Filter { criteria } => {
-                    // Logic: Iterate all nodes, check match
-                    for profile in &profiles {
-                        for node in &profile.nodes {
-                            let mut matched = true;
-                            // 1. Keyword match
-                            if let Some(keywords) = &criteria.keywords {
-                                let name_lower = node.name.to_lowercase();
-                                let any_match = keywords
-                                    .iter()
-                                    .any(|k| name_lower.contains(&k.to_lowercase()));
-                                if !any_match {
-                                    matched = false;
-                                }
-                            }
-                            // Future: Sub ID match, etc.
-
-                            if matched {
-                                member_tags.push(node.id.clone());
-                            }
-                        }
-                    }
-                }
-            }
-
-            // If group is empty, we must handle it. Singbox fails with empty selector?
-            // Let's add 'block' if empty to prevent crash
-            // Prevent crash on empty groups: if no members, fallback to direct
-            if member_tags.is_empty() {
-                member_tags.push("direct".to_string());
-            }
-
-            match group.group_type {
-                crate::profile::GroupType::Selector => {
-                    // move selected to front
-                    let mut tags = member_tags.clone();
-                    if let Some(selected) = &group.selected {
-                        if let Some(pos) = tags.iter().position(|x| x == selected) {
-                            let val = tags.remove(pos);
-                            tags.insert(0, val);
-                        }
-                    }
-                    cfg = cfg.with_selector_outbound(&group.id, tags);
-                }
-                crate::profile::GroupType::UrlTest {
-                    interval,
-                    tolerance,
-                } => {
-                    // Default url/interval for now, can be added to Group struct later
-                    cfg = cfg.with_urltest_outbound(
-                        &group.id,
-                        member_tags,
-                        None,
-                        Some(format!("{}s", interval)),
-                        Some(tolerance as u16),
-                    );
-                }
-            }
-            valid_tags.insert(group.id.clone());
+        let dll = bin_dir.join("libbox.dll");
+        
+        info!("Resource dir: {:?}", resource_dir);
+        info!("Looking for helper: {:?} (exists: {})", helper, helper.exists());
+        info!("Looking for DLL: {:?} (exists: {})", dll, dll.exists());
+        
+        if !helper.exists() {
+            return Err(format!("tunnet-helper.exe not found at {:?}", helper).into());
         }
-
-        // 5. Add MAIN 'proxy' outbound
-        // This is what the dashboard "Select Server" controls.
-        // For backward compatibility and immediate effect, 'proxy' tag should point to the selected node.
-        // We create a Selector `proxy` that contains [selected_node_id].
-        // This acts as an alias.
-
-        let mut proxy_target = "direct".to_string(); // Fallback
-        if let Some(node) = &node_opt {
-            // Verify this node ID exists in our generated outbounds (it should)
-            // But 'node_opt' might be a standalone object if not from profile?
-            // Usually it's from the list.
-            proxy_target = node.id.clone();
-
-            // Check if we already added a vmess/etc outbound for this ID.
-            if !valid_tags.contains(&proxy_target) {
-                info!("Manual node addition safety net for: {}", node.name);
-                // It might be a temp node? Add it manually (legacy behavior fallback)
-                let is_supported = match node.protocol.as_str() {
-                    "vmess" | "vless" | "shadowsocks" | "ss" | "trojan" | "hysteria2" | "hy2"
-                    | "tuic" => true,
-                    _ => false,
-                };
-
-                if is_supported {
-                    // Use the helper to add node with the custom tag pointing to actual node
-                    // But here tag is 'proxy_target' which is node.id
-                    let mut outbound = self.node_to_outbound(node);
-                    outbound.tag = proxy_target.clone();
-                    cfg.outbounds.push(outbound);
-                    valid_tags.insert(proxy_target.clone());
-                } else {
-                    proxy_target = "direct".to_string();
-                }
-            }
+        if !dll.exists() {
+            return Err(format!("libbox.dll not found at {:?}", dll).into());
         }
+        
+        Ok((helper, bin_dir))
+    }
 
-        // Define 'proxy' as a Selector wrapping the target, or just direct alias?
-        // Singbox doesn't have "Alias".
-        // We use a Selector with 1 item.
-        // This allows 'proxy' to be used in rules.
-        cfg = cfg.with_selector_outbound("proxy", vec![proxy_target]);
-        valid_tags.insert("proxy".to_string());
-
-        // Apply Rules and Routing Mode
-        let mut final_rules = Vec::new();
+    fn write_config(
+        &self,
+        node_opt: Option<&crate::profile::Node>,
+        mode: crate::config::ConfigMode,
+        routing_mode: &str,
+        settings: &crate::settings::AppSettings,
+        clash_api_port: Option<u16>,
+    ) -> Result<(), TunnetError> {
+        let cfg = self.build_config(node_opt, mode, routing_mode, settings, clash_api_port)?;
 
-        // 1. DNS Hijack (ABSOLUTE PRIORITY)
-        if settings.dns_hijack {
-            if let Some(route) = &cfg.route {
-                if let Some(dns_rule) = route
-                    .rules
-                    .iter()
-                    .find(|r| r.action == Some("hijack-dns".to_string()))
-                {
-                    final_rules.push(dns_rule.clone());
-                }
+        if let Some(path) = cfg.log.as_ref().and_then(|l| l.output.as_ref()) {
+            let path = std::path::PathBuf::from(path);
+            if let Some(dir) = path.parent() {
+                let _ = std::fs::create_dir_all(dir);
             }
+            Self::rotate_core_log(&path);
         }
-
-        // 2. Sniffing Rule (MUST follow Hijack so port 53 is caught first)
-        if tun_mode {
-            final_rules.push(
-                crate::config::RouteRule {
-                    inbound: Some(vec!["tun-in".to_string()]),
-                    action: Some("sniff".to_string()),
-                    ..Default::default()
-                },
-            );
+        if let Some(secret) = cfg
+            .experimental
+            .as_ref()
+            .and_then(|e| e.clash_api.as_ref())
+            .and_then(|c| c.secret.clone())
+        {
+            *self.clash_api_secret.lock().unwrap() = secret;
         }
 
-        // (Removed early IPv6 reject rule to allow user rules and global proxy to take precedence)
-
-        let mut default_policy = "proxy".to_string(); // Default fallback
-
-        match _routing_mode {
-            "global" => {
-                default_policy = "proxy".to_string();
-                // In Global mode, also make DNS go through proxy for safety
-                if let Some(dns) = &mut cfg.dns {
-                    for server in &mut dns.servers {
-                        if server.tag == "google" {
-                            server.detour = Some("proxy".to_string());
-                        }
-                    }
-                }
-            }
-            "direct" => {
-                default_policy = "direct".to_string();
-                // In Direct mode, also make DNS direct
-                if let Some(dns) = &mut cfg.dns {
-                    for server in &mut dns.servers {
-                        if server.tag == "google" {
-                            server.detour = Some("direct".to_string());
-                        }
-                    }
-                }
-            }
-            _ => {
-                // "rule" mode
-                if let Ok(user_rules) = self.manager.load_rules() {
-                    info!(
-                        "Loaded {} user rules for config generation",
-                        user_rules.len()
-                    );
-                    for rule in user_rules {
-                        if !rule.enabled {
-                            continue;
-                        }
-
-                        if rule.rule_type == "FINAL" {
-                            let mut policy = match rule.policy.as_str() {
-                                "PROXY" => "proxy".to_string(),
-                                "DIRECT" => "direct".to_string(),
-                                "REJECT" => "reject".to_string(),
-                                _ => rule.policy.clone(), // Likely a Group ID
-                            };
-                            // Validation
-                            if policy != "reject" && !valid_tags.contains(&policy) {
-                                warn!("Invalid FINAL policy '{}', falling back to 'proxy'", policy);
-                                policy = "proxy".to_string();
-                            }
-                            default_policy = policy;
-                            continue;
-                        }
-
-                        let (mut outbound_tag, action) = match rule.policy.as_str() {
-                            "PROXY" => (Some("proxy".to_string()), None),
-                            "DIRECT" => (Some("direct".to_string()), None),
-                            "REJECT" => (None, Some("reject".to_string())),
-                            _ => (Some(rule.policy.clone()), None), // Assume it's a Group ID or Valid Tag
-                        };
-
-                        // Validation
-                        if let Some(ref tag) = outbound_tag {
-                            if !valid_tags.contains(tag) {
-                                warn!(
-                                    "Invalid policy '{}' in rule '{}', falling back to 'proxy'",
-                                    tag, rule.id
-                                );
-                                outbound_tag = Some("proxy".to_string());
-                            }
-                        }
+        let json = serde_json::to_string_pretty(&cfg).map_err(TunnetError::from)?;
+        let config_path = self.app.path().app_local_data_dir().unwrap().join("config.json");
+        std::fs::write(&config_path, json).map_err(|e| e.to_string())?;
+        Ok(())
+    }
 
-                        let mut route_rule = crate::config::RouteRule {
-                            outbound: outbound_tag,
-                            action,
-                            ..Default::default()
-                        };
+    /// The config `start_proxy` would launch for `node`, without writing or starting
+    /// anything. With `tun` this is the helper's TUN instance config. The controller
+    /// secret is redacted so the output can be pasted into bug reports.
+    pub fn preview_config(
+        &self,
+        node: Option<crate::profile::Node>,
+        tun: bool,
+        routing_mode: &str,
+    ) -> Result<String, TunnetError> {
+        let settings = self.manager.load_settings()?;
+        let (mode, port) = if tun {
+            (crate::config::ConfigMode::TunOnly, *self.helper_api_port.lock().unwrap())
+        } else {
+            (crate::config::ConfigMode::SystemProxyOnly, *self.clash_api_port.lock().unwrap())
+        };
+        let mut cfg = self.build_config(node.as_ref(), mode, routing_mode, &settings, port)?;
+        if let Some(clash_api) = cfg.experimental.as_mut().and_then(|e| e.clash_api.as_mut()) {
+            clash_api.secret = clash_api.secret.as_ref().map(|_| "<redacted>".to_string());
+        }
+        serde_json::to_string_pretty(&cfg).map_err(TunnetError::from)
+    }
 
-                        match rule.rule_type.as_str() {
-                            "DOMAIN" => {
-                                if rule.value.starts_with("geosite:") {
-                                    let val = rule.value.replace("geosite:", "");
-                                    route_rule.rule_set = Some(vec![val]);
-                                } else {
-                                    route_rule.domain = Some(vec![rule.value.clone()]);
-                                }
-                            }
-                            "DOMAIN_SUFFIX" => {
-                                route_rule.domain_suffix = Some(vec![rule.value.clone()]);
-                            }
-                            "DOMAIN_KEYWORD" => {
-                                route_rule.domain_keyword = Some(vec![rule.value.clone()]);
-                            }
-                            "IP_CIDR" => {
-                                route_rule.ip_cidr = Some(vec![rule.value.clone()]);
-                            }
-                            "GEOIP" => {
-                                let val = rule.value.replace("geoip:", "");
-                                route_rule.rule_set = Some(vec![val]);
-                            }
-                            "IP_IS_PRIVATE" => {
-                                route_rule.ip_is_private = Some(true);
-                            }
-                            _ => {}
-                        }
+    /// Config for the mobile core: the TUN layout without desktop routing, since the
+    /// platform VPN service creates the interface and passes its fd to `start_mobile`.
+    #[cfg(mobile)]
+    pub fn build_mobile_config(
+        &self,
+        node: Option<&crate::profile::Node>,
+        routing_mode: &str,
+    ) -> Result<String, TunnetError> {
+        let settings = self.manager.load_settings()?;
+        let port = *self.clash_api_port.lock().unwrap();
+        let mode = if settings.tun_with_mixed {
+            crate::config::ConfigMode::Combined
+        } else {
+            crate::config::ConfigMode::TunOnly
+        };
+        let cfg = self
+            .build_config(node, mode, routing_mode, &settings, port)?
+            .with_external_tun();
+        serde_json::to_string(&cfg).map_err(TunnetError::from)
+    }
 
-                        final_rules.push(route_rule);
-                    }
+    /// Start the in-process core on the TUN fd provided by the platform VPN service.
+    #[cfg(mobile)]
+    pub fn start_mobile(&self, fd: i32, config: String) -> Result<(), TunnetError> {
+        if *self.local_proxy_running.lock().unwrap() {
+            info!("Stopping running core before mobile start...");
+            unsafe {
+                let err_ptr = libbox::LibboxStop();
+                if !err_ptr.is_null() {
+                    let err_msg = CStr::from_ptr(err_ptr).to_string_lossy().into_owned();
+                    warn!("LibboxStop failed: {}", err_msg);
                 }
             }
-        }
-        // IPv6 Fallback: Only reject IPv6 traffic if the user explicitly chose "Only IPv4".
-        // For "Prefer IPv4", we allow it to fall through to the proxy/direct fallback,
-        // which now has 'domain_strategy: prefer_ipv4' to handle it gracefully.
-        if settings.dns_strategy == "only4" {
-            final_rules.push(crate::config::RouteRule {
-                ip_cidr: Some(vec!["::/0".to_string()]),
-                action: Some("reject".to_string()),
-                ..Default::default()
-            });
+            *self.local_proxy_running.lock().unwrap() = false;
         }
 
-        // 3. Add the ultimate fallback rule
-        // Validate ultimate default_policy too (just in case no rule set it or it was invalid)
-        if default_policy != "reject" && !valid_tags.contains(&default_policy) {
-            default_policy = "proxy".to_string();
+        let c_config = CString::new(config).map_err(|_| "Config holds null bytes")?;
+        info!("Starting mobile core on TUN fd {}...", fd);
+        unsafe {
+            // Logs go to the `log.output` file in the config, so no fd redirect here
+            let err_ptr = libbox::LibboxStartMobile(fd, c_config.as_ptr(), 0);
+            if !err_ptr.is_null() {
+                let err_msg = CStr::from_ptr(err_ptr).to_string_lossy().into_owned();
+                error!("LibboxStartMobile failed: {}", err_msg);
+                return Err(TunnetError::CoreUnavailable(err_msg));
+            }
         }
+        *self.local_proxy_running.lock().unwrap() = true;
+        *self.tun_mode.lock().unwrap() = true;
+        let _ = self.app.emit("proxy-status-change", self.get_status());
+        Ok(())
+    }
 
-        let (fallback_outbound, fallback_action) = if default_policy == "reject" {
-            (None, Some("reject".to_string()))
-        } else {
-            (Some(default_policy.to_string()), None)
-        };
+    /// Generate the mobile config for `node` and start it on the VPN service's fd.
+    #[cfg(mobile)]
+    pub fn start_mobile_proxy(
+        &self,
+        fd: i32,
+        node: Option<crate::profile::Node>,
+        routing_mode: &str,
+    ) -> Result<(), TunnetError> {
+        let routing_mode = routing_mode.to_lowercase();
+        let config = self.build_mobile_config(node.as_ref(), &routing_mode)?;
+        *self.latest_node.lock().unwrap() = node;
+        *self.latest_routing_mode.lock().unwrap() = routing_mode;
+        self.start_mobile(fd, config)
+    }
 
-        final_rules.push(crate::config::RouteRule {
-            outbound: fallback_outbound,
-            action: fallback_action,
-            ..Default::default()
-        });
+    /// Assemble the sing-box config for one instance from the given node, mode and
+    /// settings plus the stored profiles, groups and rules. Writes nothing.
+    fn build_config(
+        &self,
+        node_opt: Option<&crate::profile::Node>,
+        mode: crate::config::ConfigMode,
+        routing_mode: &str,
+        settings: &crate::settings::AppSettings,
+        clash_api_port: Option<u16>,
+    ) -> Result<crate::config::SingBoxConfig, TunnetError> {
+        Ok(self.with_config_sources(mode, |sources| {
+            build_config(node_opt, mode, routing_mode, settings, clash_api_port, sources)
+        }))
+    }
 
-        if let Some(route) = &mut cfg.route {
-            route.rules = final_rules;
-            let rule_count = route.rules.len();
-            info!(
-                "Config generated: rules={}, mode={}, default_policy={}",
-                rule_count, _routing_mode, default_policy
-            );
-            // Log DNS detour if exists
-            if let Some(dns) = &cfg.dns {
-                if let Some(google_server) = dns.servers.iter().find(|s| s.tag == "google") {
-                    info!("DNS google detour: {:?}", google_server.detour);
-                }
-            }
-        }
-        // 6. Set Cache File to avoid writing to src-tauri in dev
-        let cache_name = if mode == crate::config::ConfigMode::TunOnly {
-            "cache_tun.db"
-        } else {
-            "cache.db"
-        };
-        let clash_api_config = if let Some(port) = clash_api_port {
-            Some(crate::config::ClashApiConfig {
-                external_controller: format!("127.0.0.1:{}", port),
-                external_ui: Some(app_local_data.join("ui").to_string_lossy().to_string()),
-                secret: None,
-            })
+    /// Run `f` over the stored profiles, groups and rules and the rule-set files the
+    /// instance started in `mode` would load.
+    fn with_config_sources<T>(
+        &self,
+        mode: crate::config::ConfigMode,
+        f: impl FnOnce(&ConfigSources) -> T,
+    ) -> T {
+        let app_local_data = self.app.path().app_local_data_dir().unwrap();
+        let resource_dir = self.app.path().resource_dir().unwrap().join("resources");
+        let profiles = self.manager.load_profiles().unwrap_or_default();
+        let groups = self.get_groups().unwrap_or_default(); // Uses the new dynamic get_groups
+        let rules = self.manager.load_rules().unwrap_or_default();
+
+        // The TUN helper runs as another user and only reads the copies `stage_databases`
+        // made; everyone else checks app_local_data (manual updates), then resources (bundled)
+        let staging = staging_dir();
+        let rule_set_dirs = if mode == crate::config::ConfigMode::TunOnly && !cfg!(mobile) {
+            vec![staging.as_path()]
         } else {
-            cfg.experimental.and_then(|e| e.clash_api) // Preserve clash_api if already set and no new port provided
+            vec![app_local_data.as_path(), resource_dir.as_path()]
         };
 
-        cfg.experimental = Some(crate::config::ExperimentalConfig {
-            cache_file: Some(crate::config::CacheFileConfig {
-                enabled: true,
-                path: app_local_data
-                    .join(cache_name)
-                    .to_string_lossy()
-                    .to_string(),
-            }),
-            clash_api: clash_api_config,
-        });
-
-        // 5.5 Set Domain Strategy for all proxy outbounds
-        let domain_strategy = match settings.dns_strategy.as_str() {
-            "ipv4" | "only4" => Some("prefer_ipv4".to_string()),
-            "ipv6" | "only6" => Some("prefer_ipv6".to_string()),
-            _ => None,
+        let sources = ConfigSources {
+            profiles: &profiles,
+            groups: &groups,
+            rules: &rules,
+            data_dir: &app_local_data,
+            local_rule_sets: find_rule_set_files(&rule_set_dirs),
+            core_log_path: self.core_log_path(),
+            session_clash_secret: &self.session_clash_secret,
         };
+        f(&sources)
+    }
 
-        if let Some(strategy) = domain_strategy {
-            for outbound in &mut cfg.outbounds {
-                // Apply ONLY to protocol outbounds. 
-                // selector, urltest, direct, block, dns do not support domain_strategy at the outbound level.
-                if matches!(
-                    outbound.outbound_type.as_str(),
-                    "vmess" | "vless" | "shadowsocks" | "ss" | "trojan" | "hysteria2" | "tuic"
-                ) {
-                    outbound.domain_strategy = Some(strategy.clone());
-                }
-            }
-        }
+    fn core_log_path(&self) -> std::path::PathBuf {
+        self.app
+            .path()
+            .app_local_data_dir()
+            .unwrap()
+            .join("logs")
+            .join("core.log")
+    }
 
-        // --- Final Stage: Robust Proxy Bypass (Routing Loop Prevention) ---
-        // Scan ALL outbounds to find their server IPs and ensure they are direct-routed.
-        // This is done last to catch all nodes across all profiles/groups/selectors.
-        let mut all_bypass_ips = Vec::new();
-        for outbound in &cfg.outbounds {
-            if let Some(server) = &outbound.server {
-                // Heuristic: if it's an IP address, we must bypass it.
-                // If it's a domain, sing-box's auto_detect_interface handles it better than a raw route.
-                if server.parse::<std::net::IpAddr>().is_ok() {
-                    all_bypass_ips.push(server.clone());
-                }
+    /// Keep one previous generation once the log grows past the limit.
+    fn rotate_core_log(path: &std::path::Path) {
+        const MAX_CORE_LOG_BYTES: u64 = 5 * 1024 * 1024;
+        let too_big = std::fs::metadata(path)
+            .map(|m| m.len() > MAX_CORE_LOG_BYTES)
+            .unwrap_or(false);
+        if too_big {
+            let rotated = path.with_extension("log.1");
+            if let Err(e) = std::fs::rename(path, &rotated) {
+                warn!("Failed to rotate core log {:?}: {}", path, e);
             }
         }
+    }
 
-        if !all_bypass_ips.is_empty() {
-            if let Some(route) = &mut cfg.route {
-                info!("Injecting {} proxy server IP bypass rules", all_bypass_ips.len());
-                route.rules.insert(0, crate::config::RouteRule {
-                    ip_cidr: Some(all_bypass_ips),
-                    outbound: Some("direct".to_string()),
-                    ..Default::default()
-                });
-            }
-        }
-        // ------------------------------------------------------------------
+    pub fn get_core_log_path(&self) -> String {
+        self.core_log_path().to_string_lossy().to_string()
+    }
 
-        let json = serde_json::to_string_pretty(&cfg).map_err(|e| e.to_string())?;
-        let config_path = app_local_data.join("config.json");
-        std::fs::write(&config_path, json).map_err(|e| e.to_string())?;
-        Ok(())
+    /// Last `tail_lines` lines of the core log file, oldest first.
+    pub fn read_core_log(&self, tail_lines: usize) -> Result<Vec<String>, TunnetError> {
+        read_log_tail(&self.core_log_path(), tail_lines)
+    }
+
+    /// Last `tail_lines` lines the privileged helper logged, oldest first. TUN mode
+    /// failures often show up only here, never reaching the `proxy-log` stream.
+    pub fn read_helper_log(&self, tail_lines: usize) -> Result<Vec<String>, TunnetError> {
+        read_log_tail(&crate::helper_client::helper_log_path(), tail_lines)
     }
 
-    pub async fn refresh_geodata(&self) -> Result<(), String> {
+    pub async fn refresh_geodata(&self) -> Result<(), TunnetError> {
+        self.databases_verified
+            .store(false, std::sync::atomic::Ordering::SeqCst);
         info!("Refreshing GeoData...");
         let app_local_data = self.app.path().app_local_data_dir().unwrap();
 
@@ -2530,7 +3663,10 @@ impl<R: Runtime> ProxyService<R> {
             }
 
             if !success {
-                return Err(format!("Failed to download {}: {}", filename, last_error));
+                return Err(TunnetError::CoreUnavailable(format!(
+                    "Failed to download {}: {}",
+                    filename, last_error
+                )));
             }
         }
 
@@ -2540,7 +3676,7 @@ impl<R: Runtime> ProxyService<R> {
             if path.exists() {
                 let _ = std::fs::remove_file(&path);
             }
-            let tmp_path = std::env::temp_dir().join(db);
+            let tmp_path = staging_dir().join(db);
             if tmp_path.exists() {
                 let _ = std::fs::remove_file(&tmp_path);
             }
@@ -2557,17 +3693,110 @@ impl<R: Runtime> ProxyService<R> {
         Ok(())
     }
 
-    fn stage_databases(&self) -> Result<(), String> {
+    /// Download `.srs` files for rule sets referenced by user rules that are not cached yet.
+    /// Best-effort: anything that fails here is declared as a remote rule set by `write_config`.
+    async fn ensure_rule_sets(&self) {
+        let app_local_data = self.app.path().app_local_data_dir().unwrap();
+        let rules = self.manager.load_rules().unwrap_or_default();
+
+        let mut tags = HashSet::new();
+        for rule in rules.iter().filter(|r| r.enabled) {
+            let tag = match rule.rule_type.as_str() {
+                "GEOIP" => rule.value.replace("geoip:", "").to_lowercase(),
+                "DOMAIN" if rule.value.starts_with("geosite:") => rule.value.replace("geosite:", ""),
+                _ => continue,
+            };
+            if !app_local_data.join(format!("{}.srs", tag)).exists() {
+                tags.insert(tag);
+            }
+        }
+
+        // Missing rule sets download concurrently rather than one timeout after another
+        let downloads = tags.into_iter().filter_map(|tag| {
+            let (url, fallback_url) = crate::config::remote_rule_set_urls(&tag)?;
+            Some(self.download_rule_set(tag, [url, fallback_url], &app_local_data))
+        });
+        futures_util::future::join_all(downloads).await;
+    }
+
+    async fn download_rule_set(&self, tag: String, urls: [String; 2], dir: &std::path::Path) {
+        for target_url in &urls {
+            info!("Downloading rule set {} from {}", tag, target_url);
+            match self
+                .internal_client
+                .get(target_url)
+                .timeout(std::time::Duration::from_secs(10))
+                .send()
+                .await
+            {
+                Ok(res) if res.status().is_success() => {
+                    if let Ok(bytes) = res.bytes().await {
+                        let path = dir.join(format!("{}.srs", tag));
+                        if std::fs::write(&path, bytes).is_ok() {
+                            return;
+                        }
+                    }
+                }
+                Ok(res) => warn!("{} failed for rule set {}: HTTP {}", target_url, tag, res.status()),
+                Err(e) => warn!("{} failed for rule set {}: {}", target_url, tag, e),
+            }
+        }
+        warn!("Rule set {} not cached, sing-box will fetch it remotely", tag);
+    }
+
+    /// Rule-set tags of one kind ("geosite" or "geoip") for the rule editor: every
+    /// `.srs` on disk (downloaded or bundled) plus a curated list of common categories.
+    /// The compiled rule sets carry no index of their own, hence the static list.
+    pub fn list_geo_categories(&self, kind: &str) -> Result<Vec<String>, TunnetError> {
+        let common = match kind {
+            "geosite" => crate::config::COMMON_GEOSITE_CATEGORIES,
+            "geoip" => crate::config::COMMON_GEOIP_CATEGORIES,
+            _ => {
+                return Err(TunnetError::ConfigInvalid(format!(
+                    "Unknown geo kind '{}', expected geosite or geoip",
+                    kind
+                )))
+            }
+        };
+        let prefix = format!("{}-", kind);
+        let mut tags: std::collections::BTreeSet<String> =
+            common.iter().map(|c| format!("{}{}", prefix, c)).collect();
+
+        let app_local_data = self.app.path().app_local_data_dir().unwrap();
+        let resource_dir = self.app.path().resource_dir().unwrap().join("resources");
+        for dir in [app_local_data, resource_dir] {
+            let Ok(entries) = std::fs::read_dir(&dir) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let name = entry.file_name().to_string_lossy().to_string();
+                if let Some(tag) = name.strip_suffix(".srs").filter(|t| t.starts_with(&prefix)) {
+                    tags.insert(tag.to_string());
+                }
+            }
+        }
+        Ok(tags.into_iter().collect())
+    }
+
+    fn stage_databases(&self) -> Result<(), TunnetError> {
         let app_local_data = self.app.path().app_local_data_dir().unwrap();
-        // Stage databases to temp dir to ensure root/helper can read them (macOS TCC bypass)
-        for db in &[
-            "cache.db",
-        ] {
-            let src = app_local_data.join(db);
-            let dst = std::env::temp_dir().join(db);
+        let resource_dir = self.app.path().resource_dir().unwrap().join("resources");
+        let staging = staging_dir();
+        prepare_staging_dir(&staging)?;
+
+        // Stage databases where root/helper can read them (macOS TCC bypass, Windows SYSTEM service)
+        let mut files: Vec<(String, std::path::PathBuf)> =
+            find_rule_set_files(&[&app_local_data, &resource_dir])
+                .into_iter()
+                .map(|(tag, path)| (format!("{}.srs", tag), path))
+                .collect();
+        files.push(("cache.db".to_string(), app_local_data.join("cache.db")));
+
+        for (name, src) in files {
+            let dst = staging.join(&name);
             if src.exists() {
                 if let Err(e) = std::fs::copy(&src, &dst) {
-                    warn!("Failed to stage {} to temp dir: {}", db, e);
+                    warn!("Failed to stage {} to {:?}: {}", name, staging, e);
                 } else {
                     #[cfg(unix)]
                     {
@@ -2633,21 +3862,177 @@ impl<R: Runtime> ProxyService<R> {
         }
     }
 
-    /// Helper to restart the proxy with the current in-memory state.
-    /// Used by rule updates and other partial config changes.
-    async fn restart_proxy_by_config(&self, tun_mode: bool) -> Result<(), String> {
-        info!("Applying config changes via full restart...");
-        let node = self.latest_node.lock().unwrap().clone();
-        let routing_mode = self.latest_routing_mode.lock().unwrap().clone();
+    /// Helper to restart the proxy with the current in-memory state.
+    /// Used by rule updates and other partial config changes.
+    async fn restart_proxy_by_config(&self, tun_mode: bool) -> Result<(), TunnetError> {
+        // TUN setup is the expensive part of a restart; swap configs in place when we can
+        if tun_mode && *self.tun_mode.lock().unwrap() && *self.local_proxy_running.lock().unwrap()
+        {
+            match self.reload_proxy_config().await {
+                Ok(_) => return Ok(()),
+                Err(e) => warn!("Live reload failed, falling back to full restart: {}", e),
+            }
+        }
+
+        info!("Applying config changes via full restart...");
+        let node = self.latest_node.lock().unwrap().clone();
+        let routing_mode = self.latest_routing_mode.lock().unwrap().clone();
+
+        // Re-entrant call to start_proxy will perform clean STOP -> START
+        // Note: For Dual-Instance, we pass tun_mode from current settings
+        return Box::pin(self.start_proxy(node, tun_mode, routing_mode)).await;
+    }
+
+    /// Regenerate both configs and reload the running instances in place.
+    /// Fails when the change needs a full restart (e.g. port or system proxy changes).
+    async fn reload_proxy_config(&self) -> Result<(), TunnetError> {
+        let _lock = self.start_lock.lock().await;
+        let settings = self.manager.load_settings()?;
+
+        let running = self
+            .running_settings
+            .lock()
+            .unwrap()
+            .clone()
+            .ok_or("Proxy not running")?;
+        if running.mixed_port != settings.mixed_port
+            || running.system_proxy != settings.system_proxy
+            || running.clash_api_port != settings.clash_api_port
+            || running.clash_api_allow_lan != settings.clash_api_allow_lan
+        {
+            return Err("Port or system proxy settings changed".into());
+        }
+
+        info!("Applying config changes via live reload...");
+        self.ensure_rule_sets().await;
+        if *self.tun_mode.lock().unwrap() {
+            self.stage_databases()?;
+        }
+        let node = self.latest_node.lock().unwrap().clone();
+        let routing_mode = self.latest_routing_mode.lock().unwrap().clone();
+        let clash_port = *self.clash_api_port.lock().unwrap();
+        let helper_port = *self.helper_api_port.lock().unwrap();
+
+        let app_local_data = self.app.path().app_local_data_dir().unwrap();
+        let config_file_path = app_local_data.join("config.json");
+        let helper_config_path = app_local_data.join("helper_config.json");
+
+        self.write_config(
+            node.as_ref(),
+            crate::config::ConfigMode::TunOnly,
+            &routing_mode,
+            &settings,
+            helper_port,
+        )?;
+        std::fs::rename(&config_file_path, &helper_config_path).map_err(|e| e.to_string())?;
+        self.write_config(
+            node.as_ref(),
+            crate::config::ConfigMode::SystemProxyOnly,
+            &routing_mode,
+            &settings,
+            clash_port,
+        )?;
+
+        let helper_config_str =
+            std::fs::read_to_string(&helper_config_path).map_err(|e| e.to_string())?;
+        crate::helper_client::HelperClient::new()
+            .reload_proxy(helper_config_str)
+            .map_err(|e| e.to_string())?;
+
+        let config_str = std::fs::read_to_string(&config_file_path).map_err(|e| e.to_string())?;
+        let c_config = CString::new(config_str).map_err(|_| "Config holds null bytes")?;
+        unsafe {
+            let err_ptr = libbox::LibboxReload(c_config.as_ptr());
+            if !err_ptr.is_null() {
+                let err_msg = CStr::from_ptr(err_ptr).to_string_lossy().into_owned();
+                error!("Local LibboxReload failed: {}", err_msg);
+                return Err(err_msg.into());
+            }
+        }
+
+        self.apply_log_level(&settings.log_level);
+        *self.running_settings.lock().unwrap() = Some(settings);
+        let _ = self.app.emit("proxy-status-change", self.get_status());
+        info!("Live reload complete.");
+        Ok(())
+    }
+
+    pub async fn stop_proxy(&self, broadcast: bool) {
+        let Ok(_lock) = self.start_requests.acquire(&self.start_lock).await else {
+            info!("stop_proxy: superseded by a newer request, skipping");
+            return;
+        };
+        self.stop_proxy_internal(broadcast, false).await;
+        self.clear_last_session();
+        self.emit_state(ProxyState::Stopped);
+    }
+
+    fn last_session_path(&self) -> std::path::PathBuf {
+        self.app
+            .path()
+            .app_local_data_dir()
+            .unwrap()
+            .join("last_session.json")
+    }
+
+    fn read_last_session(&self) -> Option<LastSession> {
+        let content = std::fs::read_to_string(self.last_session_path()).ok()?;
+        match serde_json::from_str(&content) {
+            Ok(session) => Some(session),
+            Err(e) => {
+                warn!("Discarding unreadable last_session.json: {}", e);
+                self.clear_last_session();
+                None
+            }
+        }
+    }
+
+    /// The recorded session if it can still be resumed. One without a target, or whose
+    /// node or group has since been removed, is deleted so auto-connect runs normally.
+    fn read_resumable_session(&self) -> Option<LastSession> {
+        let session = self.read_last_session()?;
+        let target_exists = session.node_id.as_deref().is_some_and(|id| {
+            self.get_nodes().is_ok_and(|nodes| nodes.iter().any(|n| n.id == id))
+                || self.get_groups().is_ok_and(|groups| groups.iter().any(|g| g.id == id))
+        });
+        if !target_exists {
+            info!("Discarding last session for missing target {:?}", session.node_id);
+            self.clear_last_session();
+            return None;
+        }
+        Some(session)
+    }
+
+    fn write_last_session(&self, session: &LastSession) {
+        let result = serde_json::to_string_pretty(session)
+            .map_err(|e| e.to_string())
+            .and_then(|json| {
+                crate::manager::write_atomic(&self.last_session_path(), json.as_bytes())
+                    .map_err(|e| e.to_string())
+            });
+        if let Err(e) = result {
+            warn!("Failed to record last session: {}", e);
+        }
+    }
 
-        // Re-entrant call to start_proxy will perform clean STOP -> START
-        // Note: For Dual-Instance, we pass tun_mode from current settings
-        return Box::pin(self.start_proxy(node, tun_mode, routing_mode)).await;
+    /// Forget the crash-resume state; called on every clean stop.
+    pub fn clear_last_session(&self) {
+        let path = self.last_session_path();
+        if path.exists() {
+            if let Err(e) = std::fs::remove_file(&path) {
+                warn!("Failed to remove {:?}: {}", path, e);
+            }
+        }
     }
 
-    pub async fn stop_proxy(&self, broadcast: bool) {
-        let _lock = self.start_lock.lock().await;
-        self.stop_proxy_internal(broadcast, false).await;
+    /// Session the previous run left behind, offered for resume when auto-connect is on
+    /// and nothing is running yet.
+    pub fn get_resumable_session(&self) -> Option<LastSession> {
+        let settings = self.manager.load_settings().ok()?;
+        if !settings.auto_connect || self.is_proxy_running() {
+            return None;
+        }
+        self.read_resumable_session()
     }
 
     /// Synchronous cleanup for application exit (Cmd+Q)
@@ -2680,6 +4065,9 @@ impl<R: Runtime> ProxyService<R> {
         // We call disable_system_proxy which uses synchronous Command
         self.disable_system_proxy();
 
+        // Quitting is a clean stop, nothing to resume next launch
+        self.clear_last_session();
+
         info!("Emergency cleanup finished.");
     }
 
@@ -2992,15 +4380,157 @@ impl<R: Runtime> ProxyService<R> {
         &self,
         url: &str,
         name: Option<String>,
-    ) -> Result<String, String> {
-        let (new_profile, parsed_content) = self.manager.fetch_subscription(url, name).await?;
+        use_proxy: bool,
+    ) -> Result<ImportResult, TunnetError> {
+        let cancel = std::sync::Arc::new(tokio::sync::Notify::new());
+        *self.import_cancel.lock().unwrap() = Some(cancel.clone());
+
+        let app = self.app.clone();
+        let progress = move |stage: &str, nodes: usize| {
+            let _ = app.emit(
+                "import-progress",
+                ImportProgress {
+                    stage: stage.to_string(),
+                    nodes,
+                },
+            );
+        };
+
+        let user_agent = self.subscription_user_agent(None);
+        let proxy_port = self.subscription_proxy_port(use_proxy);
+        let outcome = tokio::select! {
+            res = tokio::time::timeout(
+                IMPORT_TIMEOUT,
+                self.manager
+                    .fetch_subscription_with_progress(url, name, &user_agent, proxy_port, &progress),
+            ) => match res {
+                Ok(res) => res,
+                Err(_) => Err(format!(
+                    "Subscription import timed out after {}s",
+                    IMPORT_TIMEOUT.as_secs()
+                )
+                .into()),
+            },
+            _ = cancel.notified() => {
+                progress("canceled", 0);
+                Err("Import canceled".into())
+            }
+        };
+        *self.import_cancel.lock().unwrap() = None;
+
+        let (new_profile, parsed_content) = outcome?;
+        let result = self.save_imported_profile(new_profile, parsed_content)?;
+        progress("done", 0);
+        Ok(result)
+    }
 
+    /// User-Agent for a subscription fetch: the profile override, else the app-wide setting.
+    fn subscription_user_agent(&self, profile: Option<&crate::profile::Profile>) -> String {
+        profile
+            .and_then(|p| p.user_agent.clone())
+            .filter(|ua| !ua.trim().is_empty())
+            .or_else(|| {
+                self.manager
+                    .load_settings()
+                    .ok()
+                    .map(|s| s.subscription_user_agent)
+                    .filter(|ua| !ua.trim().is_empty())
+            })
+            .unwrap_or_else(|| crate::settings::DEFAULT_SUBSCRIPTION_USER_AGENT.to_string())
+    }
+
+    /// Mixed port to fetch subscriptions through when `use_proxy` is set and the proxy
+    /// is up, like `check_ip`. TUN mode already captures the fetch, so it goes direct.
+    fn subscription_proxy_port(&self, use_proxy: bool) -> Option<u16> {
+        if !use_proxy || !self.is_proxy_running() || self.is_tun_mode() {
+            return None;
+        }
+        self.get_app_settings().ok().map(|s| s.mixed_port)
+    }
+
+    /// Abort the running `import_subscription`. Returns false when nothing was importing.
+    pub fn cancel_import(&self) -> bool {
+        match self.import_cancel.lock().unwrap().take() {
+            Some(cancel) => {
+                cancel.notify_one();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Import a subscription or config file (.json/.yaml/.txt) from disk as a local profile
+    pub fn import_from_file(&self, path: &str) -> Result<ImportResult, TunnetError> {
+        const MAX_FILE_SIZE: u64 = 32 * 1024 * 1024;
+
+        let path = std::path::Path::new(path);
+        let meta = std::fs::metadata(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        if meta.len() > MAX_FILE_SIZE {
+            return Err(format!(
+                "File is too large ({} MB), the limit is {} MB",
+                meta.len() / 1024 / 1024,
+                MAX_FILE_SIZE / 1024 / 1024
+            ).into());
+        }
+
+        let bytes = std::fs::read(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let content = String::from_utf8(bytes)
+            .map_err(|_| "File is not valid UTF-8 text".to_string())?;
+
+        let name = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or("Local Import".to_string());
+
+        let (new_profile, parsed_content) =
+            crate::manager::CoreManager::<R>::profile_from_content(&content, name);
+        self.save_imported_profile(new_profile, parsed_content)
+    }
+
+    /// Import pasted share links (any mix of schemes and base64 chunks) as a local profile.
+    pub fn import_links(&self, text: &str) -> Result<LinkImportResult, TunnetError> {
+        let (new_profile, parsed_content) = crate::manager::CoreManager::<R>::profile_from_content(
+            text,
+            "Pasted Links".to_string(),
+        );
+        let result = self.save_imported_profile(new_profile, parsed_content)?;
+        // Count what was saved, after duplicates were dropped
+        let mut protocols = std::collections::BTreeMap::new();
+        let profiles = self.manager.load_profiles()?;
+        if let Some(saved) = profiles.iter().find(|p| p.id == result.profile_id) {
+            for node in &saved.nodes {
+                *protocols.entry(node.protocol.clone()).or_insert(0) += 1;
+            }
+        }
+        Ok(LinkImportResult {
+            profile_id: result.profile_id,
+            duplicates_removed: result.duplicates_removed,
+            protocols,
+        })
+    }
+
+    fn save_imported_profile(
+        &self,
+        mut new_profile: crate::profile::Profile,
+        parsed_content: crate::profile::ParsedContent,
+    ) -> Result<ImportResult, TunnetError> {
         if new_profile.nodes.is_empty() {
-            return Err("No valid nodes found in this subscription".to_string());
+            return Err("No valid nodes found in this subscription".into());
+        }
+
+        let mut duplicates_removed = 0;
+        if self.manager.load_settings().map(|s| s.dedup_on_import).unwrap_or(true) {
+            duplicates_removed = crate::profile::parser::dedup_nodes(&mut new_profile.nodes);
+            if duplicates_removed > 0 {
+                info!("Removed {} duplicate nodes from import", duplicates_removed);
+            }
         }
 
         let mut profiles = self.manager.load_profiles()?;
         let id_clone = new_profile.id.clone();
+        self.warn_subscription_usage(&new_profile);
 
         // Remove existing profile with same URL or ID if logic requires,
         // but for now we just append. Maybe check for duplicate URL?
@@ -3056,14 +4586,42 @@ impl<R: Runtime> ProxyService<R> {
         // Probes are now triggered by the frontend to ensure UI consistency and avoid race conditions
 
 
-        Ok(id_clone)
+        Ok(ImportResult {
+            profile_id: id_clone,
+            duplicates_removed,
+        })
     }
 
-    pub fn get_profiles(&self) -> Result<Vec<crate::profile::Profile>, String> {
+    pub fn get_profiles(&self) -> Result<Vec<crate::profile::Profile>, TunnetError> {
         self.manager.load_profiles()
     }
 
-    pub async fn delete_profile(&self, profile_id: &str) -> Result<(), String> {
+    pub fn get_profile_usage(&self, profile_id: &str) -> Result<crate::profile::ProfileUsage, TunnetError> {
+        let profile = self
+            .manager
+            .load_profiles()?
+            .into_iter()
+            .find(|p| p.id == profile_id)
+            .ok_or_else(|| format!("Profile not found: {}", profile_id))?;
+        Ok(profile.usage(unix_now()))
+    }
+
+    /// Emit `subscription-warning` when a subscription is close to its traffic or expiry limit.
+    fn warn_subscription_usage(&self, profile: &crate::profile::Profile) {
+        let usage = profile.usage(unix_now());
+        if usage.needs_warning() {
+            info!(
+                "Subscription '{}' is near its limit: {:?}% used, expires in {:?} days",
+                profile.name, usage.percent, usage.expires_in_days
+            );
+            let _ = self.app.emit(
+                "subscription-warning",
+                serde_json::json!({ "id": profile.id, "name": profile.name, "usage": usage }),
+            );
+        }
+    }
+
+    pub async fn delete_profile(&self, profile_id: &str) -> Result<(), TunnetError> {
         let is_running = self.is_proxy_running();
         let mut profiles = self.manager.load_profiles()?;
         
@@ -3081,7 +4639,7 @@ impl<R: Runtime> ProxyService<R> {
                 let latest = self.latest_node.lock().unwrap();
                 if let Some(n) = latest.as_ref() {
                     if p.nodes.iter().any(|node| node.id == n.id) {
-                        return Err("delete_active_error".to_string());
+                        return Err("delete_active_error".into());
                     }
                 }
             }
@@ -3114,12 +4672,6 @@ impl<R: Runtime> ProxyService<R> {
             let mut restart_needed = false;
             let mut groups_to_delete = Vec::new();
 
-            // Collect all remaining node IDs for Filter evaluation
-            let remaining_node_ids: std::collections::HashSet<String> = profiles
-                .iter()
-                .flat_map(|p| p.nodes.iter().map(|n| n.id.clone()))
-                .collect();
-
             for group in groups.iter_mut() {
                 // Only skip system-protected groups (not auto-generated ones)
                 if group.id.starts_with("system:") {
@@ -3140,17 +4692,9 @@ impl<R: Runtime> ProxyService<R> {
                     }
                     crate::profile::GroupSource::Filter { criteria } => {
                         // For Filter groups, check if any remaining nodes match the criteria
-                        let has_matching_nodes = if let Some(keywords) = &criteria.keywords {
-                            profiles.iter().any(|p| {
-                                p.nodes.iter().any(|n| {
-                                    let node_name = n.name.to_lowercase();
-                                    keywords.iter().any(|kw| node_name.contains(&kw.to_lowercase()))
-                                })
-                            })
-                        } else {
-                            // No criteria means match all, so has nodes if any remain
-                            !remaining_node_ids.is_empty()
-                        };
+                        let has_matching_nodes = profiles
+                            .iter()
+                            .any(|p| p.nodes.iter().any(|n| criteria.matches(n)));
                         
                         if !has_matching_nodes {
                             should_check_empty = true;
@@ -3197,7 +4741,8 @@ impl<R: Runtime> ProxyService<R> {
         url: Option<String>,
         update_interval: Option<u64>,
         clear_interval: bool,
-    ) -> Result<(), String> {
+        user_agent: Option<String>,
+    ) -> Result<(), TunnetError> {
         let mut profiles = self.manager.load_profiles()?;
         if let Some(profile) = profiles.iter_mut().find(|p| p.id == id) {
             profile.name = name.to_string();
@@ -3220,46 +4765,132 @@ impl<R: Runtime> ProxyService<R> {
                 profile.update_interval = update_interval;
             }
 
+            // Empty string falls back to the app-wide User-Agent
+            if let Some(ua) = user_agent {
+                let ua = ua.trim();
+                profile.user_agent = if ua.is_empty() { None } else { Some(ua.to_string()) };
+            }
+
             self.manager.save_profiles(&profiles)?;
             Ok(())
         } else {
-            Err(format!("Profile {} not found", id))
+            Err(format!("Profile {} not found", id).into())
         }
     }
 
-    pub async fn update_subscription_profile(&self, profile_id: &str) -> Result<Vec<String>, String> {
+    pub async fn update_subscription_profile(
+        &self,
+        profile_id: &str,
+        use_proxy: bool,
+    ) -> Result<Vec<String>, TunnetError> {
         let mut profiles = self.manager.load_profiles().unwrap_or_default();
         if let Some(pos) = profiles.iter().position(|p| p.id == profile_id) {
             if let Some(url) = &profiles[pos].url {
                 // Keep name and user preference for update interval
                 let name = profiles[pos].name.clone();
                 let user_interval = profiles[pos].update_interval;
+                let user_agent = self.subscription_user_agent(Some(&profiles[pos]));
+                let proxy_port = self.subscription_proxy_port(use_proxy);
 
-                let (updated_profile, _parsed_content) = self.manager.fetch_subscription(url, Some(name)).await?;
+                let (updated_profile, _parsed_content) = self
+                    .manager
+                    .fetch_subscription(url, Some(name), &user_agent, proxy_port)
+                    .await?;
 
                 if updated_profile.nodes.is_empty() {
-                    return Err("No valid nodes found in this subscription".to_string());
+                    return Err("No valid nodes found in this subscription".into());
                 }
 
                 // Preserve ID to keep selection valid if possible, but fetch generates new ID.
                 // Let's reuse the old ID.
                 let mut p = updated_profile;
+                if self.manager.load_settings().map(|s| s.dedup_on_import).unwrap_or(true) {
+                    crate::profile::parser::dedup_nodes(&mut p.nodes);
+                }
                 p.id = profiles[pos].id.clone();
+                // Keep ids stable so selections, groups and probe results survive the update
+                p.nodes = crate::profile::reconcile_nodes(&profiles[pos].nodes, p.nodes);
                 p.update_interval = user_interval; // Restore user preference
+                p.user_agent = profiles[pos].user_agent.clone();
                 // p.header_update_interval is already set by fetch_subscription
 
                 let node_ids: Vec<String> = p.nodes.iter().map(|n| n.id.clone()).collect();
-                
+                self.warn_subscription_usage(&p);
+
                 profiles[pos] = p;
                 self.manager.save_profiles(&profiles)?;
                 
                 return Ok(node_ids);
             }
         }
-        Err("Profile not found or has no URL".to_string())
+        Err("Profile not found or has no URL".into())
+    }
+
+    /// Periodically refresh subscriptions whose update interval has elapsed.
+    /// The user-set `update_interval` takes precedence over the one advertised by the server.
+    pub fn start_subscription_scheduler(&self) {
+        let app_handle = self.app.clone();
+        tauri::async_runtime::spawn(async move {
+            // Failed fetches are not retried until another full interval has passed
+            let mut last_attempt: std::collections::HashMap<String, u64> =
+                std::collections::HashMap::new();
+            loop {
+                tokio::time::sleep(tokio::time::Duration::from_secs(60)).await;
+
+                let service = app_handle.state::<ProxyService<R>>();
+                let profiles = match service.manager.load_profiles() {
+                    Ok(p) => p,
+                    Err(e) => {
+                        error!("Subscription scheduler failed to load profiles: {}", e);
+                        continue;
+                    }
+                };
+
+                let now = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs();
+
+                for p in profiles {
+                    if p.url.is_none() {
+                        continue;
+                    }
+                    let interval = match p.update_interval.or(p.header_update_interval) {
+                        Some(i) if i > 0 => i,
+                        _ => continue,
+                    };
+                    let last = p
+                        .last_updated
+                        .unwrap_or(0)
+                        .max(last_attempt.get(&p.id).copied().unwrap_or(0));
+                    if now.saturating_sub(last) < interval {
+                        continue;
+                    }
+                    last_attempt.insert(p.id.clone(), now);
+
+                    info!("Scheduled update for subscription '{}' ({})", p.name, p.id);
+                    match service.update_subscription_profile(&p.id, false).await {
+                        Ok(node_ids) => {
+                            let _ = app_handle.emit(
+                                "subscription-updated",
+                                serde_json::json!({ "id": p.id, "node_ids": node_ids }),
+                            );
+                            let _ = app_handle.emit("profiles-update", Some(node_ids));
+                        }
+                        Err(e) => {
+                            warn!("Scheduled update for '{}' failed: {}", p.name, e);
+                            let _ = app_handle.emit(
+                                "subscription-updated",
+                                serde_json::json!({ "id": p.id, "error": e }),
+                            );
+                        }
+                    }
+                }
+            }
+        });
     }
 
-    pub fn get_nodes(&self) -> Result<Vec<crate::profile::Node>, String> {
+    pub fn get_nodes(&self) -> Result<Vec<crate::profile::Node>, TunnetError> {
         let profiles = self.manager.load_profiles()?;
         let mut all_nodes = vec![];
         for p in profiles {
@@ -3268,42 +4899,102 @@ impl<R: Runtime> ProxyService<R> {
         Ok(all_nodes)
     }
 
-    pub async fn save_rules(&self, rules: Vec<crate::profile::Rule>) -> Result<(), String> {
+    /// Nodes worth pruning, see `Node::is_stale`.
+    pub fn get_stale_nodes(&self, days: u64) -> Result<Vec<crate::profile::Node>, TunnetError> {
+        let now = unix_now();
+        Ok(self
+            .get_nodes()?
+            .into_iter()
+            .filter(|n| n.is_stale(days, now))
+            .collect())
+    }
+
+    pub fn get_nodes_sorted(
+        &self,
+        by: crate::profile::NodeSortKey,
+    ) -> Result<Vec<crate::profile::Node>, TunnetError> {
+        let mut nodes = self.get_nodes()?;
+        crate::profile::sort_nodes(&mut nodes, by);
+        Ok(nodes)
+    }
+
+    pub async fn save_rules(&self, rules: Vec<crate::profile::Rule>) -> Result<(), TunnetError> {
         self.manager.save_rules(&rules)?;
         Ok(())
     }
 
-    pub async fn add_rule(&self, rule: crate::profile::Rule) -> Result<(), String> {
+    pub async fn add_rule(&self, mut rule: crate::profile::Rule) -> Result<(), TunnetError> {
+        rule.normalize();
+        rule.validate()?;
         let mut rules = self.manager.load_rules()?;
         rules.push(rule);
         self.manager.save_rules(&rules)?;
         Ok(())
     }
 
-    pub async fn update_rule(&self, rule: crate::profile::Rule) -> Result<(), String> {
+    pub async fn update_rule(&self, mut rule: crate::profile::Rule) -> Result<(), TunnetError> {
+        rule.normalize();
+        rule.validate()?;
         let mut rules = self.manager.load_rules()?;
         if let Some(pos) = rules.iter().position(|r| r.id == rule.id) {
             rules[pos] = rule;
             self.manager.save_rules(&rules)?;
             Ok(())
         } else {
-            Err("Rule not found".to_string())
+            Err("Rule not found".into())
         }
     }
 
-    pub async fn delete_rule(&self, id: &str) -> Result<(), String> {
+    /// Change a rule's priority; sing-box evaluates rules top to bottom
+    pub async fn move_rule(&self, id: &str, new_index: usize) -> Result<(), TunnetError> {
+        let rules = self.manager.load_rules()?;
+        crate::manager::save_moved_rule(&self.manager.get_rules_path(), rules, id, new_index)
+    }
+
+    pub async fn delete_rule(&self, id: &str) -> Result<(), TunnetError> {
         let mut rules = self.manager.load_rules()?;
         rules.retain(|r| r.id != id);
         self.manager.save_rules(&rules)?;
         Ok(())
     }
 
-    pub fn get_rules(&self) -> Result<Vec<crate::profile::Rule>, String> {
+    /// Report which policy a connection to `host:port` would use under the current rules.
+    /// Walks the route rules of the config the core would be started with, entering
+    /// through the TUN inbound when TUN is on and the mixed inbound otherwise.
+    pub fn simulate_route(&self, host: &str, port: u16) -> Result<crate::profile::RouteDecision, TunnetError> {
+        let rules = self.manager.load_rules()?;
+        let settings = self.manager.load_settings()?;
+        let (routing_mode, tun_mode) = if self.is_proxy_running() {
+            (self.latest_routing_mode.lock().unwrap().clone(), *self.tun_mode.lock().unwrap())
+        } else {
+            (settings.routing_mode.clone().unwrap_or("rule".to_string()), settings.tun_mode)
+        };
+        let (mode, inbound) = if tun_mode {
+            (crate::config::ConfigMode::TunOnly, "tun-in")
+        } else {
+            (crate::config::ConfigMode::SystemProxyOnly, "mixed-in")
+        };
+        let node = self.latest_node.lock().unwrap().clone();
+
+        let (cfg, origins) = self.with_config_sources(mode, |sources| {
+            build_config_with_origins(node.as_ref(), mode, &routing_mode, &settings, None, sources)
+        });
+        Ok(crate::profile::simulate_route(
+            &cfg.route.unwrap_or_default(),
+            &origins,
+            &rules,
+            inbound,
+            host,
+            port,
+        ))
+    }
+
+    pub fn get_rules(&self) -> Result<Vec<crate::profile::Rule>, TunnetError> {
         self.manager.load_rules()
     }
 
     // Group Management
-    pub fn get_groups(&self) -> Result<Vec<crate::profile::Group>, String> {
+    pub fn get_groups(&self) -> Result<Vec<crate::profile::Group>, TunnetError> {
         let saved_groups = self.manager.load_groups().unwrap_or_default();
 
         // Helper to find saved state
@@ -3510,7 +5201,7 @@ impl<R: Runtime> ProxyService<R> {
         Ok(final_groups)
     }
 
-    pub async fn save_groups(&self, groups: Vec<crate::profile::Group>) -> Result<(), String> {
+    pub async fn save_groups(&self, groups: Vec<crate::profile::Group>) -> Result<(), TunnetError> {
         self.manager.save_groups(&groups)?;
         if self.is_proxy_running() {
             let tun = *self.tun_mode.lock().unwrap();
@@ -3521,7 +5212,7 @@ impl<R: Runtime> ProxyService<R> {
     }
 
     /// Check if a group is currently in use (either active or referenced by rules)
-    fn is_group_in_use(&self, group_id: &str) -> Result<bool, String> {
+    fn is_group_in_use(&self, group_id: &str) -> Result<bool, TunnetError> {
         let settings = self.manager.load_settings()?;
         
         // 1. Check if it's the currently active target
@@ -3542,7 +5233,7 @@ impl<R: Runtime> ProxyService<R> {
         Ok(false)
     }
 
-    pub async fn add_group(&self, group: crate::profile::Group) -> Result<(), String> {
+    pub async fn add_group(&self, group: crate::profile::Group) -> Result<(), TunnetError> {
         let mut groups = self.manager.load_groups().unwrap_or_default();
         groups.push(group);
         self.manager.save_groups(&groups)?;
@@ -3551,7 +5242,7 @@ impl<R: Runtime> ProxyService<R> {
         Ok(())
     }
 
-    pub async fn update_group(&self, group: crate::profile::Group) -> Result<(), String> {
+    pub async fn update_group(&self, group: crate::profile::Group) -> Result<(), TunnetError> {
         let mut groups = self.manager.load_groups().unwrap_or_default();
         if let Some(pos) = groups.iter().position(|g| g.id == group.id) {
             groups[pos] = group.clone();
@@ -3576,11 +5267,11 @@ impl<R: Runtime> ProxyService<R> {
                 Ok(())
             }
         } else {
-            Err("Group not found".to_string())
+            Err("Group not found".into())
         }
     }
 
-    pub async fn delete_group(&self, id: &str) -> Result<(), String> {
+    pub async fn delete_group(&self, id: &str) -> Result<(), TunnetError> {
         // Check if the group is in use before deleting
         let in_use = self.is_proxy_running() && self.is_group_in_use(id).unwrap_or(false);
         
@@ -3597,16 +5288,81 @@ impl<R: Runtime> ProxyService<R> {
         }
     }
 
-    pub fn get_app_settings(&self) -> Result<crate::settings::AppSettings, String> {
+    pub fn get_app_settings(&self) -> Result<crate::settings::AppSettings, TunnetError> {
         self.manager.load_settings()
     }
 
+    /// Persist `settings` like `save_app_settings`, and when `apply` is set and the
+    /// proxy is running, reload or restart it if a connection-affecting field changed.
+    pub async fn update_settings(
+        &self,
+        settings: crate::settings::AppSettings,
+        apply: bool,
+    ) -> Result<SettingsUpdate, TunnetError> {
+        let old_settings = self.manager.load_settings().unwrap_or_default();
+        let changed: Vec<String> = settings
+            .connection_changes(&old_settings)
+            .into_iter()
+            .map(String::from)
+            .collect();
+        let was_running = self.is_proxy_running();
+        let running_before = self.running_settings.lock().unwrap().clone();
+
+        self.save_app_settings(settings.clone()).await?;
+
+        if !apply || !was_running || changed.is_empty() {
+            return Ok(SettingsUpdate {
+                changed,
+                applied: false,
+            });
+        }
+
+        // save_app_settings marks the new settings as running for the UI; put the
+        // real ones back so the reload path can tell what the core is actually using
+        *self.running_settings.lock().unwrap() = running_before;
+        if changed.iter().any(|c| c == "routing_mode") {
+            if let Some(mode) = &settings.routing_mode {
+                *self.latest_routing_mode.lock().unwrap() = mode.clone();
+            }
+        }
+        info!("Applying changed settings to the running proxy: {}", changed.join(", "));
+        self.restart_proxy_by_config(settings.tun_mode).await?;
+
+        Ok(SettingsUpdate {
+            changed,
+            applied: true,
+        })
+    }
+
+    /// Replace the list of CIDRs that bypass the TUN interface.
+    pub async fn set_tun_bypass_cidrs(&self, cidrs: Vec<String>) -> Result<(), TunnetError> {
+        let mut cleaned: Vec<String> = Vec::new();
+        for cidr in cidrs.iter().map(|c| c.trim()).filter(|c| !c.is_empty()) {
+            crate::profile::validate_cidr(cidr).map_err(TunnetError::ConfigInvalid)?;
+            if !cleaned.iter().any(|c| c == cidr) {
+                cleaned.push(cidr.to_string());
+            }
+        }
+
+        let mut settings = self.manager.load_settings()?;
+        settings.tun_bypass_cidrs = cleaned;
+        self.manager.save_settings(&settings)?;
+        let _ = self.app.emit("settings-update", &settings);
+
+        let tun = *self.tun_mode.lock().unwrap();
+        if tun && self.is_proxy_running() {
+            self.restart_proxy_by_config(tun).await
+        } else {
+            Ok(())
+        }
+    }
+
     pub fn ensure_auto_group(
         &self,
         name: String,
         references: Vec<String>,
         group_type: crate::profile::GroupType,
-    ) -> Result<String, String> {
+    ) -> Result<String, TunnetError> {
         let mut groups = self.manager.load_groups().map_err(|e| e.to_string())?;
 
         // Format an ID from name (e.g. "Auto - US" -> "auto_us") but use UUID to avoid collision?
@@ -3659,7 +5415,9 @@ impl<R: Runtime> ProxyService<R> {
     pub async fn save_app_settings(
         &self,
         settings: crate::settings::AppSettings,
-    ) -> Result<(), String> {
+    ) -> Result<(), TunnetError> {
+        settings.validate().map_err(TunnetError::ConfigInvalid)?;
+
         // Load old settings to compare
         let old_settings = self.manager.load_settings().unwrap_or_default();
 
@@ -3737,7 +5495,16 @@ impl<R: Runtime> ProxyService<R> {
         Ok(())
     }
 
-    pub async fn add_node(&self, node: crate::profile::Node) -> Result<(), String> {
+    /// Add a node from a full sing-box outbound JSON, for protocols and options the
+    /// node editor does not model.
+    pub async fn add_node_from_outbound_json(&self, json: &str) -> Result<crate::profile::Node, TunnetError> {
+        let node = crate::profile::Node::from_outbound_json(json)?;
+        self.add_node(node.clone()).await?;
+        Ok(node)
+    }
+
+    pub async fn add_node(&self, node: crate::profile::Node) -> Result<(), TunnetError> {
+        node.validate().map_err(|problems| problems.join("; "))?;
         let mut profiles = self.manager.load_profiles()?;
 
         // Find or create "Local" profile
@@ -3761,6 +5528,8 @@ impl<R: Runtime> ProxyService<R> {
                 web_page_url: None,
                 update_interval: None,
                 header_update_interval: None,
+                last_updated: None,
+                user_agent: None,
             });
         }
         self.manager.save_profiles(&profiles)?;
@@ -3780,7 +5549,8 @@ impl<R: Runtime> ProxyService<R> {
         Ok(())
     }
 
-    pub async fn update_node(&self, node: crate::profile::Node) -> Result<(), String> {
+    pub async fn update_node(&self, node: crate::profile::Node) -> Result<(), TunnetError> {
+        node.validate().map_err(|problems| problems.join("; "))?;
         let mut profiles = self.manager.load_profiles()?;
         let mut found = false;
 
@@ -3805,16 +5575,30 @@ impl<R: Runtime> ProxyService<R> {
 
             Ok(())
         } else {
-            Err("Node not found".to_string())
+            Err(TunnetError::NodeNotFound(node_id))
+        }
+    }
+
+    /// Reposition a node within its profile
+    pub fn move_node(&self, id: &str, new_index: usize) -> Result<(), TunnetError> {
+        let mut profiles = self.manager.load_profiles()?;
+        let moved = profiles
+            .iter_mut()
+            .any(|p| crate::profile::move_by_id(&mut p.nodes, id, new_index, |n| &n.id));
+        if !moved {
+            return Err(TunnetError::NodeNotFound(id.to_string()));
         }
+        self.manager.save_profiles(&profiles)?;
+        let _ = self.app.emit("profiles-update", ());
+        Ok(())
     }
 
-    pub fn delete_node(&self, id: &str) -> Result<(), String> {
+    pub fn delete_node(&self, id: &str) -> Result<(), TunnetError> {
         if self.is_proxy_running() {
             let latest = self.latest_node.lock().unwrap();
             if let Some(n) = latest.as_ref() {
                 if n.id == id {
-                    return Err("delete_active_error".to_string());
+                    return Err("delete_active_error".into());
                 }
             }
         }
@@ -3848,7 +5632,8 @@ impl<R: Runtime> ProxyService<R> {
         *self.tun_mode.lock().unwrap()
     }
 
-    pub async fn probe_nodes_latency(&self, node_ids: Vec<String>) -> Result<(), String> {
+    pub async fn probe_nodes_latency(&self, node_ids: Vec<String>) -> Result<(), TunnetError> {
+        let epoch = self.test_epoch.current();
         let profiles = self.manager.load_profiles()?;
         let mut updates = std::collections::HashMap::new();
 
@@ -3860,14 +5645,11 @@ impl<R: Runtime> ProxyService<R> {
                     continue;
                 }
                 
-                // Only probe supported protocols
-                match n.protocol.as_str() {
-                    "vmess" | "vless" | "shadowsocks" | "ss" | "trojan" | "hysteria2" | "hy2" | "tuic" | "anytls" => {
-                        target_nodes.push(n.clone());
-                    }
-                    _ => {
-                        debug!("Skipping latency probe for unsupported protocol: {}", n.protocol);
-                    }
+                // Only probe nodes a live config could use
+                if outbound_supported(n) {
+                    target_nodes.push(n.clone());
+                } else {
+                    debug!("Skipping latency probe for unsupported protocol: {}", n.protocol);
                 }
             }
         }
@@ -3878,6 +5660,8 @@ impl<R: Runtime> ProxyService<R> {
 
         let settings = self.manager.load_settings()?;
         let log_level = settings.log_level.to_lowercase();
+        let concurrency = settings.probe_concurrency.max(1);
+        let timeout_ms = (settings.probe_timeout_secs.max(1) * 1000) as i64;
 
         // Unified Native URLTest Strategy (Hiddify-like)
         // Uses sing-box native `URLTest` group for max performance and consistency.
@@ -3891,78 +5675,203 @@ impl<R: Runtime> ProxyService<R> {
             outbound.tag = node.id.clone(); 
             outbounds.push(outbound);
         }
-            
-        if !outbounds.is_empty() {
+
+        // Run in bounded batches so a large subscription doesn't open hundreds
+        // of outbound connections at once.
+        // Test batches share a cache file of their own: cache.db belongs to the running
+        // core, and opening it from a batch fails with "database is locked"
+        let cache_path = self.app.path().app_local_data_dir().unwrap().join("cache_test.db");
+        let mut tested = std::collections::HashSet::new();
+        for chunk in outbounds.chunks(concurrency) {
+             // Results so far are still saved below
+             if !self.test_epoch.is_current(epoch) {
+                 info!("Latency test canceled after {} of {} nodes", tested.len(), outbounds.len());
+                 break;
+             }
+             let chunk_ids: Vec<String> = chunk.iter().map(|o| o.tag.clone()).collect();
              // Pass log level to Go
              let wrapper = serde_json::json!({
-                 "outbounds": outbounds,
-                 "log_level": log_level
+                 "outbounds": chunk,
+                 "log_level": log_level,
+                 "cache_path": cache_path.to_string_lossy(),
              });
              let json_str = wrapper.to_string();
 
              let outbound_c = std::ffi::CString::new(json_str).unwrap();
-             // URL is configured in Go URLTest group now (but we pass it anyway for compatibility if needed)
-             let target_c = std::ffi::CString::new("http://cp.cloudflare.com/generate_204").unwrap();
+             // The Go side puts this URL on its URLTest group, which times the first response byte
+             let target_c = std::ffi::CString::new(settings.latency_test_url.as_str())
+                 .map_err(|_| "Latency test URL holds null bytes")?;
              
              // Run FFI in a blocking thread
-             let updates_clone = std::sync::Arc::new(std::sync::Mutex::new(std::collections::HashMap::new()));
-             let updates_c = updates_clone.clone();
-             
-             tokio::task::spawn_blocking(move || {
+             let results = tokio::task::spawn_blocking(move || {
                  let res_ptr = unsafe {
                      crate::libbox::LibboxTestBatch(
                          outbound_c.as_ptr(),
                          target_c.as_ptr(),
-                         5000 // 5s timeout
+                         timeout_ms
                      )
                  };
                  
-                 if !res_ptr.is_null() {
-                      let res_str = unsafe {
-                         std::ffi::CStr::from_ptr(res_ptr)
-                             .to_string_lossy()
-                             .into_owned()
-                     };
-                     
-                     if let Ok(results) = serde_json::from_str::<std::collections::HashMap<String, u64>>(&res_str) {
-                         let mut u = updates_c.lock().unwrap();
-                         for (id, latency) in results {
-                             u.insert(id, latency);
-                         }
-                     }
+                 if res_ptr.is_null() {
+                     return std::collections::HashMap::new();
                  }
+                 let res_str = unsafe {
+                     std::ffi::CStr::from_ptr(res_ptr)
+                         .to_string_lossy()
+                         .into_owned()
+                 };
+                 serde_json::from_str::<std::collections::HashMap<String, u64>>(&res_str)
+                     .unwrap_or_default()
              }).await.map_err(|e| e.to_string())?;
              
-             let u = updates_clone.lock().unwrap();
-             for (id, latency) in u.iter() {
-                 updates.insert(id.clone(), *latency);
+             // An empty answer means the batch itself failed, which says nothing about the nodes
+             if !results.is_empty() {
+                 let canceled = !self.test_epoch.is_current(epoch);
+                 for node_id in chunk_ids {
+                     // A cancel cut the others short, which is not a failed test
+                     if canceled && !results.contains_key(&node_id) {
+                         continue;
+                     }
+                     tested.insert(node_id.clone());
+                     let _ = self.app.emit("latency-test-progress", LatencyProgress {
+                         latency: results.get(&node_id).copied().filter(|l| *l > 0),
+                         node_id,
+                         done: tested.len(),
+                         total: outbounds.len(),
+                     });
+                 }
              }
+             updates.extend(results);
         }
 
         // 3. Apply updates
-        // Reload profiles to minimize race condition window (overwrite risk)
-        let mut profiles = self.manager.load_profiles()?;
-        for p in &mut profiles {
-            for n in &mut p.nodes {
-                if let Some(ping) = updates.get(&n.id) {
-                    n.ping = Some(*ping);
+        let results: std::collections::HashMap<String, Option<u64>> = tested
+            .into_iter()
+            .map(|id| {
+                let latency = updates.get(&id).copied();
+                (id, latency)
+            })
+            .collect();
+        self.record_test_results(&results)?;
+        let _ = self.app.emit("profiles-update", Some(updates.keys().cloned().collect::<Vec<String>>()));
+
+        Ok(())
+    }
+
+    /// Latency-test every node of a profile ("test all"), reporting each node through
+    /// `latency-test-progress`. Returns the profile's nodes fastest first.
+    pub async fn test_profile(&self, profile_id: &str) -> Result<Vec<crate::profile::Node>, TunnetError> {
+        let node_ids: Vec<String> = self
+            .manager
+            .load_profiles()?
+            .into_iter()
+            .find(|p| p.id == profile_id)
+            .ok_or_else(|| format!("Profile not found: {}", profile_id))?
+            .nodes
+            .into_iter()
+            .map(|n| n.id)
+            .collect();
+        // An empty id list means every node to probe_nodes_latency
+        if !node_ids.is_empty() {
+            self.probe_nodes_latency(node_ids).await?;
+        }
+
+        let mut nodes = self
+            .manager
+            .load_profiles()?
+            .into_iter()
+            .find(|p| p.id == profile_id)
+            .map(|p| p.nodes)
+            .unwrap_or_default();
+        crate::profile::sort_nodes(&mut nodes, crate::profile::NodeSortKey::Latency);
+        Ok(nodes)
+    }
+
+    /// Stop the latency and location tests in flight. Running core calls are aborted
+    /// and the test core shut down; results gathered so far are still saved.
+    pub fn cancel_tests(&self) {
+        self.test_epoch.next();
+        tauri::async_runtime::spawn_blocking(|| unsafe { crate::libbox::LibboxCancelTests() });
+    }
+
+    /// ISO country code of a node's server from the local geoip rule sets, without
+    /// connecting through it. Falls back to the live location probe when no rule set
+    /// matches, which answers with the node's exit country.
+    pub async fn resolve_node_country(&self, node_id: String) -> Result<String, TunnetError> {
+        let node = self
+            .get_nodes()?
+            .into_iter()
+            .find(|n| n.id == node_id)
+            .ok_or_else(|| TunnetError::NodeNotFound(node_id.clone()))?;
+
+        let ip = match node.server.parse::<std::net::IpAddr>() {
+            Ok(ip) => Some(ip),
+            Err(_) => tokio::net::lookup_host((node.server.as_str(), node.port))
+                .await
+                .ok()
+                .and_then(|mut addrs| addrs.next())
+                .map(|a| a.ip()),
+        };
+
+        if let Some(ip) = ip {
+            let rule_set_dir = self.app.path().app_local_data_dir().unwrap();
+            let lookup = tokio::task::spawn_blocking(move || {
+                let ip_c = CString::new(ip.to_string()).ok()?;
+                let dir_c = CString::new(rule_set_dir.to_string_lossy().to_string()).ok()?;
+                unsafe {
+                    let ptr = libbox::LibboxGeoIPLookup(ip_c.as_ptr(), dir_c.as_ptr());
+                    if ptr.is_null() {
+                        return None;
+                    }
+                    let raw = CStr::from_ptr(ptr).to_string_lossy().into_owned();
+                    let val: serde_json::Value = serde_json::from_str(&raw).ok()?;
+                    val["country"].as_str().filter(|c| !c.is_empty()).map(|c| c.to_string())
                 }
+            })
+            .await
+            .ok()
+            .flatten();
+            if let Some(code) = lookup {
+                return Ok(code);
             }
         }
-        self.manager.save_profiles(&profiles)?;
-        let _ = self.app.emit("profiles-update", Some(updates.keys().cloned().collect::<Vec<String>>()));
 
-        Ok(())
+        debug!("No offline country match for node {}, probing", node_id);
+        self.probe_nodes_location(vec![node_id.clone()]).await?;
+        let code = self
+            .get_nodes()?
+            .into_iter()
+            .find(|n| n.id == node_id)
+            .and_then(|n| n.location)
+            .map(|l| {
+                if l.country_code.is_empty() {
+                    crate::ip_check::country_code_for_name(&l.country).unwrap_or_default().to_string()
+                } else {
+                    l.country_code
+                }
+            })
+            .unwrap_or_default();
+        Ok(code)
     }
 
-    pub async fn probe_nodes_location(&self, node_ids: Vec<String>) -> Result<(), String> {
+    pub async fn probe_nodes_location(&self, node_ids: Vec<String>) -> Result<(), TunnetError> {
+        let epoch = self.test_epoch.current();
         let profiles = self.manager.load_profiles()?;
-        let _settings = self.manager.load_settings()?;
+        let settings = self.manager.load_settings()?;
+        let timeout_ms = (settings.probe_timeout_secs.max(1) * 1000) as i64;
+
+        // The configured endpoint first, then a different provider in case it is blocked
+        let mut providers = vec![settings.ip_check_url.clone()];
+        if settings.ip_check_url != crate::ip_check::FALLBACK_IP_CHECK_URL {
+            providers.push(crate::ip_check::FALLBACK_IP_CHECK_URL.to_string());
+        }
 
         let mut updates = std::collections::HashMap::new();
         let mut futures = Vec::new();
         // Limit concurrency to prevent resource exhaustion (too many sing-box instances)
-        let semaphore = std::sync::Arc::new(tokio::sync::Semaphore::new(5));
+        let semaphore = std::sync::Arc::new(tokio::sync::Semaphore::new(
+            settings.probe_concurrency.max(1),
+        ));
 
         for p in &profiles {
             for n in &p.nodes {
@@ -3977,6 +5886,8 @@ impl<R: Runtime> ProxyService<R> {
 
                 let current_latency = n.location.as_ref().map(|l| l.latency).unwrap_or(0);
                 let sem = semaphore.clone();
+                let providers = providers.clone();
+                let test_epoch = self.test_epoch.clone();
 
                 futures.push(tokio::spawn(async move {
                     // Acquire permit to limit active sing-box instances
@@ -3984,20 +5895,18 @@ impl<R: Runtime> ProxyService<R> {
 
                     let outbound_c = std::ffi::CString::new(outbound_json).unwrap();
                     
-                    // Provider strategy: Try ip-api (HTTP), then ipwho.is (HTTPS/Fallback)
-                    let providers = vec![
-                        ("http://ip-api.com/json", "ip-api"),
-                        ("https://ipwho.is/", "ipwhois"),
-                    ];
-
-                    for (url, provider) in providers {
-                        let target_c = std::ffi::CString::new(url).unwrap();
+                    for url in providers {
+                        // Queued behind the semaphore when cancel_tests was called
+                        if !test_epoch.is_current(epoch) {
+                            return None;
+                        }
+                        let target_c = std::ffi::CString::new(url.as_str()).unwrap();
 
                         let res_ptr = unsafe {
                             crate::libbox::LibboxFetch(
                                 outbound_c.as_ptr(),
                                 target_c.as_ptr(),
-                                10000, 
+                                timeout_ms,
                             )
                         };
 
@@ -4010,49 +5919,12 @@ impl<R: Runtime> ProxyService<R> {
                                 .to_string_lossy()
                                 .into_owned()
                         };
+                        let Ok(body) = crate::libbox::parse_call_result(&res_str) else {
+                            continue;
+                        };
 
-                        if let Ok(val) = serde_json::from_str::<serde_json::Value>(&res_str) {
-                            let mut success = false;
-                            
-                            // Check success based on provider
-                            if provider == "ip-api" && val["status"] == "success" {
-                                success = true;
-                            } else if provider == "ipwhois" && val["success"] == true {
-                                success = true;
-                            }
-
-                            if success {
-                                let (ip, country, city, lat, lon, isp) = if provider == "ip-api" {
-                                    (
-                                        val["query"].as_str().unwrap_or_default(),
-                                        val["country"].as_str().unwrap_or_default(), // Use full name
-                                        val["city"].as_str().unwrap_or_default(),
-                                        val["lat"].as_f64().unwrap_or_default(),
-                                        val["lon"].as_f64().unwrap_or_default(),
-                                        val["isp"].as_str().unwrap_or_default()
-                                    )
-                                } else {
-                                    (
-                                        val["ip"].as_str().unwrap_or_default(),
-                                        val["country"].as_str().unwrap_or_default(), // Use full name
-                                        val["city"].as_str().unwrap_or_default(),
-                                        val["latitude"].as_f64().unwrap_or_default(),
-                                        val["longitude"].as_f64().unwrap_or_default(),
-                                        val["connection"]["isp"].as_str().unwrap_or_default()
-                                    )
-                                };
-
-                                let loc = crate::profile::LocationInfo {
-                                    ip: ip.to_string(),
-                                    country: country.to_string(),
-                                    city: city.to_string(),
-                                    lat,
-                                    lon,
-                                    isp: isp.to_string(),
-                                    latency: current_latency,
-                                };
-                                return Some((node_id, loc));
-                            }
+                        if let Some(lookup) = crate::ip_check::parse_response(&url, &body) {
+                            return Some((node_id, lookup.into_location(current_latency)));
                         }
                     }
                     None
@@ -4068,7 +5940,8 @@ impl<R: Runtime> ProxyService<R> {
             }
         }
 
-        // Reload profiles to minimize race condition
+        // Reload profiles under the results lock so concurrent latency results survive
+        let _guard = self.test_results_lock.lock().unwrap();
         let mut profiles = self.manager.load_profiles()?;
         for p in &mut profiles {
             for n in &mut p.nodes {
@@ -4097,158 +5970,106 @@ impl<R: Runtime> ProxyService<R> {
         Ok(())
     }
 
-    pub async fn url_test(&self, node_id: String) -> Result<u64, String> {
-        // Reuse the batch strategy (filtering for just this node)
-        // This ensures the same Native URLTest mechanism is used.
-        self.probe_nodes_latency(vec![node_id.clone()]).await?;
+    /// GET `url` through one node's outbound in a throwaway core and return the body.
+    /// Needs no local inbound, so it works whether or not the proxy is running.
+    pub async fn fetch_through_node(&self, node_id: &str, url: &str) -> Result<String, TunnetError> {
+        let node = self
+            .get_nodes()?
+            .into_iter()
+            .find(|n| n.id == node_id)
+            .ok_or_else(|| TunnetError::NodeNotFound(node_id.to_string()))?;
+        let settings = self.manager.load_settings()?;
+        let timeout_ms = (settings.probe_timeout_secs.max(1) * 1000) as i64;
+
+        let outbound_json =
+            serde_json::to_string(&self.node_to_outbound(&node)).map_err(|e| e.to_string())?;
+        let outbound_c = CString::new(outbound_json).map_err(|_| "Outbound holds null bytes")?;
+        let target_c = CString::new(url).map_err(|_| "URL holds null bytes")?;
+
+        let reply = tokio::task::spawn_blocking(move || unsafe {
+            let ptr = libbox::LibboxFetch(outbound_c.as_ptr(), target_c.as_ptr(), timeout_ms);
+            (!ptr.is_null()).then(|| CStr::from_ptr(ptr).to_string_lossy().into_owned())
+        })
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("Core returned no response")?;
+
+        libbox::parse_call_result(&reply).map_err(|e| TunnetError::Other(e.to_string()))
+    }
 
-        // Retrieve the updated latency
-        let profiles = self.manager.load_profiles()?;
-        for p in profiles {
-            for n in p.nodes {
-                if n.id == node_id {
-                    let ping = n.ping.unwrap_or(0);
-                    return Ok(ping);
-                }
-            }
-        }
-        Err("Node not found after test".to_string())
+    /// Lightweight "is this node alive" check: the node's exit IP and country from the
+    /// configured `ip_check_url`.
+    pub async fn check_node_ip(
+        &self,
+        node_id: &str,
+    ) -> Result<crate::ip_check::IpLookup, TunnetError> {
+        let url = self.manager.load_settings()?.ip_check_url;
+        let body = self.fetch_through_node(node_id, &url).await?;
+        crate::ip_check::parse_response(&url, &body)
+            .ok_or_else(|| format!("Unexpected IP check response from {}", url).into())
     }
 
-    fn node_to_outbound(&self, node: &crate::profile::Node) -> crate::config::Outbound {
-        let settings = self.get_app_settings().unwrap_or_default();
-        let mut cfg = crate::config::SingBoxConfig::new(None, crate::config::ConfigMode::Combined, &settings.dns_servers, &settings.dns_strategy, "proxy");
-        let tag = node.id.clone();
-
-        match node.protocol.as_str() {
-            "vmess" => {
-                let packet_encoding = node.packet_encoding.clone().or(Some("xudp".to_string()));
-                cfg = cfg.with_vmess_outbound(
-                    &tag,
-                    node.server.clone(),
-                    node.port,
-                    node.uuid.clone().unwrap_or_default(),
-                    node.cipher.clone().unwrap_or("auto".to_string()),
-                    0,
-                    node.network.clone(),
-                    node.path.clone(),
-                    node.host.clone(),
-                    node.tls,
-                    node.insecure,
-                    packet_encoding,
-                );
-            }
-            "vless" => {
-                let packet_encoding = node.packet_encoding.clone().or(Some("xudp".to_string()));
-                cfg = cfg.with_vless_outbound(
-                    &tag,
-                    node.server.clone(),
-                    node.port,
-                    node.uuid.clone().unwrap_or_default(),
-                    node.flow.clone(),
-                    node.network.clone(),
-                    node.path.clone(),
-                    node.host.clone(),
-                    node.tls,
-                    node.insecure,
-                    node.sni.clone(),
-                    node.alpn.clone(),
-                    packet_encoding,
-                    node.fingerprint.clone(),
-                    node.public_key.clone(),
-                    node.short_id.clone(),
-                );
-            }
-            "shadowsocks" | "ss" => {
-                cfg = cfg.with_shadowsocks_outbound(
-                    &tag,
-                    node.server.clone(),
-                    node.port,
-                    node.cipher
-                        .clone()
-                        .unwrap_or("chacha20-ietf-poly1305".to_string()),
-                    node.password.clone().unwrap_or_default(),
-                );
-            }
-            "trojan" => {
-                cfg = cfg.with_trojan_outbound(
-                    &tag,
-                    node.server.clone(),
-                    node.port,
-                    node.password.clone().unwrap_or_default(),
-                    node.network.clone(),
-                    node.path.clone(),
-                    node.host.clone(),
-                    node.tls,
-                    node.insecure,
-                    node.sni.clone(),
-                    node.alpn.clone(),
-                    node.fingerprint.clone(),
-                    node.public_key.clone(),
-                    node.short_id.clone(),
-                );
-            }
-            "hysteria2" | "hy2" => {
-                let up_mbps = node.up.as_ref().and_then(|s| s.parse().ok());
-                let down_mbps = node.down.as_ref().and_then(|s| s.parse().ok());
-                cfg = cfg.with_hysteria2_outbound(
-                    &tag,
-                    node.server.clone(),
-                    node.port,
-                    node.password.clone().unwrap_or_default(),
-                    node.sni.clone(),
-                    node.insecure,
-                    node.alpn.clone(),
-                    up_mbps,
-                    down_mbps,
-                    node.obfs.clone(),
-                    node.obfs_password.clone(),
-                    node.fingerprint.clone(),
-                );
-            }
-            "tuic" => {
-                cfg = cfg.with_tuic_outbound(
-                    &tag,
-                    node.server.clone(),
-                    node.port,
-                    node.uuid.clone().unwrap_or_default(),
-                    node.password.clone(),
-                    node.sni.clone(),
-                    node.insecure,
-                    node.alpn.clone(),
-                    None,
-                    None,
-                    None,
-                    None,
-                    node.fingerprint.clone(),
-                );
-            }
-            "anytls" => {
-                cfg = cfg.with_anytls_outbound(
-                    &tag,
-                    node.server.clone(),
-                    node.port,
-                    node.password.clone().unwrap_or_default(),
-                    node.tls,
-                    node.insecure,
-                    node.sni.clone(),
-                    node.alpn.clone(),
-                    node.fingerprint.clone(),
-                    node.disable_sni,
-                );
+    /// Test one node, splitting the connect time through the proxy from the full request.
+    /// `total_ms` becomes the node's displayed latency, matching batch tests.
+    pub async fn url_test(&self, node_id: String) -> Result<LatencyResult, TunnetError> {
+        let node = self
+            .get_nodes()?
+            .into_iter()
+            .find(|n| n.id == node_id)
+            .ok_or_else(|| TunnetError::NodeNotFound(node_id.clone()))?;
+        let settings = self.manager.load_settings()?;
+        let outbound_c = std::ffi::CString::new(serde_json::to_string(&self.node_to_outbound(&node))?)
+            .map_err(|_| "Outbound holds null bytes")?;
+        let target_c = std::ffi::CString::new(settings.latency_test_url.as_str())
+            .map_err(|_| "Latency test URL holds null bytes")?;
+        let timeout_ms = (settings.probe_timeout_secs.max(1) * 1000) as i64;
+
+        let result = tokio::task::spawn_blocking(move || {
+            let res_ptr = unsafe {
+                crate::libbox::LibboxTestOutbound(outbound_c.as_ptr(), target_c.as_ptr(), timeout_ms)
+            };
+            if res_ptr.is_null() {
+                return Err("Latency test returned nothing".to_string());
             }
-            _ => {
-                // Direct for others
-                cfg = cfg.with_direct_tag(&tag);
+            let res_str = unsafe { std::ffi::CStr::from_ptr(res_ptr).to_string_lossy().into_owned() };
+            // Failures come back as plain error text
+            serde_json::from_str::<LatencyResult>(&res_str).map_err(|_| res_str)
+        })
+        .await
+        .map_err(|e| e.to_string())?;
+
+        let latency = result.as_ref().map(|r| r.total_ms).unwrap_or(0);
+        self.record_test_results(&std::collections::HashMap::from([(node_id.clone(), Some(latency))]))?;
+        let _ = self.app.emit("profiles-update", Some(vec![node_id]));
+
+        Ok(result?)
+    }
+
+    /// Record latency results (`None` for a failed test) on the stored nodes, reloading
+    /// the profiles under `test_results_lock` so a concurrent test's results survive.
+    fn record_test_results(
+        &self,
+        results: &std::collections::HashMap<String, Option<u64>>,
+    ) -> Result<(), TunnetError> {
+        let _guard = self.test_results_lock.lock().unwrap();
+        let mut profiles = self.manager.load_profiles()?;
+        let now = unix_now();
+        for n in profiles.iter_mut().flat_map(|p| p.nodes.iter_mut()) {
+            if let Some(latency) = results.get(&n.id) {
+                n.record_test(*latency, now);
             }
         }
+        self.manager.save_profiles(&profiles)
+    }
 
-        cfg.outbounds.pop().unwrap()
+    fn node_to_outbound(&self, node: &crate::profile::Node) -> crate::config::Outbound {
+        let settings = self.get_app_settings().unwrap_or_default();
+        node_to_outbound(node, &settings)
     }
 
     // --- Tray Helpers ---
 
-    pub async fn set_routing_mode(&self, mode: &str) -> Result<(), String> {
+    pub async fn set_routing_mode(&self, mode: &str) -> Result<(), TunnetError> {
         // Mode: "rule", "global", "direct"
 
         // 1. Check if we need to restart
@@ -4298,14 +6119,15 @@ impl<R: Runtime> ProxyService<R> {
 
         // 4. Cleanup System Proxy
         self.disable_system_proxy();
+        self.clear_last_session();
     }
-    pub fn decode_qr(&self, path: &str) -> Result<String, String> {
+    pub fn decode_qr(&self, path: &str) -> Result<String, TunnetError> {
         let img = image::open(path).map_err(|e| format!("Failed to open image: {}", e))?;
         let img = img.to_luma8();
         let mut img = rqrr::PreparedImage::prepare(img);
         let grids = img.detect_grids();
         if grids.is_empty() {
-            return Err("No QR code found in image".to_string());
+            return Err("No QR code found in image".into());
         }
         let (_meta, content) = grids[0].decode().map_err(|e| format!("Failed to decode QR code: {}", e))?;
         Ok(content)
@@ -4356,36 +6178,57 @@ impl<R: Runtime> ProxyService<R> {
                 
                 let main_port = *service_state.clash_api_port.lock().unwrap();
                 let helper_port = *service_state.helper_api_port.lock().unwrap();
+                let secret = service_state.clash_secret();
 
-                // Function to fetch and calc delta
-                async fn fetch_delta(client: &reqwest::Client, port: Option<u16>, prev: &mut (u64, u64)) -> (u64, u64) {
-                    if let Some(p) = port {
-                        let url = format!("http://127.0.0.1:{}/connections", p);
-                        // Timeout short to avoid overlapping ticks
-                         let resp_res = client.get(&url)
-                             .timeout(std::time::Duration::from_millis(800))
-                             .send().await;
-
-                         if let Ok(resp) = resp_res {
-                             if let Ok(json) = resp.json::<serde_json::Value>().await {
-                                 let up_total = json.get("uploadTotal").and_then(|v| v.as_u64()).unwrap_or(0);
-                                 let down_total = json.get("downloadTotal").and_then(|v| v.as_u64()).unwrap_or(0);
-                                 
-                                 // Calculate Delta
-                                 let delta_up = if up_total >= prev.0 { up_total - prev.0 } else { up_total };
-                                 let delta_down = if down_total >= prev.1 { down_total - prev.1 } else { down_total };
-                                 
-                                 *prev = (up_total, down_total);
-                                 return (delta_up, delta_down);
-                             }
-                        }
-                    }
-                    *prev = (0, 0); 
-                    (0, 0)
+                fn delta(totals: (u64, u64), prev: &mut (u64, u64)) -> (u64, u64) {
+                    let delta_up = if totals.0 >= prev.0 { totals.0 - prev.0 } else { totals.0 };
+                    let delta_down = if totals.1 >= prev.1 { totals.1 - prev.1 } else { totals.1 };
+                    *prev = totals;
+                    (delta_up, delta_down)
                 }
 
-                let (m_up, m_down) = fetch_delta(&client, main_port, &mut prev_main).await;
-                let (h_up, h_down) = fetch_delta(&client, helper_port, &mut prev_helper).await;
+                // Function to fetch cumulative totals from a Clash API port
+                async fn fetch_totals(client: &reqwest::Client, port: Option<u16>, secret: &str) -> Option<(u64, u64)> {
+                    let p = port?;
+                    let url = format!("http://127.0.0.1:{}/connections", p);
+                    // Timeout short to avoid overlapping ticks
+                    let resp = client.get(&url)
+                        .bearer_auth(secret)
+                        .timeout(std::time::Duration::from_millis(800))
+                        .send().await.ok()?;
+                    let json = resp.json::<serde_json::Value>().await.ok()?;
+                    let up_total = json.get("uploadTotal").and_then(|v| v.as_u64()).unwrap_or(0);
+                    let down_total = json.get("downloadTotal").and_then(|v| v.as_u64()).unwrap_or(0);
+                    Some((up_total, down_total))
+                }
+
+                let (m_up, m_down) = match fetch_totals(&client, main_port, &secret).await {
+                    Some(totals) => delta(totals, &mut prev_main),
+                    None => {
+                        prev_main = (0, 0);
+                        (0, 0)
+                    }
+                };
+
+                let mut helper_totals = fetch_totals(&client, helper_port, &secret).await;
+                // The helper's Clash API may be unreachable (e.g. bound in another namespace);
+                // fall back to asking the helper over IPC while TUN is active
+                if helper_totals.is_none() && *service_state.tun_mode.lock().unwrap() {
+                    helper_totals = tokio::task::spawn_blocking(|| {
+                        crate::helper_client::HelperClient::new().get_stats().ok()
+                    })
+                    .await
+                    .ok()
+                    .flatten()
+                    .map(|stats| (stats.up, stats.down));
+                }
+                let (h_up, h_down) = match helper_totals {
+                    Some(totals) => delta(totals, &mut prev_helper),
+                    None => {
+                        prev_helper = (0, 0);
+                        (0, 0)
+                    }
+                };
 
                 let current_up = m_up + h_up;
                 let current_down = m_down + h_down;
@@ -4,6 +4,65 @@ fn default_true() -> bool {
     true
 }
 
+fn default_transparent_proxy_port() -> u16 {
+    7895
+}
+
+/// Linux transparent proxy inbound, for gateways that steer LAN traffic with iptables/nftables.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum TransparentProxyMode {
+    #[default]
+    Off,
+    /// TCP and UDP; needs a TPROXY mangle rule plus policy routing, and CAP_NET_ADMIN,
+    /// so it only runs while TUN mode has the helper's core up
+    Tproxy,
+    /// TCP only; needs an nat REDIRECT rule
+    Redirect,
+}
+
+fn default_tun_address() -> String {
+    "172.19.0.1/30".to_string()
+}
+
+fn default_tun_address_v6() -> String {
+    "fdfe:dcba:9876::1/126".to_string()
+}
+
+/// Most providers serve the full node list and `subscription-userinfo` to sing-box
+pub const DEFAULT_SUBSCRIPTION_USER_AGENT: &str = "sing-box";
+
+fn default_subscription_user_agent() -> String {
+    DEFAULT_SUBSCRIPTION_USER_AGENT.to_string()
+}
+
+fn default_subscription_connect_timeout_secs() -> u64 {
+    10
+}
+
+fn default_subscription_timeout_secs() -> u64 {
+    20
+}
+
+fn default_probe_concurrency() -> usize {
+    10
+}
+
+fn default_probe_timeout_secs() -> u64 {
+    5
+}
+
+/// The 204 endpoint Clash-style clients measure against, so latencies are comparable
+pub const DEFAULT_LATENCY_TEST_URL: &str = "https://www.gstatic.com/generate_204";
+
+fn default_latency_test_url() -> String {
+    DEFAULT_LATENCY_TEST_URL.to_string()
+}
+
+fn default_ip_check_url() -> String {
+    crate::ip_check::DEFAULT_IP_CHECK_URL.to_string()
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct AppSettings {
     // General
@@ -15,6 +74,16 @@ pub struct AppSettings {
     pub auto_connect: bool,
     #[serde(default = "default_true")]
     pub show_sidebar_status: bool,
+    #[serde(default = "default_true")]
+    pub dedup_on_import: bool,
+    /// Sent when fetching subscriptions; providers pick the format (Clash, sing-box, base64) from it
+    #[serde(default = "default_subscription_user_agent")]
+    pub subscription_user_agent: String,
+    #[serde(default = "default_subscription_connect_timeout_secs")]
+    pub subscription_connect_timeout_secs: u64,
+    /// Whole-request limit for one fetch attempt, including reading the body
+    #[serde(default = "default_subscription_timeout_secs")]
+    pub subscription_timeout_secs: u64,
 
     // Connection
     pub system_proxy: bool,
@@ -23,7 +92,51 @@ pub struct AppSettings {
     pub tun_mode: bool,
     pub tun_stack: String,
     pub tun_mtu: u16,
+    /// Keeps traffic from bypassing the TUN interface. Turning it off lets other VPNs
+    /// and Docker networks keep their own routes, at the cost of that leak protection.
+    #[serde(default = "default_true")]
     pub strict_route: bool,
+    #[serde(default = "default_true")]
+    pub ipv6: bool,
+    #[serde(default = "default_tun_address")]
+    pub tun_address: String,
+    /// ULA given to the TUN interface alongside `tun_address` while `ipv6` is on
+    #[serde(default = "default_tun_address_v6")]
+    pub tun_address_v6: String,
+    #[serde(default)]
+    pub tun_interface_name: Option<String>,
+    /// CIDRs routed outside the tunnel (LAN printers, corporate ranges, ...)
+    #[serde(default)]
+    pub tun_bypass_cidrs: Vec<String>,
+    /// Serve the mixed port from the TUN instance too, for apps that need an explicit
+    /// proxy. Desktop TUN mode always keeps the separate local mixed instance, so this
+    /// changes the single-instance mobile config and how `check_ip` reaches the exit.
+    #[serde(default)]
+    pub tun_with_mixed: bool,
+    /// Linux only: let sing-box redirect TCP through nftables instead of the TUN
+    /// stack. Only applies with the `system` stack; ignored on other platforms.
+    #[serde(default)]
+    pub tun_auto_redirect: bool,
+    /// Extra inbound next to the mixed one. Tunnet does not install the firewall rules
+    /// that send traffic to it; ignored on other platforms than Linux.
+    #[serde(default)]
+    pub transparent_proxy: TransparentProxyMode,
+    #[serde(default = "default_transparent_proxy_port")]
+    pub transparent_proxy_port: u16,
+    /// Dial timeout for proxy outbounds; `None` keeps the sing-box default
+    #[serde(default)]
+    pub connect_timeout_secs: Option<u32>,
+    #[serde(default)]
+    pub tcp_fast_open: bool,
+    /// Interface the `direct` outbound dials from, overriding auto-detection
+    #[serde(default)]
+    pub direct_bind_interface: Option<String>,
+    /// IPv4 source address for the `direct` outbound
+    #[serde(default)]
+    pub direct_bind_address: Option<String>,
+    /// Use Encrypted Client Hello on every TLS outbound, not just nodes that opt in
+    #[serde(default)]
+    pub ech_enabled: bool,
 
     // DNS
     pub dns_hijack: bool,
@@ -33,9 +146,36 @@ pub struct AppSettings {
 
     // Advanced
     pub log_level: String,
+    /// Write the local core log to `logs/core.log` instead of the live stream
+    #[serde(default)]
+    pub core_log_file: bool,
     pub active_target_id: Option<String>,
     #[serde(default)]
     pub config_version: u32,
+    /// Validate generated configs in-process before launching them
+    #[serde(default = "default_true")]
+    pub preflight_check: bool,
+    /// Fixed Clash API port; `None` picks a free one on every start
+    #[serde(default)]
+    pub clash_api_port: Option<u16>,
+    /// Pinned controller secret; `None` uses a random one per app session
+    #[serde(default)]
+    pub clash_api_secret: Option<String>,
+    /// Listen on all interfaces instead of loopback (external dashboards on the LAN)
+    #[serde(default)]
+    pub clash_api_allow_lan: bool,
+
+    // Probing
+    #[serde(default = "default_probe_concurrency")]
+    pub probe_concurrency: usize,
+    #[serde(default = "default_probe_timeout_secs")]
+    pub probe_timeout_secs: u64,
+    /// Target of latency tests and url-test groups; 200 and 204 count as success
+    #[serde(default = "default_latency_test_url")]
+    pub latency_test_url: String,
+    /// Geolocation endpoint for the exit IP check and node location probes
+    #[serde(default = "default_ip_check_url")]
+    pub ip_check_url: String,
 }
 
 impl Default for AppSettings {
@@ -47,6 +187,10 @@ impl Default for AppSettings {
             auto_update: true,
             auto_connect: false,
             show_sidebar_status: true,
+            dedup_on_import: true,
+            subscription_user_agent: default_subscription_user_agent(),
+            subscription_connect_timeout_secs: default_subscription_connect_timeout_secs(),
+            subscription_timeout_secs: default_subscription_timeout_secs(),
             system_proxy: true,
             allow_lan: false,
             mixed_port: 2080,
@@ -54,13 +198,178 @@ impl Default for AppSettings {
             tun_stack: "gvisor".to_string(),
             tun_mtu: 1500,
             strict_route: true,
+            ipv6: true,
+            tun_address: default_tun_address(),
+            tun_address_v6: default_tun_address_v6(),
+            tun_interface_name: None,
+            tun_bypass_cidrs: Vec::new(),
+            tun_with_mixed: false,
+            tun_auto_redirect: false,
+            transparent_proxy: TransparentProxyMode::Off,
+            transparent_proxy_port: default_transparent_proxy_port(),
+            connect_timeout_secs: None,
+            tcp_fast_open: false,
+            direct_bind_interface: None,
+            direct_bind_address: None,
+            ech_enabled: false,
             dns_hijack: true,
             dns_strategy: "ipv4".to_string(),
             dns_servers: "8.8.8.8\n1.1.1.1".to_string(),
             routing_mode: Some("rule".to_string()),
             log_level: "info".to_string(),
+            core_log_file: false,
             active_target_id: None,
             config_version: 2,
+            preflight_check: true,
+            clash_api_port: None,
+            clash_api_secret: None,
+            clash_api_allow_lan: false,
+            probe_concurrency: default_probe_concurrency(),
+            probe_timeout_secs: default_probe_timeout_secs(),
+            latency_test_url: default_latency_test_url(),
+            ip_check_url: default_ip_check_url(),
+        }
+    }
+}
+
+impl AppSettings {
+    /// Names of the fields that differ from `other` and only take effect once the
+    /// running core picks up a new config.
+    pub fn connection_changes(&self, other: &AppSettings) -> Vec<&'static str> {
+        [
+            ("mixed_port", self.mixed_port != other.mixed_port),
+            ("allow_lan", self.allow_lan != other.allow_lan),
+            ("tun_mode", self.tun_mode != other.tun_mode),
+            ("tun_stack", self.tun_stack != other.tun_stack),
+            ("tun_mtu", self.tun_mtu != other.tun_mtu),
+            ("strict_route", self.strict_route != other.strict_route),
+            ("ipv6", self.ipv6 != other.ipv6),
+            ("tun_address", self.tun_address != other.tun_address),
+            ("tun_address_v6", self.tun_address_v6 != other.tun_address_v6),
+            ("tun_interface_name", self.tun_interface_name != other.tun_interface_name),
+            ("tun_bypass_cidrs", self.tun_bypass_cidrs != other.tun_bypass_cidrs),
+            // Desktop always runs the mixed instance next to TUN; only mobile builds it on demand
+            ("tun_with_mixed", cfg!(mobile) && self.tun_with_mixed != other.tun_with_mixed),
+            ("tun_auto_redirect", self.tun_auto_redirect != other.tun_auto_redirect),
+            ("transparent_proxy", self.transparent_proxy != other.transparent_proxy),
+            (
+                "transparent_proxy_port",
+                self.transparent_proxy_port != other.transparent_proxy_port,
+            ),
+            ("connect_timeout_secs", self.connect_timeout_secs != other.connect_timeout_secs),
+            ("tcp_fast_open", self.tcp_fast_open != other.tcp_fast_open),
+            ("direct_bind_interface", self.direct_bind_interface != other.direct_bind_interface),
+            ("direct_bind_address", self.direct_bind_address != other.direct_bind_address),
+            ("ech_enabled", self.ech_enabled != other.ech_enabled),
+            ("latency_test_url", self.latency_test_url != other.latency_test_url),
+            ("dns_hijack", self.dns_hijack != other.dns_hijack),
+            ("dns_strategy", self.dns_strategy != other.dns_strategy),
+            ("dns_servers", self.dns_servers != other.dns_servers),
+            ("routing_mode", self.routing_mode != other.routing_mode),
+            // The core's own log level is written into its config
+            ("log_level", self.log_level != other.log_level),
+            ("core_log_file", self.core_log_file != other.core_log_file),
+            ("clash_api_port", self.clash_api_port != other.clash_api_port),
+            ("clash_api_secret", self.clash_api_secret != other.clash_api_secret),
+            ("clash_api_allow_lan", self.clash_api_allow_lan != other.clash_api_allow_lan),
+        ]
+        .into_iter()
+        .filter_map(|(name, changed)| changed.then_some(name))
+        .collect()
+    }
+
+    /// The TUN address checks alone, so config generation can fall back to the default
+    /// addresses without depending on unrelated settings.
+    pub fn validate_tun(&self) -> Result<(), String> {
+        let (addr, prefix) = self
+            .tun_address
+            .split_once('/')
+            .ok_or_else(|| format!("TUN address '{}' must be a CIDR like 172.19.0.1/30", self.tun_address))?;
+        addr.parse::<std::net::Ipv4Addr>()
+            .map_err(|_| format!("TUN address '{}' is not a valid IPv4 address", addr))?;
+        match prefix.parse::<u8>() {
+            Ok(p) if (1..=30).contains(&p) => {}
+            _ => return Err(format!("TUN prefix '/{}' must be between 1 and 30", prefix)),
+        }
+        let (addr, prefix) = self
+            .tun_address_v6
+            .split_once('/')
+            .ok_or_else(|| format!("TUN IPv6 address '{}' must be a CIDR like fdfe:dcba:9876::1/126", self.tun_address_v6))?;
+        addr.parse::<std::net::Ipv6Addr>()
+            .map_err(|_| format!("TUN IPv6 address '{}' is not a valid IPv6 address", addr))?;
+        match prefix.parse::<u8>() {
+            Ok(p) if (1..=126).contains(&p) => {}
+            _ => return Err(format!("TUN IPv6 prefix '/{}' must be between 1 and 126", prefix)),
+        }
+        Ok(())
+    }
+
+    /// Reject values that would produce a broken sing-box config.
+    pub fn validate(&self) -> Result<(), String> {
+        self.validate_tun()?;
+
+        if let Some(secs) = self.connect_timeout_secs {
+            if !(1..=300).contains(&secs) {
+                return Err(format!("Connect timeout must be between 1 and 300 seconds, got {}", secs));
+            }
+        }
+
+        for (label, secs) in [
+            ("Subscription connect timeout", self.subscription_connect_timeout_secs),
+            ("Subscription timeout", self.subscription_timeout_secs),
+        ] {
+            if !(1..=300).contains(&secs) {
+                return Err(format!("{} must be between 1 and 300 seconds, got {}", label, secs));
+            }
+        }
+
+        if let Some(name) = self.tun_interface_name.as_deref().filter(|n| !n.is_empty()) {
+            let valid = name.len() <= 15
+                && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+            if !valid {
+                return Err(format!(
+                    "TUN interface name '{}' must be at most 15 letters, digits, '-' or '_'",
+                    name
+                ));
+            }
+        }
+
+        if let Some(name) = self.direct_bind_interface.as_deref().filter(|n| !n.is_empty()) {
+            if name.chars().any(|c| c.is_whitespace() || c == '/') {
+                return Err(format!("Bind interface '{}' is not a valid interface name", name));
+            }
+            if self.tun_interface_name.as_deref() == Some(name) {
+                return Err("The direct outbound cannot bind to the TUN interface".to_string());
+            }
+        }
+        if let Some(addr) = self.direct_bind_address.as_deref().filter(|a| !a.is_empty()) {
+            addr.parse::<std::net::Ipv4Addr>()
+                .map_err(|_| format!("Bind address '{}' is not a valid IPv4 address", addr))?;
+        }
+
+        if let Some(port) = self.clash_api_port {
+            if port == 0 || port == self.mixed_port {
+                return Err(format!("Clash API port {} must be non-zero and differ from the mixed port", port));
+            }
+        }
+        if self.transparent_proxy != TransparentProxyMode::Off
+            && (self.transparent_proxy_port == 0 || self.transparent_proxy_port == self.mixed_port)
+        {
+            return Err(format!(
+                "Transparent proxy port {} must be non-zero and differ from the mixed port",
+                self.transparent_proxy_port
+            ));
+        }
+        for (name, url) in [("Latency test", &self.latency_test_url), ("IP check", &self.ip_check_url)] {
+            if !(url.starts_with("http://") || url.starts_with("https://")) {
+                return Err(format!("{} URL '{}' must be an http(s) URL", name, url));
+            }
+        }
+        if let Some(secret) = self.clash_api_secret.as_deref().filter(|s| !s.is_empty()) {
+            if secret.chars().any(|c| c.is_whitespace() || c.is_control()) {
+                return Err("Clash API secret must not contain whitespace".to_string());
+            }
         }
+        Ok(())
     }
 }
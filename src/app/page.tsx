@@ -4,7 +4,7 @@ import { useState, useEffect, useRef, useMemo, useCallback } from "react"
 import { invoke } from "@tauri-apps/api/core"
 import { listen, emit } from "@tauri-apps/api/event"
 import { useTranslation } from "react-i18next"
-import { cn, safeUnlisten } from "@/lib/utils"
+import { cn, safeUnlisten, errorMessage, isSuperseded } from "@/lib/utils"
 import { AppSettings, defaultSettings, getAppSettings, saveAppSettings } from "@/lib/settings"
 import { Sidebar, ViewType } from "@/components/dashboard/sidebar"
 import { LocationsView } from "@/components/dashboard/locations-view"
@@ -89,6 +89,26 @@ export default function Home() {
   const [showLogs, setShowLogs] = useState(false)
   const [showAddSubscription, setShowAddSubscription] = useState(false)
 
+  // Seed the panel with the backend's history when it opens
+  useEffect(() => {
+    if (!showLogs) return
+    (async () => {
+      try {
+        // Drop the undelivered poll buffer first; the history already contains those lines
+        await invoke("poll_logs");
+        const history: any[] = await invoke("get_recent_logs");
+        const seeded = { local: [] as string[], helper: [] as string[] };
+        history.forEach(log => {
+          seeded[log.source === "helper" ? "helper" : "local"].push(log.message);
+        });
+        pendingLogsRef.current = { local: [], helper: [] };
+        setLogs(seeded);
+      } catch (e) {
+        // Fall back to live polling only
+      }
+    })();
+  }, [showLogs]);
+
   // Batch log updates AND Poll logs (Pull instead of Push)
   useEffect(() => {
     const interval = setInterval(async () => {
@@ -248,7 +268,7 @@ export default function Home() {
       // Let's just rely on the switch UI for feedback.
     } catch (e: any) {
       console.error(e)
-      toast.error(t('toast.save_failed', { error: e }))
+      toast.error(t('toast.save_failed', { error: errorMessage(e) }))
       // Revert
       setSettings(settings)
     } finally {
@@ -272,17 +292,15 @@ export default function Home() {
       const checkIpWithRetry = async (retries = 3) => {
         try {
           const data: any = await invoke("check_ip")
-          if (data.status === "success") {
-            const details = {
-              ip: data.query,
-              country: data.country,
-              countryCode: data.countryCode.toLowerCase(),
-              isp: data.isp
-            };
-            setConnectionDetails(details)
-            // Sync to other windows (e.g. tray)
-            emit("connection-details-update", details)
-          }
+          const details = {
+            ip: data.ip,
+            country: data.country,
+            countryCode: data.country_code.toLowerCase(),
+            isp: data.isp
+          };
+          setConnectionDetails(details)
+          // Sync to other windows (e.g. tray)
+          emit("connection-details-update", details)
         } catch (err) {
           console.warn(`Failed to fetch IP (retries left: ${retries}):`, err)
           if (retries > 0 && isConnected) {
@@ -435,10 +453,11 @@ export default function Home() {
         return t('toast.connected_to', { server: node.name });
       }
 
-      toast.promise(promise, {
+      // A superseded request leaves the shared toast to the newer one
+      toast.promise(promise.catch((err) => isSuperseded(err) ? new Promise(() => { }) : Promise.reject(err)), {
         loading: getLoadingMsg(),
         success: getSuccessMsg(),
-        error: (err: any) => t('toast.action_failed', { error: err }),
+        error: (err: any) => t('toast.action_failed', { error: errorMessage(err) }),
         id: "proxy-sync"
       })
 
@@ -461,6 +480,7 @@ export default function Home() {
         // Trigger IP refresh after successful sync
         setIpRefreshKey(prev => prev + 1)
       } catch (e) {
+        if (isSuperseded(e)) return
         console.error("Failed to sync proxy", e)
         if (pulseId !== lastPulseIdRef.current) return
 
@@ -579,6 +599,20 @@ export default function Home() {
               emit("connection-details-update", connectionDetailsRef.current)
             }
           }),
+          listen<{ id: string, name: string, usage: { percent: number | null, expires_in_days: number | null } }>("subscription-warning", (event) => {
+            if (!active) return
+            const { name, usage } = event.payload
+            if (usage.expires_in_days !== null && usage.expires_in_days <= 3) {
+              toast.warning(t('toast.subscription_expiring', { name, days: Math.max(usage.expires_in_days, 0) }))
+            }
+            if (usage.percent !== null && usage.percent >= 90) {
+              toast.warning(t('toast.subscription_traffic_low', { name, percent: Math.round(usage.percent) }))
+            }
+          }),
+          listen<string>("config-recovered", (event) => {
+            if (!active) return
+            toast.warning(t('toast.config_recovered', { file: event.payload }))
+          }),
           listen("profiles-update", (event) => {
             if (!active) return
             fetchProfiles(false)
@@ -637,6 +671,31 @@ export default function Home() {
             setProxyMode(settings.routing_mode as any)
           }
         }).catch(console.error)
+
+        // The previous run ended without a clean stop: offer to restore exactly what was running
+        invoke<{ node_id: string | null, tun_mode: boolean, routing_mode: string } | null>("get_resumable_session").then(session => {
+          if (!active || !session?.node_id) return
+          const nodeId = session.node_id
+          toast.info(t('toast.resume_session_title'), {
+            description: t('toast.resume_session_desc'),
+            action: {
+              label: t('toast.resume_session'),
+              onClick: () => {
+                manualActionRef.current = true
+                setActiveServerId(nodeId)
+                setProxyMode(session.routing_mode as any)
+                setTunEnabled(session.tun_mode)
+                setConnectionState("connecting")
+                setIsConnected(true)
+              }
+            },
+            cancel: {
+              label: t('toast.dismiss_session'),
+              onClick: () => { invoke("discard_last_session").catch(console.error) }
+            },
+            duration: Infinity,
+          })
+        }).catch(console.error)
       }
 
       // Fix: If backend is in auto-connect starting phase, force loading state
@@ -944,7 +1003,8 @@ export default function Home() {
       const importPromise = (async () => {
         try {
           // 2. Perform Import
-          const newProfileId: string = await invoke("import_subscription", { url: trimmedUrl, name: name || null })
+          const result: { profile_id: string, duplicates_removed: number } = await invoke("import_subscription", { url: trimmedUrl, name: name || null })
+          const newProfileId = result.profile_id
 
           // 3. update UI immediately to show the new card
           const postProfiles: any[] = await invoke("get_profiles")
@@ -967,7 +1027,7 @@ export default function Home() {
           } else {
             fetchProfiles(false)
           }
-          return
+          return result.duplicates_removed
         } catch (e: any) {
           setIsImporting(false)
           console.error("Import failed:", e)
@@ -975,18 +1035,32 @@ export default function Home() {
         }
       })()
 
-      toast.promise(importPromise, {
-        loading: t('importing'),
-        success: t('toast.import_success'),
-        error: (e) => {
-          const errorMsg = String(e)
-          if (errorMsg.includes("No valid nodes found in this subscription")) {
-            return t('toast.import_no_nodes')
-          }
-          return t('toast.action_failed', { error: errorMsg })
+      const cancelAction = { label: t('common.cancel'), onClick: () => { invoke("cancel_import").catch(() => {}) } }
+      const toastId = toast.loading(t('importing'), { action: cancelAction })
+      const unlistenProgress = await listen<{ stage: string, nodes: number }>("import-progress", (event) => {
+        if (event.payload.stage === "found") {
+          toast.loading(t('import_found_nodes', { count: event.payload.nodes }), { id: toastId, action: cancelAction })
         }
       })
 
+      importPromise
+        .then((duplicatesRemoved) => toast.success(t('toast.import_success'), {
+          id: toastId,
+          action: undefined,
+          description: duplicatesRemoved > 0 ? t('toast.import_duplicates_removed', { count: duplicatesRemoved }) : undefined,
+        }))
+        .catch((e) => {
+          const errorMsg = errorMessage(e)
+          if (errorMsg.includes("No valid nodes found in this subscription")) {
+            toast.error(t('toast.import_no_nodes'), { id: toastId, action: undefined })
+          } else if (errorMsg === "Import canceled") {
+            toast.dismiss(toastId)
+          } else {
+            toast.error(t('toast.action_failed', { error: errorMsg }), { id: toastId, action: undefined })
+          }
+        })
+        .finally(() => unlistenProgress())
+
       return importPromise
     }
   }, [t, fetchProfiles, updateServersState, checkLatency]) // All dependencies are now stable
@@ -1147,7 +1221,7 @@ export default function Home() {
         }
 
         console.error("Update failed:", e)
-        const errorMsg = String(e)
+        const errorMsg = errorMessage(e)
         if (errorMsg.includes("No valid nodes found in this subscription")) {
           return t('toast.import_no_nodes')
         }
@@ -1198,10 +1272,10 @@ export default function Home() {
       fetchProfiles()
       toast.success(t('toast.sub_deleted'))
     } catch (e: any) {
-      if (e === "delete_active_error") {
+      if (errorMessage(e) === "delete_active_error") {
         toast.error(t('toast.delete_active_error'))
       } else {
-        toast.error(t('toast.delete_failed', { error: e }))
+        toast.error(t('toast.delete_failed', { error: errorMessage(e) }))
       }
     }
   }
@@ -1224,7 +1298,7 @@ export default function Home() {
       fetchProfiles()
       setEditorOpen(false)
     } catch (e: any) {
-      toast.error(t('toast.save_failed', { error: e }))
+      toast.error(t('toast.save_failed', { error: errorMessage(e) }))
     }
   }
 
@@ -1246,10 +1320,10 @@ export default function Home() {
         setActiveServerId(null)
       }
     } catch (e: any) {
-      if (e === "delete_active_error") {
+      if (errorMessage(e) === "delete_active_error") {
         toast.error(t('toast.delete_active_error'))
       } else {
-        toast.error(t('toast.delete_failed', { error: e }))
+        toast.error(t('toast.delete_failed', { error: errorMessage(e) }))
       }
       console.error(e)
     } finally {
@@ -1400,7 +1474,7 @@ export default function Home() {
     setTestingNodeIds(prev => [...prev, id])
 
     try {
-      const ping: number = await invoke("url_test", { nodeId: targetPingId })
+      const { total_ms: ping } = await invoke<{ connect_ms: number, total_ms: number }>("url_test", { nodeId: targetPingId })
       setServers(prev => prev.map(s => s.id === id ? { ...s, ping } : s))
       // Also update the auto node's ping if it was tested
       if (targetPingId !== id) {
@@ -1493,7 +1567,7 @@ export default function Home() {
               }
             } catch (e: any) {
               console.error("Helper check/install failed:", e)
-              toast.error(t('toast.helper_failed', { error: e }))
+              toast.error(t('toast.helper_failed', { error: errorMessage(e) }))
               setIsLoading(false)
               return
             }
@@ -1509,7 +1583,7 @@ export default function Home() {
         setConnectionState("idle")
       }
     } catch (e: any) {
-      toast.error(t('toast.connection_failed', { error: e }))
+      toast.error(t('toast.connection_failed', { error: errorMessage(e) }))
       setIsLoading(false)
       setConnectionState("idle")
     } finally {
@@ -1593,13 +1667,14 @@ export default function Home() {
         name: data.name,
         url: data.url,
         update_interval: data.update_interval,
-        clear_interval: data.clear_interval
+        clear_interval: data.clear_interval,
+        user_agent: data.user_agent
       })
       toast.success(t('subscriptions.edit_success', { defaultValue: 'Updated successfully' }))
       fetchProfiles()
       setEditingSubscription(null)
     } catch (e) {
-      toast.error(String(e))
+      toast.error(errorMessage(e))
     }
   }
 
@@ -1625,7 +1700,7 @@ export default function Home() {
       await handleServerToggle(groupId, isConnected)
       toast.success(t('auto_select_group_created', { name: profile.name }))
     } catch (err: any) {
-      toast.error(t('toast.action_failed', { error: err }))
+      toast.error(t('toast.action_failed', { error: errorMessage(err) }))
     }
   }
 
@@ -152,9 +152,9 @@ export default function TrayPage() {
                 invoke("check_ip")
                     .then((info: any) => {
                         const details = {
-                            ip: info.query,
+                            ip: info.ip,
                             country: info.country,
-                            countryCode: info.countryCode.toLowerCase(),
+                            countryCode: info.country_code.toLowerCase(),
                             isp: info.isp
                         }
                         setIpInfo(details)
@@ -226,8 +226,8 @@ export default function TrayPage() {
         setLatency(null)
         setIsLatencyTesting(true)
 
-        invoke<number>("url_test", { nodeId })
-            .then(lat => setLatency(lat))
+        invoke<{ connect_ms: number, total_ms: number }>("url_test", { nodeId })
+            .then(res => setLatency(res.total_ms))
             .catch(e => console.error("Latency test failed", e))
             .finally(() => setIsLatencyTesting(false))
     }, [activeNode])
@@ -2,7 +2,7 @@
 
 import React, { useState, useEffect, useRef, useMemo } from "react"
 import { Search, X, Network, Wifi, Activity, ArrowUp, ArrowDown, Clock, AlertCircle, Monitor, Globe, Unplug } from "lucide-react"
-import { cn } from "@/lib/utils"
+import { cn, errorMessage } from "@/lib/utils"
 import { invoke } from "@tauri-apps/api/core"
 import { listen } from "@tauri-apps/api/event"
 import { toast } from "sonner"
@@ -114,7 +114,7 @@ export function ConnectionsView() {
             setIsLoading(false)
             setError(null)
         } catch (e: any) {
-            const errStr = String(e)
+            const errStr = errorMessage(e)
             if (errStr.includes("Proxy is not running")) {
                 setError(t('connections.not_running'))
                 setConnections([])
@@ -159,7 +159,7 @@ export function ConnectionsView() {
             // Optimistic update
             setConnections(prev => prev.filter(c => c.id !== id))
         } catch (e: any) {
-            toast.error(t('connections.toast.close_failed', { error: String(e) }))
+            toast.error(t('connections.toast.close_failed', { error: errorMessage(e) }))
         }
     }
 
@@ -169,7 +169,7 @@ export function ConnectionsView() {
             toast.success(t('connections.toast.closed_all'))
             setConnections([])
         } catch (e: any) {
-            toast.error(t('connections.toast.close_all_failed', { error: String(e) }))
+            toast.error(t('connections.toast.close_all_failed', { error: errorMessage(e) }))
         }
     }
 
@@ -1,13 +1,13 @@
 import { useState } from "react"
 import { Dialog, DialogContent, DialogHeader, DialogTitle } from "@/components/ui/dialog"
 import { useTranslation } from "react-i18next"
-import { Copy, FileDown, QrCode, Check, Share2, FileJson, Link, Zap } from "lucide-react"
+import { Copy, FileDown, QrCode, Check, Share2, FileJson, FileCode, Link, Zap } from "lucide-react"
 import { invoke } from "@tauri-apps/api/core"
 import { save } from "@tauri-apps/plugin-dialog"
 import { writeTextFile } from "@tauri-apps/plugin-fs"
 import { writeText } from "@tauri-apps/plugin-clipboard-manager"
 import { toast } from "sonner"
-import { cn } from "@/lib/utils"
+import { cn, errorMessage } from "@/lib/utils"
 import { QRModal } from "@/components/ui/qr-modal"
 
 interface ExportModalProps {
@@ -20,7 +20,7 @@ interface ExportModalProps {
 
 export function ExportModal({ isOpen, onClose, targetId, targetName, targetType }: ExportModalProps) {
     const { t } = useTranslation()
-    const [format, setFormat] = useState<"sip002" | "tunnet" | "json">("sip002")
+    const [format, setFormat] = useState<"sip002" | "tunnet" | "json" | "clash">("sip002")
     const [isLoading, setIsLoading] = useState(false)
     const [qrValue, setQrValue] = useState("")
 
@@ -48,13 +48,15 @@ export function ExportModal({ isOpen, onClose, targetId, targetName, targetType
                 }
                 setQrValue(content)
             } else if (action === "file") {
-                const ext = format === "json" ? "json" : "txt"
+                const ext = format === "json" ? "json" : format === "clash" ? "yaml" : "txt"
                 const path = await save({
                     defaultPath: `${targetName.replace(/\s+/g, '_')}_export.${ext}`,
                     filters: [{
                         name: format === "json"
                             ? t('export.filter_json', { defaultValue: "Sing-box Config" })
-                            : t('export.filter_links', { defaultValue: "Subscription Links" }),
+                            : format === "clash"
+                                ? t('export.filter_clash', { defaultValue: "Clash Config" })
+                                : t('export.filter_links', { defaultValue: "Subscription Links" }),
                         extensions: [ext]
                     }]
                 })
@@ -67,7 +69,7 @@ export function ExportModal({ isOpen, onClose, targetId, targetName, targetType
             }
         } catch (e) {
             console.error(e)
-            toast.error(t('export.failed', { defaultValue: "Export failed", error: String(e) }))
+            toast.error(t('export.failed', { defaultValue: "Export failed", error: errorMessage(e) }))
         } finally {
             setIsLoading(false)
         }
@@ -92,7 +94,7 @@ export function ExportModal({ isOpen, onClose, targetId, targetName, targetType
                         {/* Format Selection */}
                         <div className="space-y-3">
                             <label className="text-xs font-bold text-text-tertiary uppercase tracking-wider">{t('export.format', { defaultValue: "Format" })}</label>
-                            <div className="grid grid-cols-3 gap-2">
+                            <div className="grid grid-cols-4 gap-2">
                                 <button
                                     onClick={() => setFormat("sip002")}
                                     className={cn(
@@ -129,6 +131,18 @@ export function ExportModal({ isOpen, onClose, targetId, targetName, targetType
                                     <FileJson size={18} />
                                     <span className="text-[10px] font-bold">{t('export.type_json', { defaultValue: "JSON" })}</span>
                                 </button>
+                                <button
+                                    onClick={() => setFormat("clash")}
+                                    className={cn(
+                                        "flex flex-col items-center gap-2 p-3 rounded-xl border transition-all",
+                                        format === "clash"
+                                            ? "bg-primary/10 border-primary text-primary"
+                                            : "bg-black/5 dark:bg-white/5 border-transparent hover:bg-black/10 dark:hover:bg-white/10 text-text-secondary"
+                                    )}
+                                >
+                                    <FileCode size={18} />
+                                    <span className="text-[10px] font-bold">{t('export.type_clash', { defaultValue: "Clash" })}</span>
+                                </button>
                             </div>
 
                             <div className="mt-2 px-1 min-h-[32px] flex items-center">
@@ -136,6 +150,7 @@ export function ExportModal({ isOpen, onClose, targetId, targetName, targetType
                                     {format === "sip002" && t('export.type_links_desc')}
                                     {format === "tunnet" && t('export.type_tunnet_desc')}
                                     {format === "json" && t('export.type_json_desc')}
+                                    {format === "clash" && t('export.type_clash_desc')}
                                 </p>
                             </div>
                         </div>
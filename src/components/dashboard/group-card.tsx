@@ -15,6 +15,7 @@ export interface Group {
         type: "Filter"
         criteria: {
             keywords?: string[]
+            profile_id?: string
         }
     }
     icon?: string
@@ -46,12 +47,14 @@ export function GroupCard({ group, isActive, onEdit, onDelete, onActivate, onSel
             const ids = new Set(group.source.node_ids || [])
             candidates = allNodes.filter(n => ids.has(n.id))
         } else {
-            // @ts-ignore
-            const keywords = (group.source.criteria?.keywords || []).map(k => k.toLowerCase())
-            if (keywords.length > 0) {
+            const criteria = group.source.type === "Filter" ? group.source.criteria : undefined
+            const keywords = (criteria?.keywords || []).map(k => k.toLowerCase())
+            const profileId = criteria?.profile_id
+            if (keywords.length > 0 || profileId) {
                 candidates = allNodes.filter(n => {
+                    if (profileId && n.profile_id !== profileId) return false
                     const name = n.name.toLowerCase()
-                    return keywords.some((k: string) => name.includes(k))
+                    return keywords.length === 0 || keywords.some((k: string) => name.includes(k))
                 })
             }
         }
@@ -2,7 +2,7 @@
 
 import React, { useState, useEffect } from "react"
 import { Plus, Search, Trash2, Edit2, LayoutGrid, Check, X, Loader2, Play, Zap, Target, ArrowUpDown } from "lucide-react"
-import { cn, safeUnlisten } from "@/lib/utils"
+import { cn, safeUnlisten, errorMessage } from "@/lib/utils"
 import { invoke } from "@tauri-apps/api/core"
 import { listen, emit } from "@tauri-apps/api/event"
 import { toast } from "sonner"
@@ -23,6 +23,7 @@ export interface Group {
         type: "Filter"
         criteria: {
             keywords?: string[]
+            profile_id?: string
         }
     }
     icon?: string
@@ -169,7 +170,9 @@ export function GroupsView({ allNodes, activeTargetId, onSelectTarget, isConnect
                 source = {
                     type: "Filter",
                     criteria: {
-                        keywords: dialogKeywords.split(",").map(s => s.trim()).filter(Boolean)
+                        keywords: dialogKeywords.split(",").map(s => s.trim()).filter(Boolean),
+                        // Kept from the edited group; the dialog has no subscription picker yet
+                        profile_id: editingGroup?.source.type === "Filter" ? editingGroup.source.criteria?.profile_id : undefined
                     }
                 }
             }
@@ -195,7 +198,7 @@ export function GroupsView({ allNodes, activeTargetId, onSelectTarget, isConnect
             emit("groups-updated")
         } catch (e: any) {
             console.error(e)
-            toast.error(t('groups.save_failed', { error: e }))
+            toast.error(t('groups.save_failed', { error: errorMessage(e) }))
         } finally {
             setIsSaving(false)
         }
@@ -215,7 +218,7 @@ export function GroupsView({ allNodes, activeTargetId, onSelectTarget, isConnect
             // Notify parent component to refresh groups
             emit("groups-updated")
         } catch (e: any) {
-            toast.error(t('groups.delete_failed', { error: e }))
+            toast.error(t('groups.delete_failed', { error: errorMessage(e) }))
         } finally {
             setIsDeleteModalOpen(false)
             setGroupToDelete(null)
@@ -28,6 +28,9 @@ export interface Node {
     short_id?: string
     up?: string
     down?: string
+    ech?: boolean
+    ech_config?: string
+    brutal?: boolean
 }
 
 interface NodeEditorProps {
@@ -131,6 +134,7 @@ export function NodeEditor({ isOpen, initialNode, onClose, onSave }: NodeEditorP
     const hasPassword = ["trojan", "shadowsocks", "hysteria2", "tuic"].includes(node.protocol)
     const hasFlow = node.protocol === "vless"
     const hasHysteriaBW = node.protocol === "hysteria2"
+    const hasBrutal = ["vmess", "vless", "trojan", "shadowsocks"].includes(node.protocol)
     const hasReality = node.protocol === "vless" // simple assumption for Reality
 
     return (
@@ -275,7 +279,19 @@ export function NodeEditor({ isOpen, initialNode, onClose, onSave }: NodeEditorP
                                 </div>
                             )}
 
-                            {hasHysteriaBW && (
+                            {hasBrutal && (
+                                <div
+                                    onClick={() => handleChange("brutal", !node.brutal)}
+                                    className="flex items-center gap-2 cursor-pointer group"
+                                >
+                                    <div className={`size-4 rounded border flex items-center justify-center transition-all ${node.brutal ? 'bg-primary border-primary' : 'border-border-color group-hover:border-primary/50'}`}>
+                                        {node.brutal && <Check size={12} className="text-white" />}
+                                    </div>
+                                    <span className="text-xs font-medium text-text-secondary uppercase tracking-wider">{t('node_editor.brutal')}</span>
+                                </div>
+                            )}
+
+                            {(hasHysteriaBW || (hasBrutal && node.brutal)) && (
                                 <div className="grid grid-cols-2 gap-4">
                                     <div className="space-y-1.5">
                                         <label className="text-xs font-medium text-text-secondary uppercase tracking-wider">{t('node_editor.up_mbps')}</label>
@@ -394,6 +410,32 @@ export function NodeEditor({ isOpen, initialNode, onClose, onSave }: NodeEditorP
                                                     </div>
                                                     <span className="text-[10px] font-bold text-secondary uppercase tracking-wider">{t('node_editor.skip_cert_verify')}</span>
                                                 </div>
+
+                                                <div
+                                                    onClick={() => handleChange("ech", !node.ech)}
+                                                    className="flex items-center gap-2 cursor-pointer group"
+                                                >
+                                                    <div className={`size-4 rounded border flex items-center justify-center transition-all ${node.ech ? 'bg-primary border-primary' : 'border-border-color group-hover:border-primary/50'}`}>
+                                                        {node.ech && <Check size={12} className="text-white" />}
+                                                    </div>
+                                                    <span className="text-[10px] font-bold text-secondary uppercase tracking-wider">{t('node_editor.ech')}</span>
+                                                </div>
+
+                                                {node.ech && (
+                                                    <div className="space-y-1.5">
+                                                        <label className="text-[10px] font-bold text-tertiary uppercase tracking-wider">{t('node_editor.ech_config')}</label>
+                                                        <input
+                                                            type="text"
+                                                            value={node.ech_config || ""}
+                                                            onChange={e => handleChange("ech_config", e.target.value)}
+                                                            className="w-full bg-black/5 dark:bg-black/20 border border-border-color rounded-lg px-2.5 py-1.5 text-xs text-text-primary font-mono focus:outline-none"
+                                                            placeholder="AEX+DQBB..."
+                                                            autoCapitalize="none"
+                                                            autoCorrect="off"
+                                                            spellCheck={false}
+                                                        />
+                                                    </div>
+                                                )}
                                             </div>
                                         )}
                                     </div>
@@ -66,6 +66,7 @@ export function RulesView({
         enabled: true,
         description: ""
     })
+    const [geoOptions, setGeoOptions] = useState<string[]>([])
     const [isMac, setIsMac] = useState(false)
     const [portalRoot, setPortalRoot] = useState<HTMLElement | null>(null)
 
@@ -128,6 +129,18 @@ export function RulesView({
         return isRulesDifferent || isPolicyDifferent
     }, [rules, defaultPolicy, currentPreset, pendingDeleteIds])
 
+    // Autocomplete for geosite:/geoip rule values
+    useEffect(() => {
+        const kind = dialogData.type === "GEOIP" ? "geoip" : dialogData.type === "DOMAIN" ? "geosite" : null
+        if (!kind) {
+            setGeoOptions([])
+            return
+        }
+        invoke<string[]>("list_geo_categories", { kind })
+            .then(tags => setGeoOptions(kind === "geosite" ? tags.map(tag => `geosite:${tag}`) : tags))
+            .catch(() => setGeoOptions([]))
+    }, [dialogData.type])
+
     useEffect(() => {
         setPortalRoot(document.body)
         if (typeof navigator !== 'undefined') {
@@ -405,7 +418,7 @@ export function RulesView({
     }
 
     const getNextPolicy = (current: string) => {
-        const standard: string[] = ["PROXY", "DIRECT", "REJECT"]
+        const standard: string[] = ["PROXY", "DIRECT", "REJECT", "REJECT_DROP"]
         const groupIds = groups.map(g => g.id)
         const sequence = [...standard, ...groupIds]
         const nextIndex = (sequence.indexOf(current) + 1) % sequence.length
@@ -460,6 +473,7 @@ export function RulesView({
             case "PROXY": return "text-violet-400 bg-violet-600/15 border-violet-500/30 shadow-[0_0_15px_rgba(139,92,246,0.15)]"
             case "DIRECT": return "text-emerald-400 bg-emerald-600/15 border-emerald-500/30 shadow-[0_0_15px_rgba(16,185,129,0.15)]"
             case "REJECT": return "text-rose-400 bg-rose-600/15 border-rose-500/30 shadow-[0_0_15px_rgba(244,63,94,0.15)]"
+            case "REJECT_DROP": return "text-rose-400 bg-rose-600/15 border-rose-500/30 shadow-[0_0_15px_rgba(244,63,94,0.15)]"
             default: return "text-gray-400 bg-white/5 border-white/10"
         }
     }
@@ -966,7 +980,7 @@ export function RulesView({
                                 <div className="space-y-3">
                                     <label className="text-[10px] font-black text-text-tertiary uppercase tracking-widest pl-1">{t('rules.dialog.type')}</label>
                                     <div className="grid grid-cols-3 gap-2">
-                                        {(["DOMAIN", "DOMAIN_SUFFIX", "DOMAIN_KEYWORD", "IP_CIDR", "GEOIP", "IP_IS_PRIVATE"] as const).map(type => (
+                                        {(["DOMAIN", "DOMAIN_SUFFIX", "DOMAIN_KEYWORD", "IP_CIDR", "GEOIP", "IP_IS_PRIVATE", "PORT", "PROCESS_NAME", "PROCESS_PATH"] as const).map(type => (
                                             <button
                                                 key={type}
                                                 onClick={() => {
@@ -998,6 +1012,7 @@ export function RulesView({
                                         readOnly={dialogData.type === 'IP_IS_PRIVATE'}
                                         autoFocus
                                         onChange={(e) => setDialogData({ ...dialogData, value: e.target.value })}
+                                        list={geoOptions.length > 0 ? "geo-categories" : undefined}
                                         className={cn(
                                             "w-full bg-black/3 dark:bg-white/5 border border-border-color rounded-2xl px-6 py-4 text-xs font-bold text-text-primary focus:outline-none focus:ring-4 focus:ring-primary/5 focus:border-primary/30 transition-all font-mono placeholder:text-text-tertiary/60",
                                             dialogData.type === 'IP_IS_PRIVATE' && "opacity-50 cursor-not-allowed text-text-tertiary"
@@ -1007,13 +1022,16 @@ export function RulesView({
                                         autoCorrect="off"
                                         spellCheck={false}
                                     />
+                                    <datalist id="geo-categories">
+                                        {geoOptions.map(tag => <option key={tag} value={tag} />)}
+                                    </datalist>
                                 </div>
                                 <div className="space-y-3">
                                     <label className="text-[10px] font-black text-text-tertiary uppercase tracking-widest pl-1">{t('rules.dialog.policy')}</label>
                                     <div className="flex flex-wrap gap-2">
                                         {/* Standard Policies */}
                                         <div className="flex bg-black/3 dark:bg-white/5 p-1.5 rounded-2xl border border-border-color/50 flex-1 min-w-[240px]">
-                                            {(["PROXY", "DIRECT", "REJECT"] as const).map(policy => (
+                                            {(["PROXY", "DIRECT", "REJECT", "REJECT_DROP"] as const).map(policy => (
                                                 <button
                                                     key={policy}
                                                     onClick={() => setDialogData({ ...dialogData, policy })}
@@ -2,7 +2,7 @@
 
 import React, { useState } from "react"
 import { ArrowUpDown, Filter, Play, Square, Plus, Pencil, Trash2, Globe, RotateCw, Search, Scroll, Pause, Copy, Zap, X, Target, Share2 } from "lucide-react"
-import { cn } from "@/lib/utils"
+import { cn, errorMessage } from "@/lib/utils"
 import { invoke } from "@tauri-apps/api/core"
 import { useTranslation } from "react-i18next"
 import { toast } from "sonner"
@@ -247,7 +247,7 @@ export function ServerList({
             }
             toast.success(t('auto_select_group_created', { name: t('auto_select_global', { defaultValue: 'Auto - Global' }) }))
         } catch (e: any) {
-            toast.error(t('toast.action_failed', { error: e }))
+            toast.error(t('toast.action_failed', { error: errorMessage(e) }))
         }
     }
 
@@ -30,7 +30,7 @@ import {
     FileDown,
     FileUp
 } from "lucide-react"
-import { cn } from "@/lib/utils"
+import { cn, errorMessage } from "@/lib/utils"
 import { Switch } from "@/components/ui/switch"
 import { AppSettings, defaultSettings, getAppSettings, saveAppSettings } from "@/lib/settings"
 import { getVersion } from "@tauri-apps/api/app"
@@ -607,7 +607,7 @@ function CheckUpdateBtn() {
                 }
             } catch (e) {
                 console.error(e)
-                toast.error(t('settings.advanced.core.error'), { description: String(e) })
+                toast.error(t('settings.advanced.core.error'), { description: errorMessage(e) })
                 setStatus('idle')
             }
             return
@@ -643,7 +643,7 @@ function CheckUpdateBtn() {
         } catch (e) {
             console.error(e)
             toast.error(t('settings.advanced.core.error'), {
-                description: String(e)
+                description: errorMessage(e)
             })
             setStatus('idle')
         }
@@ -886,6 +886,17 @@ function AdvancedSettings({ settings, update, clashApiPort, helperApiPort, modif
     const [refreshingGeoData, setRefreshingGeoData] = useState(false)
     const [isExportingSingbox, setIsExportingSingbox] = useState(false)
     const [isExportingBackup, setIsExportingBackup] = useState(false)
+    const [clashSecret, setClashSecret] = useState<string | null>(null)
+
+    useEffect(() => {
+        if (!clashApiPort) {
+            setClashSecret(null)
+            return
+        }
+        invoke<{ secret: string }>("get_clash_api_info")
+            .then(info => setClashSecret(info.secret))
+            .catch(() => setClashSecret(null))
+    }, [clashApiPort])
 
     const handleExportSingbox = async () => {
         setIsExportingSingbox(true)
@@ -962,7 +973,7 @@ function AdvancedSettings({ settings, update, clashApiPort, helperApiPort, modif
             toast.success(t('toast.update_completed'))
         } catch (e) {
             console.error("Failed to refresh geodata", e)
-            toast.error(t('toast.update_failed', { error: String(e) }))
+            toast.error(t('toast.update_failed', { error: errorMessage(e) }))
         } finally {
             setRefreshingGeoData(false)
         }
@@ -987,6 +998,7 @@ function AdvancedSettings({ settings, update, clashApiPort, helperApiPort, modif
                         <option value="trace">Trace</option>
                         <option value="warn">Warn</option>
                         <option value="error">Error</option>
+                        <option value="disabled">Disabled</option>
                     </select>
                 </SettingItem>
             </Section>
@@ -1072,6 +1084,14 @@ function AdvancedSettings({ settings, update, clashApiPort, helperApiPort, modif
                                     </code>
                                 </div>
                             )}
+                            {clashApiPort && clashSecret && (
+                                <div className="flex items-center gap-2">
+                                    <span className="text-[10px] font-bold text-text-tertiary uppercase tracking-wider">{t('settings.advanced.core.controller_secret')}</span>
+                                    <code className="text-[11px] font-mono bg-primary/10 text-primary px-2 py-1 rounded select-all cursor-text min-w-[160px] text-center">
+                                        {clashSecret}
+                                    </code>
+                                </div>
+                            )}
                             {helperApiPort && (
                                 <div className="flex items-center gap-2">
                                     <span className="text-[10px] font-bold text-text-tertiary uppercase tracking-wider">{t('settings.advanced.core.helper_controller')}</span>
@@ -1223,7 +1243,7 @@ function ReinstallHelperBtn() {
             console.error("Helper install failed:", e)
             toast.dismiss(toastId)
             toast.error(t('settings.advanced.component.install_failed', { defaultValue: 'Installation failed' }), {
-                description: String(e)
+                description: errorMessage(e)
             })
         } finally {
             setInstalling(false)
@@ -3,7 +3,7 @@
 import React, { useState, useEffect } from "react"
 import { RefreshCw, Trash2, Globe, Server, MoreHorizontal, Database, Zap, PlusCircle, Edit2, Target, ExternalLink, ArrowUpDown } from "lucide-react"
 import { useTranslation } from "react-i18next"
-import { cn } from "@/lib/utils"
+import { cn, errorMessage } from "@/lib/utils"
 import { ConfirmationModal } from "@/components/ui/confirmation-modal"
 import { open } from "@tauri-apps/plugin-shell"
 import { invoke } from "@tauri-apps/api/core"
@@ -22,6 +22,8 @@ export interface Subscription {
     web_page_url?: string
     update_interval?: number
     header_update_interval?: number
+    last_updated?: number
+    user_agent?: string
     nodes: any[]
 }
 
@@ -139,12 +141,13 @@ export function SubscriptionsView({ profiles, onUpdate, onDelete, onAdd, onSelec
                 name: data.name,
                 url: data.url,
                 updateInterval: data.update_interval,
-                clearInterval: data.clear_interval
+                clearInterval: data.clear_interval,
+                userAgent: data.user_agent
             })
             toast.success(t('subscriptions.edit_success', { defaultValue: 'Updated successfully' }))
             onUpdateAll?.() // Refresh list
         } catch (error) {
-            toast.error(String(error))
+            toast.error(errorMessage(error))
         } finally {
             setEditingProfile(null)
         }
@@ -186,7 +189,7 @@ export function SubscriptionsView({ profiles, onUpdate, onDelete, onAdd, onSelec
             }
             toast.success(t('auto_select_group_created', { name: getDisplayName(profile.name) }))
         } catch (err: any) {
-            toast.error(t('toast.action_failed', { error: err }))
+            toast.error(t('toast.action_failed', { error: errorMessage(err) }))
         }
     }
 
@@ -518,6 +521,7 @@ export function EditSubscriptionModal({ isOpen, onClose, onSave, initialData }:
     const [name, setName] = useState(getDisplayName(initialData.name))
     const [url, setUrl] = useState(initialData.url || "")
     const [interval, setInterval] = useState(initialData.update_interval ? String(initialData.update_interval / 60) : "") // Show in minutes
+    const [userAgent, setUserAgent] = useState(initialData.user_agent || "")
 
     const handleSave = () => {
         // Convert interval back to seconds if present
@@ -540,7 +544,8 @@ export function EditSubscriptionModal({ isOpen, onClose, onSave, initialData }:
             name,
             url: url || undefined,
             update_interval: intervalSec,
-            clear_interval: clearInterval
+            clear_interval: clearInterval,
+            user_agent: userAgent.trim()
         })
     }
 
@@ -607,6 +612,19 @@ export function EditSubscriptionModal({ isOpen, onClose, onSave, initialData }:
                                     </div>
                                     <p className="text-[10px] text-text-tertiary">{t('subscriptions.interval_hint', { defaultValue: "Leave empty to use default. Set to 0 to disable." })}</p>
                                 </div>
+
+                                <div className="space-y-1">
+                                    <label className="text-xs font-bold text-text-secondary uppercase tracking-wider">{t('subscriptions.user_agent', { defaultValue: "User-Agent" })}</label>
+                                    <input
+                                        value={userAgent}
+                                        onChange={e => setUserAgent(e.target.value)}
+                                        className="w-full bg-black/5 dark:bg-white/5 border border-transparent focus:border-primary/50 rounded-xl px-4 py-2 text-xs text-text-primary focus:outline-none transition-all font-mono"
+                                        placeholder={t('subscriptions.user_agent_placeholder', { defaultValue: "Empty to use the default (sing-box)" })}
+                                        autoCapitalize="none"
+                                        autoCorrect="off"
+                                        spellCheck={false}
+                                    />
+                                </div>
                             </>
                         )}
                     </div>
@@ -1,10 +1,13 @@
 export interface Rule {
     id: string
-    type: "DOMAIN" | "DOMAIN_SUFFIX" | "DOMAIN_KEYWORD" | "IP_CIDR" | "GEOIP" | "FINAL" | "IP_IS_PRIVATE"
+    type: "DOMAIN" | "DOMAIN_SUFFIX" | "DOMAIN_KEYWORD" | "IP_CIDR" | "GEOIP" | "FINAL" | "IP_IS_PRIVATE" | "PORT" | "PORT_RANGE" | "PROCESS_NAME" | "PROCESS_PATH"
     value: string
     policy: string
     enabled: boolean
     description?: string
+    // Extra conditions ANDed with the main match
+    port?: string
+    network?: "tcp" | "udp"
 }
 
 export const LEGACY_DESCRIPTION_MAP: Record<string, string> = {
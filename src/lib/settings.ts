@@ -9,6 +9,10 @@ export interface AppSettings {
     auto_update: boolean
     auto_connect: boolean
     show_sidebar_status: boolean
+    dedup_on_import?: boolean
+    subscription_user_agent?: string
+    subscription_connect_timeout_secs?: number
+    subscription_timeout_secs?: number
 
     // Connection
     system_proxy: boolean
@@ -18,6 +22,20 @@ export interface AppSettings {
     tun_stack: string
     tun_mtu: number
     strict_route: boolean
+    ipv6?: boolean
+    tun_address?: string
+    tun_address_v6?: string
+    tun_interface_name?: string
+    tun_bypass_cidrs?: string[]
+    tun_with_mixed?: boolean
+    tun_auto_redirect?: boolean
+    transparent_proxy?: "off" | "tproxy" | "redirect"
+    transparent_proxy_port?: number
+    connect_timeout_secs?: number | null
+    tcp_fast_open?: boolean
+    direct_bind_interface?: string | null
+    direct_bind_address?: string | null
+    ech_enabled?: boolean
 
     // DNS
     dns_hijack: boolean
@@ -27,7 +45,18 @@ export interface AppSettings {
 
     // Advanced
     log_level: string
+    core_log_file?: boolean
     active_target_id?: string
+    preflight_check?: boolean
+    clash_api_port?: number | null
+    clash_api_secret?: string | null
+    clash_api_allow_lan?: boolean
+
+    // Probing
+    probe_concurrency?: number
+    probe_timeout_secs?: number
+    latency_test_url?: string
+    ip_check_url?: string
 }
 
 export const defaultSettings: AppSettings = {
@@ -37,6 +66,10 @@ export const defaultSettings: AppSettings = {
     auto_update: true,
     auto_connect: false,
     show_sidebar_status: true,
+    dedup_on_import: true,
+    subscription_user_agent: "sing-box",
+    subscription_connect_timeout_secs: 10,
+    subscription_timeout_secs: 20,
     system_proxy: true,
     allow_lan: false,
     mixed_port: 2080,
@@ -44,12 +77,22 @@ export const defaultSettings: AppSettings = {
     tun_stack: "gvisor",
     tun_mtu: 1500,
     strict_route: true,
+    ipv6: true,
+    tun_address: "172.19.0.1/30",
+    tun_address_v6: "fdfe:dcba:9876::1/126",
+    tcp_fast_open: false,
     dns_hijack: true,
     dns_strategy: "ipv4",
     dns_servers: "8.8.8.8\n1.1.1.1",
     routing_mode: "rule",
     log_level: "info",
+    core_log_file: false,
     active_target_id: undefined,
+    preflight_check: true,
+    probe_concurrency: 10,
+    probe_timeout_secs: 5,
+    latency_test_url: "https://www.gstatic.com/generate_204",
+    ip_check_url: "http://ip-api.com/json",
 }
 
 export async function getAppSettings(): Promise<AppSettings> {
@@ -71,3 +114,15 @@ export async function saveAppSettings(settings: AppSettings): Promise<void> {
         throw e
     }
 }
+
+export interface SettingsUpdate {
+    changed: string[]
+    applied: boolean
+}
+
+// Save and, when the proxy is running, apply connection-affecting changes right away
+export async function updateAppSettings(settings: AppSettings, apply = true): Promise<SettingsUpdate> {
+    const result = await invoke<SettingsUpdate>("update_settings", { settings, apply })
+    await emit("settings-update", settings)
+    return result
+}
@@ -30,3 +30,23 @@ export function safeUnlisten(unlisten: any) {
     });
   }
 }
+
+/**
+ * Extracts a readable message from a failed Tauri command.
+ * Backend errors arrive as `{ code, message }`; anything else is stringified.
+ * @param e - The value caught from an `invoke` rejection
+ */
+export function errorMessage(e: unknown): string {
+  if (e && typeof e === 'object' && 'message' in e) {
+    return String((e as { message: unknown }).message);
+  }
+  return String(e);
+}
+
+/**
+ * True when a connect/disconnect was dropped because a newer one was queued behind it.
+ * @param e - The value caught from an `invoke` rejection
+ */
+export function isSuperseded(e: unknown): boolean {
+  return !!e && typeof e === 'object' && (e as { code?: unknown }).code === 'superseded';
+}